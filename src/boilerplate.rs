@@ -0,0 +1,166 @@
+//! Detection of the standard header repeated at the top of source files.
+//!
+//! Many projects open every file with the same licence or copyright comment. Printed
+//! in full on every listing it adds pages without adding anything, so the sinks
+//! print the most common header once in the frontmatter and replace each occurrence
+//! with a one-line marker, keeping the file's line numbers.
+//!
+//! Headers are compared after the comment markers are removed (see
+//! [`crate::syntax_analysis::leading_comment`]), so the same licence written as
+//! `//` lines in one language and a `/* */` block or `#` lines in another counts as
+//! one header.
+
+use crate::syntax_analysis::leading_comment;
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use syntect::parsing::SyntaxSet;
+
+/// Title of the frontmatter page the header is printed on.
+pub const TITLE: &str = "Standard file header";
+
+/// Headers shorter than this aren't worth collapsing.
+const MIN_LINES: usize = 3;
+
+/// The header shared by many files, and where it sits in each.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StandardHeader {
+    /// The header text with comment markers removed
+    pub text: String,
+    /// Number of files that open with the header
+    pub file_count: usize,
+    /// Zero-based lines the header takes up in each file that has it
+    headers: HashMap<PathBuf, Range<usize>>,
+}
+
+impl StandardHeader {
+    /// Find the header at the top of at least `min_files` of `files`.
+    ///
+    /// Files are read relative to `root`; the returned header's lines are keyed by
+    /// the paths as given. Files that can't be read as text or have no syntax are
+    /// skipped.
+    pub fn detect(
+        root: &Path,
+        files: &[PathBuf],
+        ss: &SyntaxSet,
+        min_files: usize,
+    ) -> Result<Option<Self>> {
+        let mut found = Vec::new();
+        for file in files {
+            let Ok(contents) = std::fs::read_to_string(root.join(file)) else {
+                continue;
+            };
+            let Some(syntax) = file
+                .extension()
+                .and_then(|e| e.to_str())
+                .and_then(|e| ss.find_syntax_by_extension(e))
+            else {
+                continue;
+            };
+            let comment = leading_comment(&contents, syntax, ss)
+                .with_context(|| format!("Failed to find header of {}", file.display()))?;
+            if let Some(comment) = comment {
+                found.push((file.clone(), comment.lines, comment.text));
+            }
+        }
+        Ok(choose(found, min_files))
+    }
+
+    /// The lines the header takes up in `path`, if the file opens with it.
+    pub fn lines(&self, path: &Path) -> Option<Range<usize>> {
+        self.headers.get(path).cloned()
+    }
+}
+
+/// Pick the most common header among the files' leading comments, if it's long
+/// enough and shared by at least `min_files` files. Ties go to the longer header.
+fn choose(found: Vec<(PathBuf, Range<usize>, String)>, min_files: usize) -> Option<StandardHeader> {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for (_, _, text) in &found {
+        if text.lines().count() >= MIN_LINES {
+            *counts.entry(text.as_str()).or_default() += 1;
+        }
+    }
+    let (text, file_count) = counts
+        .into_iter()
+        .filter(|(_, count)| *count >= min_files.max(1))
+        .max_by(|(a, a_count), (b, b_count)| a_count.cmp(b_count).then(a.len().cmp(&b.len())))?;
+    let text = text.to_string();
+
+    let headers = found
+        .into_iter()
+        .filter(|(_, _, header)| *header == text)
+        .map(|(path, lines, _)| (path, lines))
+        .collect();
+    Some(StandardHeader {
+        text,
+        file_count,
+        headers,
+    })
+}
+
+/// The marker shown in place of a collapsed header.
+pub fn header_label(lines: &Range<usize>) -> String {
+    if lines.len() == 1 {
+        format!("⋯ line {}: standard file header ⋯", lines.start + 1)
+    } else {
+        format!(
+            "⋯ lines {}–{}: standard file header ⋯",
+            lines.start + 1,
+            lines.end
+        )
+    }
+}
+
+/// The file with the header's lines emptied, so it isn't read as prose by the
+/// literate layout. Line numbers are unchanged.
+pub fn without_header(contents: &str, lines: &Range<usize>) -> String {
+    contents
+        .split_inclusive('\n')
+        .enumerate()
+        .map(|(i, line)| {
+            if lines.contains(&i) {
+                if line.ends_with('\n') {
+                    "\n"
+                } else {
+                    ""
+                }
+            } else {
+                line
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chooses_the_most_common_header() {
+        let licence = "Copyright Example\n\nLicensed under MIT.".to_string();
+        let other = "Copyright Someone Else\n\nAll rights reserved.".to_string();
+        let found = vec![
+            (PathBuf::from("a.rs"), 0..3, licence.clone()),
+            (PathBuf::from("b.py"), 1..4, licence.clone()),
+            (PathBuf::from("c.c"), 0..5, licence.clone()),
+            (PathBuf::from("d.rs"), 0..3, other.clone()),
+            (PathBuf::from("e.rs"), 0..3, other),
+            (PathBuf::from("f.rs"), 0..1, "short".to_string()),
+        ];
+
+        let header = choose(found.clone(), 3).expect("header is found");
+        assert_eq!(header.text, licence);
+        assert_eq!(header.file_count, 3);
+        assert_eq!(header.lines(Path::new("b.py")), Some(1..4));
+        assert_eq!(header.lines(Path::new("d.rs")), None);
+
+        assert_eq!(choose(found, 4), None);
+        assert_eq!(
+            header_label(&(0..15)),
+            "⋯ lines 1–15: standard file header ⋯"
+        );
+        assert_eq!(without_header("a\nb\nc\nd", &(1..3)), "a\n\n\nd");
+    }
+}
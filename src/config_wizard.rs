@@ -1,7 +1,7 @@
 //! Configuration wizard for creating `src-book.toml`.
 //!
-//! The wizard collects book metadata, repository settings, frontmatter file selection,
-//! and output format options (PDF, EPUB, or both). It extracts authors from git commit
+//! The wizard collects book metadata, repository settings, frontmatter and guided tour
+//! selection, and output format options (PDF, EPUB, or both). It extracts authors from git commit
 //! history and allows manual additions with prominence ranking.
 //!
 //! ## Modes
@@ -42,15 +42,16 @@
 //! - Theme preview is skipped in non-interactive mode
 
 use crate::cli::ConfigArgs;
-use crate::detection::{detect_defaults, detect_frontmatter, DetectedDefaults};
+use crate::detection::{detect_defaults, detect_frontmatter, detect_tours, DetectedDefaults};
 use crate::file_ordering::{sort_paths, sort_with_entrypoint};
 use crate::sinks::{
-    AppendixSectionNumbering, BinaryHexConfig, BookletConfig, ColophonConfig, FontSizesConfig,
-    FooterConfig, HeaderConfig, InlineTagsConfig, MarginsConfig, MetadataConfig, NumberingConfig,
-    PageConfig, PageSize, Position, RulePosition, SyntaxTheme, TagsAppendixConfig, TitlePageConfig,
+    AppendixSectionNumbering, BinaryHexConfig, Binding, BookletConfig, ColophonConfig, DuplexFlip,
+    Fold, FontSizesConfig, FooterConfig, HeaderConfig, InlineTagsConfig, ManualDuplex,
+    ManualDuplexConfig, MarginsConfig, MetadataConfig, NumberingConfig, Orientation, PageConfig,
+    PageSize, Position, RulePosition, SyntaxTheme, TagsAppendixConfig, TitlePageConfig,
     TitlePageImagePosition, PDF,
 };
-use crate::source::{AuthorBuilder, CommitOrder, GitRepository, Source, TagOrder};
+use crate::source::{AuthorBuilder, CommitOrder, GitRepository, Source, SourceFile, TagOrder};
 use anyhow::{anyhow, Context, Result};
 use dialoguer::theme::ColorfulTheme;
 use dialoguer::{Confirm, FuzzySelect, Input, MultiSelect, Select};
@@ -479,6 +480,42 @@ pub fn run(args: &ConfigArgs) -> Result<()> {
         Vec::new()
     };
 
+    // detect and select guided tours
+    let detected_tours = detect_tours(&repo_path, &source_files);
+    let tour_files = if non_interactive || detected_tours.is_empty() {
+        detected_tours
+    } else {
+        let tour_strings: Vec<String> = detected_tours
+            .iter()
+            .map(|p| p.display().to_string())
+            .collect();
+
+        println!(
+            "Detected {} guided tour(s): {}",
+            detected_tours.len(),
+            tour_strings.join(", ")
+        );
+
+        // pre-select tours that were in existing config, otherwise default to true
+        let existing_tours = existing.as_ref().map(|e| &e.source.tour_files);
+        let defaults: Vec<bool> = detected_tours
+            .iter()
+            .map(|f| existing_tours.map(|et| et.contains(f)).unwrap_or(true))
+            .collect();
+        let selections = MultiSelect::with_theme(&theme)
+            .with_prompt("Select tours to render as guided tour chapters")
+            .items(&tour_strings)
+            .defaults(&defaults)
+            .interact()?;
+
+        selections
+            .into_iter()
+            .map(|i| detected_tours[i].clone())
+            .collect()
+    };
+    // tours are rendered as narratives, not listed as source
+    source_files.retain(|f| !tour_files.contains(f));
+
     // ask for entrypoint file to control ordering
     // in non-interactive mode, use detected entrypoint if available
     let existing_entrypoint = existing
@@ -570,7 +607,8 @@ pub fn run(args: &ConfigArgs) -> Result<()> {
         title: Some(title),
         authors,
         frontmatter_files,
-        source_files,
+        tour_files,
+        source_files: source_files.into_iter().map(SourceFile::from).collect(),
         licences,
         repository: repo_path,
         block_globs: block_glob_strings,
@@ -727,6 +765,34 @@ pub fn run(args: &ConfigArgs) -> Result<()> {
             }
         };
 
+        let orientation = if non_interactive {
+            template
+                .as_ref()
+                .and_then(|t| t.pdf.as_ref())
+                .map(|p| p.page.orientation)
+                .unwrap_or_default()
+        } else {
+            let default_orientation = existing_pdf.map(|p| p.page.orientation).unwrap_or_default();
+            let idx = Select::with_theme(&theme)
+                .with_prompt("Page orientation")
+                .items(Orientation::all())
+                .default(
+                    Orientation::all()
+                        .iter()
+                        .position(|o| *o == default_orientation)
+                        .unwrap_or(0),
+                )
+                .interact()?;
+            Orientation::all()[idx]
+        };
+        let page = PageConfig {
+            width_in: page_width_in,
+            height_in: page_height_in,
+            orientation,
+        };
+        // everything below lays out on the page as it's turned
+        let (page_width_in, page_height_in) = page.size_in();
+
         let base_font_size: f32 = if non_interactive {
             template
                 .as_ref()
@@ -748,6 +814,16 @@ pub fn run(args: &ConfigArgs) -> Result<()> {
         let mut font_size_body_pt = base_font_size.round();
         let mut font_size_small_pt = (base_font_size * 0.8).round();
 
+        // whitespace handling isn't asked about, but is kept from the template or the
+        // existing config; line lengths are measured with it
+        let code = if non_interactive {
+            template.as_ref().and_then(|t| t.pdf.as_ref())
+        } else {
+            existing_pdf
+        }
+        .map(|p| p.code.clone())
+        .unwrap_or_default();
+
         // show layout capacity so users can assess readability before committing to config
         // helps catch cases where chosen page size is too small for their code line lengths
         if !non_interactive {
@@ -788,9 +864,10 @@ pub fn run(args: &ConfigArgs) -> Result<()> {
                 // allow font size adjustment loop until user is satisfied
                 loop {
                     let stats = crate::line_analysis::analyze_line_lengths(
-                        &source.source_files,
+                        &source.source_paths(),
                         &source.repository,
                         max_chars,
+                        &code,
                     )?;
 
                     // display statistics
@@ -801,10 +878,11 @@ pub fn run(args: &ConfigArgs) -> Result<()> {
                     }
 
                     println!(
-                        "Found {} total lines, {} ({:.1}%) would wrap",
+                        "Found {} total lines, {} ({:.1}%) would wrap onto {} extra rows",
                         crate::formatting::format_number(stats.total_lines),
                         crate::formatting::format_number(stats.lines_that_wrap),
-                        stats.wrap_percentage()
+                        stats.wrap_percentage(),
+                        crate::formatting::format_number(stats.wrapped_rows)
                     );
 
                     if stats.longest_line_length > 0 {
@@ -898,7 +976,7 @@ pub fn run(args: &ConfigArgs) -> Result<()> {
                 .map(|p| p.booklet.outfile.clone())
                 .unwrap_or_default()
         } else if Confirm::with_theme(&theme)
-            .with_prompt("Generate a print-ready booklet PDF for saddle-stitch binding?")
+            .with_prompt("Generate a print-ready booklet PDF for binding?")
             .default(existing_booklet_enabled)
             .interact()?
         {
@@ -917,6 +995,56 @@ pub fn run(args: &ConfigArgs) -> Result<()> {
             String::new()
         };
 
+        let (booklet_binding, booklet_fold, booklet_collation_marks) = if non_interactive {
+            template
+                .as_ref()
+                .and_then(|t| t.pdf.as_ref())
+                .map(|p| (p.booklet.binding, p.booklet.fold, p.booklet.collation_marks))
+                .unwrap_or_default()
+        } else if !booklet_outfile.is_empty() {
+            let existing_booklet = existing_pdf.map(|p| &p.booklet);
+            let default_binding = existing_booklet.map(|b| b.binding).unwrap_or_default();
+            let binding_idx = Select::with_theme(&theme)
+                .with_prompt("Binding")
+                .items(Binding::all())
+                .default(
+                    Binding::all()
+                        .iter()
+                        .position(|b| *b == default_binding)
+                        .unwrap_or(0),
+                )
+                .interact()?;
+            let binding = Binding::all()[binding_idx];
+
+            // saddle-stitched sheets are only ever folded once
+            let fold = if binding == Binding::SaddleStitch {
+                Fold::Folio
+            } else {
+                let default_fold = existing_booklet.map(|b| b.fold).unwrap_or_default();
+                let fold_idx = Select::with_theme(&theme)
+                    .with_prompt("Pages per sheet side")
+                    .items(Fold::all())
+                    .default(
+                        Fold::all()
+                            .iter()
+                            .position(|f| *f == default_fold)
+                            .unwrap_or(0),
+                    )
+                    .interact()?;
+                Fold::all()[fold_idx]
+            };
+
+            let collation_marks = binding == Binding::Perfect
+                && Confirm::with_theme(&theme)
+                    .with_prompt("Print collation marks on the spine of each signature?")
+                    .default(existing_booklet.is_some_and(|b| b.collation_marks))
+                    .interact()?;
+
+            (binding, fold, collation_marks)
+        } else {
+            Default::default()
+        };
+
         let (booklet_signature_size, booklet_sheet_width_in, booklet_sheet_height_in) =
             if non_interactive {
                 // use template settings if available
@@ -933,17 +1061,22 @@ pub fn run(args: &ConfigArgs) -> Result<()> {
                     .unwrap_or((16, 11.0, 8.5))
             } else if !booklet_outfile.is_empty() {
                 let default_sig_size = existing_pdf.map(|p| p.booklet.signature_size).unwrap_or(16);
-                let sig_size: u32 = Input::with_theme(&theme)
-                    .with_prompt("Pages per signature (must be divisible by 4)")
-                    .default(default_sig_size)
-                    .validate_with(|input: &u32| {
-                        if *input % 4 == 0 && *input > 0 {
-                            Ok(())
-                        } else {
-                            Err("Signature size must be a positive multiple of 4")
-                        }
-                    })
-                    .interact()?;
+                // only saddle-stitch nests sheets into signatures of a chosen size
+                let sig_size: u32 = if booklet_binding == Binding::SaddleStitch {
+                    Input::with_theme(&theme)
+                        .with_prompt("Pages per signature (must be divisible by 4)")
+                        .default(default_sig_size)
+                        .validate_with(|input: &u32| {
+                            if *input % 4 == 0 && *input > 0 {
+                                Ok(())
+                            } else {
+                                Err("Signature size must be a positive multiple of 4")
+                            }
+                        })
+                        .interact()?
+                } else {
+                    default_sig_size
+                };
 
                 let default_sheet_width = existing_pdf
                     .map(|p| p.booklet.sheet_width_in)
@@ -970,6 +1103,80 @@ pub fn run(args: &ConfigArgs) -> Result<()> {
                 (16, 11.0, 8.5)
             };
 
+        let booklet_paper_thickness_in: f32 = if non_interactive {
+            template
+                .as_ref()
+                .and_then(|t| t.pdf.as_ref())
+                .map(|p| p.booklet.paper_thickness_in)
+                .unwrap_or(0.0)
+        } else if !booklet_outfile.is_empty() && booklet_binding == Binding::SaddleStitch {
+            let default_thickness = existing_pdf
+                .map(|p| p.booklet.paper_thickness_in)
+                .unwrap_or(0.0);
+            Input::with_theme(&theme)
+                .with_prompt(
+                    "Paper thickness in inches to compensate for creep (e.g., 0.004; 0 for none)",
+                )
+                .default(default_thickness)
+                .validate_with(|input: &f32| {
+                    if *input >= 0.0 {
+                        Ok(())
+                    } else {
+                        Err("Paper thickness can't be negative")
+                    }
+                })
+                .interact()?
+        } else {
+            0.0
+        };
+
+        let booklet_manual_duplex = if non_interactive {
+            template
+                .as_ref()
+                .and_then(|t| t.pdf.as_ref())
+                .map(|p| p.booklet.manual_duplex.clone())
+                .unwrap_or_default()
+        } else if !booklet_outfile.is_empty() {
+            let existing_duplex = existing_pdf
+                .map(|p| p.booklet.manual_duplex.clone())
+                .unwrap_or_default();
+            let mode_idx = Select::with_theme(&theme)
+                .with_prompt("Manual duplex (for printers that only print one side)")
+                .items(ManualDuplex::all())
+                .default(
+                    ManualDuplex::all()
+                        .iter()
+                        .position(|m| *m == existing_duplex.mode)
+                        .unwrap_or(0),
+                )
+                .interact()?;
+            let mode = ManualDuplex::all()[mode_idx];
+
+            let flip = if mode == ManualDuplex::Off {
+                existing_duplex.flip
+            } else {
+                let flip_idx = Select::with_theme(&theme)
+                    .with_prompt("Turn the printed stack over on its")
+                    .items(DuplexFlip::all())
+                    .default(
+                        DuplexFlip::all()
+                            .iter()
+                            .position(|f| *f == existing_duplex.flip)
+                            .unwrap_or(0),
+                    )
+                    .interact()?;
+                DuplexFlip::all()[flip_idx]
+            };
+
+            ManualDuplexConfig {
+                mode,
+                flip,
+                ..existing_duplex
+            }
+        } else {
+            ManualDuplexConfig::default()
+        };
+
         // ask about binary hex rendering
         // in non-interactive mode, skip hex rendering unless template has it enabled
         let existing_hex_enabled = existing_pdf.map(|p| p.binary_hex.enabled).unwrap_or(false);
@@ -1540,10 +1747,7 @@ pub fn run(args: &ConfigArgs) -> Result<()> {
             outfile,
             font: "SourceCodePro".to_string(),
             theme: syntax_theme,
-            page: PageConfig {
-                width_in: page_width_in,
-                height_in: page_height_in,
-            },
+            page,
             margins: MarginsConfig::default(),
             fonts: FontSizesConfig {
                 title_pt: font_size_title_pt,
@@ -1577,12 +1781,41 @@ pub fn run(args: &ConfigArgs) -> Result<()> {
                 signature_size: booklet_signature_size,
                 sheet_width_in: booklet_sheet_width_in,
                 sheet_height_in: booklet_sheet_height_in,
+                binding: booklet_binding,
+                fold: booklet_fold,
+                collation_marks: booklet_collation_marks,
+                paper_thickness_in: booklet_paper_thickness_in,
+                // printer's marks are for print shops, so they're only kept, not asked about
+                marks: if non_interactive {
+                    template.as_ref().and_then(|t| t.pdf.as_ref())
+                } else {
+                    existing_pdf
+                }
+                .map(|p| p.booklet.marks.clone())
+                .unwrap_or_default(),
+                manual_duplex: booklet_manual_duplex,
             },
+            // likewise the cover and volumes, which are set up for a particular printer
+            cover: if non_interactive {
+                template.as_ref().and_then(|t| t.pdf.as_ref())
+            } else {
+                existing_pdf
+            }
+            .map(|p| p.cover.clone())
+            .unwrap_or_default(),
+            volumes: if non_interactive {
+                template.as_ref().and_then(|t| t.pdf.as_ref())
+            } else {
+                existing_pdf
+            }
+            .map(|p| p.volumes.clone())
+            .unwrap_or_default(),
             binary_hex: BinaryHexConfig {
                 enabled: render_binary_hex,
                 max_bytes: binary_hex_max_bytes,
                 font_size_pt: font_size_hex_pt,
             },
+            code,
             numbering: NumberingConfig {
                 commits: AppendixSectionNumbering {
                     prefix: commits_prefix,
@@ -80,6 +80,34 @@ pub fn detect_frontmatter(files: &[PathBuf]) -> Vec<PathBuf> {
     frontmatter
 }
 
+/// Detect guided tours: `tour.md` files among the repository files, and CodeTour
+/// files in the repository's `.tours` directory.
+///
+/// The `.tours` directory is hidden, so it isn't part of the file walk and is read
+/// directly. Returned paths are relative to the repository root, Markdown tours
+/// first, then CodeTour files sorted by name.
+pub fn detect_tours(repo_path: &Path, files: &[PathBuf]) -> Vec<PathBuf> {
+    let mut tours: Vec<PathBuf> = files
+        .iter()
+        .filter(|f| crate::tour::is_tour(f))
+        .cloned()
+        .collect();
+
+    let mut code_tours: Vec<PathBuf> = std::fs::read_dir(repo_path.join(".tours"))
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| PathBuf::from(".tours").join(entry.file_name()))
+                .filter(|path| crate::tour::is_tour(path))
+                .collect()
+        })
+        .unwrap_or_default();
+    code_tours.sort();
+    tours.extend(code_tours);
+
+    tours
+}
+
 /// Detect title from directory name.
 ///
 /// Transforms the directory name into a readable title by replacing
@@ -366,6 +394,28 @@ mod tests {
         assert_eq!(frontmatter[3], PathBuf::from("LICENSE"));
     }
 
+    #[test]
+    fn can_detect_tours() {
+        let dir = std::env::temp_dir().join(format!("src-book-tours-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join(".tours")).unwrap();
+        std::fs::write(dir.join(".tours/b.tour"), "{}").unwrap();
+        std::fs::write(dir.join(".tours/a.tour"), "{}").unwrap();
+        std::fs::write(dir.join(".tours/notes.txt"), "").unwrap();
+        let files = vec![PathBuf::from("src/main.rs"), PathBuf::from("docs/tour.md")];
+
+        let tours = detect_tours(&dir, &files);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            tours,
+            vec![
+                PathBuf::from("docs/tour.md"),
+                PathBuf::from(".tours/a.tour"),
+                PathBuf::from(".tours/b.tour"),
+            ]
+        );
+    }
+
     #[test]
     fn frontmatter_ignores_nested_files() {
         let files = vec![
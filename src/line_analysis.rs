@@ -22,11 +22,17 @@
 //!
 //! # Implementation Details
 //!
-//! Line length measurement treats tabs as 4 spaces (matching common editor behaviour)
-//! and counts characters rather than bytes. The 95th percentile is used as the
+//! Line length is measured the way the renderers lay lines out: tabs are expanded
+//! and indentation reflowed according to `[pdf.code]` (see [`crate::whitespace`]),
+//! and characters rather than bytes are counted. Wrapped lines are broken with the
+//! listings' own algorithm ([`crate::wrapping`]), so the number of extra rows
+//! reported is the number the book will print. The 95th percentile is used as the
 //! optimisation target rather than the maximum because a few extremely long lines
 //! (often comments or generated code) shouldn't force the entire book to use tiny fonts.
 
+use crate::sinks::pdf::CodeConfig;
+use crate::whitespace;
+use crate::wrapping;
 use anyhow::Result;
 use indicatif::{ProgressBar, ProgressStyle};
 use std::path::{Path, PathBuf};
@@ -36,6 +42,8 @@ use std::path::{Path, PathBuf};
 pub struct LineStats {
     pub total_lines: usize,
     pub lines_that_wrap: usize,
+    /// Continuation rows the wrapped lines add
+    pub wrapped_rows: usize,
     pub longest_line_length: usize,
     pub longest_line_file: PathBuf,
     pub longest_line_number: usize,
@@ -57,8 +65,9 @@ impl LineStats {
 
 /// Analyses line lengths in source files and reports wrapping statistics.
 ///
-/// Reads all source files, measures visual line length (treating tabs as 4 spaces),
-/// and calculates statistics about how many lines would wrap given the max_chars_per_line.
+/// Reads all source files, measures visual line length (with whitespace handled as
+/// `code` asks), and calculates statistics about how many lines would wrap given the
+/// max_chars_per_line.
 ///
 /// Binary files (those that can't be read as UTF-8) are silently skipped, matching the
 /// behaviour of the EPUB renderer.
@@ -68,17 +77,19 @@ impl LineStats {
 /// - `source_files`: Paths to source files relative to the repository root
 /// - `repository_path`: Absolute path to the repository root directory
 /// - `max_chars_per_line`: Character limit to check against
+/// - `code`: Tab width, indentation and wrapping settings the listings will use
 ///
 /// # Returns
 ///
-/// Statistics including total lines, lines that wrap, longest line location, and
-/// 95th percentile line length. The 95th percentile is particularly useful as an
-/// optimisation target since it ignores outliers (e.g., extremely long generated lines)
-/// that would otherwise force unnecessarily small fonts.
+/// Statistics including total lines, lines that wrap and the rows they add, longest
+/// line location, and 95th percentile line length. The 95th percentile is particularly
+/// useful as an optimisation target since it ignores outliers (e.g., extremely long
+/// generated lines) that would otherwise force unnecessarily small fonts.
 pub fn analyze_line_lengths(
     source_files: &[PathBuf],
     repository_path: &Path,
     max_chars_per_line: usize,
+    code: &CodeConfig,
 ) -> Result<LineStats> {
     let pb = ProgressBar::new(source_files.len() as u64);
     pb.set_style(
@@ -89,8 +100,37 @@ pub fn analyze_line_lengths(
     );
     pb.set_message("Analyzing files...");
 
+    let stats = measure_lines(source_files, repository_path, max_chars_per_line, code, &pb);
+    pb.finish_and_clear();
+    stats
+}
+
+/// [`analyze_line_lengths`] without the progress bar, for use while rendering.
+pub fn line_stats(
+    source_files: &[PathBuf],
+    repository_path: &Path,
+    max_chars_per_line: usize,
+    code: &CodeConfig,
+) -> Result<LineStats> {
+    measure_lines(
+        source_files,
+        repository_path,
+        max_chars_per_line,
+        code,
+        &ProgressBar::hidden(),
+    )
+}
+
+fn measure_lines(
+    source_files: &[PathBuf],
+    repository_path: &Path,
+    max_chars_per_line: usize,
+    code: &CodeConfig,
+    pb: &ProgressBar,
+) -> Result<LineStats> {
     let mut total_lines = 0;
     let mut lines_that_wrap = 0;
+    let mut wrapped_rows = 0;
     let mut longest_line_length = 0;
     let mut longest_line_file = PathBuf::new();
     let mut longest_line_number = 0;
@@ -112,15 +152,16 @@ pub fn analyze_line_lengths(
         for (line_num, line) in contents.lines().enumerate() {
             total_lines += 1;
 
-            // calculate visual width (tabs = 4 spaces)
-            let visual_length = line
-                .chars()
-                .fold(0, |acc, c| if c == '\t' { acc + 4 } else { acc + 1 });
+            // calculate visual width as laid out
+            let visual_length = whitespace::visual_width(line, code);
 
             all_line_lengths.push(visual_length);
 
-            if visual_length > max_chars_per_line {
+            let expanded = whitespace::expand_line(line, code);
+            let rows = wrapping::row_count(&expanded, max_chars_per_line, code.wrap_indent);
+            if rows > 1 {
                 lines_that_wrap += 1;
+                wrapped_rows += rows - 1;
             }
 
             if visual_length > longest_line_length {
@@ -131,8 +172,6 @@ pub fn analyze_line_lengths(
         }
     }
 
-    pb.finish_and_clear();
-
     // calculate 95th percentile
     all_line_lengths.sort_unstable();
     let percentile_95_idx = (all_line_lengths.len() as f64 * 0.95) as usize;
@@ -144,6 +183,7 @@ pub fn analyze_line_lengths(
     Ok(LineStats {
         total_lines,
         lines_that_wrap,
+        wrapped_rows,
         longest_line_length,
         longest_line_file,
         longest_line_number,
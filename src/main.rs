@@ -13,8 +13,10 @@ use anyhow::{Context, Result};
 use cli::Cli;
 use config_wizard::Configuration;
 use indicatif::{ProgressBar, ProgressStyle};
+use sinks::{Binding, DuplexFlip, Fold, ManualDuplex, PDF};
 use std::process::ExitCode;
 
+mod boilerplate;
 mod character_width;
 mod cli;
 mod config_wizard;
@@ -23,20 +25,27 @@ mod file_ordering;
 mod formatting;
 mod highlight;
 mod line_analysis;
+mod markdown;
+mod notebook;
 mod sinks {
     pub mod epub;
     pub mod pdf;
     pub use epub::EPUB;
     pub use pdf::{
         default_colophon_template, default_title_page_template, AppendixSectionNumbering,
-        BinaryHexConfig, BookletConfig, ColophonConfig, FontSizesConfig, FooterConfig,
-        HeaderConfig, InlineTagsConfig, MarginsConfig, MetadataConfig, NumberingConfig, PageConfig,
-        PageSize, Position, RulePosition, SyntaxTheme, TagsAppendixConfig, TitlePageConfig,
+        BinaryHexConfig, Binding, BookletConfig, ColophonConfig, DuplexFlip, Fold, FontSizesConfig,
+        FooterConfig, HeaderConfig, InlineTagsConfig, ManualDuplex, ManualDuplexConfig,
+        MarginsConfig, MetadataConfig, NumberingConfig, Orientation, PageConfig, PageSize,
+        Position, RulePosition, SyntaxTheme, TagsAppendixConfig, TitlePageConfig,
         TitlePageImagePosition, PDF,
     };
 }
 mod source;
+mod syntax_analysis;
+mod tour;
 mod update;
+mod whitespace;
+mod wrapping;
 
 fn main() -> ExitCode {
     if let Err(e) = try_main() {
@@ -68,6 +77,8 @@ fn try_main() -> Result<()> {
             }
 
             let Configuration { source, pdf, epub } = config;
+            // the EPUB's listings follow the PDF's [pdf.code] settings, if there are any
+            let code = pdf.as_ref().map(|p| p.code.clone()).unwrap_or_default();
 
             // display layout capacity before rendering so users know what to expect
             // this helps identify potential readability issues (line wrapping) before
@@ -86,8 +97,9 @@ fn try_main() -> Result<()> {
                 let left_margin_pt = pdf_config.margins.inner_in * 72.0;
                 let right_margin_pt = pdf_config.margins.outer_in * 72.0;
 
+                let (page_width_in, page_height_in) = pdf_config.page.size_in();
                 let max_chars = character_width::calculate_max_chars_per_line(
-                    pdf_config.page.width_in,
+                    page_width_in,
                     left_margin_pt,
                     right_margin_pt,
                     &fonts.regular,
@@ -100,7 +112,7 @@ fn try_main() -> Result<()> {
                 );
                 println!(
                     "     Page: {}\" × {}\" (accounts for 6-character line numbers)",
-                    pdf_config.page.width_in, pdf_config.page.height_in
+                    page_width_in, page_height_in
                 );
             }
 
@@ -112,7 +124,9 @@ fn try_main() -> Result<()> {
             println!();
 
             if let Some(pdf) = pdf {
-                let total_files = source.frontmatter_files.len() + source.source_files.len();
+                let total_files = source.frontmatter_files.len()
+                    + source.tour_files.len()
+                    + source.source_files.len();
                 let progress = ProgressBar::new(total_files as u64);
                 progress.set_style(
                     ProgressStyle::default_bar()
@@ -129,41 +143,56 @@ fn try_main() -> Result<()> {
                 println!();
                 println!("  Main PDF:    {}", pdf.outfile.display());
 
-                if let (Some(booklet_path), Some(sheets)) =
-                    (pdf.booklet_outfile_path(), stats.booklet_sheets)
+                if let (Some(two_up_path), Some(sheets)) =
+                    (pdf.two_up_outfile_path(), stats.two_up_sheets)
                 {
-                    println!("  Booklet PDF: {}\n", booklet_path.display());
-
-                    let booklet_pages = stats.page_count / 2 + stats.page_count % 2;
-                    let sheets_per_sig = pdf.booklet.signature_size / 4;
-                    let booklet_pages_per_sig = pdf.booklet.signature_size / 2;
-
-                    println!("Booklet info:");
-                    println!("  Original pages:   {}", stats.page_count);
-                    println!(
-                        "  Booklet pages:    {} (2 original pages per booklet page)",
-                        booklet_pages
-                    );
+                    println!("  2-up PDF:    {}", two_up_path.display());
                     println!(
-                        "  Sheets needed:    {} (4 original pages per sheet)",
+                        "  2-up sheets: {} (print single-sided, one spread per sheet)",
                         sheets
                     );
-                    println!(
-                        "  Signature size:   {} original pages ({} sheets per signature)\n",
-                        pdf.booklet.signature_size, sheets_per_sig
-                    );
+                }
 
-                    println!("To print the booklet:");
-                    println!("  1. Print double-sided, flip on short edge");
+                for (i, volume) in stats.volumes.iter().enumerate() {
                     println!(
-                        "  2. Print {} booklet pages at a time (one {}-page signature = {} sheets)",
-                        booklet_pages_per_sig, pdf.booklet.signature_size, sheets_per_sig
+                        "  Volume {}:    {} ({} pages)",
+                        i + 1,
+                        volume.outfile.display(),
+                        volume.page_count
                     );
+                    if let Some((booklet_path, sheets)) = &volume.booklet {
+                        println!(
+                            "    Booklet:   {} ({} sheets)",
+                            booklet_path.display(),
+                            sheets
+                        );
+                    }
+                    if let Some(spine_in) = volume.cover_spine_in {
+                        println!("    Spine:     {:.3} in", spine_in);
+                    }
+                }
+
+                if let (Some(cover_path), Some(spine_in)) =
+                    (pdf.cover_outfile_path(), stats.cover_spine_in)
+                {
+                    println!("  Cover PDF:   {}", cover_path.display());
                     println!(
-                        "  3. For each signature: nest the {} sheets together and fold in half",
-                        sheets_per_sig
+                        "  Cover spine: {:.3} in ({} pages at {} in a leaf)",
+                        spine_in, stats.page_count, pdf.cover.paper_caliper_in
                     );
-                    println!("  4. Stack all signatures and sew/staple along the spine");
+                }
+
+                if let (Some(booklet_path), Some(sheets)) =
+                    (pdf.booklet_outfile_path(), stats.booklet_sheets)
+                {
+                    if let Some((fronts_path, backs_path)) = pdf.booklet_split_paths() {
+                        println!("  Booklet PDF: {} (fronts)", fronts_path.display());
+                        println!("               {} (backs)\n", backs_path.display());
+                    } else {
+                        println!("  Booklet PDF: {}\n", booklet_path.display());
+                    }
+
+                    print_booklet_info(&pdf, stats.page_count, sheets);
                 }
             } else {
                 println!("No PDF output configured.");
@@ -171,7 +200,9 @@ fn try_main() -> Result<()> {
 
             // render EPUB if configured
             if let Some(epub) = epub {
-                let total_files = source.frontmatter_files.len() + source.source_files.len();
+                let total_files = source.frontmatter_files.len()
+                    + source.tour_files.len()
+                    + source.source_files.len();
                 let progress = ProgressBar::new(total_files as u64);
                 progress.set_style(
                     ProgressStyle::default_bar()
@@ -182,7 +213,7 @@ fn try_main() -> Result<()> {
                 progress.set_message("Rendering EPUB...");
 
                 let stats = epub
-                    .render(&source, &progress)
+                    .render(&source, &code, &progress)
                     .with_context(|| "Failed to render EPUB")?;
 
                 println!();
@@ -197,3 +228,136 @@ fn try_main() -> Result<()> {
         }
     }
 }
+
+/// The first step of printing a booklet, which depends on whether the printer
+/// duplexes.
+fn duplex_step(pdf: &PDF) -> &'static str {
+    match pdf.booklet.manual_duplex.mode {
+        ManualDuplex::Off => "Print double-sided, flip on short edge",
+        ManualDuplex::SingleFile | ManualDuplex::SeparateFiles => {
+            "Print the fronts, then the backs, as described below"
+        }
+    }
+}
+
+/// How to print a booklet's backs on a printer that doesn't duplex.
+fn print_manual_duplex_steps(pdf: &PDF, sheets: usize) {
+    let duplex = &pdf.booklet.manual_duplex;
+    let (fronts, backs) = match pdf.booklet_split_paths() {
+        Some((fronts_path, backs_path)) => (
+            fronts_path.display().to_string(),
+            backs_path.display().to_string(),
+        ),
+        None => (
+            format!("pages 1-{} of the booklet PDF", sheets),
+            format!("pages {}-{}", sheets + 1, sheets * 2),
+        ),
+    };
+    let turn = match duplex.flip {
+        DuplexFlip::ShortEdge => "over from left to right, like turning a page",
+        DuplexFlip::LongEdge => "over from top to bottom, like turning a calendar",
+    };
+
+    println!("\nPrinting without duplex:");
+    println!("  1. Print the fronts: {}", fronts);
+    println!(
+        "  2. Without reordering the printed stack, turn it {}",
+        turn
+    );
+    println!("  3. Feed it back in and print the backs: {}", backs);
+    println!("     (they run last sheet first, so the stack needs no shuffling)");
+    println!("  If the backs come out upside down, toggle rotate_backs and print them again");
+}
+
+/// Print the booklet's page and sheet counts, and how to print and bind it.
+fn print_booklet_info(pdf: &PDF, page_count: usize, sheets: usize) {
+    println!("Booklet info:");
+    println!("  Original pages:   {}", page_count);
+
+    let fold = pdf.booklet.fold;
+    match pdf.booklet.binding {
+        Binding::SaddleStitch => {
+            let booklet_pages = page_count / 2 + page_count % 2;
+            let sheets_per_sig = pdf.booklet.signature_size / 4;
+            let booklet_pages_per_sig = pdf.booklet.signature_size / 2;
+
+            println!(
+                "  Booklet pages:    {} (2 original pages per booklet page)",
+                booklet_pages
+            );
+            println!(
+                "  Sheets needed:    {} (4 original pages per sheet)",
+                sheets
+            );
+            println!(
+                "  Signature size:   {} original pages ({} sheets per signature)",
+                pdf.booklet.signature_size, sheets_per_sig
+            );
+            if pdf.booklet.paper_thickness_in > 0.0 {
+                println!(
+                    "  Creep:            up to {:.3}\" on the innermost sheet ({}\" paper)\n",
+                    pdf.booklet.max_creep_in(),
+                    pdf.booklet.paper_thickness_in
+                );
+            } else {
+                println!("  Creep:            not compensated (set paper_thickness_in)\n");
+            }
+
+            println!("To print the booklet:");
+            println!("  1. {}", duplex_step(pdf));
+            println!(
+                "  2. Print {} booklet pages at a time (one {}-page signature = {} sheets)",
+                booklet_pages_per_sig, pdf.booklet.signature_size, sheets_per_sig
+            );
+            println!(
+                "  3. For each signature: nest the {} sheets together and fold in half",
+                sheets_per_sig
+            );
+            println!("  4. Stack all signatures and sew/staple along the spine");
+        }
+        Binding::Perfect => {
+            println!(
+                "  Signatures:       {} ({}, one sheet of {} original pages each)\n",
+                sheets,
+                fold,
+                fold.pages_per_sheet()
+            );
+
+            println!("To print the booklet:");
+            println!("  1. {}", duplex_step(pdf));
+            let folding = match fold {
+                Fold::Folio => "left half behind right",
+                Fold::Quarto => "top half behind bottom, then left behind right",
+                Fold::Octavo => "left behind right, top behind bottom, left behind right",
+            };
+            println!("  2. Fold each sheet {}", folding);
+            if pdf.booklet.collation_marks {
+                println!("  3. Stack the signatures in order (the spine marks form a staircase)");
+            } else {
+                println!("  3. Stack the signatures in order");
+            }
+            println!("  4. Glue along the spine, then trim the folds open");
+        }
+        Binding::CutAndStack => {
+            let (columns, rows) = fold.grid();
+            println!(
+                "  Sheets needed:    {} ({} original pages per sheet)\n",
+                sheets,
+                fold.pages_per_sheet()
+            );
+
+            println!("To print the booklet:");
+            println!("  1. {}", duplex_step(pdf));
+            println!(
+                "  2. Keeping the sheets in order, cut the stack into {} piles",
+                columns * rows
+            );
+            println!("  3. Stack the piles row by row from the top left, first on top");
+            println!("  4. Bind along the left edge");
+        }
+    }
+
+    if pdf.booklet.manual_duplex.mode != ManualDuplex::Off {
+        print_manual_duplex_steps(pdf, sheets);
+    }
+}
@@ -0,0 +1,445 @@
+//! Markdown parsing shared by the PDF and EPUB sinks.
+//!
+//! Documentation files (README, CONTRIBUTING, CHANGELOG) are parsed with
+//! pulldown-cmark into a small block tree that each sink renders in its own way:
+//! the PDF sink typesets it onto pages and the EPUB sink writes sanitized XHTML.
+//! Only the structure both sinks can present is kept; raw HTML, footnote
+//! definitions and metadata blocks are dropped.
+
+use pulldown_cmark::{CodeBlockKind, Event, HeadingLevel, Options, Parser, Tag, TagEnd};
+use std::path::{Path, PathBuf};
+
+/// Returns true if the path looks like a Markdown document.
+pub fn is_markdown(path: &Path) -> bool {
+    matches!(
+        path.extension()
+            .unwrap_or_default()
+            .to_ascii_lowercase()
+            .to_str()
+            .unwrap_or_default(),
+        "md" | "markdown"
+    )
+}
+
+/// Inline formatting applied to a run of text.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct InlineStyle {
+    pub bold: bool,
+    pub italic: bool,
+    pub code: bool,
+    pub link: bool,
+    pub strikethrough: bool,
+}
+
+/// Inline content within a paragraph, heading or table cell.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Inline {
+    Text(String, InlineStyle),
+    Image { url: String, alt: String },
+    LineBreak,
+}
+
+/// Block-level Markdown structure.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Block {
+    Heading(u8, Vec<Inline>),
+    Paragraph(Vec<Inline>),
+    Code {
+        lang: Option<String>,
+        code: String,
+    },
+    List {
+        start: Option<u64>,
+        items: Vec<Vec<Block>>,
+    },
+    Quote(Vec<Block>),
+    Table {
+        head: Vec<Vec<Inline>>,
+        rows: Vec<Vec<Vec<Inline>>>,
+    },
+    Rule,
+}
+
+/// Parse Markdown text into blocks.
+pub fn parse(markdown: &str) -> Vec<Block> {
+    let options =
+        Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TASKLISTS;
+    let mut events = Parser::new_ext(markdown, options);
+    parse_blocks(&mut events)
+}
+
+/// Gathers inline events into [`Inline`] runs, tracking nested emphasis.
+#[derive(Default)]
+struct InlineCollector {
+    inlines: Vec<Inline>,
+    style: InlineStyle,
+    /// Styles to restore when nested emphasis/strong/link tags close
+    stack: Vec<InlineStyle>,
+    /// Set while inside an image, where text events form the alt text
+    image: Option<(String, String)>,
+}
+
+impl InlineCollector {
+    /// Handle an inline event, returning false if the event isn't inline content.
+    fn push(&mut self, event: Event) -> bool {
+        match event {
+            Event::Text(text) => match &mut self.image {
+                Some((_, alt)) => alt.push_str(&text),
+                None => self.text(&text, self.style),
+            },
+            Event::Code(code) => self.text(
+                &code,
+                InlineStyle {
+                    code: true,
+                    ..self.style
+                },
+            ),
+            Event::SoftBreak => self.text(" ", self.style),
+            Event::HardBreak => self.inlines.push(Inline::LineBreak),
+            Event::TaskListMarker(checked) => {
+                self.text(if checked { "[x] " } else { "[ ] " }, self.style)
+            }
+            Event::FootnoteReference(label) => self.text(&format!("[{}]", label), self.style),
+            Event::InlineHtml(_) => {}
+            Event::Start(Tag::Emphasis) => {
+                self.stack.push(self.style);
+                self.style.italic = true;
+            }
+            Event::Start(Tag::Strong) => {
+                self.stack.push(self.style);
+                self.style.bold = true;
+            }
+            Event::Start(Tag::Strikethrough) => {
+                self.stack.push(self.style);
+                self.style.strikethrough = true;
+            }
+            Event::Start(Tag::Link { .. }) => {
+                self.stack.push(self.style);
+                self.style.link = true;
+            }
+            Event::Start(Tag::Image { dest_url, .. }) => {
+                self.image = Some((dest_url.to_string(), String::new()));
+            }
+            Event::End(TagEnd::Image) => {
+                if let Some((url, alt)) = self.image.take() {
+                    self.inlines.push(Inline::Image { url, alt });
+                }
+            }
+            Event::End(
+                TagEnd::Emphasis | TagEnd::Strong | TagEnd::Strikethrough | TagEnd::Link,
+            ) => {
+                self.style = self.stack.pop().unwrap_or_default();
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    /// Append text, merging with the previous run when the style matches.
+    fn text(&mut self, text: &str, style: InlineStyle) {
+        if let Some(Inline::Text(previous, previous_style)) = self.inlines.last_mut() {
+            if *previous_style == style {
+                previous.push_str(text);
+                return;
+            }
+        }
+        self.inlines.push(Inline::Text(text.to_string(), style));
+    }
+
+    fn take(&mut self) -> Vec<Inline> {
+        std::mem::take(&mut self.inlines)
+    }
+}
+
+/// Collect blocks until the event that closes the enclosing container.
+fn parse_blocks<'a, I: Iterator<Item = Event<'a>>>(events: &mut I) -> Vec<Block> {
+    let mut blocks = Vec::new();
+    // tight list items put inline content directly in the item, without a paragraph
+    let mut loose = InlineCollector::default();
+
+    while let Some(event) = events.next() {
+        let is_block = matches!(
+            event,
+            Event::Start(
+                Tag::Paragraph
+                    | Tag::Heading { .. }
+                    | Tag::CodeBlock(_)
+                    | Tag::List(_)
+                    | Tag::BlockQuote(_)
+                    | Tag::Table(_)
+                    | Tag::HtmlBlock
+                    | Tag::FootnoteDefinition(_)
+                    | Tag::MetadataBlock(_)
+            ) | Event::Rule
+                | Event::End(_)
+        );
+        if !is_block {
+            loose.push(event);
+            continue;
+        }
+
+        // an end tag that the inline collector owns (e.g. closing emphasis in a tight item)
+        if let Event::End(
+            TagEnd::Emphasis
+            | TagEnd::Strong
+            | TagEnd::Strikethrough
+            | TagEnd::Link
+            | TagEnd::Image,
+        ) = event
+        {
+            loose.push(event);
+            continue;
+        }
+
+        let inlines = loose.take();
+        if !inlines.is_empty() {
+            blocks.push(Block::Paragraph(inlines));
+        }
+
+        match event {
+            Event::Start(Tag::Paragraph) => blocks.push(Block::Paragraph(parse_inlines(events))),
+            Event::Start(Tag::Heading { level, .. }) => {
+                blocks.push(Block::Heading(heading_level(level), parse_inlines(events)))
+            }
+            Event::Start(Tag::CodeBlock(kind)) => {
+                let lang = match kind {
+                    CodeBlockKind::Fenced(info) => info
+                        .split(|c: char| c.is_whitespace() || c == ',')
+                        .next()
+                        .filter(|s| !s.is_empty())
+                        .map(str::to_string),
+                    CodeBlockKind::Indented => None,
+                };
+                let mut code = String::new();
+                for event in events.by_ref() {
+                    match event {
+                        Event::Text(text) => code.push_str(&text),
+                        Event::End(_) => break,
+                        _ => {}
+                    }
+                }
+                blocks.push(Block::Code { lang, code });
+            }
+            Event::Start(Tag::List(start)) => {
+                let mut items = Vec::new();
+                while let Some(event) = events.next() {
+                    match event {
+                        Event::Start(Tag::Item) => items.push(parse_blocks(events)),
+                        Event::End(_) => break,
+                        _ => {}
+                    }
+                }
+                blocks.push(Block::List { start, items });
+            }
+            Event::Start(Tag::BlockQuote(_)) => blocks.push(Block::Quote(parse_blocks(events))),
+            Event::Start(Tag::Table(_)) => blocks.push(parse_table(events)),
+            Event::Start(_) => skip_container(events),
+            Event::Rule => blocks.push(Block::Rule),
+            _ => break,
+        }
+    }
+
+    let inlines = loose.take();
+    if !inlines.is_empty() {
+        blocks.push(Block::Paragraph(inlines));
+    }
+    blocks
+}
+
+/// Collect inline content until the event that closes the paragraph, heading or cell.
+fn parse_inlines<'a, I: Iterator<Item = Event<'a>>>(events: &mut I) -> Vec<Inline> {
+    let mut collector = InlineCollector::default();
+    for event in events.by_ref() {
+        let is_end = matches!(event, Event::End(_));
+        if !collector.push(event) && is_end {
+            break;
+        }
+    }
+    collector.take()
+}
+
+fn parse_table<'a, I: Iterator<Item = Event<'a>>>(events: &mut I) -> Block {
+    let mut head = Vec::new();
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    while let Some(event) = events.next() {
+        match event {
+            Event::End(TagEnd::TableHead) => head = std::mem::take(&mut row),
+            Event::End(TagEnd::TableRow) => rows.push(std::mem::take(&mut row)),
+            Event::Start(Tag::TableCell) => row.push(parse_inlines(events)),
+            Event::End(TagEnd::Table) => break,
+            _ => {}
+        }
+    }
+    Block::Table { head, rows }
+}
+
+/// Skip over a container we don't render (raw HTML, footnote definitions, metadata).
+fn skip_container<'a, I: Iterator<Item = Event<'a>>>(events: &mut I) {
+    let mut depth = 0;
+    for event in events.by_ref() {
+        match event {
+            Event::Start(_) => depth += 1,
+            Event::End(_) if depth == 0 => break,
+            Event::End(_) => depth -= 1,
+            _ => {}
+        }
+    }
+}
+
+fn heading_level(level: HeadingLevel) -> u8 {
+    match level {
+        HeadingLevel::H1 => 1,
+        HeadingLevel::H2 => 2,
+        HeadingLevel::H3 => 3,
+        HeadingLevel::H4 => 4,
+        HeadingLevel::H5 => 5,
+        HeadingLevel::H6 => 6,
+    }
+}
+
+/// Concatenate the text of inline content, dropping formatting.
+pub fn plain_text(inlines: &[Inline]) -> String {
+    let mut text = String::new();
+    for inline in inlines {
+        match inline {
+            Inline::Text(t, _) => text.push_str(t),
+            Inline::Image { alt, .. } => text.push_str(alt),
+            Inline::LineBreak => text.push(' '),
+        }
+    }
+    text
+}
+
+/// Returns true for image URLs that can't be loaded from the repository.
+pub fn is_remote_url(url: &str) -> bool {
+    url.starts_with("http://")
+        || url.starts_with("https://")
+        || url.starts_with("data:")
+        || url.starts_with("//")
+}
+
+/// Resolve an image link against the directory of the Markdown file, or against
+/// the repository root if it starts with `/`, as GitHub does.
+///
+/// Query strings and fragments are dropped. Returns None for remote URLs, for
+/// files that don't exist, and for links that lead out of the repository (the
+/// working directory), so a `../` link can't pull in files from elsewhere.
+pub fn resolve_local_image(base_dir: &Path, url: &str) -> Option<PathBuf> {
+    let root = std::env::current_dir().ok()?;
+    resolve_image_within(&root, base_dir, url)
+}
+
+/// [`resolve_local_image`] with the repository root given.
+fn resolve_image_within(root: &Path, base_dir: &Path, url: &str) -> Option<PathBuf> {
+    if is_remote_url(url) {
+        return None;
+    }
+    let url = url.split(['?', '#']).next().unwrap_or_default();
+    if url.is_empty() {
+        return None;
+    }
+    let path = match url.strip_prefix('/') {
+        Some(from_root) => root.join(from_root.trim_start_matches('/')),
+        None => base_dir.join(url),
+    };
+    let canonical = path.canonicalize().ok()?;
+    let root = root.canonicalize().ok()?;
+    (canonical.starts_with(&root) && canonical.is_file()).then_some(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_image_links_inside_the_repository() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("repo");
+        std::fs::create_dir_all(root.join("docs")).unwrap();
+        std::fs::write(root.join("docs/inside.png"), "").unwrap();
+        std::fs::write(root.join("logo.png"), "").unwrap();
+        std::fs::write(dir.path().join("outside.png"), "").unwrap();
+
+        let base_dir = root.join("docs");
+        let inside = resolve_image_within(&root, &base_dir, "inside.png?raw=true");
+        let up_and_back = resolve_image_within(&root, &base_dir, "../docs/inside.png");
+        let from_root = resolve_image_within(&root, &base_dir, "/logo.png");
+        let outside = resolve_image_within(&root, &base_dir, "../../outside.png");
+        let missing = resolve_image_within(&root, &base_dir, "missing.png");
+
+        assert_eq!(inside, Some(base_dir.join("inside.png")));
+        assert_eq!(up_and_back, Some(base_dir.join("../docs/inside.png")));
+        assert_eq!(from_root, Some(root.join("logo.png")));
+        assert_eq!(outside, None);
+        assert_eq!(missing, None);
+    }
+
+    #[test]
+    fn can_parse_headings_and_paragraphs() {
+        let blocks = parse("# Title\n\nSome *emphasis* and `code`.\n");
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(
+            blocks[0],
+            Block::Heading(
+                1,
+                vec![Inline::Text("Title".to_string(), InlineStyle::default())]
+            )
+        );
+        let Block::Paragraph(inlines) = &blocks[1] else {
+            panic!("expected a paragraph");
+        };
+        assert_eq!(plain_text(inlines), "Some emphasis and code.");
+        assert!(inlines.iter().any(|i| matches!(
+            i,
+            Inline::Text(t, InlineStyle { italic: true, .. }) if t == "emphasis"
+        )));
+    }
+
+    #[test]
+    fn can_parse_tight_lists() {
+        let blocks = parse("- one\n- **two**\n");
+        let Block::List { start, items } = &blocks[0] else {
+            panic!("expected a list");
+        };
+        assert_eq!(*start, None);
+        assert_eq!(items.len(), 2);
+        assert!(matches!(&items[1][0], Block::Paragraph(inlines) if plain_text(inlines) == "two"));
+    }
+
+    #[test]
+    fn can_parse_code_blocks_and_tables() {
+        let blocks =
+            parse("```rust,ignore\nfn main() {}\n```\n\n| a | b |\n|---|---|\n| 1 | 2 |\n");
+        assert_eq!(
+            blocks[0],
+            Block::Code {
+                lang: Some("rust".to_string()),
+                code: "fn main() {}\n".to_string()
+            }
+        );
+        let Block::Table { head, rows } = &blocks[1] else {
+            panic!("expected a table");
+        };
+        assert_eq!(head.len(), 2);
+        assert_eq!(rows.len(), 1);
+        assert_eq!(plain_text(&rows[0][1]), "2");
+    }
+
+    #[test]
+    fn skips_raw_html_blocks() {
+        let blocks = parse("<p align=\"center\">logo</p>\n\ntext\n");
+        assert_eq!(blocks.len(), 1);
+        assert!(matches!(&blocks[0], Block::Paragraph(_)));
+    }
+
+    #[test]
+    fn treats_web_urls_as_remote() {
+        assert!(is_remote_url("https://example.com/logo.png"));
+        assert!(!is_remote_url("docs/logo.png"));
+        assert_eq!(
+            resolve_local_image(Path::new("."), "https://example.com/a.png"),
+            None
+        );
+    }
+}
@@ -0,0 +1,341 @@
+//! Jupyter notebook parsing shared by the PDF and EPUB sinks.
+//!
+//! `.ipynb` files are JSON documents (nbformat 4) holding a list of cells. Rather
+//! than listing the JSON, the sinks render each cell: Markdown cells as prose,
+//! code cells highlighted in the kernel's language with `In [n]` labels, and the
+//! outputs recorded when the notebook was last run.
+//!
+//! Image outputs are stored in the notebook as base64 (PNG, JPEG) or text (SVG).
+//! They're written out to a scratch directory by [`OutputImages`] so they can go
+//! through the same load-from-disk image path as image files in the repository;
+//! the PDF booklet in particular reloads images by path. The directory belongs to
+//! the render and is removed when it finishes.
+
+use anyhow::{Context, Result};
+use base64::Engine;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+
+/// Returns true if the path looks like a Jupyter notebook.
+pub fn is_notebook(path: &Path) -> bool {
+    path.extension()
+        .map(|e| e.eq_ignore_ascii_case("ipynb"))
+        .unwrap_or_default()
+}
+
+/// A parsed notebook.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Notebook {
+    /// Language of the notebook's kernel, used to pick a syntax for code cells
+    pub language: String,
+    pub cells: Vec<Cell>,
+}
+
+/// A notebook cell.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Cell {
+    Markdown(String),
+    Code {
+        execution_count: Option<u64>,
+        source: String,
+        outputs: Vec<Output>,
+    },
+    /// Raw cells are passed through untouched by Jupyter; we show them as plain text
+    Raw(String),
+}
+
+/// An output recorded for a code cell.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Output {
+    /// Text written to stdout or stderr
+    Stream { name: String, text: String },
+    /// The value of the cell's last expression, as plain text
+    Result {
+        execution_count: Option<u64>,
+        text: String,
+    },
+    /// An image from a result or display call
+    Image {
+        extension: &'static str,
+        data: Vec<u8>,
+    },
+    /// An exception, with the traceback's terminal colour codes removed
+    Error(String),
+}
+
+/// Image MIME types we can embed, in order of preference, with file extensions.
+const IMAGE_TYPES: [(&str, &str); 3] = [
+    ("image/png", "png"),
+    ("image/svg+xml", "svg"),
+    ("image/jpeg", "jpg"),
+];
+
+/// Read and parse a notebook file.
+pub fn load(path: &Path) -> Result<Notebook> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read notebook {}", path.display()))?;
+    parse(&contents).with_context(|| format!("Failed to parse notebook {}", path.display()))
+}
+
+/// Parse notebook JSON.
+pub fn parse(json: &str) -> Result<Notebook> {
+    let value: Value = serde_json::from_str(json).with_context(|| "Notebook is not valid JSON")?;
+
+    let metadata = &value["metadata"];
+    let language = metadata["kernelspec"]["language"]
+        .as_str()
+        .or_else(|| metadata["language_info"]["name"].as_str())
+        .unwrap_or("python")
+        .to_string();
+
+    let cells = value["cells"]
+        .as_array()
+        .with_context(|| "Notebook has no cells array (only nbformat 4 is supported)")?
+        .iter()
+        .map(parse_cell)
+        .collect::<Result<Vec<Cell>>>()?;
+
+    Ok(Notebook { language, cells })
+}
+
+fn parse_cell(cell: &Value) -> Result<Cell> {
+    let source = multiline_text(&cell["source"]);
+    match cell["cell_type"].as_str() {
+        Some("markdown") => Ok(Cell::Markdown(source)),
+        Some("code") => {
+            let outputs = cell["outputs"]
+                .as_array()
+                .map(|outputs| outputs.iter().filter_map(parse_output).collect())
+                .unwrap_or_default();
+            Ok(Cell::Code {
+                execution_count: cell["execution_count"].as_u64(),
+                source,
+                outputs,
+            })
+        }
+        Some("raw") => Ok(Cell::Raw(source)),
+        other => anyhow::bail!("Unknown cell type {:?}", other),
+    }
+}
+
+fn parse_output(output: &Value) -> Option<Output> {
+    match output["output_type"].as_str()? {
+        "stream" => Some(Output::Stream {
+            name: output["name"].as_str().unwrap_or("stdout").to_string(),
+            text: multiline_text(&output["text"]),
+        }),
+        kind @ ("execute_result" | "display_data") => {
+            let data = &output["data"];
+            for (mime, extension) in IMAGE_TYPES {
+                let Some(value) = data.get(mime) else {
+                    continue;
+                };
+                let text = multiline_text(value);
+                let bytes = if mime == "image/svg+xml" {
+                    Some(text.into_bytes())
+                } else {
+                    let encoded: String = text.split_whitespace().collect();
+                    base64::engine::general_purpose::STANDARD
+                        .decode(encoded)
+                        .ok()
+                };
+                if let Some(data) = bytes {
+                    return Some(Output::Image { extension, data });
+                }
+            }
+
+            let text = multiline_text(data.get("text/plain")?);
+            if kind == "execute_result" {
+                Some(Output::Result {
+                    execution_count: output["execution_count"].as_u64(),
+                    text,
+                })
+            } else {
+                Some(Output::Stream {
+                    name: "stdout".to_string(),
+                    text,
+                })
+            }
+        }
+        "error" => {
+            let traceback = output["traceback"]
+                .as_array()
+                .map(|lines| {
+                    lines
+                        .iter()
+                        .filter_map(Value::as_str)
+                        .map(strip_ansi)
+                        .collect::<Vec<_>>()
+                        .join("\n")
+                })
+                .unwrap_or_default();
+            let text = if traceback.is_empty() {
+                format!(
+                    "{}: {}",
+                    output["ename"].as_str().unwrap_or_default(),
+                    output["evalue"].as_str().unwrap_or_default()
+                )
+            } else {
+                traceback
+            };
+            Some(Output::Error(text))
+        }
+        _ => None,
+    }
+}
+
+/// Notebook text fields are either a string or a list of lines.
+fn multiline_text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Array(lines) => lines.iter().filter_map(Value::as_str).collect(),
+        _ => String::new(),
+    }
+}
+
+/// Remove ANSI escape sequences (colours in tracebacks).
+fn strip_ansi(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\u{1b}' && chars.peek() == Some(&'[') {
+            chars.next();
+            // skip parameters up to and including the final byte
+            for c in chars.by_ref() {
+                if ('@'..='~').contains(&c) {
+                    break;
+                }
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// Scratch directory for the image outputs of the notebooks in one render.
+///
+/// The directory and everything written to it are removed when this is dropped,
+/// so it should live until the last image has been loaded from it.
+pub struct OutputImages {
+    dir: tempfile::TempDir,
+}
+
+impl OutputImages {
+    pub fn new() -> Result<OutputImages> {
+        let dir = tempfile::Builder::new()
+            .prefix("src-book-notebook-outputs-")
+            .tempdir()
+            .with_context(|| "Failed to create directory for notebook outputs")?;
+        Ok(OutputImages { dir })
+    }
+
+    /// Write an image output to the scratch directory and return its path.
+    ///
+    /// The file name is derived from the notebook path and the cell and output
+    /// positions, so writing the same output twice reuses it.
+    pub fn write(
+        &self,
+        notebook: &Path,
+        cell: usize,
+        output: usize,
+        extension: &str,
+        data: &[u8],
+    ) -> Result<PathBuf> {
+        let stem: String = notebook
+            .to_string_lossy()
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+        let path = self
+            .dir
+            .path()
+            .join(format!("{}-{}-{}.{}", stem, cell, output, extension));
+        std::fs::write(&path, data)
+            .with_context(|| format!("Failed to write notebook output {}", path.display()))?;
+        Ok(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOTEBOOK: &str = r##"{
+        "metadata": {"kernelspec": {"language": "python", "name": "python3"}},
+        "nbformat": 4,
+        "cells": [
+            {"cell_type": "markdown", "metadata": {}, "source": ["# Title\n", "Some text"]},
+            {
+                "cell_type": "code",
+                "execution_count": 3,
+                "metadata": {},
+                "source": "print('hi')\n1 + 1",
+                "outputs": [
+                    {"output_type": "stream", "name": "stdout", "text": ["hi\n"]},
+                    {"output_type": "execute_result", "execution_count": 3,
+                     "data": {"text/plain": ["2"]}, "metadata": {}},
+                    {"output_type": "display_data", "metadata": {},
+                     "data": {"image/png": "aGVs\nbG8=", "text/plain": ["<Figure>"]}},
+                    {"output_type": "error", "ename": "ValueError", "evalue": "bad",
+                     "traceback": ["\u001b[0;31mValueError\u001b[0m: bad"]}
+                ]
+            }
+        ]
+    }"##;
+
+    #[test]
+    fn can_parse_cells() {
+        let notebook = parse(NOTEBOOK).expect("can parse notebook");
+        assert_eq!(notebook.language, "python");
+        assert_eq!(notebook.cells.len(), 2);
+        assert_eq!(
+            notebook.cells[0],
+            Cell::Markdown("# Title\nSome text".to_string())
+        );
+    }
+
+    #[test]
+    fn can_parse_outputs() {
+        let notebook = parse(NOTEBOOK).expect("can parse notebook");
+        let Cell::Code {
+            execution_count,
+            outputs,
+            ..
+        } = &notebook.cells[1]
+        else {
+            panic!("expected a code cell");
+        };
+        assert_eq!(*execution_count, Some(3));
+        assert_eq!(
+            outputs,
+            &vec![
+                Output::Stream {
+                    name: "stdout".to_string(),
+                    text: "hi\n".to_string()
+                },
+                Output::Result {
+                    execution_count: Some(3),
+                    text: "2".to_string()
+                },
+                Output::Image {
+                    extension: "png",
+                    data: b"hello".to_vec()
+                },
+                Output::Error("ValueError: bad".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn output_images_are_removed_with_the_render() {
+        let output_images = OutputImages::new().unwrap();
+        let path = output_images
+            .write(Path::new("docs/plot.ipynb"), 2, 0, "png", b"png")
+            .unwrap();
+        assert!(path.ends_with("docs_plot_ipynb-2-0.png"));
+        assert!(path.is_file());
+        drop(output_images);
+        assert!(!path.exists());
+    }
+}
@@ -87,6 +87,60 @@ impl Default for FontsConfig {
     }
 }
 
+/// Markdown frontmatter rendering configuration.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MarkdownConfig {
+    /// Convert Markdown frontmatter to reflowing XHTML chapters instead of
+    /// showing it as a preformatted listing.
+    pub enabled: bool,
+}
+
+impl Default for MarkdownConfig {
+    fn default() -> Self {
+        Self { enabled: true }
+    }
+}
+
+/// Literate layout configuration.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LiterateConfig {
+    /// Typeset documentation comments as prose paragraphs interleaved with the
+    /// code they document, instead of leaving them in the listing.
+    pub enabled: bool,
+    /// Treat every whole-line comment as prose, not just documentation comments.
+    /// Useful for languages whose syntaxes don't mark doc comments (Go, C, Java).
+    pub all_comments: bool,
+}
+
+/// Symbol index appendix configuration.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct IndexConfig {
+    /// Include an index of the functions, types, traits, classes and constants
+    /// defined in the source files, linking to their definitions.
+    pub enabled: bool,
+}
+
+/// Standard file header collapsing.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BoilerplateConfig {
+    /// Print the comment block repeated at the top of many files (usually a licence
+    /// header) once in the frontmatter, and collapse it to a one-line marker in
+    /// each listing.
+    pub enabled: bool,
+    /// Minimum number of files that must open with the same block for it to be
+    /// collapsed.
+    pub min_files: usize,
+}
+
+impl Default for BoilerplateConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            min_files: 3,
+        }
+    }
+}
+
 /// Configuration for displaying tags inline with commits.
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct InlineTagsConfig {
@@ -130,12 +184,24 @@ pub struct EPUB {
     /// Font configuration
     pub fonts: FontsConfig,
 
+    /// Markdown frontmatter rendering
+    #[serde(default)]
+    pub markdown: MarkdownConfig,
+    /// Literate layout for source files
+    #[serde(default)]
+    pub literate: LiterateConfig,
     /// Inline tag badges in commit history
     #[serde(default)]
     pub inline_tags: InlineTagsConfig,
     /// Tags appendix configuration
     #[serde(default)]
     pub tags_appendix: TagsAppendixConfig,
+    /// Symbol index appendix configuration
+    #[serde(default)]
+    pub index: IndexConfig,
+    /// Standard file header collapsing
+    #[serde(default)]
+    pub boilerplate: BoilerplateConfig,
 }
 
 impl Default for EPUB {
@@ -147,8 +213,12 @@ impl Default for EPUB {
             colophon: ColophonConfig::default(),
             metadata: MetadataConfig::default(),
             fonts: FontsConfig::default(),
+            markdown: MarkdownConfig::default(),
+            literate: LiterateConfig::default(),
             inline_tags: InlineTagsConfig::default(),
             tags_appendix: TagsAppendixConfig::default(),
+            index: IndexConfig::default(),
+            boilerplate: BoilerplateConfig::default(),
         }
     }
 }
@@ -4,6 +4,7 @@
 //! - Cover page with template placeholders and optional image
 //! - Table of contents with hierarchical navigation
 //! - Frontmatter files (README, LICENSE, etc.)
+//! - Guided tours from CodeTour files or a `tour.md`
 //! - Syntax-highlighted source files with line numbers
 //! - Commit history (if enabled)
 //! - Colophon with repository statistics and commit chart
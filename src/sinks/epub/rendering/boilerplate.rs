@@ -0,0 +1,30 @@
+//! The "Standard file header" frontmatter chapter for EPUB.
+//!
+//! The EPUB counterpart of the PDF page: with `[epub.boilerplate]` enabled, the
+//! header shared by many source files is shown once after the frontmatter, and each
+//! listing shows a one-line marker in its place. See [`crate::boilerplate`].
+
+use crate::boilerplate::{StandardHeader, TITLE};
+use crate::sinks::epub::rendering::markdown::{Chapter, Writer};
+use anyhow::Result;
+use std::path::Path;
+use syntect::highlighting::Theme;
+use syntect::parsing::SyntaxSet;
+
+/// Render the standard header as an XHTML chapter.
+pub fn render(header: &StandardHeader, ss: &SyntaxSet, theme: &Theme) -> Result<Chapter> {
+    let mut writer = Writer::new(ss, theme, Path::new(""), "standard-header");
+    writer.html.push_str("<div class=\"markdown\">\n");
+    writer.markdown(&format!(
+        "{} source files open with the comment below. It is shown once here; in each \
+         listing it is replaced by a one-line marker, and the lines that follow keep \
+         their original numbers.",
+        header.file_count
+    ))?;
+    writer.html.push_str(&format!(
+        "<pre><code>{}</code></pre>\n",
+        html_escape::encode_text(&header.text)
+    ));
+    writer.html.push_str("</div>\n");
+    Ok(writer.finish(TITLE))
+}
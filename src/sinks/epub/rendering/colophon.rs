@@ -140,7 +140,7 @@ fn compute_stats(source: &Source) -> ColophonStats {
     let mut language_lines: HashMap<String, usize> = HashMap::new();
 
     // count source files
-    for path in &source.source_files {
+    for path in source.source_files.iter().map(|f| &f.path) {
         let full_path = source.repository.join(path);
         if let Ok(metadata) = std::fs::metadata(&full_path) {
             total_bytes += metadata.len();
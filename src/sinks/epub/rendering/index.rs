@@ -0,0 +1,76 @@
+//! Symbol index appendix rendering for EPUB.
+//!
+//! Lists the symbols defined in the source files alphabetically under letter
+//! headings. E-readers have no page numbers to cite, so each entry links straight
+//! to the `L<n>` anchor on its definition's line instead.
+
+use crate::syntax_analysis::{compare_symbols, index_letter, SymbolKind};
+use anyhow::Result;
+use std::path::PathBuf;
+
+/// A symbol definition and where to find it in the EPUB.
+pub struct IndexEntry {
+    pub name: String,
+    pub kind: SymbolKind,
+    pub file: PathBuf,
+    /// Zero-based line index of the definition
+    pub line: usize,
+    /// Document containing the definition, e.g. `source-0003.xhtml`
+    pub document: String,
+}
+
+/// Render the symbol index as XHTML.
+pub fn render(title: &str, mut entries: Vec<IndexEntry>) -> Result<String> {
+    entries.sort_by(|a, b| {
+        compare_symbols(&a.name, &b.name)
+            .then_with(|| a.file.cmp(&b.file))
+            .then_with(|| a.line.cmp(&b.line))
+    });
+
+    let mut body = String::new();
+    let mut letter = None;
+    for entry in &entries {
+        let entry_letter = index_letter(&entry.name);
+        if letter != Some(entry_letter) {
+            if letter.is_some() {
+                body.push_str("</ul>\n");
+            }
+            letter = Some(entry_letter);
+            body.push_str(&format!(
+                "<h3 class=\"index-letter\">{}</h3>\n<ul class=\"symbol-index\">\n",
+                html_escape::encode_text(&entry_letter.to_string())
+            ));
+        }
+        body.push_str(&format!(
+            r#"<li><a href="{document}#L{line}">{name}</a> <span class="index-kind">{kind}</span> <span class="index-location">{file}:{line}</span></li>"#,
+            document = entry.document,
+            line = entry.line + 1,
+            name = html_escape::encode_text(&entry.name),
+            kind = entry.kind.label(),
+            file = html_escape::encode_text(&entry.file.display().to_string()),
+        ));
+        body.push('\n');
+    }
+    if letter.is_some() {
+        body.push_str("</ul>\n");
+    } else {
+        body.push_str("<p>No symbols found.</p>\n");
+    }
+
+    Ok(format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE html PUBLIC "-//W3C//DTD XHTML 1.1//EN" "http://www.w3.org/TR/xhtml11/DTD/xhtml11.dtd">
+<html xmlns="http://www.w3.org/1999/xhtml" xml:lang="en">
+<head>
+    <meta http-equiv="Content-Type" content="text/html; charset=UTF-8"/>
+    <title>Index - {title}</title>
+    <link rel="stylesheet" type="text/css" href="stylesheet.css"/>
+</head>
+<body>
+<h2>Index</h2>
+{body}</body>
+</html>"#,
+        title = html_escape::encode_text(title),
+        body = body,
+    ))
+}
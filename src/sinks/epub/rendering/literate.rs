@@ -0,0 +1,184 @@
+//! Literate source file rendering for EPUB.
+//!
+//! The EPUB counterpart of the PDF literate layout: documentation comments found
+//! by [`crate::syntax_analysis::literate_segments`] become reflowing Markdown
+//! prose, and the code between them is shown in `<pre>` blocks that keep their
+//! original line numbers and inline-colour highlighting. Excerpts keep prose only
+//! where the comment itself is among the selected lines, as in the PDF, and the
+//! standard file header is collapsed to a marker whether it's read as prose or code.
+//!
+//! Files without documentation comments, binary files and files without a known
+//! syntax fall back to the regular listing.
+
+use crate::boilerplate::without_header;
+use crate::sinks::epub::rendering::markdown::{Chapter, Writer};
+use crate::sinks::epub::rendering::source_file::{self, header_html, omission_html, push_code};
+use crate::sinks::epub::styles;
+use crate::sinks::pdf::CodeConfig;
+use crate::source::{Excerpt, Omissions};
+use crate::syntax_analysis::{literate_segments, segment_lines, Segment};
+use crate::whitespace;
+use anyhow::{Context, Result};
+use std::ops::Range;
+use std::path::Path;
+use syntect::easy::HighlightLines;
+use syntect::highlighting::Theme;
+use syntect::parsing::SyntaxSet;
+use syntect::util::LinesWithEndings;
+
+/// Which comments become prose and which lines of the file are shown.
+pub struct LiterateContext<'a> {
+    /// Read every comment as prose, not just documentation comments
+    pub all_comments: bool,
+    /// Names any images referenced from the comments, as in
+    /// [`super::markdown::render`]
+    pub resource_prefix: &'a str,
+    /// The lines to show, if the file is included as an excerpt
+    pub excerpt: Option<&'a Excerpt>,
+    /// Zero-based lines of the standard file header, collapsed to a marker
+    pub header: Option<Range<usize>>,
+}
+
+/// Render a source file as a chapter of prose interleaved with code, as
+/// `context` asks.
+pub fn render(
+    path: &Path,
+    title: &str,
+    ss: &SyntaxSet,
+    theme: &Theme,
+    code: &CodeConfig,
+    context: LiterateContext,
+) -> Result<Chapter> {
+    let LiterateContext {
+        all_comments,
+        resource_prefix,
+        excerpt,
+        header,
+    } = context;
+    let listing = || -> Result<Chapter> {
+        Ok(Chapter {
+            html: source_file::render(path, title, ss, theme, code, excerpt, header.clone())?,
+            headings: Vec::new(),
+            images: Vec::new(),
+        })
+    };
+
+    let Ok(contents) = std::fs::read_to_string(path) else {
+        return listing();
+    };
+    let Some(syntax) = ss.find_syntax_by_extension(
+        path.extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or_default(),
+    ) else {
+        return listing();
+    };
+
+    let contents = whitespace::expand(&contents, code);
+    // the collapsed header is emptied so it isn't lifted out as prose
+    let segment_source = match &header {
+        Some(header) => without_header(&contents, header),
+        None => contents.clone(),
+    };
+    let segments = literate_segments(&segment_source, syntax, ss, all_comments)
+        .with_context(|| format!("Failed to find comments in {}", path.display()))?;
+    if !segments.iter().any(|s| matches!(s, Segment::Prose(_))) {
+        return listing();
+    }
+
+    let selection = match excerpt {
+        Some(excerpt) => Some(excerpt.select(path, &contents, Some(syntax), ss)?),
+        None => None,
+    };
+    let shown = |lines: Range<usize>| selection.as_ref().is_none_or(|s| s.intersects(&lines));
+
+    // highlight every line so the parse state is right for code after each comment
+    let prefix = styles::scope_prefix();
+    let mut h = HighlightLines::new(syntax, theme);
+    let mut lines = Vec::new();
+    for (line_num, line) in LinesWithEndings::from(&contents).enumerate() {
+        let ranges = h
+            .highlight_line(line, ss)
+            .with_context(|| format!("Failed to highlight line {}", line_num + 1))?;
+        let mut html = format!(
+            r#"<span class="line-number" id="L{}">{:>4}</span>"#,
+            line_num + 1,
+            line_num + 1
+        );
+        push_code(&mut html, &ranges, prefix, code);
+        if !line.ends_with('\n') {
+            html.push('\n');
+        }
+        lines.push(html);
+    }
+
+    let base_dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+    let mut writer = Writer::new(ss, theme, &base_dir, resource_prefix);
+    let mut omissions = Omissions::default();
+    let in_header = |lines: &Range<usize>| {
+        header
+            .as_ref()
+            .is_some_and(|h| h.start < lines.end && lines.start < h.end)
+    };
+    let mut header_placed = false;
+    for (segment, range) in segments.iter().zip(segment_lines(&segments, lines.len())) {
+        if !shown(range.clone()) {
+            omissions.skip(range.start);
+            continue;
+        }
+        match segment {
+            Segment::Prose(text) => {
+                if let Some(run) = omissions.show(range.start) {
+                    writer.html.push_str(&format!(
+                        "<pre><code>{}</code></pre>\n",
+                        omission_html(&run)
+                    ));
+                }
+                if in_header(&range) && !header_placed {
+                    let header = header.as_ref().expect("file has a header");
+                    writer.html.push_str(&format!(
+                        "<pre><code>{}</code></pre>\n",
+                        header_html(header)
+                    ));
+                    header_placed = true;
+                }
+                writer
+                    .html
+                    .push_str("<div class=\"markdown literate-prose\">\n");
+                writer.markdown(text)?;
+                writer.html.push_str("</div>\n");
+            }
+            Segment::Code { start, end } => {
+                writer.html.push_str("<pre><code>");
+                for (n, line) in lines.iter().enumerate().take(*end).skip(*start) {
+                    if !shown(n..n + 1) {
+                        omissions.skip(n);
+                        continue;
+                    }
+                    if let Some(run) = omissions.show(n) {
+                        writer.html.push_str(&omission_html(&run));
+                        writer.html.push('\n');
+                    }
+                    if in_header(&(n..n + 1)) {
+                        if !header_placed {
+                            let header = header.as_ref().expect("file has a header");
+                            writer.html.push_str(&header_html(header));
+                            writer.html.push('\n');
+                            header_placed = true;
+                        }
+                        continue;
+                    }
+                    writer.html.push_str(line);
+                }
+                writer.html.push_str("</code></pre>\n");
+            }
+        }
+    }
+    if let Some(run) = omissions.finish(lines.len()) {
+        writer.html.push_str(&format!(
+            "<pre><code>{}</code></pre>\n",
+            omission_html(&run)
+        ));
+    }
+    Ok(writer.finish(title))
+}
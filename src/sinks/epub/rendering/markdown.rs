@@ -0,0 +1,337 @@
+//! Markdown rendering as reflowable XHTML for EPUB.
+//!
+//! Markdown frontmatter is converted to prose chapters instead of being wrapped in
+//! a `<pre>` block. The XHTML is generated from the parsed [`crate::markdown`]
+//! block tree rather than from the source text, so raw HTML in the document never
+//! reaches the output and every piece of text is escaped.
+//!
+//! Headings get anchor ids and are returned alongside the XHTML so the caller can
+//! add them to the EPUB navigation. Fenced code blocks use the same inline-colour
+//! highlighting as source files. Relative image links are returned as resources to
+//! package into the EPUB, with the `src` rewritten to the packaged path; remote and
+//! missing images fall back to their alt text.
+
+use crate::markdown::{parse, plain_text, resolve_local_image, Block, Inline};
+use crate::sinks::epub::rendering::source_file::push_highlighted;
+use crate::sinks::epub::styles;
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use syntect::easy::HighlightLines;
+use syntect::highlighting::Theme;
+use syntect::parsing::SyntaxSet;
+use syntect::util::LinesWithEndings;
+
+/// A heading in a Markdown chapter, for the EPUB navigation.
+pub struct Heading {
+    pub level: u8,
+    pub title: String,
+    /// Anchor id of the heading within the chapter
+    pub id: String,
+}
+
+/// An image referenced by a Markdown chapter, to be added as an EPUB resource.
+pub struct ImageResource {
+    /// Path of the image on disk
+    pub source: PathBuf,
+    /// Path of the image inside the EPUB, as referenced from the chapter
+    pub href: String,
+}
+
+/// A rendered Markdown chapter.
+pub struct Chapter {
+    pub html: String,
+    pub headings: Vec<Heading>,
+    pub images: Vec<ImageResource>,
+}
+
+/// Render a Markdown file as an XHTML chapter.
+///
+/// `resource_prefix` names the chapter's packaged images (e.g. `frontmatter-0002`)
+/// so images from different documents never collide.
+pub fn render(
+    path: &Path,
+    title: &str,
+    ss: &SyntaxSet,
+    theme: &Theme,
+    resource_prefix: &str,
+) -> Result<Chapter> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    let base_dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+
+    let mut writer = Writer::new(ss, theme, &base_dir, resource_prefix);
+    writer.html.push_str("<div class=\"markdown\">\n");
+    writer.markdown(&contents)?;
+    writer.html.push_str("</div>\n");
+    Ok(writer.finish(title))
+}
+
+/// Wrap a chapter body in an XHTML document with the file name as a header.
+fn page(title: &str, body: &str) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE html PUBLIC "-//W3C//DTD XHTML 1.1//EN" "http://www.w3.org/TR/xhtml11/DTD/xhtml11.dtd">
+<html xmlns="http://www.w3.org/1999/xhtml" xml:lang="en">
+<head>
+    <meta http-equiv="Content-Type" content="text/html; charset=UTF-8"/>
+    <title>{title}</title>
+    <link rel="stylesheet" type="text/css" href="stylesheet.css"/>
+</head>
+<body>
+<div class="source-header">{title}</div>
+{body}</body>
+</html>"#,
+        title = html_escape::encode_text(title),
+        body = body,
+    )
+}
+
+/// Builds the XHTML body of a chapter from Markdown, collecting the headings and
+/// images it references along the way.
+pub struct Writer<'a> {
+    ss: &'a SyntaxSet,
+    theme: &'a Theme,
+    base_dir: &'a Path,
+    resource_prefix: &'a str,
+    /// The body written so far; callers may append their own markup between
+    /// Markdown sections
+    pub html: String,
+    headings: Vec<Heading>,
+    images: Vec<ImageResource>,
+    /// Packaged href for each image already added, so repeated images are stored once
+    image_hrefs: HashMap<PathBuf, String>,
+}
+
+impl<'a> Writer<'a> {
+    pub fn new(
+        ss: &'a SyntaxSet,
+        theme: &'a Theme,
+        base_dir: &'a Path,
+        resource_prefix: &'a str,
+    ) -> Self {
+        Self {
+            ss,
+            theme,
+            base_dir,
+            resource_prefix,
+            html: String::new(),
+            headings: Vec::new(),
+            images: Vec::new(),
+            image_hrefs: HashMap::new(),
+        }
+    }
+
+    /// Parse Markdown text and append it to the body.
+    pub fn markdown(&mut self, text: &str) -> Result<()> {
+        self.blocks(&parse(text))
+    }
+
+    /// Wrap the body in a page and return the finished chapter.
+    pub fn finish(self, title: &str) -> Chapter {
+        Chapter {
+            html: page(title, &self.html),
+            headings: self.headings,
+            images: self.images,
+        }
+    }
+
+    fn blocks(&mut self, blocks: &[Block]) -> Result<()> {
+        for block in blocks {
+            self.block(block)?;
+        }
+        Ok(())
+    }
+
+    fn block(&mut self, block: &Block) -> Result<()> {
+        match block {
+            Block::Heading(level, inlines) => {
+                let title = plain_text(inlines);
+                let id = self.heading_id(&title);
+                self.html
+                    .push_str(&format!("<h{level} id=\"{id}\">", level = level, id = id));
+                self.inlines(inlines);
+                self.html.push_str(&format!("</h{}>\n", level));
+                self.headings.push(Heading {
+                    level: *level,
+                    title,
+                    id,
+                });
+            }
+            Block::Paragraph(inlines) => {
+                self.html.push_str("<p>");
+                self.inlines(inlines);
+                self.html.push_str("</p>\n");
+            }
+            Block::Code { lang, code } => self.code(lang.as_deref(), code)?,
+            Block::List { start, items } => {
+                let tag = match start {
+                    Some(1) => {
+                        self.html.push_str("<ol>\n");
+                        "ol"
+                    }
+                    Some(n) => {
+                        self.html.push_str(&format!("<ol start=\"{}\">\n", n));
+                        "ol"
+                    }
+                    None => {
+                        self.html.push_str("<ul>\n");
+                        "ul"
+                    }
+                };
+                for item in items {
+                    self.html.push_str("<li>");
+                    self.blocks(item)?;
+                    self.html.push_str("</li>\n");
+                }
+                self.html.push_str(&format!("</{}>\n", tag));
+            }
+            Block::Quote(blocks) => {
+                self.html.push_str("<blockquote>\n");
+                self.blocks(blocks)?;
+                self.html.push_str("</blockquote>\n");
+            }
+            Block::Table { head, rows } => {
+                self.html.push_str("<table>\n");
+                if !head.is_empty() {
+                    self.html.push_str("<thead><tr>");
+                    for cell in head {
+                        self.html.push_str("<th>");
+                        self.inlines(cell);
+                        self.html.push_str("</th>");
+                    }
+                    self.html.push_str("</tr></thead>\n");
+                }
+                self.html.push_str("<tbody>\n");
+                for row in rows {
+                    self.html.push_str("<tr>");
+                    for cell in row {
+                        self.html.push_str("<td>");
+                        self.inlines(cell);
+                        self.html.push_str("</td>");
+                    }
+                    self.html.push_str("</tr>\n");
+                }
+                self.html.push_str("</tbody>\n</table>\n");
+            }
+            Block::Rule => self.html.push_str("<hr/>\n"),
+        }
+        Ok(())
+    }
+
+    /// Build a unique anchor id from a heading's text.
+    fn heading_id(&self, title: &str) -> String {
+        let mut slug = String::from("md-");
+        for c in title.chars() {
+            if c.is_ascii_alphanumeric() {
+                slug.push(c.to_ascii_lowercase());
+            } else if !slug.ends_with('-') {
+                slug.push('-');
+            }
+        }
+        let slug = slug.trim_end_matches('-').to_string();
+
+        let taken = |id: &str| self.headings.iter().any(|h| h.id == id);
+        if !taken(&slug) {
+            return slug;
+        }
+        (2..)
+            .map(|n| format!("{}-{}", slug, n))
+            .find(|id| !taken(id))
+            .expect("an unused id exists")
+    }
+
+    fn inlines(&mut self, inlines: &[Inline]) {
+        for inline in inlines {
+            match inline {
+                Inline::Text(text, style) => {
+                    let mut open = Vec::new();
+                    if style.link {
+                        open.push(("span class=\"md-link\"", "span"));
+                    }
+                    if style.bold {
+                        open.push(("strong", "strong"));
+                    }
+                    if style.italic {
+                        open.push(("em", "em"));
+                    }
+                    if style.strikethrough {
+                        open.push(("del", "del"));
+                    }
+                    if style.code {
+                        open.push(("code", "code"));
+                    }
+                    for (tag, _) in &open {
+                        self.html.push_str(&format!("<{}>", tag));
+                    }
+                    self.html.push_str(&html_escape::encode_text(text));
+                    for (_, tag) in open.iter().rev() {
+                        self.html.push_str(&format!("</{}>", tag));
+                    }
+                }
+                Inline::Image { url, alt } => self.image(url, alt),
+                Inline::LineBreak => self.html.push_str("<br/>"),
+            }
+        }
+    }
+
+    fn image(&mut self, url: &str, alt: &str) {
+        let Some(source) = resolve_local_image(self.base_dir, url) else {
+            let label = if alt.is_empty() { url } else { alt };
+            self.html.push_str(&format!(
+                "<span class=\"md-image-alt\">[image: {}]</span>",
+                html_escape::encode_text(label)
+            ));
+            return;
+        };
+        self.image_file(source, alt);
+    }
+
+    /// Package an image from disk and reference it from the body.
+    pub fn image_file(&mut self, source: PathBuf, alt: &str) {
+        let href = match self.image_hrefs.get(&source) {
+            Some(href) => href.clone(),
+            None => {
+                let extension = source
+                    .extension()
+                    .map(|e| e.to_string_lossy().to_ascii_lowercase())
+                    .unwrap_or_default();
+                let href = format!(
+                    "images/{}-{:02}.{}",
+                    self.resource_prefix,
+                    self.images.len(),
+                    extension
+                );
+                self.image_hrefs.insert(source.clone(), href.clone());
+                self.images.push(ImageResource {
+                    source,
+                    href: href.clone(),
+                });
+                href
+            }
+        };
+        self.html.push_str(&format!(
+            "<img src=\"{}\" alt=\"{}\"/>",
+            html_escape::encode_double_quoted_attribute(&href),
+            html_escape::encode_double_quoted_attribute(alt)
+        ));
+    }
+
+    /// Append a highlighted code block.
+    pub fn code(&mut self, lang: Option<&str>, code: &str) -> Result<()> {
+        let syntax = lang
+            .and_then(|lang| self.ss.find_syntax_by_token(lang))
+            .unwrap_or_else(|| self.ss.find_syntax_plain_text());
+        let mut h = HighlightLines::new(syntax, self.theme);
+
+        self.html.push_str("<pre><code>");
+        for line in LinesWithEndings::from(code) {
+            let ranges = h
+                .highlight_line(line, self.ss)
+                .with_context(|| format!("Failed to highlight code block line `{}`", line))?;
+            push_highlighted(&mut self.html, &ranges, styles::scope_prefix());
+        }
+        self.html.push_str("</code></pre>\n");
+        Ok(())
+    }
+}
@@ -4,30 +4,56 @@
 //! commit history, and colophon. Uses the `epub-builder` crate which handles
 //! the complex EPUB packaging requirements (OPF manifest, NCX navigation, ZIP
 //! structure with proper MIME type). Each source file becomes a separate XHTML
-//! document for efficient navigation on e-readers.
+//! document for efficient navigation on e-readers. Markdown frontmatter becomes
+//! a prose chapter whose headings are nested under the file in the navigation.
+//! Guided tours each become a chapter after the frontmatter, linking their steps
+//! to the source file chapters.
+//! With `[epub.boilerplate]` enabled, the comment block most source files open
+//! with gets a chapter of its own at the end of the frontmatter, and each listing
+//! collapses it to a marker.
+//! With `[epub.index]` enabled, the symbols defined in the source files are
+//! gathered into an index whose entries link to their definition lines.
 
+mod boilerplate;
 mod colophon;
 mod commits;
 mod cover;
+mod index;
+mod literate;
+mod markdown;
+mod notebook;
 mod source_file;
 mod tags;
 mod toc;
+mod tour;
 
 use super::config::{RenderStats, EPUB};
 use super::styles;
+use crate::boilerplate::StandardHeader;
+use crate::sinks::epub::rendering::literate::LiterateContext;
+use crate::sinks::epub::rendering::tour::TourContext;
+use crate::sinks::pdf::CodeConfig;
 use crate::source::{CommitOrder, Source};
 use anyhow::{Context, Result};
-use epub_builder::{EpubBuilder, EpubContent, ReferenceType, ZipLibrary};
+use epub_builder::{EpubBuilder, EpubContent, ReferenceType, TocElement, ZipLibrary};
 use indicatif::ProgressBar;
+use std::collections::HashMap;
 use std::fs::File;
 use std::io::BufWriter;
+use std::path::Path;
 use syntect::parsing::SyntaxSet;
 
 impl EPUB {
-    /// Render the source repository to an EPUB file.
+    /// Render the source repository to an EPUB file, laying out listings following
+    /// `code`, the PDF's `[pdf.code]` settings, so both books agree.
     ///
     /// Returns statistics about the generated EPUB.
-    pub fn render(&self, source: &Source, progress: &ProgressBar) -> Result<RenderStats> {
+    pub fn render(
+        &self,
+        source: &Source,
+        code: &CodeConfig,
+        progress: &ProgressBar,
+    ) -> Result<RenderStats> {
         progress.set_message("Generating EPUB...");
 
         // load syntax highlighting assets
@@ -42,6 +68,9 @@ impl EPUB {
         // generate stylesheet
         let stylesheet = styles::generate_stylesheet(&theme, &self.fonts.family);
 
+        // notebook image outputs are written here until they're packaged
+        let output_images = crate::notebook::OutputImages::new()?;
+
         // create epub builder
         let zip = ZipLibrary::new().with_context(|| "Failed to create ZIP library for EPUB")?;
         let mut builder = EpubBuilder::new(zip).with_context(|| "Failed to build builder")?;
@@ -132,8 +161,27 @@ impl EPUB {
             document_count += 1;
         }
 
+        // tours are loaded up front, the table of contents lists them by title
+        let tours = source
+            .tour_files
+            .iter()
+            .map(|path| crate::tour::load(&source.repository.join(path)))
+            .collect::<Result<Vec<_>>>()?;
+
+        let standard_header = if self.boilerplate.enabled {
+            StandardHeader::detect(
+                &source.repository,
+                &source.source_paths(),
+                &ss,
+                self.boilerplate.min_files,
+            )
+            .with_context(|| "Failed to find the standard file header")?
+        } else {
+            None
+        };
+
         // add table of contents page
-        let toc_html = toc::render(source)?;
+        let toc_html = toc::render(source, &tours, standard_header.is_some())?;
         builder
             .add_content(
                 EpubContent::new("toc.xhtml", toc_html.as_bytes())
@@ -150,31 +198,152 @@ impl EPUB {
             let file_path = source.repository.join(path);
             let title = path.display().to_string();
 
-            let html = source_file::render(&file_path, &title, &ss, &theme)?;
-            builder
-                .add_content(EpubContent::new(&filename, html.as_bytes()).title(&title))
-                .with_context(|| {
+            if self.markdown.enabled && crate::markdown::is_markdown(path) {
+                let prefix = format!("frontmatter-{:04}", i);
+                let chapter = markdown::render(&file_path, &title, &ss, &theme, &prefix)?;
+                add_chapter(&mut builder, &filename, &title, chapter).with_context(|| {
+                    format!(
+                        "Failed to add frontmatter file to EPUB: {}",
+                        file_path.display()
+                    )
+                })?;
+            } else if crate::notebook::is_notebook(path) {
+                let prefix = format!("frontmatter-{:04}", i);
+                let chapter =
+                    notebook::render(&file_path, &title, &ss, &theme, &prefix, &output_images)?;
+                add_chapter(&mut builder, &filename, &title, chapter).with_context(|| {
                     format!(
                         "Failed to add frontmatter file to EPUB: {}",
                         file_path.display()
                     )
                 })?;
+            } else {
+                let html = source_file::render(&file_path, &title, &ss, &theme, code, None, None)?;
+                builder
+                    .add_content(EpubContent::new(&filename, html.as_bytes()).title(&title))
+                    .with_context(|| {
+                        format!(
+                            "Failed to add frontmatter file to EPUB: {}",
+                            file_path.display()
+                        )
+                    })?;
+            }
+            document_count += 1;
+        }
+
+        // add the standard file header after the frontmatter
+        if let Some(header) = &standard_header {
+            let chapter = boilerplate::render(header, &ss, &theme)?;
+            add_chapter(
+                &mut builder,
+                "standard-header.xhtml",
+                crate::boilerplate::TITLE,
+                chapter,
+            )
+            .with_context(|| "Failed to add the standard file header to EPUB")?;
+            document_count += 1;
+        }
+
+        // add guided tours
+        let listings: HashMap<&Path, usize> = source
+            .source_files
+            .iter()
+            .enumerate()
+            .map(|(i, entry)| (entry.path.as_path(), i))
+            .collect();
+        for (i, (path, tour)) in source.tour_files.iter().zip(&tours).enumerate() {
+            progress.inc(1);
+            let filename = format!("tour-{:04}.xhtml", i);
+            let file_path = source.repository.join(path);
+            let base_dir = file_path
+                .parent()
+                .map(Path::to_path_buf)
+                .unwrap_or_default();
+            let prefix = format!("tour-{:04}", i);
+            let chapter = tour::render(
+                tour,
+                &base_dir,
+                &ss,
+                &theme,
+                code,
+                &prefix,
+                TourContext {
+                    repository: &source.repository,
+                    listings: &listings,
+                },
+            )?;
+            add_chapter(&mut builder, &filename, &tour.title, chapter)
+                .with_context(|| format!("Failed to add tour to EPUB: {}", file_path.display()))?;
             document_count += 1;
         }
 
         // add source files
-        for (i, path) in source.source_files.iter().enumerate() {
+        let mut index_entries: Vec<index::IndexEntry> = Vec::new();
+        for (i, entry) in source.source_files.iter().enumerate() {
             progress.inc(1);
+            let path = &entry.path;
+            let excerpt = entry.excerpt.as_ref();
+            let header = standard_header.as_ref().and_then(|h| h.lines(path));
             let filename = format!("source-{:04}.xhtml", i);
             let file_path = source.repository.join(path);
             let title = path.display().to_string();
 
-            let html = source_file::render(&file_path, &title, &ss, &theme)?;
-            builder
-                .add_content(EpubContent::new(&filename, html.as_bytes()).title(&title))
-                .with_context(|| {
+            if self.index.enabled && !crate::notebook::is_notebook(path) {
+                let definitions = crate::syntax_analysis::file_definitions(&file_path, &ss)
+                    .with_context(|| format!("Failed to index source file {}", path.display()))?;
+                // definitions left out of an excerpt have no line to link to
+                let selection = match excerpt {
+                    Some(excerpt) => Some(excerpt.select_file(&file_path, &ss)?),
+                    None => None,
+                };
+                let definitions = definitions.into_iter().filter(|definition| {
+                    selection
+                        .as_ref()
+                        .is_none_or(|s| s.contains(definition.line))
+                });
+                index_entries.extend(definitions.map(|definition| index::IndexEntry {
+                    name: definition.name,
+                    kind: definition.kind,
+                    file: path.clone(),
+                    line: definition.line,
+                    document: filename.clone(),
+                }));
+            }
+
+            if crate::notebook::is_notebook(path) {
+                let prefix = format!("source-{:04}", i);
+                let chapter =
+                    notebook::render(&file_path, &title, &ss, &theme, &prefix, &output_images)?;
+                add_chapter(&mut builder, &filename, &title, chapter).with_context(|| {
+                    format!("Failed to add source file to EPUB: {}", file_path.display())
+                })?;
+            } else if self.literate.enabled {
+                let prefix = format!("source-{:04}", i);
+                let chapter = literate::render(
+                    &file_path,
+                    &title,
+                    &ss,
+                    &theme,
+                    code,
+                    LiterateContext {
+                        all_comments: self.literate.all_comments,
+                        resource_prefix: &prefix,
+                        excerpt,
+                        header,
+                    },
+                )?;
+                add_chapter(&mut builder, &filename, &title, chapter).with_context(|| {
                     format!("Failed to add source file to EPUB: {}", file_path.display())
                 })?;
+            } else {
+                let html =
+                    source_file::render(&file_path, &title, &ss, &theme, code, excerpt, header)?;
+                builder
+                    .add_content(EpubContent::new(&filename, html.as_bytes()).title(&title))
+                    .with_context(|| {
+                        format!("Failed to add source file to EPUB: {}", file_path.display())
+                    })?;
+            }
             document_count += 1;
         }
 
@@ -213,6 +382,19 @@ impl EPUB {
             document_count += 1;
         }
 
+        // add symbol index if enabled
+        if self.index.enabled {
+            let index_html = index::render(&title, index_entries)?;
+            builder
+                .add_content(
+                    EpubContent::new("index.xhtml", index_html.as_bytes())
+                        .title("Index")
+                        .reftype(ReferenceType::Index),
+                )
+                .with_context(|| "Failed to add index page")?;
+            document_count += 1;
+        }
+
         // write epub to file
         let output_file = File::create(&self.outfile)
             .with_context(|| format!("Failed to create EPUB file: {}", self.outfile.display()))?;
@@ -227,6 +409,58 @@ impl EPUB {
     }
 }
 
+/// Add a prose chapter, packaging its images and nesting its headings under it in
+/// the navigation.
+fn add_chapter(
+    builder: &mut EpubBuilder<ZipLibrary>,
+    filename: &str,
+    title: &str,
+    chapter: markdown::Chapter,
+) -> Result<()> {
+    for image in &chapter.images {
+        let data = File::open(&image.source)
+            .with_context(|| format!("Failed to read image: {}", image.source.display()))?;
+        builder
+            .add_resource(&image.href, data, mime_from_path(&image.source))
+            .with_context(|| format!("Failed to add image to EPUB: {}", image.source.display()))?;
+    }
+
+    let mut content = EpubContent::new(filename, chapter.html.as_bytes()).title(title);
+    for element in heading_toc(filename, &chapter.headings) {
+        content = content.child(element);
+    }
+    builder
+        .add_content(content)
+        .with_context(|| format!("Failed to add chapter {}", filename))?;
+    Ok(())
+}
+
+/// Build navigation entries for a chapter's headings, nesting each heading under
+/// the nearest preceding heading of a higher level.
+fn heading_toc(filename: &str, headings: &[markdown::Heading]) -> Vec<TocElement> {
+    let mut elements = Vec::new();
+    let mut i = 0;
+    while i < headings.len() {
+        let heading = &headings[i];
+        let end = headings[i + 1..]
+            .iter()
+            .position(|h| h.level <= heading.level)
+            .map(|offset| i + 1 + offset)
+            .unwrap_or(headings.len());
+
+        let mut element = TocElement::new(
+            format!("{}#{}", filename, heading.id),
+            heading.title.as_str(),
+        );
+        for child in heading_toc(filename, &headings[i + 1..end]) {
+            element = element.child(child);
+        }
+        elements.push(element);
+        i = end;
+    }
+    elements
+}
+
 /// Determine MIME type from file extension.
 fn mime_from_path(path: &std::path::Path) -> &'static str {
     match path
@@ -0,0 +1,101 @@
+//! Jupyter notebook rendering for EPUB.
+//!
+//! Each notebook becomes one chapter: Markdown cells as prose, code cells as
+//! highlighted `<pre>` blocks under an `In [n]:` label, and the recorded outputs
+//! below them. Image outputs are written to disk and packaged as EPUB resources
+//! the same way images linked from Markdown are.
+
+use crate::notebook::{self, Cell, Output, OutputImages};
+use crate::sinks::epub::rendering::markdown::{Chapter, Writer};
+use anyhow::Result;
+use std::path::Path;
+use syntect::highlighting::Theme;
+use syntect::parsing::SyntaxSet;
+
+/// Render a notebook file as an XHTML chapter.
+///
+/// `resource_prefix` names the chapter's packaged images, as in
+/// [`super::markdown::render`]; image outputs are written to `output_images`.
+pub fn render(
+    path: &Path,
+    title: &str,
+    ss: &SyntaxSet,
+    theme: &Theme,
+    resource_prefix: &str,
+    output_images: &OutputImages,
+) -> Result<Chapter> {
+    let notebook = notebook::load(path)?;
+    let base_dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+
+    let mut writer = Writer::new(ss, theme, &base_dir, resource_prefix);
+    writer.html.push_str("<div class=\"markdown notebook\">\n");
+    for (cell_index, cell) in notebook.cells.iter().enumerate() {
+        match cell {
+            Cell::Markdown(text) => writer.markdown(text)?,
+            Cell::Raw(text) => push_output(&mut writer, "nb-output", text),
+            Cell::Code {
+                execution_count,
+                source,
+                outputs,
+            } => {
+                push_label(&mut writer, "In", *execution_count);
+                writer.code(Some(&notebook.language), source)?;
+
+                for (output_index, output) in outputs.iter().enumerate() {
+                    match output {
+                        Output::Stream { name, text } => {
+                            let class = if name == "stderr" {
+                                "nb-output nb-error"
+                            } else {
+                                "nb-output"
+                            };
+                            push_output(&mut writer, class, text);
+                        }
+                        Output::Result {
+                            execution_count,
+                            text,
+                        } => {
+                            push_label(&mut writer, "Out", *execution_count);
+                            push_output(&mut writer, "nb-output", text);
+                        }
+                        Output::Image { extension, data } => {
+                            let image_path = output_images.write(
+                                path,
+                                cell_index,
+                                output_index,
+                                extension,
+                                data,
+                            )?;
+                            writer.html.push_str("<p class=\"nb-image\">");
+                            writer.image_file(image_path, "output image");
+                            writer.html.push_str("</p>\n");
+                        }
+                        Output::Error(text) => push_output(&mut writer, "nb-output nb-error", text),
+                    }
+                }
+            }
+        }
+    }
+    writer.html.push_str("</div>\n");
+    Ok(writer.finish(title))
+}
+
+/// Append a Jupyter-style execution label such as `In [3]:`.
+fn push_label(writer: &mut Writer, prefix: &str, execution_count: Option<u64>) {
+    let count = execution_count
+        .map(|n| n.to_string())
+        .unwrap_or_else(|| " ".to_string());
+    writer.html.push_str(&format!(
+        "<div class=\"nb-label\">{} [{}]:</div>\n",
+        prefix, count
+    ));
+}
+
+/// Append program output as preformatted text.
+fn push_output(writer: &mut Writer, class: &str, text: &str) {
+    writer.html.push_str(&format!(
+        "<pre class=\"{}\">{}</pre>\n",
+        class,
+        html_escape::encode_text(text)
+    ));
+}
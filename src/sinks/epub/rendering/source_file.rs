@@ -4,23 +4,41 @@
 //! syntect with a hybrid styling approach: inline RGB colours ensure accurate
 //! colour rendering regardless of e-reader CSS support, while CSS classes handle
 //! bold/italic/underline styling for cleaner markup. Binary files show a placeholder
-//! since hex dumps aren't practical in reflowable e-reader formats.
+//! since hex dumps aren't practical in reflowable e-reader formats. Each line
+//! number carries an `L<n>` anchor so the symbol index can link to definitions.
+//! Excerpts show only their selected lines, with a marker for each run left out,
+//! and the standard file header is collapsed to a marker the same way. Tabs,
+//! indentation and visible whitespace follow `[pdf.code]`, as in the PDF.
 
+use crate::boilerplate::header_label;
 use crate::sinks::epub::styles;
+use crate::sinks::pdf::CodeConfig;
+use crate::source::{omission_label, Excerpt, Omissions};
+use crate::whitespace;
 use anyhow::{Context, Result};
+use std::ops::Range;
 use std::path::Path;
 use syntect::easy::HighlightLines;
-use syntect::highlighting::{FontStyle, Theme};
+use syntect::highlighting::{FontStyle, Style, Theme};
 use syntect::parsing::SyntaxSet;
 use syntect::util::LinesWithEndings;
 
-/// Render a source file as syntax-highlighted XHTML.
-pub fn render(path: &Path, title: &str, ss: &SyntaxSet, theme: &Theme) -> Result<String> {
+/// Render a source file as syntax-highlighted XHTML, or only the lines of
+/// `excerpt` if it's given. The `header` lines, if any, are shown as one marker.
+pub fn render(
+    path: &Path,
+    title: &str,
+    ss: &SyntaxSet,
+    theme: &Theme,
+    code: &CodeConfig,
+    excerpt: Option<&Excerpt>,
+    header: Option<Range<usize>>,
+) -> Result<String> {
     let prefix = styles::scope_prefix();
 
     // read file contents
     let (contents, _is_binary) = match std::fs::read_to_string(path) {
-        Ok(contents) => (contents.replace('\t', "    "), false),
+        Ok(contents) => (whitespace::expand(&contents, code), false),
         Err(e) if e.kind() == std::io::ErrorKind::InvalidData => {
             // binary file
             return Ok(render_binary_placeholder(title));
@@ -37,52 +55,82 @@ pub fn render(path: &Path, title: &str, ss: &SyntaxSet, theme: &Theme) -> Result
             .unwrap_or_default(),
     );
 
+    let selection = match excerpt {
+        Some(excerpt) => Some(excerpt.select(path, &contents, syntax, ss)?),
+        None => None,
+    };
+    let shown = |line: usize| selection.as_ref().is_none_or(|s| s.contains(line));
+    let mut omissions = Omissions::default();
+    let total = contents.lines().count();
+
     let code_html = if let Some(syntax) = syntax {
         // highlight with syntect
         let mut h = HighlightLines::new(syntax, theme);
         let mut html = String::new();
+        let mut header_placed = false;
 
         for (line_num, line) in LinesWithEndings::from(&contents).enumerate() {
-            // line number
-            html.push_str(&format!(
-                r#"<span class="line-number">{:>4}</span>"#,
-                line_num + 1
-            ));
-
-            // highlighted tokens
+            // omitted lines still go through the highlighter to keep its parse state
             let ranges = h
                 .highlight_line(line, ss)
                 .with_context(|| format!("Failed to highlight line {}", line_num + 1))?;
-
-            for (style, text) in ranges {
-                let class = scope_to_class(style.font_style, prefix);
-                let escaped = html_escape::encode_text(text);
-
-                // always use inline colour, add classes for bold/italic/underline
-                if class.is_empty() {
-                    html.push_str(&format!(
-                        r#"<span style="color: rgb({}, {}, {})">{}</span>"#,
-                        style.foreground.r, style.foreground.g, style.foreground.b, escaped
-                    ));
-                } else {
-                    html.push_str(&format!(
-                        r#"<span class="{}" style="color: rgb({}, {}, {})">{}</span>"#,
-                        class, style.foreground.r, style.foreground.g, style.foreground.b, escaped
-                    ));
+            if !shown(line_num) {
+                omissions.skip(line_num);
+                continue;
+            }
+            if let Some(lines) = omissions.show(line_num) {
+                html.push_str(&omission_html(&lines));
+                html.push('\n');
+            }
+            if let Some(header) = header.as_ref().filter(|h| h.contains(&line_num)) {
+                if !header_placed {
+                    html.push_str(&header_html(header));
+                    html.push('\n');
+                    header_placed = true;
                 }
+                continue;
             }
+
+            // line number, with an anchor for index links
+            html.push_str(&format!(
+                r#"<span class="line-number" id="L{}">{:>4}</span>"#,
+                line_num + 1,
+                line_num + 1
+            ));
+
+            // highlighted tokens
+            push_code(&mut html, &ranges, prefix, code);
+        }
+        if let Some(lines) = omissions.finish(total) {
+            html.push_str(&omission_html(&lines));
         }
         html
     } else {
         // no syntax highlighting - plain text with line numbers
         let mut html = String::new();
         for (line_num, line) in contents.lines().enumerate() {
+            if !shown(line_num) {
+                omissions.skip(line_num);
+                continue;
+            }
+            if let Some(lines) = omissions.show(line_num) {
+                html.push_str(&omission_html(&lines));
+                html.push_str("<br/>");
+            }
+            let line = if code.visible_whitespace {
+                whitespace::visible(line)
+            } else {
+                line.to_string()
+            };
             html.push_str(&format!(
                 r#"<span class="line-number">{:>4}</span>{}<br/>"#,
                 line_num + 1,
-                html_escape::encode_text(line)
+                html_escape::encode_text(&line)
             ));
         }
+        if let Some(lines) = omissions.finish(total) {
+            html.push_str(&omission_html(&lines));
+        }
         html
     };
 
@@ -105,6 +153,25 @@ pub fn render(path: &Path, title: &str, ss: &SyntaxSet, theme: &Theme) -> Result
     ))
 }
 
+/// The marker standing in for a run of lines left out of an excerpt, lined up with
+/// the code after an empty line number.
+pub fn omission_html(lines: &Range<usize>) -> String {
+    format!(
+        r#"<span class="line-number"></span><span class="omitted">{}</span>"#,
+        omission_label(lines)
+    )
+}
+
+/// The marker standing in for a collapsed standard file header, anchored at its
+/// first line.
+pub fn header_html(lines: &Range<usize>) -> String {
+    format!(
+        r#"<span class="line-number" id="L{}"></span><span class="omitted">{}</span>"#,
+        lines.start + 1,
+        header_label(lines)
+    )
+}
+
 /// Render a placeholder for binary files.
 fn render_binary_placeholder(title: &str) -> String {
     format!(
@@ -125,6 +192,74 @@ fn render_binary_placeholder(title: &str) -> String {
     )
 }
 
+/// Append highlighted tokens as spans with inline colours.
+///
+/// Colours are always inline; classes are only added for bold/italic/underline.
+pub fn push_highlighted(html: &mut String, ranges: &[(Style, &str)], prefix: &str) {
+    for (style, text) in ranges {
+        let class = scope_to_class(style.font_style, prefix);
+        let escaped = html_escape::encode_text(text);
+
+        if class.is_empty() {
+            html.push_str(&format!(
+                r#"<span style="color: rgb({}, {}, {})">{}</span>"#,
+                style.foreground.r, style.foreground.g, style.foreground.b, escaped
+            ));
+        } else {
+            html.push_str(&format!(
+                r#"<span class="{}" style="color: rgb({}, {}, {})">{}</span>"#,
+                class, style.foreground.r, style.foreground.g, style.foreground.b, escaped
+            ));
+        }
+    }
+}
+
+/// Append a highlighted line of code, showing its whitespace as `code` asks: all of
+/// it as glyphs with visible whitespace, and trailing whitespace as glyphs in a
+/// `trailing-whitespace` span when it's marked.
+pub fn push_code(html: &mut String, ranges: &[(Style, &str)], prefix: &str, code: &CodeConfig) {
+    if !code.visible_whitespace && !code.mark_trailing_whitespace {
+        push_highlighted(html, ranges, prefix);
+        return;
+    }
+
+    let line: String = ranges.iter().map(|(_, s)| *s).collect();
+    let trailing = if code.mark_trailing_whitespace {
+        whitespace::trailing_start(&line)
+    } else {
+        usize::MAX
+    };
+    let ending = &line[line.trim_end_matches(['\n', '\r']).len()..];
+
+    let mut body = Vec::with_capacity(ranges.len());
+    let mut tail = String::new();
+    let mut byte = 0;
+    for (style, s) in ranges {
+        let s = s.trim_end_matches(['\n', '\r']);
+        let (text, rest) = s.split_at(trailing.saturating_sub(byte).min(s.len()));
+        byte += s.len();
+        if !text.is_empty() {
+            let text = if code.visible_whitespace {
+                whitespace::visible(text)
+            } else {
+                text.to_string()
+            };
+            body.push((*style, text));
+        }
+        tail.push_str(rest);
+    }
+
+    let body: Vec<(Style, &str)> = body.iter().map(|(style, s)| (*style, s.as_str())).collect();
+    push_highlighted(html, &body, prefix);
+    if !tail.is_empty() {
+        html.push_str(&format!(
+            r#"<span class="trailing-whitespace">{}</span>"#,
+            whitespace::visible(&tail)
+        ));
+    }
+    html.push_str(ending);
+}
+
 /// Map font style to CSS class names.
 fn scope_to_class(font_style: FontStyle, prefix: &str) -> String {
     let mut classes = Vec::new();
@@ -1,17 +1,21 @@
 //! Table of contents rendering for EPUB.
 //!
-//! Generates a navigable TOC page with frontmatter listed first, followed by
-//! source files in a hierarchical tree structure reflecting directory layout.
+//! Generates a navigable TOC page with frontmatter (and the standard file header,
+//! if it's collapsed) listed first, then guided tours,
+//! followed by source files in a hierarchical tree structure reflecting directory
+//! layout.
 //! This complements the EPUB's built-in navigation (NCX/nav.xhtml) with a
 //! human-readable page that readers can browse.
 
 use crate::source::Source;
+use crate::tour::Tour;
 use anyhow::Result;
 use std::collections::HashMap;
 use std::path::Path;
 
-/// Render the table of contents as XHTML.
-pub fn render(source: &Source) -> Result<String> {
+/// Render the table of contents as XHTML. `standard_header` adds the standard file
+/// header's chapter to the frontmatter.
+pub fn render(source: &Source, tours: &[Tour], standard_header: bool) -> Result<String> {
     let title = source
         .title
         .clone()
@@ -21,7 +25,7 @@ pub fn render(source: &Source) -> Result<String> {
     let mut file_index = 0;
 
     // frontmatter section
-    if !source.frontmatter_files.is_empty() {
+    if !source.frontmatter_files.is_empty() || standard_header {
         toc_items.push("<h3>Frontmatter</h3>".to_string());
         toc_items.push("<ol>".to_string());
         for path in &source.frontmatter_files {
@@ -34,13 +38,33 @@ pub fn render(source: &Source) -> Result<String> {
             ));
             file_index += 1;
         }
+        if standard_header {
+            toc_items.push(format!(
+                r#"<li><a href="standard-header.xhtml">{}</a></li>"#,
+                crate::boilerplate::TITLE
+            ));
+        }
+        toc_items.push("</ol>".to_string());
+    }
+
+    // guided tours section
+    if !tours.is_empty() {
+        toc_items.push("<h3>Guided Tour</h3>".to_string());
+        toc_items.push("<ol>".to_string());
+        for (i, tour) in tours.iter().enumerate() {
+            toc_items.push(format!(
+                r#"<li><a href="tour-{:04}.xhtml">{}</a></li>"#,
+                i,
+                html_escape::encode_text(&tour.title)
+            ));
+        }
         toc_items.push("</ol>".to_string());
     }
 
     // source files section with hierarchy
     if !source.source_files.is_empty() {
         toc_items.push("<h3>Source Files</h3>".to_string());
-        toc_items.push(render_hierarchical_toc(&source.source_files));
+        toc_items.push(render_hierarchical_toc(&source.source_paths()));
     }
 
     Ok(format!(
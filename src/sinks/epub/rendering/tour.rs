@@ -0,0 +1,142 @@
+//! Guided tour rendering for EPUB.
+//!
+//! Each tour becomes one chapter, the EPUB counterpart of the PDF tour section:
+//! the title and introduction, then each step's commentary as Markdown prose
+//! followed by the lines it points at in a highlighted `<pre>` block with their
+//! line numbers. Steps pointing at a file that's in the book end with a link to
+//! the line in the file's own chapter.
+
+use crate::sinks::epub::rendering::markdown::{Chapter, Writer};
+use crate::sinks::epub::rendering::source_file::push_code;
+use crate::sinks::epub::styles;
+use crate::sinks::pdf::CodeConfig;
+use crate::tour::{Step, Tour};
+use crate::whitespace;
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::path::Path;
+use syntect::easy::HighlightLines;
+use syntect::highlighting::Theme;
+use syntect::parsing::SyntaxSet;
+use syntect::util::LinesWithEndings;
+
+/// Where a tour's step files are and which of them are in the book.
+pub struct TourContext<'a> {
+    /// The repository the step files are read from
+    pub repository: &'a Path,
+    /// Source files in the book by their index, for the links back to their
+    /// chapters
+    pub listings: &'a HashMap<&'a Path, usize>,
+}
+
+/// Render a tour as an XHTML chapter.
+///
+/// Images in the commentary are resolved against `base_dir` and named with
+/// `resource_prefix`, as in [`super::markdown::render`]. Step files are found
+/// through `context` and laid out following `code`.
+pub fn render(
+    tour: &Tour,
+    base_dir: &Path,
+    ss: &SyntaxSet,
+    theme: &Theme,
+    code: &CodeConfig,
+    resource_prefix: &str,
+    context: TourContext,
+) -> Result<Chapter> {
+    let TourContext {
+        repository,
+        listings,
+    } = context;
+    let mut writer = Writer::new(ss, theme, base_dir, resource_prefix);
+    writer.html.push_str("<div class=\"markdown tour\">\n");
+
+    // the chapter header already carries the title, so steps are the top headings
+    if let Some(description) = &tour.description {
+        writer.markdown(description)?;
+    }
+
+    for (i, step) in tour.steps.iter().enumerate() {
+        let heading = match &step.title {
+            Some(title) => format!("## {}. {}\n\n", i + 1, title),
+            None => format!("## Step {}\n\n", i + 1),
+        };
+        writer.markdown(&(heading + &step.description))?;
+
+        let Some(file) = &step.file else {
+            continue;
+        };
+        let lines = step_code(&repository.join(file), step, ss, theme, code)
+            .with_context(|| format!("Failed to render step {} of {}", i + 1, tour.title))?;
+        writer.html.push_str(&lines);
+
+        if let Some(index) = listings.get(file.as_path()) {
+            let line = step
+                .lines
+                .as_ref()
+                .map(|lines| lines.start + 1)
+                .unwrap_or(1);
+            writer.html.push_str(&format!(
+                r#"<p class="tour-listing"><a href="source-{:04}.xhtml#L{}">Full listing: {}, line {}</a></p>"#,
+                index,
+                line,
+                html_escape::encode_text(&file.display().to_string()),
+                line
+            ));
+            writer.html.push('\n');
+        }
+    }
+
+    writer.html.push_str("</div>\n");
+    Ok(writer.finish(&tour.title))
+}
+
+/// The lines a step points at as a highlighted `<pre>` block, or nothing if the
+/// step has no lines or the file can't be read as text.
+fn step_code(
+    path: &Path,
+    step: &Step,
+    ss: &SyntaxSet,
+    theme: &Theme,
+    code: &CodeConfig,
+) -> Result<String> {
+    let Some(lines) = &step.lines else {
+        return Ok(String::new());
+    };
+    let Ok(contents) = std::fs::read_to_string(path) else {
+        return Ok(String::new());
+    };
+    let contents = whitespace::expand(&contents, code);
+    let syntax = ss
+        .find_syntax_by_extension(
+            path.extension()
+                .and_then(|e| e.to_str())
+                .unwrap_or_default(),
+        )
+        .unwrap_or_else(|| ss.find_syntax_plain_text());
+
+    // highlight from the top of the file so the parse state is right for the step
+    let prefix = styles::scope_prefix();
+    let mut h = HighlightLines::new(syntax, theme);
+    let mut html = String::from("<pre><code>");
+    for (line_num, line) in LinesWithEndings::from(&contents)
+        .enumerate()
+        .take(lines.end)
+    {
+        let ranges = h
+            .highlight_line(line, ss)
+            .with_context(|| format!("Failed to highlight line {}", line_num + 1))?;
+        if line_num < lines.start {
+            continue;
+        }
+        html.push_str(&format!(
+            r#"<span class="line-number">{:>4}</span>"#,
+            line_num + 1
+        ));
+        push_code(&mut html, &ranges, prefix, code);
+        if !line.ends_with('\n') {
+            html.push('\n');
+        }
+    }
+    html.push_str("</code></pre>\n");
+    Ok(html)
+}
@@ -109,6 +109,17 @@ code {{
     min-width: 3em;
 }}
 
+/* Lines left out of an excerpt */
+.omitted {{
+    color: #888;
+    font-style: italic;
+}}
+
+/* Marked trailing whitespace */
+.trailing-whitespace {{
+    color: rgb(220, 50, 47);
+}}
+
 /* Table of contents */
 .toc {{
     margin: 1em 0;
@@ -264,6 +275,102 @@ code {{
     font-size: 0.9em;
 }}
 
+/* Markdown chapters */
+.markdown h1 {{
+    text-align: left;
+    font-size: 1.8em;
+}}
+
+.markdown pre {{
+    white-space: pre-wrap;
+}}
+
+.markdown blockquote {{
+    margin: 1em 0;
+    padding-left: 1em;
+    border-left: 3px solid #ddd;
+    color: #555;
+}}
+
+.markdown table {{
+    border-collapse: collapse;
+    margin: 1em 0;
+}}
+
+.markdown th,
+.markdown td {{
+    border: 1px solid #ddd;
+    padding: 0.25em 0.5em;
+    text-align: left;
+}}
+
+.markdown img {{
+    max-width: 100%;
+}}
+
+.literate-prose {{
+    margin: 1em 0;
+}}
+
+/* Guided tours */
+.tour-listing {{
+    font-size: 0.9em;
+    font-style: italic;
+    margin-top: 0.25em;
+}}
+
+/* Symbol index */
+.index-letter {{
+    margin-top: 1em;
+}}
+
+.symbol-index {{
+    list-style: none;
+    padding-left: 0;
+    font-family: "{font_family}", monospace;
+    font-size: 0.9em;
+}}
+
+.index-kind {{
+    color: #888;
+    font-style: italic;
+}}
+
+.index-location {{
+    color: #888;
+}}
+
+/* Jupyter notebooks */
+.nb-label {{
+    font-family: "{font_family}", monospace;
+    font-size: 0.8em;
+    color: #888;
+    margin-top: 1em;
+}}
+
+.nb-output {{
+    border: none;
+    background: none;
+    white-space: pre-wrap;
+}}
+
+.nb-error {{
+    color: #dc322f;
+}}
+
+.nb-image {{
+    text-align: center;
+}}
+
+.md-link {{
+    color: #268bd2;
+}}
+
+.md-image-alt {{
+    font-style: italic;
+    color: #888;
+}}
+
 /* Binary file placeholder */
 .binary-placeholder {{
     font-style: italic;
@@ -1,12 +1,15 @@
-//! Booklet PDF generation for saddle-stitch binding.
+//! Booklet PDF generation for saddle-stitch, perfect or cut-and-stack binding.
 //!
 //! Converts the digital PDF into a print-ready booklet by:
 //! 1. Converting each page into a Form XObject for reuse
 //! 2. Calculating imposition layout (which pages go where on physical sheets)
-//! 3. Placing two logical pages side-by-side on each sheet side
+//! 3. Placing two, four or eight logical pages on each sheet side
 //!
-//! The output is designed for duplex printing: print the PDF, fold the stack
-//! in half, and staple along the spine.
+//! The output is designed for duplex printing: print the PDF, then fold the
+//! sheets and staple along the spine, fold and glue them, or cut and stack them,
+//! depending on the binding. For printers that can't duplex, the fronts and
+//! backs can be split instead, in one file or two, with the backs in reverse
+//! order and turned upside down if the stack is fed back in that way.
 //!
 //! ## Document Metadata
 //!
@@ -26,12 +29,16 @@
 //! Displays a progress bar during XObject creation since this can take time
 //! for large documents (one XObject per page).
 
-use crate::sinks::pdf::config::PDF;
+use crate::sinks::pdf::config::{Binding, Fold, ManualDuplex, PrinterMarksConfig, PDF};
 use crate::sinks::pdf::fonts::{FontIds, LoadedFonts};
-use crate::sinks::pdf::imposition::{calculate_imposition, create_imposed_page, BookletConfig};
+use crate::sinks::pdf::imposition::{
+    add_collation_mark, add_slug, calculate_cut_and_stack, calculate_imposition,
+    calculate_perfect_binding, calculate_signature_sheets, create_grid_page, creep, print_order,
+    BookletConfig, GridSheet, GridSide, PlacedPage, Side,
+};
 use crate::sinks::pdf::rendering::ImagePathMap;
 use crate::source::Source;
-use anyhow::{Context, Result};
+use anyhow::{anyhow, Context, Result};
 use indicatif::{ProgressBar, ProgressStyle};
 use pdf_gen::id_arena_crate::Id;
 use pdf_gen::*;
@@ -41,41 +48,162 @@ use std::path::PathBuf;
 /// Generate a print-ready booklet PDF from the digital document.
 ///
 /// This creates Form XObjects from each page's content and arranges them
-/// 2-up on larger sheets according to saddle-stitch signature imposition.
+/// on larger sheets according to the configured binding and fold, moving the
+/// pages of nested sheets in to make up for creep, and draws any printer's marks
+/// around them. Pages go side by side along the sheet's longer edge, or head to foot
+/// if they're landscape, whichever way round the sheet is given.
 ///
 /// The `source` parameter provides book metadata (title, authors) for setting
-/// PDF document properties. Images are reloaded from disk using paths recorded
-/// in `image_paths` during initial rendering, then remapped to new indices.
+/// PDF document properties. The pages come from `runs`, usually the whole digital
+/// document but a volume's title page and its share of the book when it's split.
+/// Images are reloaded from disk using paths recorded during initial rendering,
+/// then remapped to new indices.
+///
+/// For manual duplex printing the fronts come first and the backs after them in
+/// reverse order, either in `output_path` or in separate fronts and backs files
+/// beside it.
 ///
 /// Returns the number of physical sheets needed to print the booklet.
 pub fn render_booklet(
     config: &PDF,
     source: &Source,
-    source_doc: &Document,
-    source_font_ids: &FontIds,
-    image_paths: &ImagePathMap,
+    runs: &[PageRun],
     output_path: &PathBuf,
 ) -> Result<usize> {
-    let page_width = Pt(config.page.width_in * 72.0);
-    let page_height = Pt(config.page.height_in * 72.0);
-    let sheet_width = Pt(config.booklet.sheet_width_in * 72.0);
-    let sheet_height = Pt(config.booklet.sheet_height_in * 72.0);
+    let binding = config.booklet.binding;
+    let fold = config.booklet.fold;
+    if binding == Binding::SaddleStitch && fold != Fold::Folio {
+        return Err(anyhow!(
+            "Saddle-stitch booklets are folded once; use Perfect or CutAndStack binding \
+             for a {:?} fold",
+            fold
+        ));
+    }
 
     let booklet_config = BookletConfig {
-        signature_size: config.booklet.signature_size,
+        marks: config.booklet.marks.clone(),
+        ..sheet_layout(
+            config,
+            config.booklet.sheet_width_in,
+            config.booklet.sheet_height_in,
+        )
+    };
+
+    // calculate imposition layout
+    let total_pages: usize = runs.iter().map(|run| run.pages.len()).sum();
+    let sheets: Vec<GridSheet> = match binding {
+        Binding::SaddleStitch => {
+            let per_signature = calculate_signature_sheets(config.booklet.signature_size).len();
+            calculate_imposition(total_pages, config.booklet.signature_size)
+                .into_iter()
+                .enumerate()
+                .map(|(sheet_idx, sheet)| GridSheet {
+                    signature: Some(sheet_idx / per_signature),
+                    ..GridSheet::from(sheet)
+                })
+                .collect()
+        }
+        Binding::Perfect => calculate_perfect_binding(total_pages, fold),
+        Binding::CutAndStack => calculate_cut_and_stack(total_pages, fold),
+    };
+
+    let sheet_count = sheets.len();
+
+    // only nested sheets creep; folded perfect-bound sheets are too thin to matter
+    let paper_thickness = Pt(config.booklet.paper_thickness_in * 72.0);
+    let page_creep = |page: usize| match binding {
+        Binding::SaddleStitch => creep(page, config.booklet.signature_size, paper_thickness),
+        Binding::Perfect | Binding::CutAndStack => Pt(0.0),
+    };
+
+    // without a duplexing printer, every front is printed before any back, and
+    // the backs may need turning upside down to match how the stack is fed back in
+    let duplex = &config.booklet.manual_duplex;
+    let manual_duplex = duplex.mode != ManualDuplex::Off;
+    let order = print_order(sheet_count, manual_duplex);
+    let rotate_backs = manual_duplex && duplex.backs_rotated();
+
+    // create imposed pages (each sheet side becomes a page); every perfect-bound
+    // sheet is a signature of its own, so it carries its own collation mark
+    let collate = binding == Binding::Perfect && config.booklet.collation_marks;
+    let write_sides = |order: &[(usize, Side)], path: &PathBuf, kind: &str| -> Result<()> {
+        let (mut booklet_doc, booklet_font_ids) = imposed_document(config, source, kind)?;
+        let bleed = Pt(config.booklet.marks.bleed_in * 72.0);
+        let page_xobjs = page_xobjects(&mut booklet_doc, &booklet_font_ids, runs, bleed, kind)?;
+
+        for &(sheet_idx, side) in order {
+            let sheet = &sheets[sheet_idx];
+            let rotated;
+            let grid = match side {
+                Side::Front => &sheet.front,
+                Side::Back if rotate_backs => {
+                    rotated = sheet.back.rotated();
+                    &rotated
+                }
+                Side::Back => &sheet.back,
+            };
+
+            let mut page = side_page(&booklet_config, grid, &page_xobjs, page_creep);
+            if collate && side == Side::Front {
+                add_collation_mark(&mut page, &booklet_config, fold, sheet_idx);
+            }
+            if config.booklet.marks.slug {
+                let text = slug_text(source, sheet_idx, sheet_count, side, sheet.signature);
+                add_slug(
+                    &mut page,
+                    &booklet_config,
+                    grid.columns,
+                    grid.rows,
+                    grid.spine,
+                    booklet_font_ids.regular,
+                    text,
+                );
+            }
+            booklet_doc.add_page(page);
+        }
+
+        write_imposed(&booklet_doc, path, kind)
+    };
+
+    match config.booklet_split_paths() {
+        Some((fronts_path, backs_path)) => {
+            let (fronts, backs) = order.split_at(sheet_count);
+            write_sides(fronts, &fronts_path, "booklet fronts")?;
+            write_sides(backs, &backs_path, "booklet backs")?;
+        }
+        None => write_sides(&order, output_path, "booklet")?,
+    }
+    Ok(sheet_count)
+}
+
+/// The logical page size, and the sheet turned so two pages fit along its longer
+/// edge: side by side on a landscape sheet, or head to foot on a portrait one if
+/// the pages are landscape.
+pub fn sheet_layout(config: &PDF, sheet_width_in: f32, sheet_height_in: f32) -> BookletConfig {
+    let (page_width, page_height) = config.page_size();
+    let long = Pt(sheet_width_in.max(sheet_height_in) * 72.0);
+    let short = Pt(sheet_width_in.min(sheet_height_in) * 72.0);
+    let turned = *page_width > *page_height;
+    let (sheet_width, sheet_height) = if turned { (short, long) } else { (long, short) };
+    BookletConfig {
         sheet_width,
         sheet_height,
         page_width,
         page_height,
-    };
+        marks: PrinterMarksConfig::default(),
+        turned,
+    }
+}
 
-    // create a new document for the booklet
+/// A new document for imposed output, with the book's metadata (`kind` appended to
+/// the title in brackets, e.g. "(Booklet)") and the fonts reloaded.
+pub fn imposed_document(config: &PDF, source: &Source, kind: &str) -> Result<(Document, FontIds)> {
     let mut booklet_doc = Document::default();
 
     // set PDF metadata for the booklet
     let mut info = Info::default();
     if let Some(title) = &source.title {
-        info.title(format!("{} (Booklet)", title));
+        info.title(format!("{} ({})", title, title_case(kind)));
     }
     let authors = source
         .authors
@@ -97,7 +225,7 @@ pub fn render_booklet(
 
     // reload fonts for the booklet document (fonts can't be cloned)
     let fonts = LoadedFonts::load(&config.font)
-        .with_context(|| format!("Failed to reload font '{}' for booklet", config.font))?;
+        .with_context(|| format!("Failed to reload font '{}' for {}", config.font, kind))?;
     let booklet_font_ids = FontIds {
         regular: booklet_doc.add_font(fonts.regular),
         bold: booklet_doc.add_font(fonts.bold),
@@ -105,119 +233,196 @@ pub fn render_booklet(
         bold_italic: booklet_doc.add_font(fonts.bold_italic),
     };
 
-    // maps source image indices to booklet document image indices
-    let mut image_remap: HashMap<usize, usize> = HashMap::new();
+    Ok((booklet_doc, booklet_font_ids))
+}
+
+/// A run of pages to copy into an imposed document, with the fonts and images of
+/// the document they were rendered in.
+pub struct PageRun<'a> {
+    pub doc: &'a Document,
+    pub font_ids: &'a FontIds,
+    pub image_paths: &'a ImagePathMap,
+    pub pages: &'a [Id<Page>],
+}
+
+impl<'a> PageRun<'a> {
+    /// Every page of `doc`, in page order.
+    pub fn all(doc: &'a Document, font_ids: &'a FontIds, image_paths: &'a ImagePathMap) -> Self {
+        Self {
+            doc,
+            font_ids,
+            image_paths,
+            pages: &doc.page_order,
+        }
+    }
+}
 
+/// Copy each page of `runs`, in order, into a Form XObject in `booklet_doc`. Each
+/// keeps whatever its page draws up to `bleed` past the trim, rather than being
+/// clipped at it.
+pub fn page_xobjects(
+    booklet_doc: &mut Document,
+    booklet_font_ids: &FontIds,
+    runs: &[PageRun],
+    bleed: Pt,
+    kind: &str,
+) -> Result<Vec<Id<FormXObject>>> {
     // create Form XObjects from each source page
-    let progress = ProgressBar::new(source_doc.page_order.len() as u64);
+    let page_count: usize = runs.iter().map(|run| run.pages.len()).sum();
+    let progress = ProgressBar::new(page_count as u64);
     progress.set_style(
         ProgressStyle::default_bar()
             .template("{spinner:.green} [{bar:40.cyan/blue}] {pos}/{len} {msg}")
             .expect("can parse progress style")
             .progress_chars("#>-"),
     );
-    progress.set_message("Creating booklet...");
+    progress.set_message(format!("Creating {}...", kind));
 
     let mut page_xobjs: Vec<Id<FormXObject>> = Vec::new();
-    for page_id in source_doc.page_order.iter() {
-        let page = &source_doc.pages[*page_id];
-        let mut xobj = FormXObject::new(page.media_box.x2, page.media_box.y2);
-
-        // copy page contents to the form xobject
-        for content in page.contents.iter() {
-            match content {
-                PageContents::Text(spans) => {
-                    for span in spans {
-                        // remap font ids to the booklet document
-                        let new_font_id = if span.font.id == source_font_ids.regular {
-                            booklet_font_ids.regular
-                        } else if span.font.id == source_font_ids.bold {
-                            booklet_font_ids.bold
-                        } else if span.font.id == source_font_ids.italic {
-                            booklet_font_ids.italic
+    for run in runs {
+        let source_font_ids = run.font_ids;
+
+        // maps source image indices to booklet document image indices
+        let mut image_remap: HashMap<usize, usize> = HashMap::new();
+
+        for page_id in run.pages.iter() {
+            let page = &run.doc.pages[*page_id];
+            let mut xobj = FormXObject::new(page.media_box.x2, page.media_box.y2);
+            xobj.bbox = Rect {
+                x1: Pt(-*bleed),
+                y1: Pt(-*bleed),
+                x2: page.media_box.x2 + bleed,
+                y2: page.media_box.y2 + bleed,
+            };
+
+            // copy page contents to the form xobject
+            for content in page.contents.iter() {
+                match content {
+                    PageContents::Text(spans) => {
+                        for span in spans {
+                            // remap font ids to the booklet document
+                            let new_font_id = if span.font.id == source_font_ids.regular {
+                                booklet_font_ids.regular
+                            } else if span.font.id == source_font_ids.bold {
+                                booklet_font_ids.bold
+                            } else if span.font.id == source_font_ids.italic {
+                                booklet_font_ids.italic
+                            } else {
+                                booklet_font_ids.bold_italic
+                            };
+                            xobj.add_span(SpanLayout {
+                                text: span.text.clone(),
+                                font: SpanFont {
+                                    id: new_font_id,
+                                    size: span.font.size,
+                                },
+                                colour: span.colour,
+                                coords: span.coords,
+                            });
+                        }
+                    }
+                    PageContents::Image(img) => {
+                        // remap image index or load from disk if not yet in booklet doc
+                        let new_index = if let Some(&idx) = image_remap.get(&img.image_index) {
+                            idx
+                        } else if let Some(path) = run.image_paths.get(&img.image_index) {
+                            let image = Image::new_from_disk(path).with_context(|| {
+                                format!("Failed to reload image '{}' for {}", path.display(), kind)
+                            })?;
+                            let new_id = booklet_doc.add_image(image);
+                            let new_idx = new_id.index();
+                            image_remap.insert(img.image_index, new_idx);
+                            new_idx
                         } else {
-                            booklet_font_ids.bold_italic
+                            // image path not recorded; skip this image
+                            continue;
                         };
-                        xobj.add_span(SpanLayout {
-                            text: span.text.clone(),
-                            font: SpanFont {
-                                id: new_font_id,
-                                size: span.font.size,
-                            },
-                            colour: span.colour,
-                            coords: span.coords,
+
+                        xobj.add_image(ImageLayout {
+                            image_index: new_index,
+                            position: img.position,
                         });
                     }
-                }
-                PageContents::Image(img) => {
-                    // remap image index or load from disk if not yet in booklet doc
-                    let new_index = if let Some(&idx) = image_remap.get(&img.image_index) {
-                        idx
-                    } else if let Some(path) = image_paths.get(&img.image_index) {
-                        let image = Image::new_from_disk(path).with_context(|| {
-                            format!("Failed to reload image '{}' for booklet", path.display())
-                        })?;
-                        let new_id = booklet_doc.add_image(image);
-                        let new_idx = new_id.index();
-                        image_remap.insert(img.image_index, new_idx);
-                        new_idx
-                    } else {
-                        // image path not recorded; skip this image
-                        continue;
-                    };
-
-                    xobj.add_image(ImageLayout {
-                        image_index: new_index,
-                        position: img.position,
-                    });
-                }
-                PageContents::RawContent(raw) => {
-                    xobj.add_raw_content(raw.clone());
-                }
-                PageContents::FormXObject(_) => {
-                    // nested form xobjects not supported in this context
+                    PageContents::RawContent(raw) => {
+                        xobj.add_raw_content(raw.clone());
+                    }
+                    PageContents::FormXObject(_) => {
+                        // nested form xobjects not supported in this context
+                    }
                 }
             }
-        }
 
-        let xobj_id = booklet_doc.add_form_xobject(xobj);
-        page_xobjs.push(xobj_id);
-        progress.inc(1);
+            let xobj_id = booklet_doc.add_form_xobject(xobj);
+            page_xobjs.push(xobj_id);
+            progress.inc(1);
+        }
     }
-    progress.finish_with_message("Booklet created");
+    progress.finish_with_message(format!("Created {}", kind));
 
-    // calculate imposition layout
-    let total_pages = page_xobjs.len();
-    let sheets = calculate_imposition(total_pages, config.booklet.signature_size);
+    Ok(page_xobjs)
+}
 
-    let sheet_count = sheets.len();
+/// Place the pages of one sheet side.
+fn side_page(
+    layout: &BookletConfig,
+    side: &GridSide,
+    page_xobjs: &[Id<FormXObject>],
+    page_creep: impl Fn(usize) -> Pt,
+) -> Page {
+    let pages = side
+        .slots
+        .iter()
+        .map(|slot| PlacedPage {
+            xobj: slot.page.map(|idx| page_xobjs[idx]),
+            inverted: slot.inverted,
+            creep: slot.page.map(&page_creep).unwrap_or(Pt(0.0)),
+        })
+        .collect::<Vec<_>>();
+    create_grid_page(layout, side.columns, side.rows, side.spine, &pages)
+}
 
-    // create imposed pages (each sheet side becomes a page)
-    for sheet in sheets.iter() {
-        // front side
-        let front_left = sheet.front.left_page.map(|idx| page_xobjs[idx]);
-        let front_right = sheet.front.right_page.map(|idx| page_xobjs[idx]);
-        let front_page = create_imposed_page(&booklet_config, front_left, front_right);
-        booklet_doc.add_page(front_page);
-
-        // back side
-        let back_left = sheet.back.left_page.map(|idx| page_xobjs[idx]);
-        let back_right = sheet.back.right_page.map(|idx| page_xobjs[idx]);
-        let back_page = create_imposed_page(&booklet_config, back_left, back_right);
-        booklet_doc.add_page(back_page);
+/// The slug line for one side of a sheet, e.g. "My Book - sheet 3 of 12, back,
+/// signature 2".
+fn slug_text(
+    source: &Source,
+    sheet_idx: usize,
+    sheet_count: usize,
+    side: Side,
+    signature: Option<usize>,
+) -> String {
+    let mut text = format!(
+        "sheet {} of {}, {}",
+        sheet_idx + 1,
+        sheet_count,
+        side.name()
+    );
+    if let Some(signature) = signature {
+        text.push_str(&format!(", signature {}", signature + 1));
+    }
+    match &source.title {
+        Some(title) => format!("{} - {}", title, text),
+        None => text,
     }
+}
 
-    // write the booklet PDF
+/// Write an imposed document to `output_path`.
+pub fn write_imposed(doc: &Document, output_path: &PathBuf, kind: &str) -> Result<()> {
     let file = std::fs::File::create(output_path).with_context(|| {
         format!(
-            "Failed to create booklet output file: {}",
+            "Failed to create {} output file: {}",
+            kind,
             output_path.display()
         )
     })?;
     let mut file = std::io::BufWriter::new(file);
-    booklet_doc
-        .write(&mut file)
-        .with_context(|| "Failed to write booklet PDF")?;
+    doc.write(&mut file)
+        .with_context(|| format!("Failed to write {} PDF", kind))
+}
 
-    Ok(sheet_count)
+fn title_case(kind: &str) -> String {
+    let mut chars = kind.chars();
+    chars
+        .next()
+        .map(|first| first.to_uppercase().chain(chars).collect())
+        .unwrap_or_default()
 }
@@ -1,7 +1,8 @@
-use pdf_gen::Pt;
+use pdf_gen::layout::Margins;
+use pdf_gen::{In, Page, Pt};
 use serde::{Deserialize, Serialize};
 use std::fmt;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 /// Horizontal position for headers and footers.
 #[derive(Copy, Clone, Eq, PartialEq, Serialize, Deserialize, Debug, Default)]
@@ -93,27 +94,36 @@ impl fmt::Display for PageNumberStyle {
     }
 }
 
-/// Document section for section-specific page numbering.
-#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+/// Document section for section-specific page numbering and layout.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
 pub enum Section {
     /// Front matter (README, LICENSE, etc.)
     #[default]
     Frontmatter,
+    /// Guided tours
+    Tour,
     /// Source code files
     Source,
     /// Commit history appendix
     CommitHistory,
     /// Tags appendix
     Tags,
+    /// TODO register appendix
+    Todos,
+    /// Symbol index appendix
+    Index,
 }
 
 impl fmt::Display for Section {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Section::Frontmatter => write!(f, "Frontmatter"),
+            Section::Tour => write!(f, "Guided Tour"),
             Section::Source => write!(f, "Source"),
             Section::CommitHistory => write!(f, "Commit History"),
             Section::Tags => write!(f, "Tags"),
+            Section::Todos => write!(f, "TODO Register"),
+            Section::Index => write!(f, "Index"),
         }
     }
 }
@@ -288,6 +298,9 @@ pub struct PageConfig {
     pub width_in: f32,
     /// Page height in inches
     pub height_in: f32,
+    /// Landscape turns the page so its longer side runs across
+    #[serde(default)]
+    pub orientation: Orientation,
 }
 
 impl Default for PageConfig {
@@ -295,19 +308,59 @@ impl Default for PageConfig {
         Self {
             width_in: 5.5,
             height_in: 8.5,
+            orientation: Orientation::Portrait,
+        }
+    }
+}
+
+impl PageConfig {
+    /// Width and height of the page as laid out, in inches.
+    pub fn size_in(&self) -> (f32, f32) {
+        match self.orientation {
+            Orientation::Portrait => (self.width_in, self.height_in),
+            Orientation::Landscape => (
+                self.width_in.max(self.height_in),
+                self.width_in.min(self.height_in),
+            ),
         }
     }
 }
 
+/// Which way round the page is laid out.
+#[derive(Copy, Clone, Eq, PartialEq, Serialize, Deserialize, Debug, Default)]
+pub enum Orientation {
+    /// As given by the width and height
+    #[default]
+    Portrait,
+    /// Longer side across, e.g. for code written for wide screens
+    Landscape,
+}
+
+impl fmt::Display for Orientation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Orientation::Portrait => write!(f, "Portrait"),
+            Orientation::Landscape => write!(f, "Landscape (longer side across)"),
+        }
+    }
+}
+
+impl Orientation {
+    pub fn all() -> &'static [Orientation] {
+        &[Orientation::Portrait, Orientation::Landscape]
+    }
+}
+
 /// Page margin configuration.
 ///
 /// Margins are asymmetric to support booklet printing: inner margins accommodate
-/// binding, while outer margins can be smaller.
+/// binding, while outer margins can be smaller. The running header sits inside the
+/// top margin's content area and the footer in the middle of the bottom margin.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MarginsConfig {
-    /// Top margin in inches (typically larger for headers)
+    /// Top margin in inches
     pub top_in: f32,
-    /// Bottom margin in inches
+    /// Bottom margin in inches (holds the footer)
     pub bottom_in: f32,
     /// Inner margin in inches (binding/gutter side)
     pub inner_in: f32,
@@ -318,10 +371,10 @@ pub struct MarginsConfig {
 impl Default for MarginsConfig {
     fn default() -> Self {
         Self {
-            top_in: 0.5,
-            bottom_in: 0.25,
-            inner_in: 0.25,
-            outer_in: 0.125,
+            top_in: 0.25,
+            bottom_in: 0.5,
+            inner_in: 0.5,
+            outer_in: 0.25,
         }
     }
 }
@@ -456,12 +509,34 @@ pub struct MetadataConfig {
 pub struct BookletConfig {
     /// Output path for print-ready booklet PDF. Empty string disables booklet generation.
     pub outfile: String,
-    /// Number of pages per signature (must be divisible by 4)
+    /// Number of pages per signature (must be divisible by 4). Only used for
+    /// saddle-stitch binding; the other bindings take theirs from the fold.
     pub signature_size: u32,
     /// Physical sheet width in inches (default 11.0 for US Letter landscape)
     pub sheet_width_in: f32,
     /// Physical sheet height in inches (default 8.5 for US Letter landscape)
     pub sheet_height_in: f32,
+    /// How the printed sheets are put together
+    #[serde(default)]
+    pub binding: Binding,
+    /// How many pages go on each side of a sheet, and how it's folded
+    #[serde(default)]
+    pub fold: Fold,
+    /// Print a stepped mark on the spine fold of each perfect-bound signature, so
+    /// a missing or misplaced signature shows as a break in the staircase
+    #[serde(default)]
+    pub collation_marks: bool,
+    /// Thickness of one sheet of paper in inches (about 0.004 for 20 lb bond), used
+    /// to move the pages of inner saddle-stitched sheets towards the spine so
+    /// their fore-edges trim evenly. 0 leaves pages where they are.
+    #[serde(default)]
+    pub paper_thickness_in: f32,
+    /// Crop, fold and registration marks and the slug line for a print shop
+    #[serde(default)]
+    pub marks: PrinterMarksConfig,
+    /// Printing both sides on a printer that can only print one
+    #[serde(default)]
+    pub manual_duplex: ManualDuplexConfig,
 }
 
 impl Default for BookletConfig {
@@ -471,10 +546,363 @@ impl Default for BookletConfig {
             signature_size: 16,
             sheet_width_in: 11.0,
             sheet_height_in: 8.5,
+            binding: Binding::SaddleStitch,
+            fold: Fold::Folio,
+            collation_marks: false,
+            paper_thickness_in: 0.0,
+            marks: PrinterMarksConfig::default(),
+            manual_duplex: ManualDuplexConfig::default(),
+        }
+    }
+}
+
+/// Printer's marks drawn around the imposed pages of a booklet. The pages shrink
+/// to make room for them on the sheet.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PrinterMarksConfig {
+    /// Trim marks at the corners of the pages
+    pub crop: bool,
+    /// Marks at the head and foot of the spine fold
+    pub fold: bool,
+    /// Crosshair targets in the middle of each side of the sheet, for lining up
+    /// the front and back
+    pub registration: bool,
+    /// How far each page's content runs past its trim, in inches, so nothing is
+    /// left unprinted if the cut wanders. The marks start outside it
+    pub bleed_in: f32,
+    /// A line under the pages giving the sheet number, side and signature
+    pub slug: bool,
+}
+
+impl Default for PrinterMarksConfig {
+    fn default() -> Self {
+        Self {
+            crop: false,
+            fold: false,
+            registration: false,
+            bleed_in: 0.0,
+            slug: false,
+        }
+    }
+}
+
+/// Manual duplex printing: every front is printed, the stack is turned over and
+/// fed back in, and every back is printed, last sheet first.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManualDuplexConfig {
+    /// Whether the fronts and backs are split, and into one file or two
+    pub mode: ManualDuplex,
+    /// How the printed stack is turned over before it's fed back in
+    pub flip: DuplexFlip,
+    /// Turn the backs upside down as well, for printers that take the stack the
+    /// other way round
+    pub rotate_backs: bool,
+}
+
+impl Default for ManualDuplexConfig {
+    fn default() -> Self {
+        Self {
+            mode: ManualDuplex::Off,
+            flip: DuplexFlip::ShortEdge,
+            rotate_backs: false,
+        }
+    }
+}
+
+impl ManualDuplexConfig {
+    /// Whether the backs print upside down. The booklet assumes the sheet turns
+    /// over on its short edge; turning it on its long edge puts the back upside
+    /// down relative to that.
+    pub fn backs_rotated(&self) -> bool {
+        (self.flip == DuplexFlip::LongEdge) != self.rotate_backs
+    }
+}
+
+/// How the booklet's sheet sides are split for manual duplex printing.
+#[derive(Copy, Clone, Eq, PartialEq, Serialize, Deserialize, Debug, Default)]
+pub enum ManualDuplex {
+    /// Each front followed by its back, for a printer that duplexes
+    #[default]
+    Off,
+    /// One file with every front, then every back in reverse order
+    SingleFile,
+    /// The fronts and the reversed backs in files of their own
+    SeparateFiles,
+}
+
+impl fmt::Display for ManualDuplex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ManualDuplex::Off => write!(f, "Off (the printer prints both sides)"),
+            ManualDuplex::SingleFile => write!(f, "One file, fronts then backs"),
+            ManualDuplex::SeparateFiles => write!(f, "Separate fronts and backs files"),
+        }
+    }
+}
+
+impl ManualDuplex {
+    pub fn all() -> &'static [ManualDuplex] {
+        &[
+            ManualDuplex::Off,
+            ManualDuplex::SingleFile,
+            ManualDuplex::SeparateFiles,
+        ]
+    }
+}
+
+/// Which edge the printed stack is turned over on.
+#[derive(Copy, Clone, Eq, PartialEq, Serialize, Deserialize, Debug, Default)]
+pub enum DuplexFlip {
+    /// Left to right, like turning a page
+    #[default]
+    ShortEdge,
+    /// Top to bottom, like turning a calendar
+    LongEdge,
+}
+
+impl fmt::Display for DuplexFlip {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DuplexFlip::ShortEdge => write!(f, "Short edge (left to right)"),
+            DuplexFlip::LongEdge => write!(f, "Long edge (top to bottom)"),
+        }
+    }
+}
+
+impl DuplexFlip {
+    pub fn all() -> &'static [DuplexFlip] {
+        &[DuplexFlip::ShortEdge, DuplexFlip::LongEdge]
+    }
+}
+
+impl PrinterMarksConfig {
+    /// Whether anything is drawn outside the pages.
+    pub fn any(&self) -> bool {
+        self.crop || self.fold || self.registration || self.slug
+    }
+}
+
+impl BookletConfig {
+    /// How far the pages of the innermost sheet of a saddle-stitched signature are
+    /// moved towards the spine, in inches.
+    pub fn max_creep_in(&self) -> f32 {
+        let sheets = (self.signature_size / 4).max(1);
+        (sheets - 1) as f32 * self.paper_thickness_in
+    }
+}
+
+/// How the booklet's printed sheets are put together.
+#[derive(Copy, Clone, Eq, PartialEq, Serialize, Deserialize, Debug, Default)]
+pub enum Binding {
+    /// Sheets nested inside each other in signatures, folded once and stapled or
+    /// sewn through the fold
+    #[default]
+    SaddleStitch,
+    /// Each sheet folded on its own into a signature, and the signatures stacked
+    /// and glued at the spine
+    Perfect,
+    /// Sheets stacked and guillotined, and the cut stacks piled on one another;
+    /// nothing is folded
+    CutAndStack,
+}
+
+impl fmt::Display for Binding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Binding::SaddleStitch => write!(f, "Saddle-stitch (nested signatures)"),
+            Binding::Perfect => write!(f, "Perfect binding (stacked signatures)"),
+            Binding::CutAndStack => write!(f, "Cut and stack (guillotined)"),
+        }
+    }
+}
+
+impl Binding {
+    pub fn all() -> &'static [Binding] {
+        &[
+            Binding::SaddleStitch,
+            Binding::Perfect,
+            Binding::CutAndStack,
+        ]
+    }
+}
+
+/// How many pages go on each side of a sheet.
+#[derive(Copy, Clone, Eq, PartialEq, Serialize, Deserialize, Debug, Default)]
+pub enum Fold {
+    /// 2-up, folded once into 4 pages
+    #[default]
+    Folio,
+    /// 4-up, folded twice into 8 pages
+    Quarto,
+    /// 8-up, folded three times into 16 pages
+    Octavo,
+}
+
+impl fmt::Display for Fold {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Fold::Folio => write!(f, "Folio (2-up)"),
+            Fold::Quarto => write!(f, "Quarto (4-up)"),
+            Fold::Octavo => write!(f, "Octavo (8-up)"),
+        }
+    }
+}
+
+impl Fold {
+    pub fn all() -> &'static [Fold] {
+        &[Fold::Folio, Fold::Quarto, Fold::Octavo]
+    }
+
+    /// Columns and rows of pages on each side of the sheet.
+    pub fn grid(&self) -> (usize, usize) {
+        match self {
+            Fold::Folio => (2, 1),
+            Fold::Quarto => (2, 2),
+            Fold::Octavo => (4, 2),
+        }
+    }
+
+    /// Logical pages on one sheet, both sides.
+    pub fn pages_per_sheet(&self) -> usize {
+        let (columns, rows) = self.grid();
+        columns * rows * 2
+    }
+}
+
+/// 2-up reading copy configuration: pairs of pages side by side on landscape
+/// sheets, printed single-sided and read flat on a desk rather than folded.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TwoUpConfig {
+    /// Output path for the 2-up PDF. Empty string disables it.
+    pub outfile: String,
+    /// Physical sheet width in inches (default 11.0 for US Letter landscape)
+    pub sheet_width_in: f32,
+    /// Physical sheet height in inches (default 8.5 for US Letter landscape)
+    pub sheet_height_in: f32,
+}
+
+impl Default for TwoUpConfig {
+    fn default() -> Self {
+        Self {
+            outfile: String::new(),
+            sheet_width_in: 11.0,
+            sheet_height_in: 8.5,
         }
     }
 }
 
+/// Wraparound cover for a print-on-demand service: the back cover, spine and
+/// front cover on one sheet, with bleed all round.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CoverConfig {
+    /// Output path for the cover PDF. Empty string disables it.
+    pub outfile: String,
+    /// Thickness of one leaf (two pages) in inches, from the printer's paper
+    /// specification. The spine is this times the number of leaves.
+    pub paper_caliper_in: f32,
+    /// Bleed beyond the trimmed cover on every side, in inches
+    pub bleed_in: f32,
+    /// Spine text, set across the spine reading top to bottom. Placeholders:
+    /// {title}, {version}, and {volume} and {volumes} on the covers of volumes
+    /// (empty on the whole book's). Left off if the spine is too thin for it.
+    pub spine_template: String,
+    /// Back cover blurb. Placeholders as for the title page, plus {version}.
+    /// The front cover uses the title page template and image.
+    pub back_template: String,
+    /// Version for {version}. Empty string uses the newest git tag.
+    pub version: String,
+    /// Font size of the spine text in points
+    pub spine_font_pt: f32,
+}
+
+impl Default for CoverConfig {
+    fn default() -> Self {
+        Self {
+            outfile: String::new(),
+            paper_caliper_in: 0.0045,
+            bleed_in: 0.125,
+            spine_template: "{title}   {version}".to_string(),
+            back_template: default_cover_back_template(),
+            version: String::new(),
+            spine_font_pt: 10.0,
+        }
+    }
+}
+
+impl CoverConfig {
+    /// Width of the spine for a book of `page_count` pages, in inches. An odd page
+    /// count still takes a whole leaf.
+    pub fn spine_width_in(&self, page_count: usize) -> f32 {
+        page_count.div_ceil(2) as f32 * self.paper_caliper_in
+    }
+}
+
+/// Splitting a book too thick to bind into volumes.
+///
+/// The digital PDF stays whole; each volume is written beside it, with its own
+/// title page, as are its booklet and cover. The table of contents in the first
+/// volume covers them all.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VolumesConfig {
+    /// Most pages in one volume, including its title page. Volumes only break
+    /// where a source file or the appendices start, so a single file longer than
+    /// this still makes a volume of its own. 0 for no limit.
+    pub max_pages_per_volume: usize,
+    /// Source files or directories that start a new volume, whatever the limit
+    pub split_before: Vec<String>,
+    /// Line added under the title page template on each volume's title page.
+    /// Placeholders: {volume}, {volumes}
+    pub label_template: String,
+    /// Whether page numbers run on through the volumes or start again in each
+    #[serde(default)]
+    pub numbering: VolumeNumbering,
+}
+
+impl Default for VolumesConfig {
+    fn default() -> Self {
+        Self {
+            max_pages_per_volume: 0,
+            split_before: Vec::new(),
+            label_template: "Volume {volume} of {volumes}".to_string(),
+            numbering: VolumeNumbering::default(),
+        }
+    }
+}
+
+/// How the pages of a book split into volumes are numbered.
+#[derive(Copy, Clone, Eq, PartialEq, Serialize, Deserialize, Debug, Default)]
+pub enum VolumeNumbering {
+    /// Pages keep the book's numbering, so no two pages share a number
+    #[default]
+    Continued,
+    /// Each section starts again from its first page in each volume, and pages in
+    /// the table of contents, index and references are cited with their volume
+    PerVolume,
+}
+
+impl VolumesConfig {
+    /// Whether the book is split at all.
+    pub fn enabled(&self) -> bool {
+        self.max_pages_per_volume > 0 || !self.split_before.is_empty()
+    }
+
+    /// Which of `paths`, the source files in book order, start a volume because of
+    /// `split_before`: for each entry, the first file that is, or is in, it.
+    pub fn split_files(&self, paths: &[PathBuf]) -> Vec<usize> {
+        let mut starts: Vec<usize> = self
+            .split_before
+            .iter()
+            .filter_map(|split| {
+                let split = Path::new(split.trim_end_matches('/'));
+                paths.iter().position(|path| path.starts_with(split))
+            })
+            .collect();
+        starts.sort_unstable();
+        starts.dedup();
+        starts
+    }
+}
+
 /// Binary file hex dump rendering configuration.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BinaryHexConfig {
@@ -497,6 +925,337 @@ impl Default for BinaryHexConfig {
     }
 }
 
+/// Markdown frontmatter rendering configuration.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MarkdownConfig {
+    /// Typeset Markdown frontmatter (headings, lists, tables, images) instead of
+    /// printing it as a raw listing.
+    pub enabled: bool,
+}
+
+impl Default for MarkdownConfig {
+    fn default() -> Self {
+        Self { enabled: true }
+    }
+}
+
+/// Literate layout configuration.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LiterateConfig {
+    /// Typeset documentation comments as prose paragraphs interleaved with the
+    /// code they document, instead of leaving them in the listing.
+    pub enabled: bool,
+    /// Treat every whole-line comment as prose, not just documentation comments.
+    /// Useful for languages whose syntaxes don't mark doc comments (Go, C, Java).
+    pub all_comments: bool,
+}
+
+/// Symbol index appendix configuration.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct IndexConfig {
+    /// Include an index of the functions, types, traits, classes and constants
+    /// defined in the source files, with links to the pages they're defined on.
+    pub enabled: bool,
+}
+
+/// How entries in the TODO register are grouped.
+#[derive(Copy, Clone, Eq, PartialEq, Serialize, Deserialize, Debug, Default)]
+pub enum TodoGrouping {
+    /// Under each tag, in the order the tags are configured
+    #[default]
+    Tag,
+    /// Under each file, in book order
+    File,
+}
+
+/// TODO register appendix configuration.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TodosConfig {
+    /// Include an appendix listing every marker comment with its location and page.
+    pub enabled: bool,
+    /// Words that mark a comment for the register, matched as whole words.
+    pub tags: Vec<String>,
+    /// Group entries by tag or by file.
+    pub group_by: TodoGrouping,
+    /// Glyph printed in the margin beside marked lines in the listing (e.g. "▶"),
+    /// or none to leave the listing unmarked.
+    pub margin_glyph: Option<String>,
+}
+
+impl Default for TodosConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            tags: ["TODO", "FIXME", "HACK", "XXX"]
+                .iter()
+                .map(ToString::to_string)
+                .collect(),
+            group_by: TodoGrouping::Tag,
+            margin_glyph: None,
+        }
+    }
+}
+
+/// Cross-reference links from identifiers in source listings to their definitions.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CrossReferencesConfig {
+    /// Link identifiers that name a function, type, trait, class or constant defined
+    /// elsewhere in the book to the page of its definition.
+    pub enabled: bool,
+    /// Print a small superscript page reference (e.g. `→p.43`) after the first
+    /// reference to each definition on a page, for readers of the printed book.
+    pub page_refs: bool,
+}
+
+/// Per-file outline configuration.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutlineConfig {
+    /// Open each source file with a box listing its top-level items (functions,
+    /// types, impls, classes) with line and page numbers, and add the items as
+    /// child bookmarks under the file.
+    pub enabled: bool,
+    /// Minimum number of top-level items a file needs to get an outline box.
+    /// Items are always added to the bookmarks.
+    pub min_items: usize,
+}
+
+impl Default for OutlineConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            min_items: 5,
+        }
+    }
+}
+
+/// Whitespace handling and line wrapping in code listings. See [`crate::whitespace`]
+/// and [`crate::wrapping`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CodeConfig {
+    /// Columns between tab stops; tabs expand to the next stop.
+    pub tab_width: usize,
+    /// Indentation step the source is written with, in columns.
+    pub indent: usize,
+    /// Reflow leading indentation to this step (e.g. 2 prints 4-space indentation
+    /// as 2), or none to keep it. Whitespace after the first non-blank character is
+    /// never touched.
+    pub reflow_indent: Option<usize>,
+    /// Show spaces as `·` and tabs as `→`.
+    pub visible_whitespace: bool,
+    /// Show trailing whitespace as `·` in a warning colour.
+    pub mark_trailing_whitespace: bool,
+    /// Columns a wrapped line's continuation rows are indented beyond the line's
+    /// own indentation.
+    pub wrap_indent: usize,
+    /// Glyph printed in the line-number gutter of continuation rows; empty for none.
+    pub continuation_glyph: String,
+}
+
+impl Default for CodeConfig {
+    fn default() -> Self {
+        Self {
+            tab_width: 4,
+            indent: 4,
+            reflow_indent: Some(2),
+            visible_whitespace: false,
+            mark_trailing_whitespace: false,
+            wrap_indent: 2,
+            continuation_glyph: "↪".to_string(),
+        }
+    }
+}
+
+/// Layout overrides for one section of the book. Anything left unset falls back to
+/// the book-wide `margins` and `fonts`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SectionLayout {
+    /// Page margins for the section's pages
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub margins: Option<MarginsConfig>,
+    /// Font sizes for the section's pages
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fonts: Option<FontSizesConfig>,
+    /// Columns to flow the section's content into
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub columns: Option<ColumnsConfig>,
+    /// Where each file starts, for the sections made of files (frontmatter, tours
+    /// and source)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub placement: Option<FilePlacement>,
+}
+
+impl SectionLayout {
+    fn is_unset(&self) -> bool {
+        self.margins.is_none()
+            && self.fonts.is_none()
+            && self.columns.is_none()
+            && self.placement.is_none()
+    }
+}
+
+/// Where each file of a section starts.
+#[derive(Copy, Clone, Eq, PartialEq, Serialize, Deserialize, Debug, Default)]
+pub enum FilePlacement {
+    /// On a right-hand page, after a blank page if need be (for hardback editions)
+    RectoStart,
+    /// On a new page
+    #[default]
+    NewPage,
+    /// Straight after the file before, under a rule and the file's name, to save
+    /// paper
+    Continuous,
+}
+
+/// Multi-column flow for a section, e.g. the commit history or symbol index set in
+/// two narrow columns rather than one wide one.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ColumnsConfig {
+    /// Number of columns across the measure
+    pub count: usize,
+    /// Space between adjacent columns, in inches
+    pub gap_in: f32,
+    /// Draw a thin vertical rule down the middle of each gap
+    pub rule: bool,
+    /// Source listings only: use the columns only when 95% of lines fit a column
+    /// without wrapping, and a single column otherwise
+    pub fit_lines: bool,
+}
+
+impl Default for ColumnsConfig {
+    fn default() -> Self {
+        Self {
+            count: 1,
+            gap_in: 0.25,
+            rule: false,
+            fit_lines: false,
+        }
+    }
+}
+
+/// How one section's pages are laid out: the book-wide page size, margins and font
+/// sizes with the section's `[pdf.layout.<section>]` overrides applied, and the
+/// section's columns and file placement. Built by [`PDF::layout_for`] and handed to
+/// the section's renderers, which make every page with [`PageLayout::new_page`].
+#[derive(Debug, Clone)]
+pub struct PageLayout {
+    /// Page width and height
+    pub page_size: (Pt, Pt),
+    /// Page margins
+    pub margins: MarginsConfig,
+    /// Font sizes
+    pub fonts: FontSizesConfig,
+    /// Columns to flow the section's content into
+    pub columns: ColumnsConfig,
+    /// Where each file of the section starts
+    pub placement: FilePlacement,
+}
+
+impl PageLayout {
+    /// Margins of the page that will sit at `page_index` in the book. The inner
+    /// margin is on the binding side: the left of recto (even-indexed) pages and
+    /// the right of verso pages.
+    pub fn page_margins(&self, page_index: usize) -> Margins {
+        let margins = &self.margins;
+        let (left, right) = if page_index % 2 == 0 {
+            (margins.inner_in, margins.outer_in)
+        } else {
+            (margins.outer_in, margins.inner_in)
+        };
+        Margins::trbl(
+            In(margins.top_in).into(),
+            In(right).into(),
+            In(margins.bottom_in).into(),
+            In(left).into(),
+        )
+    }
+
+    /// A new page with the margins for `page_index`.
+    pub fn new_page(&self, page_index: usize) -> Page {
+        Page::new(self.page_size, Some(self.page_margins(page_index)))
+    }
+
+    /// Width between the inner and outer margins.
+    pub fn content_width(&self) -> Pt {
+        let (width, _) = self.page_size;
+        Pt(width.0 - (self.margins.inner_in + self.margins.outer_in) * 72.0)
+    }
+}
+
+/// Per-section layout overrides, e.g. smaller type and margins for the commit
+/// history than for the source listings.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LayoutConfig {
+    /// Frontmatter
+    #[serde(default, skip_serializing_if = "SectionLayout::is_unset")]
+    pub frontmatter: SectionLayout,
+    /// Table of contents
+    #[serde(default, skip_serializing_if = "SectionLayout::is_unset")]
+    pub contents: SectionLayout,
+    /// Guided tours
+    #[serde(default, skip_serializing_if = "SectionLayout::is_unset")]
+    pub tour: SectionLayout,
+    /// Source listings and images
+    #[serde(default, skip_serializing_if = "SectionLayout::is_unset")]
+    pub source: SectionLayout,
+    /// Commit history appendix
+    #[serde(default, skip_serializing_if = "SectionLayout::is_unset")]
+    pub commits: SectionLayout,
+    /// Tags appendix
+    #[serde(default, skip_serializing_if = "SectionLayout::is_unset")]
+    pub tags: SectionLayout,
+    /// TODO register appendix
+    #[serde(default, skip_serializing_if = "SectionLayout::is_unset")]
+    pub todos: SectionLayout,
+    /// Symbol index appendix
+    #[serde(default, skip_serializing_if = "SectionLayout::is_unset")]
+    pub index: SectionLayout,
+}
+
+/// Keep-together pagination for source listings.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeepTogetherConfig {
+    /// Avoid splitting short top-level items across pages, and leave no lone lines
+    /// of an item at the bottom or top of a page. Costs some blank space at the
+    /// foot of pages.
+    pub enabled: bool,
+    /// Items shorter than this many lines move whole to the next page rather than
+    /// split.
+    pub max_lines: usize,
+    /// Lines of an item that must stay together on either side of a page break.
+    pub min_lines: usize,
+}
+
+impl Default for KeepTogetherConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_lines: 12,
+            min_lines: 2,
+        }
+    }
+}
+
+/// Standard file header collapsing.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BoilerplateConfig {
+    /// Print the comment block repeated at the top of many files (usually a licence
+    /// header) once in the frontmatter, and collapse it to a one-line marker in
+    /// each listing.
+    pub enabled: bool,
+    /// Minimum number of files that must open with the same block for it to be
+    /// collapsed.
+    pub min_files: usize,
+}
+
+impl Default for BoilerplateConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            min_files: 3,
+        }
+    }
+}
+
 /// Configuration for displaying tags inline with commits.
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct InlineTagsConfig {
@@ -561,6 +1320,9 @@ impl From<SectionNumbering> for AppendixSectionNumbering {
 pub struct NumberingConfig {
     /// Numbering for frontmatter section
     pub frontmatter: SectionNumbering,
+    /// Numbering for guided tour section (with optional prefix)
+    #[serde(default)]
+    pub tour: AppendixSectionNumbering,
     /// Numbering for source code section
     pub source: SectionNumbering,
     /// Numbering for commit history appendix (with optional prefix)
@@ -569,6 +1331,12 @@ pub struct NumberingConfig {
     /// Numbering for tags appendix (with optional prefix)
     #[serde(default)]
     pub tags: AppendixSectionNumbering,
+    /// Numbering for TODO register appendix (with optional prefix)
+    #[serde(default)]
+    pub todos: AppendixSectionNumbering,
+    /// Numbering for symbol index appendix (with optional prefix)
+    #[serde(default)]
+    pub index: AppendixSectionNumbering,
 
     /// Legacy: single appendix numbering (migrated to commits/tags)
     #[serde(default, skip_serializing)]
@@ -579,9 +1347,12 @@ impl Default for NumberingConfig {
     fn default() -> Self {
         Self {
             frontmatter: SectionNumbering::roman_lower(),
+            tour: AppendixSectionNumbering::default(),
             source: SectionNumbering::default(),
             commits: AppendixSectionNumbering::default(),
             tags: AppendixSectionNumbering::default(),
+            todos: AppendixSectionNumbering::default(),
+            index: AppendixSectionNumbering::default(),
             appendix: None,
         }
     }
@@ -619,6 +1390,9 @@ pub struct PDF {
     pub margins: MarginsConfig,
     /// Font sizes
     pub fonts: FontSizesConfig,
+    /// Per-section margin and font size overrides
+    #[serde(default)]
+    pub layout: LayoutConfig,
 
     /// Header configuration
     pub header: HeaderConfig,
@@ -635,13 +1409,52 @@ pub struct PDF {
 
     /// Booklet printing configuration
     pub booklet: BookletConfig,
+    /// 2-up reading copy
+    #[serde(default)]
+    pub two_up: TwoUpConfig,
+    /// Print-on-demand wraparound cover
+    #[serde(default)]
+    pub cover: CoverConfig,
+    /// Splitting the book into volumes
+    #[serde(default)]
+    pub volumes: VolumesConfig,
     /// Binary file hex dump rendering
     pub binary_hex: BinaryHexConfig,
+    /// Tabs, indentation, visible whitespace and wrapping in listings
+    #[serde(default)]
+    pub code: CodeConfig,
+    /// Keep-together pagination of listings
+    #[serde(default)]
+    pub keep_together: KeepTogetherConfig,
+    /// Markdown frontmatter typesetting
+    #[serde(default)]
+    pub markdown: MarkdownConfig,
+    /// Literate layout for source files
+    #[serde(default)]
+    pub literate: LiterateConfig,
 
     /// Inline tag badges in commit history
     pub inline_tags: InlineTagsConfig,
     /// Tags appendix configuration
     pub tags_appendix: TagsAppendixConfig,
+    /// TODO register appendix configuration
+    #[serde(default)]
+    pub todos: TodosConfig,
+    /// Symbol index appendix configuration
+    #[serde(default)]
+    pub index: IndexConfig,
+
+    /// Go-to-definition links in source listings
+    #[serde(default)]
+    pub cross_references: CrossReferencesConfig,
+
+    /// Per-file outline boxes and bookmarks
+    #[serde(default)]
+    pub outline: OutlineConfig,
+
+    /// Standard file header collapsing
+    #[serde(default)]
+    pub boilerplate: BoilerplateConfig,
 
     /// Section-specific page numbering
     pub numbering: NumberingConfig,
@@ -739,6 +1552,15 @@ pub fn default_title_page_template() -> String {
         .to_string()
 }
 
+pub fn default_cover_back_template() -> String {
+    r#"{title}
+
+{authors}
+
+Released under {licences}"#
+        .to_string()
+}
+
 pub fn default_colophon_template() -> String {
     r#"{title}
 
@@ -779,15 +1601,28 @@ impl Default for PDF {
             page: PageConfig::default(),
             margins: MarginsConfig::default(),
             fonts: FontSizesConfig::default(),
+            layout: LayoutConfig::default(),
             header: HeaderConfig::default(),
             footer: FooterConfig::default(),
             title_page: TitlePageConfig::default(),
             colophon: ColophonConfig::default(),
             metadata: MetadataConfig::default(),
             booklet: BookletConfig::default(),
+            two_up: TwoUpConfig::default(),
+            cover: CoverConfig::default(),
+            volumes: VolumesConfig::default(),
             binary_hex: BinaryHexConfig::default(),
+            code: CodeConfig::default(),
+            keep_together: KeepTogetherConfig::default(),
+            markdown: MarkdownConfig::default(),
+            literate: LiterateConfig::default(),
             inline_tags: InlineTagsConfig::default(),
             tags_appendix: TagsAppendixConfig::default(),
+            todos: TodosConfig::default(),
+            index: IndexConfig::default(),
+            cross_references: CrossReferencesConfig::default(),
+            outline: OutlineConfig::default(),
+            boilerplate: BoilerplateConfig::default(),
             numbering: NumberingConfig::default(),
             // legacy fields
             page_width_in: None,
@@ -833,10 +1668,46 @@ impl Default for PDF {
 impl PDF {
     /// Returns the page size as (width, height) in points.
     pub fn page_size(&self) -> (Pt, Pt) {
-        (
-            Pt(self.page.width_in * 72.0),
-            Pt(self.page.height_in * 72.0),
-        )
+        let (width_in, height_in) = self.page.size_in();
+        (Pt(width_in * 72.0), Pt(height_in * 72.0))
+    }
+
+    /// The page layout of a section: the book-wide settings with the section's
+    /// `[pdf.layout.<section>]` overrides applied.
+    pub fn layout_for(&self, section: Section) -> PageLayout {
+        let layout = match section {
+            Section::Frontmatter => &self.layout.frontmatter,
+            Section::Tour => &self.layout.tour,
+            Section::Source => &self.layout.source,
+            Section::CommitHistory => &self.layout.commits,
+            Section::Tags => &self.layout.tags,
+            Section::Todos => &self.layout.todos,
+            Section::Index => &self.layout.index,
+        };
+        self.with_layout(layout)
+    }
+
+    /// The page layout of the table of contents.
+    pub fn layout_for_contents(&self) -> PageLayout {
+        self.with_layout(&self.layout.contents)
+    }
+
+    fn with_layout(&self, layout: &SectionLayout) -> PageLayout {
+        PageLayout {
+            page_size: self.page_size(),
+            margins: layout
+                .margins
+                .clone()
+                .unwrap_or_else(|| self.margins.clone()),
+            fonts: layout.fonts.clone().unwrap_or_else(|| self.fonts.clone()),
+            columns: layout.columns.clone().unwrap_or_default(),
+            placement: layout.placement.unwrap_or_default(),
+        }
+    }
+
+    /// Whether the header or footer template uses a placeholder, e.g. `{symbol}`.
+    pub fn uses_placeholder(&self, placeholder: &str) -> bool {
+        self.header.template.contains(placeholder) || self.footer.template.contains(placeholder)
     }
 
     /// Returns the numbering configuration for a given section.
@@ -846,6 +1717,10 @@ impl PDF {
     pub fn numbering_for_section(&self, section: Section) -> SectionNumbering {
         let base = match section {
             Section::Frontmatter => self.numbering.frontmatter,
+            Section::Tour => SectionNumbering {
+                style: self.numbering.tour.style,
+                start: self.numbering.tour.start,
+            },
             Section::Source => self.numbering.source,
             Section::CommitHistory => SectionNumbering {
                 style: self.numbering.commits.style,
@@ -855,6 +1730,14 @@ impl PDF {
                 style: self.numbering.tags.style,
                 start: self.numbering.tags.start,
             },
+            Section::Todos => SectionNumbering {
+                style: self.numbering.todos.style,
+                start: self.numbering.todos.start,
+            },
+            Section::Index => SectionNumbering {
+                style: self.numbering.index.style,
+                start: self.numbering.index.start,
+            },
         };
 
         // apply legacy overrides if present
@@ -870,12 +1753,16 @@ impl PDF {
 
     /// Returns the page number prefix for a given section.
     ///
-    /// Only appendix sections (CommitHistory, Tags) support prefixes.
+    /// Only the guided tour and appendix sections (CommitHistory, Tags, Todos, Index)
+    /// support prefixes.
     pub fn prefix_for_section(&self, section: Section) -> &str {
         match section {
             Section::Frontmatter | Section::Source => "",
+            Section::Tour => &self.numbering.tour.prefix,
             Section::CommitHistory => &self.numbering.commits.prefix,
             Section::Tags => &self.numbering.tags.prefix,
+            Section::Todos => &self.numbering.todos.prefix,
+            Section::Index => &self.numbering.index.prefix,
         }
     }
 
@@ -1029,6 +1916,37 @@ impl PDF {
         }
     }
 
+    /// Returns the booklet's fronts and backs paths, if they're written to separate
+    /// files for manual duplexing: `book.pdf` becomes `book-fronts.pdf` and
+    /// `book-backs.pdf`.
+    pub fn booklet_split_paths(&self) -> Option<(PathBuf, PathBuf)> {
+        let path = self.booklet_outfile_path()?;
+        if self.booklet.manual_duplex.mode != ManualDuplex::SeparateFiles {
+            return None;
+        }
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        let sibling = |suffix: &str| path.with_file_name(format!("{}-{}.pdf", stem, suffix));
+        Some((sibling("fronts"), sibling("backs")))
+    }
+
+    /// Returns the 2-up output path, if configured.
+    pub fn two_up_outfile_path(&self) -> Option<PathBuf> {
+        if self.two_up.outfile.is_empty() {
+            None
+        } else {
+            Some(PathBuf::from(&self.two_up.outfile))
+        }
+    }
+
+    /// Returns the cover output path, if configured.
+    pub fn cover_outfile_path(&self) -> Option<PathBuf> {
+        if self.cover.outfile.is_empty() {
+            None
+        } else {
+            Some(PathBuf::from(&self.cover.outfile))
+        }
+    }
+
     /// Returns the subject, if configured.
     pub fn subject_opt(&self) -> Option<&str> {
         if self.metadata.subject.is_empty() {
@@ -1054,6 +1972,25 @@ pub struct RenderStats {
     pub page_count: usize,
     /// If a booklet was generated, the number of sheets needed
     pub booklet_sheets: Option<usize>,
+    /// If a 2-up copy was generated, the number of sheets needed
+    pub two_up_sheets: Option<usize>,
+    /// If a cover was generated, the width of its spine in inches
+    pub cover_spine_in: Option<f32>,
+    /// If the book was split into volumes, what was written for each. The whole
+    /// book's booklet and cover aren't written then, only each volume's.
+    pub volumes: Vec<VolumeStats>,
+}
+
+/// What was written for one volume of a book split into volumes.
+pub struct VolumeStats {
+    /// The volume's PDF
+    pub outfile: PathBuf,
+    /// Number of pages in the volume, its title page included
+    pub page_count: usize,
+    /// If a booklet was generated, its path and the number of sheets needed
+    pub booklet: Option<(PathBuf, usize)>,
+    /// If a cover was generated, the width of its spine in inches
+    pub cover_spine_in: Option<f32>,
 }
 
 #[cfg(test)]
@@ -1066,6 +2003,136 @@ mod test {
         toml::to_string(&pdf).expect("can serialize PDF to TOML");
     }
 
+    #[test]
+    fn applies_section_layout_overrides() {
+        let pdf: PDF = toml::from_str(
+            &(toml::to_string(&PDF::default()).expect("can serialize")
+                + "\n[layout.commits.fonts]\ntitle_pt = 20.0\nheading_pt = 14.0\n\
+                   subheading_pt = 8.0\nbody_pt = 6.0\nsmall_pt = 5.0\n"),
+        )
+        .expect("can deserialize");
+
+        assert_eq!(pdf.layout_for(Section::CommitHistory).fonts.body_pt, 6.0);
+        assert_eq!(
+            pdf.layout_for(Section::Source).fonts.body_pt,
+            pdf.fonts.body_pt
+        );
+        assert_eq!(
+            pdf.layout_for(Section::CommitHistory).margins.top_in,
+            pdf.margins.top_in
+        );
+    }
+
+    #[test]
+    fn columns_apply_only_to_their_section() {
+        let pdf: PDF = toml::from_str(
+            &(toml::to_string(&PDF::default()).expect("can serialize")
+                + "\n[layout.tags.columns]\ncount = 2\ngap_in = 0.2\nrule = true\n\
+                   fit_lines = false\n"),
+        )
+        .expect("can deserialize");
+
+        assert_eq!(pdf.layout_for(Section::Source).columns.count, 1);
+        assert_eq!(pdf.layout_for(Section::Tags).columns.count, 2);
+        assert_eq!(pdf.layout_for(Section::Index).columns.count, 1);
+        assert_eq!(pdf.layout_for_contents().columns.count, 1);
+    }
+
+    #[test]
+    fn files_start_on_a_new_page_unless_configured() {
+        let pdf: PDF = toml::from_str(
+            &(toml::to_string(&PDF::default()).expect("can serialize")
+                + "\n[layout.source]\nplacement = \"Continuous\"\n"),
+        )
+        .expect("can deserialize");
+
+        assert_eq!(
+            pdf.layout_for(Section::Source).placement,
+            FilePlacement::Continuous
+        );
+        assert_eq!(
+            pdf.layout_for(Section::Frontmatter).placement,
+            FilePlacement::NewPage
+        );
+    }
+
+    #[test]
+    fn creep_grows_with_signature_size() {
+        let mut booklet = BookletConfig::default();
+        assert_eq!(booklet.max_creep_in(), 0.0);
+
+        // a 48-page signature nests 12 sheets, 11 of them inside the outermost
+        booklet.signature_size = 48;
+        booklet.paper_thickness_in = 0.004;
+        assert!((booklet.max_creep_in() - 0.044).abs() < 1e-6);
+    }
+
+    #[test]
+    fn manual_duplex_splits_the_booklet() {
+        let mut pdf = PDF::default();
+        pdf.booklet.outfile = "out/book-booklet.pdf".to_string();
+        assert!(pdf.booklet_split_paths().is_none());
+
+        pdf.booklet.manual_duplex.mode = ManualDuplex::SeparateFiles;
+        let (fronts, backs) = pdf.booklet_split_paths().expect("split paths");
+        assert_eq!(fronts, PathBuf::from("out/book-booklet-fronts.pdf"));
+        assert_eq!(backs, PathBuf::from("out/book-booklet-backs.pdf"));
+
+        // turning the stack on its long edge and rotating the backs cancel out
+        let duplex = &mut pdf.booklet.manual_duplex;
+        assert!(!duplex.backs_rotated());
+        duplex.flip = DuplexFlip::LongEdge;
+        assert!(duplex.backs_rotated());
+        duplex.rotate_backs = true;
+        assert!(!duplex.backs_rotated());
+    }
+
+    #[test]
+    fn spine_width_counts_leaves() {
+        let mut cover = CoverConfig::default();
+        cover.paper_caliper_in = 0.005;
+        assert!((cover.spine_width_in(200) - 0.5).abs() < 1e-6);
+        // a last page on its own still takes a leaf
+        assert!((cover.spine_width_in(201) - 0.505).abs() < 1e-6);
+        assert_eq!(cover.spine_width_in(0), 0.0);
+    }
+
+    #[test]
+    fn splits_volumes_before_files_and_directories() {
+        let paths: Vec<PathBuf> = [
+            "README.md",
+            "src/main.rs",
+            "src/parser/mod.rs",
+            "tests/a.rs",
+        ]
+        .iter()
+        .map(PathBuf::from)
+        .collect();
+        let mut volumes = VolumesConfig::default();
+        assert!(!volumes.enabled());
+        assert!(volumes.split_files(&paths).is_empty());
+
+        volumes.split_before = vec!["tests/".to_string(), "src/parser".to_string()];
+        assert!(volumes.enabled());
+        assert_eq!(volumes.split_files(&paths), vec![2, 3]);
+
+        // a prefix of a name isn't a directory
+        volumes.split_before = vec!["src/par".to_string()];
+        assert!(volumes.split_files(&paths).is_empty());
+    }
+
+    #[test]
+    fn landscape_turns_the_page() {
+        let mut pdf = PDF::default();
+        assert_eq!(pdf.page.size_in(), (5.5, 8.5));
+        pdf.page.orientation = Orientation::Landscape;
+        assert_eq!(pdf.page.size_in(), (8.5, 5.5));
+        assert_eq!(
+            pdf.layout_for(Section::Source).content_width().0,
+            (8.5 - 0.5 - 0.25) * 72.0
+        );
+    }
+
     #[test]
     fn can_roundtrip_pdf() {
         let pdf = PDF::default();
@@ -0,0 +1,187 @@
+//! Wraparound cover generation for print-on-demand services.
+//!
+//! A perfect-bound book from a print-on-demand service needs its cover as one
+//! sheet: the back cover on the left, then the spine, then the front cover, with
+//! bleed all round. Its width depends on the book: the spine is as thick as the
+//! pages are, so it's worked out from the final page count and the paper's caliper
+//! (see [`crate::sinks::pdf::config::CoverConfig::spine_width_in`]).
+//!
+//! The front cover is laid out like the title page, with the same template and
+//! image; the back cover uses its own blurb template with the same placeholders,
+//! plus `{version}`. Both go through the title page's
+//! [`render_template`], centred within the trim less a safe margin. The spine text
+//! is set on a Form XObject turned a quarter turn clockwise, so it reads top to
+//! bottom with the book lying face up, and is left off when the spine is too thin
+//! for it.
+
+use crate::sinks::pdf::booklet::{imposed_document, write_imposed};
+use crate::sinks::pdf::config::PDF;
+use crate::sinks::pdf::fonts::FontIds;
+use crate::sinks::pdf::rendering::{load_image, render_template, TemplateBlock};
+use crate::source::{Source, TagOrder};
+use anyhow::{Context, Result};
+use pdf_gen::id_arena_crate::Id;
+use pdf_gen::*;
+use std::f32::consts::FRAC_PI_2;
+use std::path::PathBuf;
+
+/// Distance text and images stay inside the trim, as print-on-demand services ask.
+const SAFE_MARGIN: Pt = Pt(72.0 * 0.25);
+
+/// Space either side of the spine text, so it doesn't wrap onto the covers if the
+/// binding drifts.
+const SPINE_CLEARANCE: Pt = Pt(72.0 * 0.0625);
+
+/// Generate the wraparound cover for a book of `page_count` pages.
+///
+/// Returns the width of the spine in inches.
+pub fn render_cover(
+    config: &PDF,
+    source: &Source,
+    page_count: usize,
+    output_path: &PathBuf,
+) -> Result<f32> {
+    let spine_in = config.cover.spine_width_in(page_count);
+    let (page_width, page_height) = config.page_size();
+    let spine = Pt(spine_in * 72.0);
+    let bleed = Pt(config.cover.bleed_in * 72.0);
+
+    let (mut doc, font_ids) = imposed_document(config, source, "cover")?;
+    let mut page = Page::new(
+        (
+            page_width * 2.0 + spine + bleed * 2.0,
+            page_height + bleed * 2.0,
+        ),
+        None,
+    );
+
+    let version = cover_version(config, source);
+    let back_x = bleed;
+    let spine_x = back_x + page_width;
+    let front_x = spine_x + spine;
+    let safe_area = |x: Pt| Rect {
+        x1: x + SAFE_MARGIN,
+        y1: bleed + SAFE_MARGIN,
+        x2: x + page_width - SAFE_MARGIN,
+        y2: bleed + page_height - SAFE_MARGIN,
+    };
+
+    // back cover: the blurb
+    let back_template = config.cover.back_template.replace("{version}", &version);
+    render_template(
+        config,
+        &doc,
+        &font_ids,
+        &mut page,
+        source,
+        TemplateBlock {
+            template: &back_template,
+            image: None,
+            area: safe_area(back_x),
+        },
+    );
+
+    // front cover: the title page again, image and all
+    let front = safe_area(front_x);
+    let image_data = match config.title_page_image_path() {
+        Some(image_path) => {
+            let area_size = (front.x2 - front.x1, front.y2 - front.y1);
+            let image =
+                load_image(config, &mut doc, &image_path, area_size).with_context(|| {
+                    format!("Failed to load cover image '{}'", image_path.display())
+                })?;
+            Some(image)
+        }
+        None => None,
+    };
+    let front_template = config.title_page.template.replace("{version}", &version);
+    render_template(
+        config,
+        &doc,
+        &font_ids,
+        &mut page,
+        source,
+        TemplateBlock {
+            template: &front_template,
+            image: image_data,
+            area: front,
+        },
+    );
+
+    // spine
+    let spine_text = config
+        .cover
+        .spine_template
+        .replace("{title}", source.title.as_deref().unwrap_or("untitled"))
+        .replace("{version}", &version)
+        // only a volume's cover has a number; the whole book's leaves it out
+        .replace("{volume}", "")
+        .replace("{volumes}", "")
+        .trim()
+        .to_string();
+    if !spine_text.is_empty() {
+        let spine_centre = (spine_x + spine / 2.0, bleed + page_height / 2.0);
+        if let Some((xobj_id, text_width, line_height)) =
+            spine_xobject(config, &mut doc, &font_ids, &spine_text, spine, page_height)
+        {
+            // a quarter turn clockwise takes the text's box to the right of and
+            // below the origin, so the translation puts its centre on the spine's
+            let transform = Transform::rotate(-FRAC_PI_2).with_translate(
+                spine_centre.0 - line_height / 2.0,
+                spine_centre.1 + text_width / 2.0,
+            );
+            page.add_form_xobject(FormXObjectLayout { xobj_id, transform });
+        }
+    }
+
+    doc.add_page(page);
+    write_imposed(&doc, output_path, "cover")?;
+    Ok(spine_in)
+}
+
+/// The spine text on a Form XObject of its own, unrotated, with the text's width
+/// and line height, or `None` if it won't fit across the spine or along it.
+fn spine_xobject(
+    config: &PDF,
+    doc: &mut Document,
+    font_ids: &FontIds,
+    text: &str,
+    spine: Pt,
+    page_height: Pt,
+) -> Option<(Id<FormXObject>, Pt, Pt)> {
+    let font = &doc.fonts[font_ids.bold];
+    let size = Pt(config.cover.spine_font_pt);
+    let text_width = layout::width_of_text(text, font, size);
+    let line_height = font.line_height(size);
+    let ascent = font.ascent(size);
+    if (line_height + SPINE_CLEARANCE * 2.0).0 > spine.0
+        || (text_width + SAFE_MARGIN * 2.0).0 > page_height.0
+    {
+        return None;
+    }
+
+    let mut xobj = FormXObject::new(text_width, line_height);
+    xobj.add_span(SpanLayout {
+        text: text.to_string(),
+        font: SpanFont {
+            id: font_ids.bold,
+            size,
+        },
+        colour: colours::BLACK,
+        coords: (Pt(0.0), line_height - ascent),
+    });
+    Some((doc.add_form_xobject(xobj), text_width, line_height))
+}
+
+/// The version for `{version}`: the configured one, or else the newest git tag.
+fn cover_version(config: &PDF, source: &Source) -> String {
+    if !config.cover.version.is_empty() {
+        return config.cover.version.clone();
+    }
+    source
+        .tags(TagOrder::NewestFirst)
+        .ok()
+        .and_then(|tags| tags.into_iter().next())
+        .map(|tag| tag.name)
+        .unwrap_or_default()
+}
@@ -1,4 +1,4 @@
-//! Saddle-stitch booklet imposition calculations.
+//! Booklet imposition calculations.
 //!
 //! Imposition is the process of arranging pages on physical sheets so they appear
 //! in the correct order after printing, folding, and binding. For saddle-stitch
@@ -10,15 +10,48 @@
 //!
 //! The imposition formula ensures that when sheets are stacked outer-to-inner and
 //! folded, pages 1, 2, 3, ... N appear in sequence.
+//!
+//! Perfect binding folds each sheet on its own into a signature, and the
+//! signatures are stacked rather than nested. A sheet may carry 2 (folio), 4
+//! (quarto) or 8 (octavo) pages a side; for the larger folds the top row is
+//! printed upside down so every page comes out upright once folded. Cut-and-stack
+//! uses the same grids without folding: the printed stack is guillotined into one
+//! pile per page position and the piles are laid on top of each other.
+//!
+//! All of these are expressed as [`GridSheet`]s; saddle-stitch sheets convert into
+//! them too, so one function places pages for every binding.
+//!
+//! The 2-up reading copy isn't folded: each sheet shows one spread, the pages a
+//! reader sees side by side in the bound book.
+//!
+//! Pages wider than they're tall would have to shrink to sit side by side, so
+//! they're stacked head to foot on an upright sheet instead, folding across it.
+//! The grid is worked out on the sheet and pages turned a quarter turn, where
+//! everything above applies unchanged, and turned back as it's drawn.
+//!
+//! For a print shop, the pages can be surrounded by printer's marks: crop marks
+//! at the trim, fold marks at the spine, registration targets, and a slug line
+//! identifying the sheet. The pages shrink so the marks fit in a band around the
+//! sheet's edge. Each page's content can run a bleed past its trim, so a cut that
+//! wanders doesn't leave a white edge; the cells make room for it and the marks
+//! start outside it. Pages still meet at the spine, where nothing is cut.
 
+use crate::sinks::pdf::config::{Fold, PrinterMarksConfig};
 use pdf_gen::id_arena_crate::Id;
-use pdf_gen::{FormXObject, FormXObjectLayout, Page, Pt, Transform};
+use pdf_gen::{
+    Colour, Content, Font, FormXObject, FormXObjectLayout, LineCapStyle, Page, Pt, SpanFont,
+    SpanLayout, Transform,
+};
+
+/// Least gap between a page's trim and where a crop or fold mark starts, in points
+const MARK_OFFSET: f32 = 4.5;
+/// Length of crop and fold marks, in points
+const MARK_LENGTH: f32 = 18.0;
+/// Size of the slug line's text, in points
+const SLUG_SIZE: f32 = 6.0;
 
-/// Configuration for booklet imposition
-#[allow(dead_code)]
+/// Sizes of the sheets and the logical pages placed on them
 pub struct BookletConfig {
-    /// Number of pages per signature (must be divisible by 4)
-    pub signature_size: u32,
     /// Width of the physical sheet in points
     pub sheet_width: Pt,
     /// Height of the physical sheet in points
@@ -27,6 +60,22 @@ pub struct BookletConfig {
     pub page_width: Pt,
     /// Height of each logical page in points
     pub page_height: Pt,
+    /// Printer's marks around the pages
+    pub marks: PrinterMarksConfig,
+    /// The pages are stacked head to foot on an upright sheet rather than side by
+    /// side, and fold across it
+    pub turned: bool,
+}
+
+impl BookletConfig {
+    /// Width of the band around the sheet's edge kept for the marks, in points.
+    fn inset(&self) -> f32 {
+        if self.marks.any() {
+            MARK_OFFSET + MARK_LENGTH
+        } else {
+            0.0
+        }
+    }
 }
 
 /// Represents a single side of a printed sheet (front or back)
@@ -43,6 +92,99 @@ pub struct PrintSheet {
     pub back: SheetSide,
 }
 
+/// A place for a logical page on one side of a sheet
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct Slot {
+    /// Page index (None for blank)
+    pub page: Option<usize>,
+    /// Printed upside down, so it's upright once the sheet is folded
+    pub inverted: bool,
+}
+
+/// One side of a sheet with its pages in a grid
+pub struct GridSide {
+    pub columns: usize,
+    pub rows: usize,
+    /// Row by row, starting at the top left
+    pub slots: Vec<Slot>,
+    /// The column boundary the sheet folds along at the spine, counted from the
+    /// left; None if the sheet isn't folded
+    pub spine: Option<usize>,
+}
+
+/// A complete printed sheet (both sides) with its pages in a grid.
+///
+/// The back is printed flipped on the short edge, so the page behind the front's
+/// slot at (column, row) is the back's slot at (columns - 1 - column, row).
+pub struct GridSheet {
+    pub front: GridSide,
+    pub back: GridSide,
+    /// The signature the sheet belongs to, from 0; None if it isn't folded into one
+    pub signature: Option<usize>,
+}
+
+impl GridSide {
+    /// The side turned upside down: each page moves to the opposite slot and
+    /// flips over, and the spine is mirrored.
+    pub fn rotated(&self) -> GridSide {
+        GridSide {
+            columns: self.columns,
+            rows: self.rows,
+            slots: self
+                .slots
+                .iter()
+                .rev()
+                .map(|slot| Slot {
+                    page: slot.page,
+                    inverted: !slot.inverted,
+                })
+                .collect(),
+            spine: self.spine.map(|spine| self.columns - spine),
+        }
+    }
+}
+
+/// Which side of a sheet is printed
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Side {
+    Front,
+    Back,
+}
+
+impl Side {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Side::Front => "front",
+            Side::Back => "back",
+        }
+    }
+}
+
+impl From<SheetSide> for GridSide {
+    fn from(side: SheetSide) -> Self {
+        let slot = |page| Slot {
+            page,
+            inverted: false,
+        };
+        GridSide {
+            columns: 2,
+            rows: 1,
+            slots: vec![slot(side.left_page), slot(side.right_page)],
+            spine: Some(1),
+        }
+    }
+}
+
+impl From<PrintSheet> for GridSheet {
+    fn from(sheet: PrintSheet) -> Self {
+        GridSheet {
+            front: sheet.front.into(),
+            back: sheet.back.into(),
+            signature: None,
+        }
+    }
+}
+
 /// Calculate the signature layout for booklet imposition.
 ///
 /// For saddle-stitch binding, pages must be arranged so that when the sheets
@@ -143,7 +285,209 @@ pub fn calculate_imposition(total_pages: usize, signature_size: u32) -> Vec<Prin
     all_sheets
 }
 
-/// Create a booklet page with two logical pages placed side by side.
+/// Slots on one side of a sheet as (page, inverted)
+type SideTemplate = Vec<(usize, bool)>;
+
+/// Page order on the two sides of one folded sheet, 0-indexed.
+///
+/// Returned as (front, back) slots, row by row from the top left. A folio folds
+/// left half behind right; a quarto top half behind bottom, then left behind
+/// right; an octavo left behind right, top behind bottom, then left behind right
+/// again. Either way the spine is the left edge of the first page, which is on the
+/// front's bottom row (see [`spine_column`]).
+fn fold_template(fold: Fold) -> (SideTemplate, SideTemplate) {
+    const UP: bool = false;
+    const DOWN: bool = true;
+    match fold {
+        Fold::Folio => (vec![(3, UP), (0, UP)], vec![(1, UP), (2, UP)]),
+        Fold::Quarto => (
+            vec![(4, DOWN), (3, DOWN), (7, UP), (0, UP)],
+            vec![(2, DOWN), (5, DOWN), (1, UP), (6, UP)],
+        ),
+        Fold::Octavo => (
+            vec![
+                (4, DOWN),
+                (11, DOWN),
+                (8, DOWN),
+                (7, DOWN),
+                (3, UP),
+                (12, UP),
+                (15, UP),
+                (0, UP),
+            ],
+            vec![
+                (6, DOWN),
+                (9, DOWN),
+                (10, DOWN),
+                (5, DOWN),
+                (1, UP),
+                (14, UP),
+                (13, UP),
+                (2, UP),
+            ],
+        ),
+    }
+}
+
+/// The column boundary the front of a folded sheet folds along at the spine: the
+/// left edge of its first page. The back's spine is mirrored.
+pub fn spine_column(fold: Fold) -> usize {
+    let (columns, _) = fold.grid();
+    let (front, _) = fold_template(fold);
+    let first = front
+        .iter()
+        .position(|&(page, _)| page == 0)
+        .expect("every fold has a first page");
+    first % columns
+}
+
+/// Calculate the imposition for perfect binding.
+///
+/// Each sheet is folded on its own into a signature of
+/// [`Fold::pages_per_sheet`] pages, and the signatures are stacked in order.
+/// The last signature is padded with blank pages.
+pub fn calculate_perfect_binding(total_pages: usize, fold: Fold) -> Vec<GridSheet> {
+    let (columns, rows) = fold.grid();
+    let (front, back) = fold_template(fold);
+    let per_sheet = fold.pages_per_sheet();
+    let spine = spine_column(fold);
+
+    (0..total_pages.div_ceil(per_sheet))
+        .map(|sheet_idx| {
+            let side = |template: &[(usize, bool)], spine: usize| GridSide {
+                columns,
+                rows,
+                slots: template
+                    .iter()
+                    .map(|&(local, inverted)| {
+                        let global = sheet_idx * per_sheet + local;
+                        Slot {
+                            page: (global < total_pages).then_some(global),
+                            inverted,
+                        }
+                    })
+                    .collect(),
+                spine: Some(spine),
+            };
+            GridSheet {
+                front: side(&front, spine),
+                back: side(&back, columns - spine),
+                signature: Some(sheet_idx),
+            }
+        })
+        .collect()
+}
+
+/// Calculate the imposition for cut-and-stack.
+///
+/// Each page position on the sheet becomes a pile once the stack is guillotined,
+/// and the piles are laid on top of each other, top left first. So with S
+/// sheets, the leaf at slot k of sheet s is leaf k * S + s of the book, with its
+/// recto on the front and its verso behind it on the back.
+pub fn calculate_cut_and_stack(total_pages: usize, fold: Fold) -> Vec<GridSheet> {
+    let (columns, rows) = fold.grid();
+    let slots = columns * rows;
+    let leaves = total_pages.div_ceil(2);
+    let sheet_count = leaves.div_ceil(slots);
+    let page = |idx: usize| Slot {
+        page: (idx < total_pages).then_some(idx),
+        inverted: false,
+    };
+
+    (0..sheet_count)
+        .map(|sheet_idx| {
+            let leaf = |slot: usize| slot * sheet_count + sheet_idx;
+            let front = (0..slots).map(|slot| page(leaf(slot) * 2)).collect();
+            let back = (0..slots)
+                .map(|slot| {
+                    // the back is mirrored left to right
+                    let (column, row) = (slot % columns, slot / columns);
+                    let behind = row * columns + (columns - 1 - column);
+                    page(leaf(behind) * 2 + 1)
+                })
+                .collect();
+            GridSheet {
+                front: GridSide {
+                    columns,
+                    rows,
+                    slots: front,
+                    spine: None,
+                },
+                back: GridSide {
+                    columns,
+                    rows,
+                    slots: back,
+                    spine: None,
+                },
+                signature: None,
+            }
+        })
+        .collect()
+}
+
+/// How many sheets are nested outside the one a page is printed on, in a
+/// saddle-stitched signature of `signature_size` pages.
+///
+/// The outermost sheet carries the first two and last two pages of the
+/// signature, the next sheet in the two after and before those, and so on.
+pub fn nesting_depth(page: usize, signature_size: u32) -> usize {
+    let local = page % signature_size as usize;
+    let from_end = signature_size as usize - 1 - local;
+    local.min(from_end) / 2
+}
+
+/// How far a page in a saddle-stitched signature is moved towards the spine so
+/// its fore-edge margin survives trimming.
+///
+/// Each sheet wrapped around it pushes its fore-edge out by about one sheet's
+/// thickness, so the innermost pages of a deep signature move the most and the
+/// outermost don't move at all.
+pub fn creep(page: usize, signature_size: u32, paper_thickness: Pt) -> Pt {
+    Pt(nesting_depth(page, signature_size) as f32 * *paper_thickness)
+}
+
+/// The order sheet sides are printed in, as (sheet index, side).
+///
+/// A duplexing printer takes each front followed by its back. For manual duplex,
+/// every front is printed, then the stack is turned over and fed back in, so the
+/// last sheet printed is the first to take its back.
+pub fn print_order(sheet_count: usize, manual_duplex: bool) -> Vec<(usize, Side)> {
+    if manual_duplex {
+        let fronts = (0..sheet_count).map(|sheet| (sheet, Side::Front));
+        let backs = (0..sheet_count).rev().map(|sheet| (sheet, Side::Back));
+        fronts.chain(backs).collect()
+    } else {
+        (0..sheet_count)
+            .flat_map(|sheet| [(sheet, Side::Front), (sheet, Side::Back)])
+            .collect()
+    }
+}
+
+/// Calculate the spreads of the book, one per sheet side, for reading flat.
+///
+/// The first page is a recto, so it sits alone on the right of the first spread;
+/// after it, each verso is paired with the recto that follows it.
+pub fn calculate_spreads(total_pages: usize) -> Vec<SheetSide> {
+    let page = |idx: usize| (idx < total_pages).then_some(idx);
+    let mut spreads = Vec::with_capacity(total_pages / 2 + 1);
+    if total_pages == 0 {
+        return spreads;
+    }
+    spreads.push(SheetSide {
+        left_page: None,
+        right_page: Some(0),
+    });
+    for left in (1..total_pages).step_by(2) {
+        spreads.push(SheetSide {
+            left_page: page(left),
+            right_page: page(left + 1),
+        });
+    }
+    spreads
+}
+
+/// Create a booklet page with two logical pages placed side by side, or head to
+/// foot if the layout is turned.
 ///
 /// Each page is scaled to fit within its half of the sheet and centred
 /// both horizontally and vertically within that half.
@@ -151,44 +495,381 @@ pub fn create_imposed_page(
     config: &BookletConfig,
     left_xobj: Option<Id<FormXObject>>,
     right_xobj: Option<Id<FormXObject>>,
+) -> Page {
+    let upright = |xobj| PlacedPage {
+        xobj,
+        inverted: false,
+        creep: Pt(0.0),
+    };
+    create_grid_page(
+        config,
+        2,
+        1,
+        None,
+        &[upright(left_xobj), upright(right_xobj)],
+    )
+}
+
+/// A logical page as it's placed in its cell on a sheet side
+pub struct PlacedPage {
+    /// The page's content (None for blank)
+    pub xobj: Option<Id<FormXObject>>,
+    /// Printed upside down, so it's upright once the sheet is folded
+    pub inverted: bool,
+    /// How far the page is moved in towards the sheet's centre line to make up for
+    /// creep (see [`creep`])
+    pub creep: Pt,
+}
+
+/// Create a sheet side with logical pages in a grid, given row by row from the top
+/// left, folding along the `spine` column boundary if it's folded at all.
+///
+/// Each page is scaled to fit within its cell of the grid and centred both
+/// horizontally and vertically within it, then moved in by its creep. Crop, fold
+/// and registration marks are drawn around the grid if configured.
+pub fn create_grid_page(
+    config: &BookletConfig,
+    columns: usize,
+    rows: usize,
+    spine: Option<usize>,
+    pages: &[PlacedPage],
 ) -> Page {
     let mut page = Page::new((config.sheet_width, config.sheet_height), None);
+    let cell = GridCell::new(config, columns, rows, spine);
 
-    // calculate scaling to fit pages side by side
-    // each page gets half the sheet width
-    let available_width = config.sheet_width / 2.0;
-    let available_height = config.sheet_height;
-
-    let scale_x = *available_width / *config.page_width;
-    let scale_y = *available_height / *config.page_height;
-    let scale = scale_x.min(scale_y);
-
-    // centre pages within their half of the sheet
-    let scaled_width = *config.page_width * scale;
-    let scaled_height = *config.page_height * scale;
-    let x_offset = (*available_width - scaled_width) / 2.0;
-    let y_offset = (*available_height - scaled_height) / 2.0;
-
-    // place left page (centred in left half)
-    // scale first, then translate - order matters for matrix composition
-    if let Some(xobj_id) = left_xobj {
-        let transform = Transform::scale(scale, scale).with_translate(Pt(x_offset), Pt(y_offset));
+    for (slot, placed) in pages.iter().enumerate() {
+        let Some(xobj_id) = placed.xobj else {
+            continue;
+        };
+        let (column, row) = (slot % columns, slot / columns);
+        // pages left of the centre line move right, and those right of it left
+        let inward = if (column * 2) < columns {
+            *placed.creep
+        } else {
+            -*placed.creep
+        };
+        let (x, y) = cell.trim_origin(column, row);
+        let (x, y) = cell.page_corner(x + inward, y);
+
+        // scale first, then translate - order matters for matrix composition.
+        // turning a page upside down is a negative scale, which swings it about its
+        // origin, so it's translated from its far corner instead
+        let transform = if placed.inverted {
+            Transform::scale(-cell.scale, -cell.scale).with_translate(
+                Pt(x + *config.page_width * cell.scale),
+                Pt(y + *config.page_height * cell.scale),
+            )
+        } else {
+            Transform::scale(cell.scale, cell.scale).with_translate(Pt(x), Pt(y))
+        };
         page.add_form_xobject(FormXObjectLayout { xobj_id, transform });
     }
 
-    // place right page (centred in right half)
-    if let Some(xobj_id) = right_xobj {
-        let transform = Transform::scale(scale, scale)
-            .with_translate(Pt(*available_width + x_offset), Pt(y_offset));
-        page.add_form_xobject(FormXObjectLayout { xobj_id, transform });
+    if config.marks.any() {
+        let mut content = Content::new();
+        content
+            .set_stroke_gray(0.0)
+            .set_line_cap(LineCapStyle::ButtCap)
+            .set_line_width(0.25);
+        if config.marks.crop {
+            add_crop_marks(&mut content, config, &cell, spine);
+        }
+        if let (true, Some(spine)) = (config.marks.fold, spine) {
+            add_fold_marks(&mut content, config, &cell, spine);
+        }
+        if config.marks.registration {
+            add_registration_marks(&mut content, config);
+        }
+        page.add_content(content);
     }
 
     page
 }
 
+/// Trim marks at the corners of every page, outside the grid and its bleed. Edges
+/// along the spine are folded rather than cut, so they aren't marked.
+fn add_crop_marks(
+    content: &mut Content,
+    config: &BookletConfig,
+    cell: &GridCell,
+    spine: Option<usize>,
+) {
+    let away = cell.bleed + MARK_OFFSET;
+    let (top, bottom) = (cell.top(), cell.bottom());
+    let (left, right) = (cell.left(), cell.right());
+
+    for column in 0..cell.columns {
+        let (x, _) = cell.trim_origin(column, 0);
+        for (edge, x) in [(column, x), (column + 1, x + cell.page_width)] {
+            if spine == Some(edge) {
+                continue;
+            }
+            cell.line(content, (x, top + away), (x, top + away + MARK_LENGTH));
+            cell.line(
+                content,
+                (x, bottom - away),
+                (x, bottom - away - MARK_LENGTH),
+            );
+        }
+    }
+    for row in 0..cell.rows {
+        let (_, y) = cell.trim_origin(0, row);
+        for y in [y, y + cell.page_height] {
+            cell.line(content, (left - away, y), (left - away - MARK_LENGTH, y));
+            cell.line(content, (right + away, y), (right + away + MARK_LENGTH, y));
+        }
+    }
+    content.stroke();
+}
+
+/// Marks above and below the grid where it folds at the spine.
+fn add_fold_marks(content: &mut Content, config: &BookletConfig, cell: &GridCell, spine: usize) {
+    let away = cell.bleed + MARK_OFFSET;
+    let x = cell.column_edge(spine);
+    // short dashes, so they can't be taken for crop marks
+    let dash = MARK_LENGTH / 5.0;
+    for step in [0.0, 2.0, 4.0] {
+        let from = away + dash * step;
+        cell.line(
+            content,
+            (x, cell.top() + from),
+            (x, cell.top() + from + dash),
+        );
+        cell.line(
+            content,
+            (x, cell.bottom() - from),
+            (x, cell.bottom() - from - dash),
+        );
+    }
+    content.stroke();
+}
+
+/// Crosshair targets in the middle of each side of the sheet's edge band, which
+/// line up through the paper when the front and back are in register.
+fn add_registration_marks(content: &mut Content, config: &BookletConfig) {
+    let (width, height) = (*config.sheet_width, *config.sheet_height);
+    let band = config.inset() / 2.0;
+    let arm = MARK_LENGTH / 3.0;
+    let box_half = arm / 2.0;
+    for (x, y) in [
+        (width / 2.0, height - band),
+        (width / 2.0, band),
+        (band, height / 2.0),
+        (width - band, height / 2.0),
+    ] {
+        content
+            .move_to(x - arm, y)
+            .line_to(x + arm, y)
+            .move_to(x, y - arm)
+            .line_to(x, y + arm)
+            .move_to(x - box_half, y - box_half)
+            .line_to(x + box_half, y - box_half)
+            .line_to(x + box_half, y + box_half)
+            .line_to(x - box_half, y + box_half)
+            .line_to(x - box_half, y - box_half);
+    }
+    content.stroke();
+}
+
+/// Write the slug line under the first page of a sheet side, identifying it for
+/// whoever prints and binds it.
+pub fn add_slug(
+    page: &mut Page,
+    config: &BookletConfig,
+    columns: usize,
+    rows: usize,
+    spine: Option<usize>,
+    font_id: Id<Font>,
+    text: String,
+) {
+    let cell = GridCell::new(config, columns, rows, spine);
+    // clear of the crop mark at the page's corner, roughly centred in the band
+    let (x, bottom) = cell.sheet_corner();
+    let x = x + MARK_OFFSET * 2.0;
+    let y = bottom / 2.0 - SLUG_SIZE / 3.0;
+    page.add_span(SpanLayout {
+        text,
+        font: SpanFont {
+            id: font_id,
+            size: Pt(SLUG_SIZE),
+        },
+        colour: Colour::new_grey(0.0),
+        coords: (Pt(x), Pt(y)),
+    });
+}
+
+/// Draw the collation mark for a signature on the front of its sheet.
+///
+/// The mark straddles the spine fold at the head of the front's bottom row, and
+/// steps down the spine by its own height for each signature, starting again at
+/// the head when it runs out of room.
+pub fn add_collation_mark(page: &mut Page, config: &BookletConfig, fold: Fold, signature: usize) {
+    const MARK_WIDTH: f32 = 6.0;
+    const MARK_HEIGHT: f32 = 18.0;
+
+    let (columns, rows) = fold.grid();
+    let spine = spine_column(fold);
+    let cell = GridCell::new(config, columns, rows, Some(spine));
+    let spine_x = cell.column_edge(spine);
+    let (_, foot_y) = cell.trim_origin(0, rows - 1);
+    let head_y = foot_y + cell.page_height;
+    let steps = ((cell.page_height / MARK_HEIGHT).floor() as usize).max(1);
+    let top = head_y - MARK_HEIGHT * (signature % steps) as f32;
+
+    let mut content = Content::new();
+    content
+        .set_stroke_gray(0.0)
+        .set_line_cap(LineCapStyle::ButtCap)
+        .set_line_width(MARK_WIDTH);
+    cell.line(&mut content, (spine_x, top), (spine_x, top - MARK_HEIGHT));
+    content.stroke();
+    page.add_content(content);
+}
+
+/// Where a logical page sits in one cell of a sheet's grid, in points.
+///
+/// Positions are in the grid's frame: the sheet as it is, or turned a quarter turn
+/// anticlockwise with its pages if they're stacked head to foot, so the columns always
+/// run across and the spine up the middle. [`GridCell::on_sheet`] turns them back.
+struct GridCell {
+    columns: usize,
+    rows: usize,
+    /// The column boundary the pages meet at, if the sheet is folded
+    spine: Option<usize>,
+    /// The frame is the sheet turned a quarter turn
+    turned: bool,
+    /// Width of the frame, which is the sheet's height if it's turned
+    frame_width: f32,
+    /// Width of the band around the sheet's edge, outside the grid
+    inset: f32,
+    width: f32,
+    height: f32,
+    scale: f32,
+    page_width: f32,
+    page_height: f32,
+    /// How far each page's content runs past its trim, as placed
+    bleed: f32,
+    x_offset: f32,
+    y_offset: f32,
+}
+
+impl GridCell {
+    fn new(config: &BookletConfig, columns: usize, rows: usize, spine: Option<usize>) -> Self {
+        let (mut sheet, mut page) = (
+            (*config.sheet_width, *config.sheet_height),
+            (*config.page_width, *config.page_height),
+        );
+        if config.turned {
+            sheet = (sheet.1, sheet.0);
+            page = (page.1, page.0);
+        }
+        let inset = config.inset();
+        let width = (sheet.0 - inset * 2.0) / columns as f32;
+        let height = (sheet.1 - inset * 2.0) / rows as f32;
+        // each cell holds a page with its bleed all round
+        let bleed = config.marks.bleed_in * 72.0;
+        let scale = (width / (page.0 + bleed * 2.0)).min(height / (page.1 + bleed * 2.0));
+        let page_width = page.0 * scale;
+        let page_height = page.1 * scale;
+        GridCell {
+            columns,
+            rows,
+            spine,
+            turned: config.turned,
+            frame_width: sheet.0,
+            inset,
+            width,
+            height,
+            scale,
+            page_width,
+            page_height,
+            bleed: bleed * scale,
+            x_offset: (width - page_width) / 2.0,
+            y_offset: (height - page_height) / 2.0,
+        }
+    }
+
+    /// Bottom left corner of the page in a cell, before any creep. Rows count down
+    /// from the top of the sheet.
+    ///
+    /// Pages either side of the spine are moved towards it by their bleed, as it's
+    /// folded rather than cut and needs none.
+    fn trim_origin(&self, column: usize, row: usize) -> (f32, f32) {
+        let to_spine = match self.spine {
+            Some(spine) if column + 1 == spine => self.bleed,
+            Some(spine) if column == spine => -self.bleed,
+            _ => 0.0,
+        };
+        (
+            self.inset + self.width * column as f32 + self.x_offset + to_spine,
+            self.inset + self.height * (self.rows - 1 - row) as f32 + self.y_offset,
+        )
+    }
+
+    /// The boundary between cells `boundary - 1` and `boundary`.
+    fn column_edge(&self, boundary: usize) -> f32 {
+        self.inset + self.width * boundary as f32
+    }
+
+    fn left(&self) -> f32 {
+        self.trim_origin(0, 0).0
+    }
+
+    fn right(&self) -> f32 {
+        self.trim_origin(self.columns - 1, 0).0 + self.page_width
+    }
+
+    fn bottom(&self) -> f32 {
+        self.trim_origin(0, self.rows - 1).1
+    }
+
+    fn top(&self) -> f32 {
+        self.trim_origin(0, 0).1 + self.page_height
+    }
+
+    /// A point of the frame on the sheet. Turning the frame back puts its left
+    /// edge at the head of the sheet.
+    fn on_sheet(&self, (x, y): (f32, f32)) -> (f32, f32) {
+        if self.turned {
+            (y, self.frame_width - x)
+        } else {
+            (x, y)
+        }
+    }
+
+    /// Bottom left corner on the sheet of the page whose trim starts at `(x, y)` in
+    /// the frame, where it's placed upright.
+    fn page_corner(&self, x: f32, y: f32) -> (f32, f32) {
+        if self.turned {
+            self.on_sheet((x + self.page_width, y))
+        } else {
+            (x, y)
+        }
+    }
+
+    /// Bottom left corner of the grid on the sheet.
+    fn sheet_corner(&self) -> (f32, f32) {
+        // the frame's bottom right corner ends up bottom left once it's turned back
+        let x = if self.turned {
+            self.right()
+        } else {
+            self.left()
+        };
+        self.on_sheet((x, self.bottom()))
+    }
+
+    /// Draw a line between two points of the frame onto the sheet.
+    fn line(&self, content: &mut Content, from: (f32, f32), to: (f32, f32)) {
+        let (from, to) = (self.on_sheet(from), self.on_sheet(to));
+        content.move_to(from.0, from.1).line_to(to.0, to.1);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::sinks::pdf::booklet::sheet_layout;
+    use crate::sinks::pdf::config::{Orientation, PDF};
 
     #[test]
     fn test_signature_sheets_16_pages() {
@@ -227,6 +908,177 @@ mod tests {
         assert_eq!(sheets[8].front.right_page, Some(16));
     }
 
+    #[test]
+    fn test_spreads() {
+        let spreads = calculate_spreads(4);
+        assert_eq!(spreads.len(), 3);
+        assert_eq!(spreads[0].left_page, None);
+        assert_eq!(spreads[0].right_page, Some(0));
+        assert_eq!(spreads[1].left_page, Some(1));
+        assert_eq!(spreads[1].right_page, Some(2));
+        // the last verso has no recto to face
+        assert_eq!(spreads[2].left_page, Some(3));
+        assert_eq!(spreads[2].right_page, None);
+
+        assert!(calculate_spreads(0).is_empty());
+        assert_eq!(calculate_spreads(3).len(), 2);
+    }
+
+    #[test]
+    fn test_creep() {
+        // a 16-page signature is 4 sheets; 1, 2, 15 and 16 are on the outermost
+        for (page, depth) in [(0, 0), (1, 0), (2, 1), (7, 3), (8, 3), (13, 1), (15, 0)] {
+            assert_eq!(nesting_depth(page, 16), depth, "page {}", page + 1);
+        }
+        // the second signature starts again from the outside
+        assert_eq!(nesting_depth(16, 16), 0);
+        assert_eq!(nesting_depth(23, 16), 3);
+
+        assert_eq!(creep(8, 16, Pt(0.25)).0, 0.75);
+        assert_eq!(creep(8, 16, Pt(0.0)).0, 0.0);
+    }
+
+    #[test]
+    fn test_spine_column() {
+        assert_eq!(spine_column(Fold::Folio), 1);
+        assert_eq!(spine_column(Fold::Quarto), 1);
+        // an octavo's spine is between pages 16 and 1, three quarters across
+        assert_eq!(spine_column(Fold::Octavo), 3);
+
+        // and mirrored on the back, between pages 2 and 15
+        let sheets = calculate_perfect_binding(16, Fold::Octavo);
+        assert_eq!(sheets[0].front.spine, Some(3));
+        assert_eq!(sheets[0].back.spine, Some(1));
+        assert_eq!(sheets[0].back.slots[4].page, Some(1));
+        assert_eq!(sheets[0].back.slots[5].page, Some(14));
+
+        assert!(calculate_cut_and_stack(16, Fold::Octavo)[0]
+            .front
+            .spine
+            .is_none());
+    }
+
+    #[test]
+    fn test_print_order() {
+        use Side::{Back, Front};
+        assert_eq!(
+            print_order(2, false),
+            vec![(0, Front), (0, Back), (1, Front), (1, Back)]
+        );
+        assert_eq!(
+            print_order(3, true),
+            vec![
+                (0, Front),
+                (1, Front),
+                (2, Front),
+                (2, Back),
+                (1, Back),
+                (0, Back)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rotated_side() {
+        let sheets = calculate_perfect_binding(8, Fold::Quarto);
+        let back = sheets[0].back.rotated();
+        // upright 2, 7 become an upside-down top row 7, 2, and upside-down 3, 6 an
+        // upright bottom row 6, 3
+        assert_eq!(pages(&back), vec![Some(6), Some(1), Some(5), Some(2)]);
+        assert!(back.slots[0].inverted);
+        assert!(!back.slots[3].inverted);
+        assert_eq!(back.spine, Some(1));
+
+        let octavo = calculate_perfect_binding(16, Fold::Octavo);
+        assert_eq!(octavo[0].front.rotated().spine, Some(1));
+        assert_eq!(
+            octavo[0].front.rotated().rotated().slots,
+            octavo[0].front.slots
+        );
+    }
+
+    fn pages(side: &GridSide) -> Vec<Option<usize>> {
+        side.slots.iter().map(|slot| slot.page).collect()
+    }
+
+    #[test]
+    fn test_perfect_binding_quarto() {
+        // 10 pages = 2 sheets of 8, the second mostly blank
+        let sheets = calculate_perfect_binding(10, Fold::Quarto);
+        assert_eq!(sheets.len(), 2);
+
+        // front: top row 5, 4 (upside down), bottom row 8, 1 (1-indexed)
+        assert_eq!(
+            pages(&sheets[0].front),
+            vec![Some(4), Some(3), Some(7), Some(0)]
+        );
+        assert!(sheets[0].front.slots[0].inverted);
+        assert!(!sheets[0].front.slots[3].inverted);
+        assert_eq!(
+            pages(&sheets[0].back),
+            vec![Some(2), Some(5), Some(1), Some(6)]
+        );
+
+        // second signature starts at page 9 (1-indexed)
+        assert_eq!(pages(&sheets[1].front), vec![None, None, None, Some(8)]);
+        assert_eq!(pages(&sheets[1].back), vec![None, None, Some(9), None]);
+    }
+
+    #[test]
+    fn test_folded_leaves_back_each_other() {
+        for fold in Fold::all() {
+            let per_sheet = fold.pages_per_sheet();
+            let sheets = calculate_perfect_binding(per_sheet * 2, *fold);
+            let mut seen = Vec::new();
+            for sheet in sheets.iter() {
+                let (columns, rows) = (sheet.front.columns, sheet.front.rows);
+                assert_eq!(columns * rows * 2, per_sheet);
+                for slot in 0..columns * rows {
+                    let (column, row) = (slot % columns, slot / columns);
+                    let front = sheet.front.slots[slot];
+                    let back = sheet.back.slots[row * columns + columns - 1 - column];
+                    // every leaf has a recto on one side and its verso on the other
+                    let (recto, verso) = match (front.page, back.page) {
+                        (Some(f), Some(b)) if f % 2 == 0 => (f, b),
+                        (Some(f), Some(b)) => (b, f),
+                        _ => panic!("unexpected blank"),
+                    };
+                    assert_eq!(verso, recto + 1, "{fold}");
+                    assert_eq!(front.inverted, back.inverted);
+                    // the top row of a folded sheet is upside down
+                    assert_eq!(front.inverted, rows > 1 && row == 0);
+                    seen.extend([recto, verso]);
+                }
+            }
+            seen.sort();
+            assert_eq!(seen, (0..per_sheet * 2).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn test_cut_and_stack() {
+        // 8 pages = 4 leaves on 2 sheets; the left pile is leaves 1-2, the right 3-4
+        let sheets = calculate_cut_and_stack(8, Fold::Folio);
+        assert_eq!(sheets.len(), 2);
+        assert_eq!(pages(&sheets[0].front), vec![Some(0), Some(4)]);
+        assert_eq!(pages(&sheets[0].back), vec![Some(5), Some(1)]);
+        assert_eq!(pages(&sheets[1].front), vec![Some(2), Some(6)]);
+        assert_eq!(pages(&sheets[1].back), vec![Some(7), Some(3)]);
+
+        // 4-up: 7 pages = 4 leaves on 1 sheet, the last verso blank
+        let sheets = calculate_cut_and_stack(7, Fold::Quarto);
+        assert_eq!(sheets.len(), 1);
+        assert_eq!(
+            pages(&sheets[0].front),
+            vec![Some(0), Some(2), Some(4), Some(6)]
+        );
+        assert_eq!(
+            pages(&sheets[0].back),
+            vec![Some(3), Some(1), None, Some(5)]
+        );
+        assert!(sheets[0].front.slots.iter().all(|slot| !slot.inverted));
+    }
+
     #[test]
     fn test_signature_sheets_4_pages() {
         let sheets = calculate_signature_sheets(4);
@@ -239,4 +1091,64 @@ mod tests {
         assert_eq!(sheets[0].back.left_page, Some(1));
         assert_eq!(sheets[0].back.right_page, Some(2));
     }
+
+    #[test]
+    fn test_bleed_fits_in_the_cells() {
+        // half-letter pages on a letter sheet, with an eighth of an inch of bleed
+        let config = BookletConfig {
+            sheet_width: Pt(792.0),
+            sheet_height: Pt(612.0),
+            page_width: Pt(396.0),
+            page_height: Pt(612.0),
+            marks: PrinterMarksConfig {
+                crop: true,
+                bleed_in: 0.125,
+                ..PrinterMarksConfig::default()
+            },
+            turned: false,
+        };
+        let cell = GridCell::new(&config, 2, 1, Some(1));
+        assert!((cell.bleed - 9.0 * cell.scale).abs() < 0.001);
+
+        // the bleed and the marks outside it stay on the sheet
+        let reach = cell.bleed + MARK_OFFSET + MARK_LENGTH;
+        assert!(cell.left() - reach >= -0.001);
+        assert!(cell.right() + reach <= 792.001);
+        assert!(cell.bottom() - reach >= -0.001);
+        assert!(cell.top() + reach <= 612.001);
+
+        // the pages sit as close to the spine as they would without a bleed
+        let spine = cell.column_edge(1);
+        let (left_page, _) = cell.trim_origin(0, 0);
+        let (right_page, _) = cell.trim_origin(1, 0);
+        let gap = cell.x_offset - cell.bleed;
+        assert!((spine - (left_page + cell.page_width) - gap).abs() < 0.001);
+        assert!((right_page - spine - gap).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_landscape_pages_stack_head_to_foot() {
+        // landscape half-letter pages on letter paper
+        let mut pdf = PDF::default();
+        pdf.page.orientation = Orientation::Landscape;
+        let config = sheet_layout(&pdf, 11.0, 8.5);
+        assert!(config.turned);
+        assert_eq!(
+            (config.sheet_width.0, config.sheet_height.0),
+            (612.0, 792.0)
+        );
+
+        let cell = GridCell::new(&config, 2, 1, Some(1));
+        assert_eq!(cell.scale, 1.0);
+        // the first column goes at the head of the upright sheet, the second below
+        let (x, y) = cell.trim_origin(0, 0);
+        assert_eq!(cell.page_corner(x, y), (0.0, 396.0));
+        let (x, y) = cell.trim_origin(1, 0);
+        assert_eq!(cell.page_corner(x, y), (0.0, 0.0));
+
+        // portrait pages still go side by side on the sheet turned on its side
+        let config = sheet_layout(&PDF::default(), 8.5, 11.0);
+        assert!(!config.turned);
+        assert_eq!(GridCell::new(&config, 2, 1, Some(1)).scale, 1.0);
+    }
 }
@@ -3,6 +3,12 @@
 //! This module converts a `Source` into one or two PDFs:
 //! - A digital PDF optimised for on-screen reading with clickable links and bookmarks
 //! - An optional print-ready booklet PDF with saddle-stitch imposition
+//! - An optional 2-up reading copy with the book's spreads on landscape sheets
+//! - An optional wraparound cover for a print-on-demand service, its spine as thick
+//!   as the book
+//!
+//! A book too thick to bind can also be split into volumes, each written as a PDF
+//! of its own with its own booklet and cover.
 //!
 //! The rendering process creates a title page, syntax-highlighted source files,
 //! embedded images, commit history, and a table of contents. Headers and footers
@@ -10,15 +16,19 @@
 
 mod booklet;
 mod config;
+mod cover;
 mod fonts;
 mod imposition;
 mod rendering;
+mod two_up;
+mod volumes;
 
 pub use config::{
     default_colophon_template, default_title_page_template, AppendixSectionNumbering,
-    BinaryHexConfig, BookletConfig, ColophonConfig, FontSizesConfig, FooterConfig, HeaderConfig,
-    InlineTagsConfig, MarginsConfig, MetadataConfig, NumberingConfig, PageConfig, PageSize,
-    Position, RulePosition, SyntaxTheme, TagsAppendixConfig, TitlePageConfig,
+    BinaryHexConfig, Binding, BookletConfig, CodeConfig, ColophonConfig, DuplexFlip, Fold,
+    FontSizesConfig, FooterConfig, HeaderConfig, InlineTagsConfig, ManualDuplex,
+    ManualDuplexConfig, MarginsConfig, MetadataConfig, NumberingConfig, Orientation, PageConfig,
+    PageSize, Position, RulePosition, SyntaxTheme, TagsAppendixConfig, TitlePageConfig,
     TitlePageImagePosition, PDF,
 };
 pub use fonts::LoadedFonts;
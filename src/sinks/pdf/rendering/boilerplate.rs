@@ -0,0 +1,56 @@
+//! The "Standard file header" frontmatter page.
+//!
+//! With `[pdf.boilerplate]` enabled, the header shared by many source files is
+//! printed once here, at the end of the frontmatter, and each listing shows a
+//! one-line marker in its place. See [`crate::boilerplate`].
+
+use crate::boilerplate::{StandardHeader, TITLE};
+use crate::markdown::parse;
+use crate::sinks::pdf::config::{PageLayout, PDF};
+use crate::sinks::pdf::fonts::FontIds;
+use crate::sinks::pdf::rendering::markdown::Typesetter;
+use crate::sinks::pdf::rendering::source_file::RenderResult;
+use crate::sinks::pdf::rendering::ImagePathMap;
+use anyhow::Result;
+use pdf_gen::*;
+use std::path::Path;
+use syntect::highlighting::Theme;
+use syntect::parsing::SyntaxSet;
+
+/// Render the standard header page, starting on a fresh page.
+///
+/// Returns the first page index and number of pages rendered, like
+/// [`super::source_file::render`].
+pub fn render(
+    config: &PDF,
+    page_layout: &PageLayout,
+    doc: &mut Document,
+    font_ids: &FontIds,
+    header: &StandardHeader,
+    ss: &SyntaxSet,
+    theme: &Theme,
+    image_paths: &mut ImagePathMap,
+) -> Result<RenderResult> {
+    let mut typesetter = Typesetter::new(
+        config,
+        page_layout,
+        doc,
+        font_ids,
+        ss,
+        theme,
+        Path::new(""),
+        image_paths,
+    );
+    let introduction = format!(
+        "# {}\n\n{} source files open with the comment below. It is printed once here; \
+         in each listing it is replaced by a one-line marker, and the lines that follow \
+         keep their original numbers.\n",
+        TITLE, header.file_count
+    );
+    typesetter.blocks(&parse(&introduction), Pt(0.0))?;
+    let gap =
+        typesetter.flow.doc.fonts[font_ids.regular].line_height(Pt(page_layout.fonts.body_pt));
+    typesetter.flow.gap(gap * 0.5);
+    typesetter.preformatted(&header.text, colours::BLACK);
+    Ok(typesetter.flow.finish())
+}
@@ -52,7 +52,7 @@ pub fn compute_stats(source: &Source, commits: &[Commit]) -> ColophonStats {
     let mut stats = ColophonStats::default();
     let mut lang_map: HashMap<String, (usize, usize)> = HashMap::new();
 
-    for file in &source.source_files {
+    for file in source.source_files.iter().map(|f| &f.path) {
         let full_path = source.repository.join(file);
 
         // get file extension
@@ -4,11 +4,11 @@
 //! Commits are rendered in the order provided (typically newest first).
 //! Optionally displays tag badges inline with commits.
 
-use crate::sinks::pdf::config::PDF;
+use crate::sinks::pdf::config::PageLayout;
 use crate::sinks::pdf::fonts::FontIds;
+use crate::sinks::pdf::rendering::flow::{column_boxes, draw_column_rules};
 use crate::source::Commit;
 use anyhow::Result;
-use pdf_gen::layout::Margins;
 use pdf_gen::*;
 use std::collections::HashMap;
 
@@ -27,14 +27,14 @@ pub struct CommitRenderResult {
 ///
 /// Returns render result with first page index and blank page info.
 pub fn render(
-    config: &PDF,
+    page_layout: &PageLayout,
     doc: &mut Document,
     font_ids: &FontIds,
     commits: Vec<Commit>,
     tags_by_commit: Option<&HashMap<String, Vec<String>>>,
 ) -> Result<CommitRenderResult> {
-    let small_size = Pt(config.fonts.small_pt);
-    let subheading_size = Pt(config.fonts.subheading_pt);
+    let small_size = Pt(page_layout.fonts.small_pt);
+    let subheading_size = Pt(page_layout.fonts.subheading_pt);
 
     // convert the commits to a series of text spans
     let mut text: Vec<(String, Colour, SpanFont)> = Vec::with_capacity(commits.len() * 6 + 1);
@@ -42,7 +42,7 @@ pub fn render(
     // section title
     let heading_font = SpanFont {
         id: font_ids.bold,
-        size: Pt(config.fonts.heading_pt),
+        size: Pt(page_layout.fonts.heading_pt),
     };
     text.push((
         format!("Commit History ({} commits)\n\n", commits.len()),
@@ -121,22 +121,13 @@ pub fn render(
     let mut blank_inserted = false;
 
     while !text.is_empty() {
-        let margins = Margins::trbl(
-            In(0.25).into(),
-            In(0.25).into(),
-            In(0.5).into(),
-            In(0.25).into(),
-        )
-        .with_gutter(In(0.25).into(), doc.page_order.len().saturating_sub(1));
-        let page_size = config.page_size();
-
         // insert a blank page so we open to the correct side (recto)
         if first_page.is_none() && doc.page_order.len() % 2 == 1 {
-            doc.add_page(Page::new(page_size, Some(margins.clone())));
+            doc.add_page(page_layout.new_page(doc.page_order.len()));
             blank_inserted = true;
         }
 
-        let mut page = Page::new(page_size, Some(margins));
+        let mut page = page_layout.new_page(doc.page_order.len());
         let start = layout::baseline_start(&page, &doc.fonts[font_ids.bold], span_font_bold.size);
         let start = (
             start.0,
@@ -145,21 +136,32 @@ pub fn render(
                     - doc.fonts[font_ids.regular].descent(subheading_size))
                 - In(0.125).into(),
         );
-        let bbox = page.content_box;
-
-        // don't start a page with empty lines
-        while let Some(span) = text.first() {
-            if span.0 == "\n" {
-                text.remove(0);
-            } else {
+        let boxes = column_boxes(page.content_box, &page_layout.columns);
+
+        let mut used = 0;
+        for bbox in &boxes {
+            // don't start a column with empty lines
+            while let Some(span) = text.first() {
+                if span.0 == "\n" {
+                    text.remove(0);
+                } else {
+                    break;
+                }
+            }
+            if text.is_empty() {
                 break;
             }
+
+            let start = (bbox.x1, start.1);
+            layout::layout_text_naive(doc, &mut page, start, &mut text, wrap_width, *bbox);
+            used += 1;
         }
-        if text.is_empty() {
+        if used == 0 {
             break;
         }
 
-        layout::layout_text_naive(doc, &mut page, start, &mut text, wrap_width, bbox);
+        let top = start.1 + doc.fonts[font_ids.bold].ascent(span_font_bold.size);
+        draw_column_rules(&mut page, &boxes, used, top, &page_layout.columns);
         let page_id = doc.add_page(page);
         if first_page.is_none() {
             first_page = Some(doc.index_of_page(page_id).expect("page was just added"));
@@ -0,0 +1,227 @@
+//! Go-to-definition links in source listings.
+//!
+//! With `[pdf.cross_references]` enabled, identifiers in the listing that name a
+//! symbol defined elsewhere in the book become internal links to the page of the
+//! definition, the same kind of link the table of contents uses. Optionally a small
+//! superscript page reference (`→p.43`) is printed after the identifier for readers
+//! of the printed book.
+//!
+//! This takes two passes. Before any file is rendered, [`CrossReferences::build`]
+//! reads every source file's definitions into a table, so that references to
+//! symbols in files later in the book are recognised. While rendering, listings
+//! find their references in the same pass over the file as their highlighting (see
+//! [`crate::syntax_analysis::SymbolScanner`]), report where each landed with
+//! [`CrossReferences::add_links`], and report which page each line ended up on.
+//! Only once everything has been laid out (and the table of contents inserted) are
+//! the definitions' pages known, so [`CrossReferences::resolve`] adds the link
+//! annotations and page references to the pages at the very end.
+//!
+//! A name defined more than once links to the definition in the same file if there
+//! is one; otherwise names defined in several files are left unlinked rather than
+//! guessing.
+
+use crate::sinks::pdf::config::{PageLayout, PDF};
+use crate::sinks::pdf::fonts::FontIds;
+use crate::sinks::pdf::rendering::header_footer::{page_label, page_reference, PageMetadata};
+use crate::sinks::pdf::rendering::listing::PlacedMark;
+use crate::syntax_analysis::{file_definitions, Reference};
+use anyhow::{Context, Result};
+use pdf_gen::*;
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use syntect::parsing::SyntaxSet;
+
+/// A reference to a definition found in a line of a listing.
+pub struct LineReference {
+    /// Byte range of the identifier within the line
+    pub range: Range<usize>,
+    /// The definition it refers to
+    pub target: usize,
+}
+
+/// A placed reference waiting for its definition's page to be known.
+struct PendingLink {
+    page: usize,
+    rect: Rect,
+    baseline: Pt,
+    target: usize,
+}
+
+/// Definitions across the book and the references to them found while rendering.
+pub struct CrossReferences {
+    page_refs: bool,
+    /// Definition sites as (file, zero-based line)
+    sites: Vec<(PathBuf, usize)>,
+    /// Definition sites by symbol name
+    by_name: HashMap<String, Vec<usize>>,
+    /// Page index of each line of each rendered file, if it was shown
+    line_pages: HashMap<PathBuf, Vec<Option<usize>>>,
+    links: Vec<PendingLink>,
+}
+
+impl CrossReferences {
+    /// Build the definition table from every source file in the book.
+    pub fn build(config: &PDF, files: &[PathBuf], ss: &SyntaxSet) -> Result<Self> {
+        let mut sites = Vec::new();
+        let mut by_name: HashMap<String, Vec<usize>> = HashMap::new();
+        for file in files {
+            if crate::notebook::is_notebook(file) {
+                continue;
+            }
+            let definitions = file_definitions(file, ss)
+                .with_context(|| format!("Failed to find definitions in {}", file.display()))?;
+            for definition in definitions {
+                by_name
+                    .entry(definition.name)
+                    .or_default()
+                    .push(sites.len());
+                sites.push((file.clone(), definition.line));
+            }
+        }
+
+        Ok(Self {
+            page_refs: config.cross_references.page_refs,
+            sites,
+            by_name,
+            line_pages: HashMap::new(),
+            links: Vec::new(),
+        })
+    }
+
+    /// Pick the definition a name in `path` refers to, if it's unambiguous.
+    fn target(&self, name: &str, path: &Path) -> Option<usize> {
+        let candidates = self.by_name.get(name)?;
+        let local: Vec<usize> = candidates
+            .iter()
+            .copied()
+            .filter(|&site| self.sites[site].0 == path)
+            .collect();
+        match (local.first(), candidates.len()) {
+            (Some(&site), _) => Some(site),
+            (None, 1) => Some(candidates[0]),
+            _ => None,
+        }
+    }
+
+    /// Whether `name` is defined somewhere in the book, and so worth looking for.
+    pub fn defines(&self, name: &str) -> bool {
+        self.by_name.contains_key(name)
+    }
+
+    /// Group the references found in a file by line, keeping those that link to a
+    /// definition.
+    ///
+    /// They must have been found in exactly what the listing lays out, since they
+    /// are located by byte offset within each line.
+    pub fn line_references(&self, path: &Path, found: Vec<Reference>) -> Vec<Vec<LineReference>> {
+        let mut lines: Vec<Vec<LineReference>> = Vec::new();
+        for reference in found {
+            let Some(target) = self.target(&reference.name, path) else {
+                continue;
+            };
+            // a use of a symbol on its own definition line is part of the definition
+            let (file, line) = &self.sites[target];
+            if file == path && *line == reference.line {
+                continue;
+            }
+            if lines.len() <= reference.line {
+                lines.resize_with(reference.line + 1, Vec::new);
+            }
+            lines[reference.line].push(LineReference {
+                range: reference.range,
+                target,
+            });
+        }
+        lines
+    }
+
+    /// Record where a line's references were placed by the listing.
+    pub fn add_links(&mut self, references: &[LineReference], placed: Vec<PlacedMark>) {
+        for mark in placed {
+            self.links.push(PendingLink {
+                page: mark.page,
+                rect: mark.rect,
+                baseline: mark.baseline,
+                target: references[mark.mark].target,
+            });
+        }
+    }
+
+    /// Record the page each line of a rendered file landed on.
+    pub fn record_pages(&mut self, path: &Path, line_pages: &[Option<usize>]) {
+        self.line_pages
+            .insert(path.to_path_buf(), line_pages.to_vec());
+    }
+
+    /// Add the link annotations (and page references, if enabled) to the pages.
+    ///
+    /// Page indices recorded during rendering are shifted by `link_offset` for the
+    /// pages inserted ahead of the content since (the table of contents), and
+    /// `content_offset` is the page index matching the first entry of
+    /// `page_metadata`.
+    pub fn resolve(
+        self,
+        config: &PDF,
+        page_layout: &PageLayout,
+        doc: &mut Document,
+        font_ids: &FontIds,
+        content_offset: usize,
+        page_metadata: &[PageMetadata],
+        link_offset: usize,
+    ) {
+        let ref_font = SpanFont {
+            id: font_ids.regular,
+            size: Pt(page_layout.fonts.small_pt * 0.75),
+        };
+        let ref_colour = Colour::new_grey(0.5);
+        let raise = doc.fonts[font_ids.regular].ascent(Pt(page_layout.fonts.body_pt)) * 0.6;
+
+        // only the first reference to a definition on each page gets a page number
+        let mut referenced: HashSet<(usize, usize)> = HashSet::new();
+        for link in self.links {
+            let (file, line) = &self.sites[link.target];
+            let Some(&Some(target_page)) = self.line_pages.get(file).and_then(|p| p.get(*line))
+            else {
+                continue;
+            };
+            let page_id = doc.page_order[link.page + link_offset];
+
+            let mut rect = link.rect;
+            if self.page_refs
+                && target_page != link.page
+                && referenced.insert((link.page, link.target))
+            {
+                if let Some(metadata) =
+                    page_metadata.get(target_page.saturating_sub(content_offset))
+                {
+                    // pages in another volume are cited with it if numbered per volume
+                    let here = page_metadata.get(link.page.saturating_sub(content_offset));
+                    let text = if here.map(|here| here.volume) == Some(metadata.volume) {
+                        format!("→p.{}", page_label(config, metadata))
+                    } else {
+                        format!("→{}", page_reference(config, metadata))
+                    };
+                    let width =
+                        layout::width_of_text(&text, &doc.fonts[ref_font.id], ref_font.size);
+                    let x = rect.x2;
+                    rect.x2 = rect.x2 + width;
+                    doc.pages
+                        .get_mut(page_id)
+                        .expect("page exists")
+                        .add_span(SpanLayout {
+                            text,
+                            font: ref_font,
+                            colour: ref_colour,
+                            coords: (x, link.baseline + raise),
+                        });
+                }
+            }
+
+            doc.pages
+                .get_mut(page_id)
+                .expect("page exists")
+                .add_intradocument_link_by_index(rect, target_page + link_offset);
+        }
+    }
+}
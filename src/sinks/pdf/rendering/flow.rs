@@ -0,0 +1,480 @@
+//! Line-by-line page flow for block-structured content.
+//!
+//! Commits and tags are handed to `layout::layout_text_naive` as one flat list of
+//! spans, which is fine when everything is the same kind of text. Code listings and
+//! typeset content (Markdown headings, indented lists, rules, tables and inline
+//! images) need to place each line themselves, so they go through a [`Flow`] instead.
+//! The flow tracks a vertical cursor on the page being filled and starts a new page
+//! whenever the next line or image would cross the bottom margin.
+//!
+//! A section configured with `[pdf.layout.<section>.columns]` flows into columns:
+//! the flow fills each column of a page top to bottom before moving to the next,
+//! and [`Flow::content_box`] is the current column's, so everything measured
+//! against it narrows to the column. Commits and tags split each page into the
+//! same [`column_boxes`] themselves.
+//!
+//! Files placed `Continuous` follow each other down the page: a file's flow leaves
+//! its last page open in a [`Continuation`] and the next file's flow carries on
+//! from it, under a rule and the file's name. The page is accounted to the file
+//! at its top, so its running header names that file, while the next file reports
+//! it as its first page for the table of contents and bookmarks.
+//!
+//! Pages come from [`PageLayout::new_page`] like every other laid-out page, and leave the
+//! same header clearance as source file pages, so flowed content sits consistently
+//! with the rest of the book when headers are applied.
+
+use crate::sinks::pdf::config::{ColumnsConfig, PageLayout};
+use crate::sinks::pdf::fonts::FontIds;
+use crate::sinks::pdf::rendering::source_file::RenderResult;
+use crate::sinks::pdf::rendering::ImagePathMap;
+use anyhow::{Context, Result};
+use pdf_gen::pdf_writer_crate::types::LineCapStyle;
+use pdf_gen::pdf_writer_crate::Content;
+use pdf_gen::*;
+use std::path::Path;
+
+/// A run of text in a single font and colour, matching the span tuples used elsewhere.
+pub type Span = (String, Colour, SpanFont);
+
+/// Splits a page's content box into the configured columns, left to right.
+pub fn column_boxes(content_box: Rect, columns: &ColumnsConfig) -> Vec<Rect> {
+    let count = columns.count.max(1);
+    let gap: Pt = In(columns.gap_in).into();
+    let width = (content_box.x2 - content_box.x1 - gap * (count - 1) as f32) / count as f32;
+    (0..count)
+        .map(|i| {
+            let x1 = content_box.x1 + (width + gap) * i as f32;
+            Rect {
+                x1,
+                x2: x1 + width,
+                ..content_box
+            }
+        })
+        .collect()
+}
+
+/// Draw a rule down the middle of the gap after each of the first `used` columns
+/// but the last, from `top` to the bottom of the columns. Does nothing unless the
+/// columns are configured with rules.
+pub fn draw_column_rules(
+    page: &mut Page,
+    boxes: &[Rect],
+    used: usize,
+    top: Pt,
+    columns: &ColumnsConfig,
+) {
+    if !columns.rule {
+        return;
+    }
+    for pair in boxes[..used.min(boxes.len())].windows(2) {
+        let x = (pair[0].x2 + pair[1].x1) / 2.0;
+        let mut content = Content::new();
+        content
+            .set_stroke_gray(0.75)
+            .set_line_cap(LineCapStyle::ButtCap)
+            .set_line_width(0.5)
+            .move_to(*x, *top)
+            .line_to(*x, *pair[0].y1)
+            .stroke();
+        page.add_content(content);
+    }
+}
+
+/// Draw a straight line between two points on `page`.
+pub fn draw_line(page: &mut Page, from: (Pt, Pt), to: (Pt, Pt), thickness: Pt, grey: f32) {
+    let mut content = Content::new();
+    content
+        .set_stroke_gray(grey)
+        .set_line_cap(LineCapStyle::ButtCap)
+        .set_line_width(*thickness)
+        .move_to(*from.0, *from.1)
+        .line_to(*to.0, *to.1)
+        .stroke();
+    page.add_content(content);
+}
+
+/// A partly filled page left by one file in continuous flow for the next.
+struct OpenPage {
+    page: Page,
+    y: Pt,
+    column: usize,
+    /// Top of the columns, for their rules
+    column_top: Pt,
+}
+
+/// The page the last file of a section in continuous flow ended on, if any.
+#[derive(Default)]
+pub struct Continuation {
+    open: Option<OpenPage>,
+}
+
+impl Continuation {
+    /// Add the open page to the document, so the next page starts afresh.
+    pub fn close(&mut self, doc: &mut Document, columns: &ColumnsConfig) {
+        if let Some(mut open) = self.open.take() {
+            let boxes = column_boxes(open.page.content_box, columns);
+            draw_column_rules(
+                &mut open.page,
+                &boxes,
+                open.column + 1,
+                open.column_top,
+                columns,
+            );
+            doc.add_page(open.page);
+        }
+    }
+}
+
+/// Lays out lines, rules and images onto pages, breaking pages as needed.
+pub struct Flow<'a> {
+    page_layout: &'a PageLayout,
+    pub doc: &'a mut Document,
+    page: Option<Page>,
+    /// Baseline cursor: the top of the next line to be placed
+    y: Pt,
+    /// Whether anything has been placed on the current page
+    page_has_content: bool,
+    /// The column being filled, counting from the left
+    column: usize,
+    /// Whether anything has been placed in the current column
+    column_has_content: bool,
+    /// Space reserved at the top of each page for the running header
+    header_clearance: Pt,
+    first_page: Option<usize>,
+    page_count: usize,
+    /// Whether the current page was begun by the file before, which accounts for it
+    carried: bool,
+    /// Whether the first content landed on a carried page
+    continued: bool,
+    /// Where to leave the last page open, for files in continuous flow
+    continuation: Option<&'a mut Continuation>,
+}
+
+impl<'a> Flow<'a> {
+    pub fn new(page_layout: &'a PageLayout, doc: &'a mut Document, font_ids: &FontIds) -> Self {
+        let text_size = Pt(page_layout.fonts.body_pt);
+        let subheading_size = Pt(page_layout.fonts.subheading_pt);
+        let header_clearance = doc.fonts[font_ids.regular].ascent(text_size)
+            - doc.fonts[font_ids.regular].descent(subheading_size)
+            + In(0.125).into();
+
+        Self {
+            page_layout,
+            doc,
+            page: None,
+            y: Pt(0.0),
+            page_has_content: false,
+            column: 0,
+            column_has_content: false,
+            header_clearance,
+            first_page: None,
+            page_count: 0,
+            carried: false,
+            continued: false,
+            continuation: None,
+        }
+    }
+
+    /// Start a file placed `Continuous`, carrying on from the page the file before
+    /// left open, if any, under a rule and the file's name. The last page is left
+    /// open in `continuation` in turn when the flow finishes.
+    pub fn continue_file(
+        &mut self,
+        continuation: &'a mut Continuation,
+        font_ids: &FontIds,
+        path: &Path,
+    ) {
+        let size = Pt(self.page_layout.fonts.subheading_pt);
+        let font = &self.doc.fonts[font_ids.bold];
+        let (line_height, ascent) = (font.line_height(size), font.ascent(size));
+        let body_height =
+            self.doc.fonts[font_ids.regular].line_height(Pt(self.page_layout.fonts.body_pt));
+
+        if let Some(open) = continuation.open.take() {
+            self.page = Some(open.page);
+            self.y = open.y;
+            self.column = open.column;
+            self.page_has_content = true;
+            self.column_has_content = true;
+            self.carried = true;
+            // the name goes with at least the first couple of lines of the file
+            self.gap(body_height);
+            self.ensure_space(body_height + line_height + body_height * 2.0);
+            if !self.at_top() {
+                self.add_rule(Pt(0.0), Pt(0.5), 0.75);
+            }
+        }
+        let name = (
+            path.display().to_string(),
+            colours::BLACK,
+            SpanFont {
+                id: font_ids.bold,
+                size,
+            },
+        );
+        self.add_line(Pt(0.0), vec![name], line_height, ascent);
+        self.gap(body_height * 0.5);
+        self.continuation = Some(continuation);
+    }
+
+    /// Returns the page currently being filled, starting one if necessary.
+    pub fn page(&mut self) -> &mut Page {
+        if self.page.is_none() {
+            self.start_page();
+        }
+        self.page.as_mut().expect("page was just started")
+    }
+
+    /// Returns the content box of the column currently being filled.
+    pub fn content_box(&mut self) -> Rect {
+        let content_box = self.page().content_box;
+        column_boxes(content_box, &self.page_layout.columns)[self.column]
+    }
+
+    /// Width available for content across the current column.
+    pub fn measure(&mut self) -> Pt {
+        let bbox = self.content_box();
+        bbox.x2 - bbox.x1
+    }
+
+    /// Vertical space left in the current column.
+    pub fn remaining(&mut self) -> Pt {
+        let bbox = self.content_box();
+        self.y - bbox.y1
+    }
+
+    /// Index the page currently being filled will have in the document once it's added.
+    pub fn page_index(&self) -> usize {
+        self.doc.page_order.len()
+    }
+
+    /// Whether the cursor is at the top of a column with nothing placed yet.
+    pub fn at_top(&self) -> bool {
+        !self.column_has_content
+    }
+
+    fn column_top(&self, page: &Page) -> Pt {
+        page.content_box.y2 - self.header_clearance
+    }
+
+    fn start_page(&mut self) {
+        let page = self.page_layout.new_page(self.doc.page_order.len());
+        self.y = self.column_top(&page);
+        self.page = Some(page);
+        self.page_has_content = false;
+        self.column = 0;
+        self.column_has_content = false;
+        self.carried = false;
+    }
+
+    fn placed(&mut self) {
+        if self.first_page.is_none() {
+            self.first_page = Some(self.page_index());
+            self.continued = self.carried;
+        }
+        self.page_has_content = true;
+        self.column_has_content = true;
+    }
+
+    /// Move to the top of the next column, or of a fresh page after the last one.
+    pub fn break_column(&mut self) {
+        let top = self.page.as_ref().map(|page| self.column_top(page));
+        match top {
+            Some(top) if self.column + 1 < self.page_layout.columns.count => {
+                self.y = top;
+                self.column += 1;
+                self.column_has_content = false;
+            }
+            _ => self.break_page(),
+        }
+    }
+
+    /// Finish the current page (if it has content) and start a fresh one.
+    pub fn break_page(&mut self) {
+        self.flush_page();
+        self.start_page();
+    }
+
+    fn flush_page(&mut self) {
+        if let Some(mut page) = self.page.take() {
+            if !self.page_has_content {
+                return;
+            }
+            let boxes = column_boxes(page.content_box, &self.page_layout.columns);
+            let top = self.column_top(&page);
+            draw_column_rules(
+                &mut page,
+                &boxes,
+                self.column + 1,
+                top,
+                &self.page_layout.columns,
+            );
+            self.doc.add_page(page);
+            if !self.carried {
+                self.page_count += 1;
+            }
+        }
+    }
+
+    /// Make sure `height` fits below the cursor, moving to the next column or page if
+    /// it doesn't.
+    ///
+    /// Content taller than a whole column is allowed through at the top of one rather
+    /// than breaking forever.
+    pub fn ensure_space(&mut self, height: Pt) {
+        if self.page.is_none() {
+            self.start_page();
+        }
+        if self.remaining() < height && !self.at_top() {
+            self.break_column();
+        }
+    }
+
+    /// Add vertical space between blocks. Gaps are dropped at the top of a column.
+    pub fn gap(&mut self, height: Pt) {
+        if self.page.is_none() || self.at_top() {
+            return;
+        }
+        if self.remaining() < height {
+            self.break_column();
+        } else {
+            self.y -= height;
+        }
+    }
+
+    /// Place a line of spans one after another, starting `indent` from the left of the
+    /// column.
+    pub fn add_line(&mut self, indent: Pt, spans: Vec<Span>, line_height: Pt, ascent: Pt) {
+        let mut x = indent;
+        let mut placed = Vec::with_capacity(spans.len());
+        for span in spans {
+            let width = layout::width_of_text(&span.0, &self.doc.fonts[span.2.id], span.2.size);
+            placed.push((x, span));
+            x = x + width;
+        }
+        self.add_line_at(placed, line_height, ascent);
+    }
+
+    /// Place a line of spans at explicit offsets from the left of the column.
+    ///
+    /// Returns the baseline the spans were placed on.
+    pub fn add_line_at(&mut self, spans: Vec<(Pt, Span)>, line_height: Pt, ascent: Pt) -> Pt {
+        self.ensure_space(line_height);
+        let baseline = self.y - ascent;
+        let left = self.content_box().x1;
+        let page = self.page();
+        for (x, (text, colour, font)) in spans {
+            if text.is_empty() {
+                continue;
+            }
+            page.add_span(SpanLayout {
+                text,
+                font,
+                colour,
+                coords: (left + x, baseline),
+            });
+        }
+        self.y -= line_height;
+        self.placed();
+        baseline
+    }
+
+    /// Draw a horizontal rule across the measure at the cursor, starting at `indent`.
+    pub fn add_rule(&mut self, indent: Pt, thickness: Pt, grey: f32) {
+        let gap: Pt = Pt(thickness.0 * 4.0);
+        self.ensure_space(gap * 2.0);
+        let bbox = self.content_box();
+        let y = self.y - gap;
+        self.draw_line((bbox.x1 + indent, y), (bbox.x2, y), thickness, grey);
+        self.y -= gap * 2.0;
+        self.placed();
+    }
+
+    /// Draw a straight line between two points on the current page.
+    pub fn draw_line(&mut self, from: (Pt, Pt), to: (Pt, Pt), thickness: Pt, grey: f32) {
+        draw_line(self.page(), from, to, thickness, grey);
+    }
+
+    /// Current cursor position, in page coordinates.
+    pub fn cursor(&mut self) -> Pt {
+        if self.page.is_none() {
+            self.start_page();
+        }
+        self.y
+    }
+
+    /// Load an image from disk and place it on its own line, scaled to fit within
+    /// `max_width` and `max_height` while keeping its aspect ratio.
+    ///
+    /// The image path is recorded in `image_paths` so the booklet can reload it.
+    pub fn add_image(
+        &mut self,
+        path: &Path,
+        indent: Pt,
+        max_width: Pt,
+        max_height: Pt,
+        image_paths: &mut ImagePathMap,
+    ) -> Result<()> {
+        let image = Image::new_from_disk(path)
+            .with_context(|| format!("Failed to load image {}", path.display()))?;
+        let aspect_ratio = image.aspect_ratio();
+        let image_id = self.doc.add_image(image);
+        let image_index = image_id.index();
+        image_paths.insert(image_index, path.to_path_buf());
+
+        let mut width = max_width;
+        let mut height = width / aspect_ratio;
+        if height > max_height {
+            height = max_height;
+            width = height * aspect_ratio;
+        }
+
+        self.ensure_space(height);
+        let bbox = self.content_box();
+        let x = bbox.x1 + indent;
+        let y = self.y;
+        self.page().add_image(ImageLayout {
+            image_index,
+            position: Rect {
+                x1: x,
+                y1: y - height,
+                x2: x + width,
+                y2: y,
+            },
+        });
+        self.y -= height;
+        self.placed();
+        Ok(())
+    }
+
+    /// Add the final page and report where the flowed content landed.
+    ///
+    /// In continuous flow the last page is left open for the next file instead.
+    pub fn finish(mut self) -> RenderResult {
+        match (self.continuation.take(), self.page.take()) {
+            (Some(continuation), Some(page)) if self.page_has_content => {
+                if !self.carried {
+                    self.page_count += 1;
+                }
+                continuation.open = Some(OpenPage {
+                    column_top: self.column_top(&page),
+                    page,
+                    y: self.y,
+                    column: self.column,
+                });
+            }
+            (_, page) => {
+                self.page = page;
+                self.flush_page();
+            }
+        }
+        RenderResult {
+            first_page: self.first_page,
+            page_count: self.page_count,
+            continued: self.continued,
+            line_pages: Vec::new(),
+            page_lines: Vec::new(),
+            definitions: Vec::new(),
+        }
+    }
+}
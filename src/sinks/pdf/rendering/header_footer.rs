@@ -5,7 +5,9 @@
 //! - `{file}` - current file path
 //! - `{title}` - book title
 //! - `{n}` - page number (formatted per page_number_style)
-//! - `{total}` - total page count
+//! - `{total}` - total page count of the section (in the volume, if numbered per volume)
+//! - `{symbol}` - top-level item a source page is in, e.g. `fn main`
+//! - `{lines}` - range of source lines on the page, e.g. `120–168`
 //!
 //! Position can be Outer (alternating for binding), Centre, Inner, Left, or Right.
 //! Optional horizontal rules can be placed Above or Below the text.
@@ -16,6 +18,16 @@ use owned_ttf_parser::AsFaceRef;
 use pdf_gen::pdf_writer_crate::types::LineCapStyle;
 use pdf_gen::pdf_writer_crate::Content;
 use pdf_gen::*;
+use std::collections::HashMap;
+
+/// The part of a source listing a page shows.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PageLines {
+    /// First and last line numbers (1-based) on the page
+    pub lines: Option<(usize, usize)>,
+    /// Top-level item the page is in, e.g. `fn main`
+    pub symbol: Option<String>,
+}
 
 /// Metadata tracked for each page during rendering.
 #[derive(Clone, Debug, Default)]
@@ -28,6 +40,10 @@ pub struct PageMetadata {
     pub page_in_section: usize,
     /// Skip header/footer rendering for this page (e.g., blank recto-alignment pages)
     pub skip_numbering: bool,
+    /// Lines and item shown, for listing pages
+    pub listing: PageLines,
+    /// Volume the page is in (from 1), if pages are numbered per volume
+    pub volume: Option<usize>,
 }
 
 impl PageMetadata {
@@ -37,6 +53,8 @@ impl PageMetadata {
             section,
             page_in_section,
             skip_numbering: false,
+            listing: PageLines::default(),
+            volume: None,
         }
     }
 
@@ -45,6 +63,11 @@ impl PageMetadata {
         self
     }
 
+    pub fn with_listing(mut self, listing: PageLines) -> Self {
+        self.listing = listing;
+        self
+    }
+
     pub fn skip_numbering(mut self) -> Self {
         self.skip_numbering = true;
         self
@@ -55,9 +78,12 @@ impl PageMetadata {
 #[derive(Clone, Debug, Default)]
 pub struct SectionTotals {
     pub frontmatter: usize,
+    pub tour: usize,
     pub source: usize,
     pub commit_history: usize,
     pub tags: usize,
+    pub todos: usize,
+    pub index: usize,
 }
 
 impl SectionTotals {
@@ -65,9 +91,12 @@ impl SectionTotals {
     pub fn total_for(&self, section: Section) -> usize {
         match section {
             Section::Frontmatter => self.frontmatter,
+            Section::Tour => self.tour,
             Section::Source => self.source,
             Section::CommitHistory => self.commit_history,
             Section::Tags => self.tags,
+            Section::Todos => self.todos,
+            Section::Index => self.index,
         }
     }
 }
@@ -114,11 +143,34 @@ pub fn format_page_number(n: i32, style: PageNumberStyle) -> String {
     }
 }
 
+/// Format the page number printed on a page, with its section's style and prefix.
+///
+/// Used for `{n}` and anywhere else that refers to a page by number (the index).
+pub fn page_label(config: &PDF, metadata: &PageMetadata) -> String {
+    let numbering = config.numbering_for_section(metadata.section);
+    let prefix = config.prefix_for_section(metadata.section);
+    let page_number = numbering.start + metadata.page_in_section as i32;
+    format!(
+        "{}{}",
+        prefix,
+        format_page_number(page_number, numbering.style)
+    )
+}
+
+/// Cite a page from elsewhere in the book: its label, with its volume as well if
+/// pages are numbered per volume, e.g. "vol. 2 · 14".
+pub fn page_reference(config: &PDF, metadata: &PageMetadata) -> String {
+    match metadata.volume {
+        Some(volume) => format!("vol. {} · {}", volume, page_label(config, metadata)),
+        None => page_label(config, metadata),
+    }
+}
+
 /// Expand a template string with placeholder values using section-aware numbering.
 ///
 /// The page number is calculated as: section_start + page_in_section
 /// The total is the section's page count, not the entire document.
-/// Appendix sections (CommitHistory, Tags) may have optional prefixes.
+/// Appendix sections (CommitHistory, Tags, Todos, Index) may have optional prefixes.
 fn expand_template(
     template: &str,
     file_path: Option<&str>,
@@ -129,25 +181,28 @@ fn expand_template(
 ) -> String {
     let numbering = config.numbering_for_section(metadata.section);
     let prefix = config.prefix_for_section(metadata.section);
-    let page_number = numbering.start + metadata.page_in_section as i32;
     let section_total = section_totals.total_for(metadata.section);
 
-    let page_str = format!(
-        "{}{}",
-        prefix,
-        format_page_number(page_number, numbering.style)
-    );
+    let page_str = page_label(config, metadata);
     let total_str = format!(
         "{}{}",
         prefix,
         format_page_number(section_total as i32, numbering.style)
     );
 
+    let lines_str = match metadata.listing.lines {
+        Some((first, last)) if first == last => first.to_string(),
+        Some((first, last)) => format!("{}–{}", first, last),
+        None => String::new(),
+    };
+
     template
         .replace("{file}", file_path.unwrap_or(""))
         .replace("{title}", title.unwrap_or(""))
         .replace("{n}", &page_str)
         .replace("{total}", &total_str)
+        .replace("{symbol}", metadata.listing.symbol.as_deref().unwrap_or(""))
+        .replace("{lines}", &lines_str)
 }
 
 /// Calculate the x-coordinate for text based on position and page parity.
@@ -199,7 +254,9 @@ fn render_rule(page: &mut Page, content_box: &Rect, y: Pt, thickness: Pt) {
 
 /// Calculate total page counts per section from page metadata.
 /// Excludes blank recto-alignment pages (skip_numbering = true).
-pub fn calculate_section_totals(page_metadata: &[PageMetadata]) -> SectionTotals {
+pub fn calculate_section_totals<'a>(
+    page_metadata: impl IntoIterator<Item = &'a PageMetadata>,
+) -> SectionTotals {
     let mut totals = SectionTotals::default();
     for meta in page_metadata {
         if meta.skip_numbering {
@@ -207,9 +264,12 @@ pub fn calculate_section_totals(page_metadata: &[PageMetadata]) -> SectionTotals
         }
         match meta.section {
             Section::Frontmatter => totals.frontmatter += 1,
+            Section::Tour => totals.tour += 1,
             Section::Source => totals.source += 1,
             Section::CommitHistory => totals.commit_history += 1,
             Section::Tags => totals.tags += 1,
+            Section::Todos => totals.todos += 1,
+            Section::Index => totals.index += 1,
         }
     }
     totals
@@ -228,8 +288,13 @@ pub fn render_headers_and_footers(
     page_metadata: &[PageMetadata],
     title: Option<&str>,
 ) {
-    // calculate section totals from page metadata
-    let section_totals = calculate_section_totals(page_metadata);
+    // calculate section totals from page metadata, per volume if it's numbered so
+    let mut section_totals: HashMap<Option<usize>, SectionTotals> = HashMap::new();
+    for volume in page_metadata.iter().map(|meta| meta.volume) {
+        section_totals.entry(volume).or_insert_with(|| {
+            calculate_section_totals(page_metadata.iter().filter(|meta| meta.volume == volume))
+        });
+    }
 
     let header_size = Pt(config.fonts.subheading_pt);
     let footer_size = Pt(config.fonts.small_pt);
@@ -259,6 +324,10 @@ pub fn render_headers_and_footers(
         if metadata.skip_numbering {
             continue;
         }
+        let totals = section_totals
+            .get(&metadata.volume)
+            .cloned()
+            .unwrap_or_default();
 
         let page = doc.pages.get_mut(*page_id).expect("page exists");
         let content_box = page.content_box;
@@ -270,7 +339,7 @@ pub fn render_headers_and_footers(
                 metadata.file_path.as_deref(),
                 title,
                 &metadata,
-                &section_totals,
+                &totals,
                 config,
             );
 
@@ -316,7 +385,7 @@ pub fn render_headers_and_footers(
                 metadata.file_path.as_deref(),
                 title,
                 &metadata,
-                &section_totals,
+                &totals,
                 config,
             );
 
@@ -325,8 +394,13 @@ pub fn render_headers_and_footers(
                     layout::width_of_text(&text, &doc.fonts[font_ids.regular], footer_size);
                 let x = calculate_x_position(config.footer.position, pi, &content_box, text_width);
 
-                // footer near bottom of page
-                let y: Pt = In(0.25).into();
+                // footer in the middle of the bottom margin, or near the bottom of
+                // pages laid out without margins
+                let y: Pt = if content_box.y1 > Pt(0.0) {
+                    content_box.y1 / 2.0
+                } else {
+                    In(0.25).into()
+                };
 
                 page.add_span(SpanLayout {
                     text,
@@ -386,9 +460,12 @@ mod tests {
         let metadata = PageMetadata::new(Section::Source, 4).with_file("src/main.rs");
         let totals = SectionTotals {
             frontmatter: 0,
+            tour: 0,
             source: 100,
             commit_history: 0,
             tags: 0,
+            todos: 0,
+            index: 0,
         };
         let result = expand_template(
             "Page {n} of {total} - {file}",
@@ -402,6 +479,38 @@ mod tests {
         assert_eq!(result, "Page 5 of 100 - src/main.rs");
     }
 
+    #[test]
+    fn can_expand_listing_placeholders() {
+        let config = PDF::default();
+        let metadata = PageMetadata::new(Section::Source, 0)
+            .with_file("src/main.rs")
+            .with_listing(PageLines {
+                lines: Some((120, 168)),
+                symbol: Some("fn main".to_string()),
+            });
+        let result = expand_template(
+            "{file} · {symbol} · {lines}",
+            metadata.file_path.as_deref(),
+            None,
+            &metadata,
+            &SectionTotals::default(),
+            &config,
+        );
+        assert_eq!(result, "src/main.rs · fn main · 120–168");
+
+        // pages without a listing leave the placeholders empty
+        let metadata = PageMetadata::new(Section::CommitHistory, 0);
+        let result = expand_template(
+            "[{symbol}{lines}]",
+            None,
+            None,
+            &metadata,
+            &SectionTotals::default(),
+            &config,
+        );
+        assert_eq!(result, "[]");
+    }
+
     #[test]
     fn can_expand_template_with_roman() {
         let config = PDF::default();
@@ -412,6 +521,8 @@ mod tests {
             source: 0,
             commit_history: 0,
             tags: 0,
+            todos: 0,
+            index: 0,
         };
         let result = expand_template(
             "- {n} -",
@@ -425,6 +536,24 @@ mod tests {
         assert_eq!(result, "- iv -");
     }
 
+    #[test]
+    fn can_label_appendix_pages_with_prefix() {
+        let mut config = PDF::default();
+        config.numbering.index.prefix = "C-".to_string();
+        let metadata = PageMetadata::new(Section::Index, 2);
+        assert_eq!(page_label(&config, &metadata), "C-3");
+    }
+
+    #[test]
+    fn cites_the_volume_of_pages_numbered_per_volume() {
+        let config = PDF::default();
+        let mut metadata = PageMetadata::new(Section::Source, 13);
+        assert_eq!(page_reference(&config, &metadata), "14");
+        metadata.volume = Some(2);
+        assert_eq!(page_reference(&config, &metadata), "vol. 2 · 14");
+        assert_eq!(page_label(&config, &metadata), "14");
+    }
+
     #[test]
     fn can_calculate_section_totals() {
         let metadata = vec![
@@ -23,10 +23,9 @@
 //! A single 64KB binary file produces thousands of individually-coloured text spans.
 //! Use the `binary_hex_max_bytes` config option to limit the amount rendered per file.
 
-use crate::sinks::pdf::config::PDF;
+use crate::sinks::pdf::config::{PageLayout, PDF};
 use crate::sinks::pdf::fonts::FontIds;
 use crate::sinks::pdf::rendering::source_file::RenderResult;
-use pdf_gen::layout::Margins;
 use pdf_gen::*;
 use std::path::Path;
 
@@ -113,6 +112,7 @@ fn category_colour(category: ByteCategory, theme: &syntect::highlighting::Theme)
 /// Returns the first page index and page count.
 pub fn render(
     config: &PDF,
+    page_layout: &PageLayout,
     doc: &mut Document,
     font_ids: &FontIds,
     _path: &Path,
@@ -124,21 +124,28 @@ pub fn render(
         return RenderResult {
             first_page: None,
             page_count: 0,
+            continued: false,
+            line_pages: Vec::new(),
+            page_lines: Vec::new(),
+            definitions: Vec::new(),
         };
     }
 
     let hex_size = Pt(config.binary_hex.font_size_pt);
-    let subheading_size = Pt(config.fonts.subheading_pt);
-    let text_size = Pt(config.fonts.body_pt);
+    let subheading_size = Pt(page_layout.fonts.subheading_pt);
+    let text_size = Pt(page_layout.fonts.body_pt);
 
     // sanity check: ensure at least one byte (2 hex chars) fits per line
-    let page_size = config.page_size();
     let byte_width = layout::width_of_text("00", &doc.fonts[font_ids.regular], hex_size);
-    let content_width = page_size.0 - In(0.5).into() - In(0.25).into(); // margins
+    let content_width = page_layout.content_width();
     if byte_width > content_width {
         return RenderResult {
             first_page: None,
             page_count: 0,
+            continued: false,
+            line_pages: Vec::new(),
+            page_lines: Vec::new(),
+            definitions: Vec::new(),
         };
     }
 
@@ -184,15 +191,7 @@ pub fn render(
     let mut first_page = None;
     let mut page_count = 0;
     while !text.is_empty() {
-        let margins = Margins::trbl(
-            In(0.25).into(),
-            In(0.25).into(),
-            In(0.5).into(),
-            In(0.25).into(),
-        )
-        .with_gutter(In(0.25).into(), doc.page_order.len());
-
-        let mut page = Page::new(page_size, Some(margins));
+        let mut page = page_layout.new_page(doc.page_order.len());
         let start = layout::baseline_start(&page, &doc.fonts[font_ids.regular], text_size);
         let start = (
             start.0,
@@ -228,6 +227,10 @@ pub fn render(
     RenderResult {
         first_page,
         page_count,
+        continued: false,
+        line_pages: Vec::new(),
+        page_lines: Vec::new(),
+        definitions: Vec::new(),
     }
 }
 
@@ -2,12 +2,11 @@
 //!
 //! Displays images (PNG, JPG, SVG, etc.) centred on the page with file metadata.
 
-use crate::sinks::pdf::config::PDF;
+use crate::sinks::pdf::config::PageLayout;
 use crate::sinks::pdf::fonts::FontIds;
 use crate::sinks::pdf::rendering::ImagePathMap;
 use anyhow::Result;
 use jiff::{tz::TimeZone, Timestamp};
-use pdf_gen::layout::Margins;
 use pdf_gen::*;
 use std::path::Path;
 
@@ -15,14 +14,14 @@ use std::path::Path;
 ///
 /// Records the image path in `image_paths` so booklet rendering can reload the image.
 pub fn render(
-    config: &PDF,
+    page_layout: &PageLayout,
     doc: &mut Document,
     font_ids: &FontIds,
     path: &Path,
     image_paths: &mut ImagePathMap,
 ) -> Result<usize> {
-    let subheading_size = Pt(config.fonts.subheading_pt);
-    let small_size = Pt(config.fonts.small_pt);
+    let subheading_size = Pt(page_layout.fonts.subheading_pt);
+    let small_size = Pt(page_layout.fonts.small_pt);
 
     let image = Image::new_from_disk(path)?;
     let aspect_ratio = image.aspect_ratio();
@@ -32,14 +31,7 @@ pub fn render(
     // record path for booklet rendering
     image_paths.insert(image_index, path.to_path_buf());
 
-    let margins = Margins::trbl(
-        In(0.25).into(),
-        In(0.25).into(),
-        In(0.5).into(),
-        In(0.25).into(),
-    )
-    .with_gutter(In(0.25).into(), doc.page_order.len());
-    let mut page = Page::new(config.page_size(), Some(margins));
+    let mut page = page_layout.new_page(doc.page_order.len());
 
     let image_size = if aspect_ratio >= 1.0 {
         let width = page.content_box.x2 - page.content_box.x1;
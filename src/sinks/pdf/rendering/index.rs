@@ -0,0 +1,245 @@
+//! Symbol index appendix rendering.
+//!
+//! Lists the functions, types, traits, classes and constants defined in the source
+//! files alphabetically under letter headings, the way a printed book's index does.
+//! Each entry shows the symbol's kind and where it's defined, ends with the number
+//! of the page it's on, and links to that page like a table of contents entry.
+//!
+//! Definitions are found while each file is highlighted, and reported with the
+//! line-to-page bookkeeping in [`RenderResult::definitions`] and
+//! [`RenderResult::line_pages`], and are printed with the same [`PageMetadata`]
+//! that numbers the headers and footers, so the index agrees with the page numbers
+//! printed on the pages themselves.
+//!
+//! The index is rendered after the table of contents has been inserted, so link
+//! targets are shifted by the number of TOC pages.
+
+use crate::sinks::pdf::config::{PageLayout, PDF};
+use crate::sinks::pdf::fonts::FontIds;
+use crate::sinks::pdf::rendering::flow::Flow;
+use crate::sinks::pdf::rendering::header_footer::{page_reference, PageMetadata};
+use crate::sinks::pdf::rendering::source_file::RenderResult;
+use crate::syntax_analysis::{compare_symbols, index_letter, SymbolKind};
+use anyhow::Result;
+use pdf_gen::*;
+use std::path::{Path, PathBuf};
+
+/// A symbol definition and the page it landed on.
+pub struct IndexEntry {
+    pub name: String,
+    pub kind: SymbolKind,
+    pub file: PathBuf,
+    /// Zero-based line index of the definition
+    pub line: usize,
+    /// Page index of the definition, before the table of contents is inserted
+    pub page: usize,
+}
+
+/// Result of rendering the index section.
+pub struct IndexRenderResult {
+    /// Page index of the first content page, or None if there were no entries.
+    pub first_page: Option<usize>,
+    /// Whether a blank page was inserted for recto alignment.
+    pub blank_inserted: bool,
+}
+
+/// Collect index entries for a rendered source file.
+///
+/// Files the renderer didn't lay out line by line (images, notebooks, hex dumps)
+/// report no definitions and contribute nothing.
+pub fn collect(path: &Path, result: &RenderResult) -> Vec<IndexEntry> {
+    result
+        .definitions
+        .iter()
+        .filter_map(|definition| {
+            let page = (*result.line_pages.get(definition.line)?)?;
+            Some(IndexEntry {
+                name: definition.name.clone(),
+                kind: definition.kind,
+                file: path.to_path_buf(),
+                line: definition.line,
+                page,
+            })
+        })
+        .collect()
+}
+
+/// The page index the index would start on if it were rendered after `page_count`
+/// pages, allowing for the blank page that puts it on a recto.
+pub fn first_page_after(page_count: usize) -> usize {
+    page_count + page_count % 2
+}
+
+/// Render the index section.
+///
+/// `content_offset` is the page index of the first content page (matching the
+/// first entry of `page_metadata`) and `link_offset` the number of pages inserted
+/// ahead of the content since the entries' pages were recorded.
+pub fn render(
+    config: &PDF,
+    page_layout: &PageLayout,
+    doc: &mut Document,
+    font_ids: &FontIds,
+    mut entries: Vec<IndexEntry>,
+    content_offset: usize,
+    page_metadata: &[PageMetadata],
+    link_offset: usize,
+) -> Result<IndexRenderResult> {
+    if entries.is_empty() {
+        return Ok(IndexRenderResult {
+            first_page: None,
+            blank_inserted: false,
+        });
+    }
+
+    entries.sort_by(|a, b| {
+        compare_symbols(&a.name, &b.name)
+            .then_with(|| a.file.cmp(&b.file))
+            .then_with(|| a.line.cmp(&b.line))
+    });
+
+    // insert a blank page so we open to the correct side (recto)
+    let blank_inserted = doc.page_order.len() % 2 == 1;
+    if blank_inserted {
+        doc.add_page(Page::new(page_layout.page_size, None));
+    }
+
+    let heading_size = Pt(page_layout.fonts.heading_pt);
+    let subheading_size = Pt(page_layout.fonts.subheading_pt);
+    let text_size = Pt(page_layout.fonts.body_pt);
+    let small_size = Pt(page_layout.fonts.small_pt);
+
+    let heading_font = SpanFont {
+        id: font_ids.bold,
+        size: heading_size,
+    };
+    let letter_font = SpanFont {
+        id: font_ids.bold,
+        size: subheading_size,
+    };
+    let name_font = SpanFont {
+        id: font_ids.regular,
+        size: text_size,
+    };
+    let detail_font = SpanFont {
+        id: font_ids.regular,
+        size: small_size,
+    };
+    let kind_font = SpanFont {
+        id: font_ids.italic,
+        size: small_size,
+    };
+    let detail_colour = Colour::new_grey(0.5);
+
+    let mut flow = Flow::new(page_layout, doc, font_ids);
+    let heading_height = flow.doc.fonts[font_ids.bold].line_height(heading_size);
+    let heading_ascent = flow.doc.fonts[font_ids.bold].ascent(heading_size);
+    let letter_height = flow.doc.fonts[font_ids.bold].line_height(subheading_size);
+    let letter_ascent = flow.doc.fonts[font_ids.bold].ascent(subheading_size);
+    let entry_height = flow.doc.fonts[font_ids.regular].line_height(text_size);
+    let entry_ascent = flow.doc.fonts[font_ids.regular].ascent(text_size);
+
+    flow.add_line(
+        Pt(0.0),
+        vec![("Index".to_string(), colours::BLACK, heading_font)],
+        heading_height,
+        heading_ascent,
+    );
+
+    let mut letter = None;
+    for entry in entries {
+        let entry_letter = index_letter(&entry.name);
+        if letter != Some(entry_letter) {
+            letter = Some(entry_letter);
+            flow.gap(entry_height * 0.5);
+            // keep the letter heading with its first entry
+            flow.ensure_space(letter_height + entry_height);
+            flow.add_line(
+                Pt(0.0),
+                vec![(entry_letter.to_string(), colours::BLACK, letter_font)],
+                letter_height,
+                letter_ascent,
+            );
+        }
+
+        let label = page_metadata
+            .get(entry.page.saturating_sub(content_offset))
+            .map(|metadata| page_reference(config, metadata))
+            .unwrap_or_default();
+        let label_width = layout::width_of_text(
+            &format!(" {}", label),
+            &flow.doc.fonts[font_ids.regular],
+            text_size,
+        );
+
+        // name, kind and location, dropping the front of long paths to fit
+        let measure = flow.measure();
+        let name = format!("{} ", entry.name);
+        let kind = format!("{} ", entry.kind.label());
+        let name_width = layout::width_of_text(&name, &flow.doc.fonts[font_ids.regular], text_size);
+        let kind_width = layout::width_of_text(&kind, &flow.doc.fonts[font_ids.italic], small_size);
+        let available = measure - label_width - name_width - kind_width;
+        let location = fit_start(
+            &format!("{}:{}", entry.file.display(), entry.line + 1),
+            available,
+            |text| layout::width_of_text(text, &flow.doc.fonts[font_ids.regular], small_size),
+        );
+        let location_width =
+            layout::width_of_text(&location, &flow.doc.fonts[font_ids.regular], small_size);
+
+        let label_x = measure - label_width;
+        let baseline = flow.add_line_at(
+            vec![
+                (Pt(0.0), (name, colours::BLACK, name_font)),
+                (name_width, (kind, detail_colour, kind_font)),
+                (
+                    name_width + kind_width,
+                    (location, detail_colour, detail_font),
+                ),
+                (label_x, (format!(" {}", label), colours::BLACK, name_font)),
+            ],
+            entry_height,
+            entry_ascent,
+        );
+
+        // a leader from the entry to its page number, as in the table of contents
+        let bbox = flow.content_box();
+        let leader_start = bbox.x1 + name_width + kind_width + location_width;
+        let leader_end = bbox.x1 + label_x;
+        if leader_end > leader_start {
+            let y = baseline - Pt(1.5);
+            flow.draw_line((leader_start, y), (leader_end, y), Pt(0.5), 0.75);
+        }
+
+        flow.page().add_intradocument_link_by_index(
+            Rect {
+                x1: bbox.x1,
+                x2: bbox.x2,
+                y1: baseline,
+                y2: baseline + entry_ascent,
+            },
+            entry.page + link_offset,
+        );
+    }
+
+    let result = flow.finish();
+    Ok(IndexRenderResult {
+        first_page: result.first_page,
+        blank_inserted,
+    })
+}
+
+/// Shorten text from the front with an ellipsis until it fits `width`.
+pub fn fit_start(text: &str, width: Pt, measure: impl Fn(&str) -> Pt) -> String {
+    if measure(text) <= width {
+        return text.to_string();
+    }
+    let mut chars = text.chars();
+    while chars.next().is_some() {
+        let shortened = format!("…{}", chars.as_str());
+        if measure(&shortened) <= width {
+            return shortened;
+        }
+    }
+    String::new()
+}
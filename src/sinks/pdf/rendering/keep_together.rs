@@ -0,0 +1,92 @@
+//! Keep-together pagination for source listings.
+//!
+//! Listings normally fill each page to the bottom, so a short function can end up
+//! with its signature at the foot of one page and its body on the next. With
+//! `[pdf.keep_together]` enabled, the listing asks before each line whether to
+//! break the page early:
+//!
+//! - a top-level item shorter than `max_lines` that would be split moves whole to
+//!   the next page;
+//! - a longer item starts on the next page if fewer than `min_lines` of it would
+//!   fit at the foot of this one;
+//! - inside an item, the page breaks early rather than leave fewer than
+//!   `min_lines` of it for the next page.
+//!
+//! Items are found by indentation, along with their doc comments and attributes
+//! (see [`crate::syntax_analysis::item_lines`]). Room is counted in wrapped rows.
+//! The blank space this leaves at the foot of pages is the cost, so it's opt-in.
+
+use crate::sinks::pdf::config::KeepTogetherConfig;
+use std::ops::Range;
+
+/// The top-level items of a listing and the rows each of its lines takes up.
+pub struct KeepTogether {
+    blocks: Vec<Range<usize>>,
+    rows: Vec<usize>,
+    max_lines: usize,
+    min_lines: usize,
+}
+
+impl KeepTogether {
+    /// `blocks` are the zero-based lines of each top-level item and `rows` the number
+    /// of rows each line of the file is printed on (zero for lines not shown).
+    pub fn new(config: &KeepTogetherConfig, blocks: Vec<Range<usize>>, rows: Vec<usize>) -> Self {
+        Self {
+            blocks,
+            rows,
+            max_lines: config.max_lines,
+            min_lines: config.min_lines,
+        }
+    }
+
+    fn rows(&self, lines: Range<usize>) -> usize {
+        let end = lines.end.min(self.rows.len());
+        let start = lines.start.min(end);
+        self.rows[start..end].iter().sum()
+    }
+
+    /// Whether the page should break before `line`, with room left for `fits` more
+    /// rows on the current page.
+    pub fn break_before(&self, line: usize, fits: usize) -> bool {
+        let Some(block) = self.blocks.iter().find(|block| block.contains(&line)) else {
+            return false;
+        };
+        if self.rows(line..block.end) <= fits {
+            return false;
+        }
+        if line == block.start {
+            return block.len() < self.max_lines || fits < self.min_lines;
+        }
+        // this is the last line the break can come before and still leave enough
+        // for the next page
+        self.rows(line + 1..block.end) < self.min_lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_short_items_and_item_ends_together() {
+        let config = KeepTogetherConfig {
+            enabled: true,
+            max_lines: 5,
+            min_lines: 2,
+        };
+        let keep = KeepTogether::new(&config, vec![0..3, 4..20], vec![1; 20]);
+
+        // a short item moves whole unless it fits
+        assert!(!keep.break_before(0, 10));
+        assert!(keep.break_before(0, 2));
+        // a long item is split, but not with a lone line at the foot of the page
+        assert!(!keep.break_before(4, 10));
+        assert!(keep.break_before(4, 1));
+        // nor a lone line at the top of the next
+        assert!(!keep.break_before(5, 14));
+        assert!(!keep.break_before(17, 2));
+        assert!(keep.break_before(18, 1));
+        // lines outside an item break wherever
+        assert!(!keep.break_before(3, 0));
+    }
+}
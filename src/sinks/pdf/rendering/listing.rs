@@ -0,0 +1,298 @@
+//! Numbered code listing layout.
+//!
+//! Source listings, literate code runs and fenced code blocks all lay out code the
+//! same way: each source line is wrapped to the measure, preferably at whitespace
+//! or punctuation (see [`crate::wrapping`]), and continuation rows hang past the
+//! line's own indentation. [`wrap_code`] does the wrapping and [`listing_line`]
+//! places a numbered line through a [`Flow`], with a continuation glyph rather than
+//! a number in the gutter of each continuation row.
+//!
+//! Excerpts leave runs of lines out; [`omission`] places the marker that stands in
+//! for each run. Collapsed file headers get the same treatment through
+//! [`gutter_note`].
+//!
+//! Because every line is placed individually, the listing knows where things land:
+//! callers can ask for the positions of byte ranges within a line ("marks"), which is
+//! how identifiers become link annotations.
+
+use crate::sinks::pdf::config::{PageLayout, PDF};
+use crate::sinks::pdf::fonts::FontIds;
+use crate::sinks::pdf::rendering::flow::{Flow, Span};
+use crate::source::omission_label;
+use crate::wrapping;
+use pdf_gen::*;
+use std::ops::Range;
+
+/// Settings shared by every line of a listing.
+pub struct ListingStyle {
+    pub body_size: Pt,
+    pub small_size: Pt,
+    /// Glyph printed in the margin beside flagged lines
+    pub margin_glyph: Option<String>,
+    /// Glyph printed in the gutter of continuation rows
+    pub continuation_glyph: Option<String>,
+    /// Columns continuation rows hang past the line's indentation
+    pub wrap_indent: usize,
+}
+
+impl ListingStyle {
+    pub fn new(config: &PDF, page_layout: &PageLayout) -> Self {
+        Self {
+            body_size: Pt(page_layout.fonts.body_pt),
+            small_size: Pt(page_layout.fonts.small_pt),
+            margin_glyph: config
+                .todos
+                .margin_glyph
+                .clone()
+                .filter(|glyph| config.todos.enabled && !glyph.is_empty()),
+            continuation_glyph: Some(config.code.continuation_glyph.clone())
+                .filter(|glyph| !glyph.is_empty()),
+            wrap_indent: config.code.wrap_indent,
+        }
+    }
+}
+
+/// One row of a wrapped code line.
+pub struct Row {
+    /// Spans with their offset from the start of the code column and the byte
+    /// offset of their first character within the line
+    pub spans: Vec<(Pt, usize, Span)>,
+}
+
+impl Row {
+    /// Byte range of the line covered by this row.
+    fn range(&self) -> Range<usize> {
+        let start = self.spans.first().map(|(_, start, _)| *start).unwrap_or(0);
+        let end = self
+            .spans
+            .last()
+            .map(|(_, start, span)| start + span.0.len())
+            .unwrap_or(start);
+        start..end
+    }
+
+    /// Offset from the start of the code column of the character at `byte`.
+    fn x_of(&self, byte: usize, width: &impl Fn(&str, SpanFont) -> Pt) -> Option<Pt> {
+        self.spans.iter().find_map(|(x, start, (text, _, font))| {
+            let local = byte.checked_sub(*start)?;
+            if local > text.len() || !text.is_char_boundary(local) {
+                return None;
+            }
+            Some(*x + width(&text[..local], *font))
+        })
+    }
+}
+
+/// A mark's position on the page: which mark, the page index it landed on, its
+/// bounding box and the baseline of its row.
+pub struct PlacedMark {
+    pub mark: usize,
+    pub page: usize,
+    pub rect: Rect,
+    pub baseline: Pt,
+}
+
+/// Break a line of spans into rows no wider than `measure`, continuation rows
+/// hanging `offset` past the line's indentation.
+///
+/// Always returns at least one row so blank lines keep their height.
+pub fn wrap_code(
+    spans: Vec<Span>,
+    measure: Pt,
+    offset: Pt,
+    width: impl Fn(&str, SpanFont) -> Pt,
+) -> Vec<Row> {
+    // measure every character, remembering which span it came from
+    let mut glyphs = Vec::new();
+    let mut sources = Vec::new();
+    let mut byte = 0;
+    for (source, (text, _, font)) in spans.iter().enumerate() {
+        for c in text.chars() {
+            let w = width(c.encode_utf8(&mut [0; 4]), *font);
+            glyphs.push((byte, c, w.0));
+            sources.push(source);
+            byte += c.len_utf8();
+        }
+    }
+    let hang = wrapping::hang(&glyphs, offset.0, measure.0);
+    let mut breaks = wrapping::break_line(&glyphs, measure.0, hang)
+        .into_iter()
+        .peekable();
+
+    let mut rows = vec![Row { spans: Vec::new() }];
+    let mut x = Pt(0.0);
+    for (i, (byte, c, w)) in glyphs.into_iter().enumerate() {
+        // whether the last span in the row came from the same highlighted span
+        let mut continuing = i > 0 && sources[i - 1] == sources[i];
+        if breaks.next_if_eq(&i).is_some() {
+            rows.push(Row { spans: Vec::new() });
+            x = Pt(hang);
+            continuing = false;
+        }
+        let (_, colour, font) = &spans[sources[i]];
+        let row = rows.last_mut().expect("there is always a row");
+        match row.spans.last_mut() {
+            Some((_, _, last)) if continuing => last.0.push(c),
+            _ => row.spans.push((x, byte, (c.to_string(), *colour, *font))),
+        }
+        x = x + Pt(w);
+    }
+    rows
+}
+
+/// The width code wraps to beside the line-number gutter, and how far continuation
+/// rows hang past a line's indentation.
+fn code_measure(flow: &mut Flow, font_ids: &FontIds, style: &ListingStyle) -> (Pt, Pt) {
+    let measure = flow.measure();
+    let regular = &flow.doc.fonts[font_ids.regular];
+    let gutter_width = layout::width_of_text("      ", regular, style.small_size);
+    let offset = layout::width_of_text(&" ".repeat(style.wrap_indent), regular, style.body_size);
+    (measure - gutter_width, offset)
+}
+
+/// The number of rows a line of code will take up in a listing.
+///
+/// Every character is measured in the regular face, which is exact for monospaced
+/// fonts whose bold and italic faces share its advance widths.
+pub fn row_count(flow: &mut Flow, font_ids: &FontIds, style: &ListingStyle, line: &str) -> usize {
+    let (measure, offset) = code_measure(flow, font_ids, style);
+    let regular = &flow.doc.fonts[font_ids.regular];
+    let glyphs: Vec<wrapping::Glyph> = line
+        .trim_end_matches(['\n', '\r'])
+        .char_indices()
+        .map(|(i, c)| {
+            let w = layout::width_of_text(c.encode_utf8(&mut [0; 4]), regular, style.body_size);
+            (i, c, w.0)
+        })
+        .collect();
+    let hang = wrapping::hang(&glyphs, offset.0, measure.0);
+    wrapping::break_line(&glyphs, measure.0, hang).len() + 1
+}
+
+/// Place a numbered line of highlighted code, laid out like a source listing.
+///
+/// `marks` are byte ranges within the line (the concatenated span text) whose
+/// positions should be reported; a mark that wraps onto a second row is reported
+/// once per row. `flagged` lines get the style's margin glyph, if it has one.
+pub fn listing_line(
+    flow: &mut Flow,
+    font_ids: &FontIds,
+    style: &ListingStyle,
+    number: usize,
+    spans: Vec<Span>,
+    marks: &[Range<usize>],
+    flagged: bool,
+) -> Vec<PlacedMark> {
+    let body_size = style.body_size;
+    let small_size = style.small_size;
+    let small = SpanFont {
+        id: font_ids.regular,
+        size: small_size,
+    };
+    let gutter = format!("{:>4}  ", number);
+    let gutter_width =
+        layout::width_of_text("      ", &flow.doc.fonts[font_ids.regular], small_size);
+    let (measure, offset) = code_measure(flow, font_ids, style);
+    let line_height = flow.doc.fonts[font_ids.regular].line_height(body_size);
+    let ascent = flow.doc.fonts[font_ids.regular].ascent(body_size);
+    let descent = flow.doc.fonts[font_ids.regular].descent(body_size);
+
+    let width = |text: &str, font: SpanFont| {
+        layout::width_of_text(text, &flow.doc.fonts[font.id], font.size)
+    };
+    let rows = wrap_code(spans, measure, offset, width);
+
+    let mut placed_marks = Vec::new();
+    for (i, row) in rows.into_iter().enumerate() {
+        // find the marks on this row before the spans are handed over
+        let width = |text: &str, font: SpanFont| {
+            layout::width_of_text(text, &flow.doc.fonts[font.id], font.size)
+        };
+        let range = row.range();
+        let row_marks: Vec<(usize, Pt, Pt)> = marks
+            .iter()
+            .enumerate()
+            .filter_map(|(m, mark)| {
+                let start = mark.start.max(range.start);
+                let end = mark.end.min(range.end);
+                if start >= end {
+                    return None;
+                }
+                Some((m, row.x_of(start, &width)?, row.x_of(end, &width)?))
+            })
+            .collect();
+
+        let mut placed: Vec<(Pt, Span)> = Vec::with_capacity(row.spans.len() + 1);
+        if i == 0 {
+            placed.push((Pt(0.0), (gutter.clone(), Colour::new_grey(0.75), small)));
+            if let (true, Some(glyph)) = (flagged, &style.margin_glyph) {
+                // hang the glyph in the margin, left of the line number
+                let glyph_width = layout::width_of_text(
+                    &format!("{} ", glyph),
+                    &flow.doc.fonts[small.id],
+                    small.size,
+                );
+                placed.push((
+                    Pt(0.0) - glyph_width,
+                    (glyph.clone(), colours::BLACK, small),
+                ));
+            }
+        } else if let Some(glyph) = &style.continuation_glyph {
+            // right-aligned where the line number's last digit would be
+            let number_width = layout::width_of_text("    ", &flow.doc.fonts[small.id], small.size);
+            let glyph_width = layout::width_of_text(glyph, &flow.doc.fonts[small.id], small.size);
+            placed.push((
+                number_width - glyph_width,
+                (glyph.clone(), Colour::new_grey(0.75), small),
+            ));
+        }
+        placed.extend(
+            row.spans
+                .into_iter()
+                .map(|(x, _, span)| (gutter_width + x, span)),
+        );
+        let baseline = flow.add_line_at(placed, line_height, ascent);
+
+        let left = flow.content_box().x1 + gutter_width;
+        let page = flow.page_index();
+        placed_marks.extend(row_marks.into_iter().map(|(mark, x1, x2)| PlacedMark {
+            mark,
+            page,
+            rect: Rect {
+                x1: left + x1,
+                x2: left + x2,
+                y1: baseline + descent,
+                y2: baseline + ascent,
+            },
+            baseline,
+        }));
+    }
+    placed_marks
+}
+
+/// Place the marker for a run of lines (zero-based, half-open) left out of an
+/// excerpt, lined up with the code.
+pub fn omission(flow: &mut Flow, font_ids: &FontIds, style: &ListingStyle, lines: &Range<usize>) {
+    gutter_note(flow, font_ids, style, omission_label(lines));
+}
+
+/// Place a grey note in place of some lines, lined up with the code.
+pub fn gutter_note(flow: &mut Flow, font_ids: &FontIds, style: &ListingStyle, text: String) {
+    let regular = &flow.doc.fonts[font_ids.regular];
+    let gutter_width = layout::width_of_text("      ", regular, style.small_size);
+    let line_height = regular.line_height(style.body_size);
+    let ascent = regular.ascent(style.body_size);
+    flow.add_line(
+        gutter_width,
+        vec![(
+            text,
+            Colour::new_grey(0.5),
+            SpanFont {
+                id: font_ids.italic,
+                size: style.small_size,
+            },
+        )],
+        line_height,
+        ascent,
+    );
+}
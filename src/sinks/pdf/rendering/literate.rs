@@ -0,0 +1,231 @@
+//! Literate source file rendering.
+//!
+//! With `[pdf.literate]` enabled, documentation comments are lifted out of the
+//! listing and typeset as Markdown prose between the runs of code they document.
+//! Comment regions are found from syntect scopes by
+//! [`crate::syntax_analysis::literate_segments`], so no per-language parsing is
+//! needed. Code keeps its original line numbers so the gaps left by the prose
+//! remain visible. In an excerpt, prose is kept only where the comment itself is
+//! among the selected lines. The standard file header, whether it's read as prose
+//! or code, is collapsed to a marker as in the regular listing.
+//!
+//! Files without any documentation comments, binary files and files without a
+//! known syntax fall back to the regular listing.
+
+use crate::boilerplate::without_header;
+use crate::markdown::parse;
+use crate::sinks::pdf::config::{PageLayout, PDF};
+use crate::sinks::pdf::fonts::FontIds;
+use crate::sinks::pdf::rendering::flow::Span;
+use crate::sinks::pdf::rendering::markdown::Typesetter;
+use crate::sinks::pdf::rendering::outline::place_box;
+use crate::sinks::pdf::rendering::source_file::{self, code_spans, ListingContext, RenderResult};
+use crate::sinks::pdf::rendering::todos::Todos;
+use crate::sinks::pdf::rendering::ImagePathMap;
+use crate::source::Omissions;
+use crate::syntax_analysis::{
+    highlight_and_scan, literate_segments, segment_lines, Segment, SymbolScanner,
+};
+use crate::whitespace;
+use anyhow::{Context, Result};
+use pdf_gen::*;
+use std::ops::Range;
+use std::path::Path;
+use syntect::highlighting::Theme;
+use syntect::parsing::SyntaxSet;
+use syntect::util::LinesWithEndings;
+
+/// What a literate file reports to, beyond what a plain listing does.
+pub struct LiterateContext<'a> {
+    /// Paths of the images placed in the document, so the booklet can reload them
+    pub image_paths: &'a mut ImagePathMap,
+    /// Everything a plain listing of the file reports to
+    pub listing: ListingContext<'a>,
+}
+
+/// Render a source file with its documentation comments typeset as prose.
+///
+/// Returns the first page index and number of pages rendered, like
+/// [`source_file::render`], and reports to `context.listing` the same way.
+pub fn render(
+    config: &PDF,
+    page_layout: &PageLayout,
+    doc: &mut Document,
+    font_ids: &FontIds,
+    path: &Path,
+    ss: &SyntaxSet,
+    theme: &Theme,
+    context: LiterateContext,
+) -> Result<RenderResult> {
+    let image_paths = context.image_paths;
+    let mut context = context.listing;
+    let Ok(contents) = std::fs::read_to_string(path) else {
+        return source_file::render(config, page_layout, doc, font_ids, path, ss, theme, context);
+    };
+    let Some(syntax) = ss.find_syntax_by_extension(
+        path.extension()
+            .map(std::ffi::OsStr::to_str)
+            .unwrap_or_default()
+            .unwrap_or_default(),
+    ) else {
+        return source_file::render(config, page_layout, doc, font_ids, path, ss, theme, context);
+    };
+
+    let contents = whitespace::expand(&contents, &config.code);
+    // the collapsed header is emptied so it isn't lifted out as prose
+    let segment_source = match &context.header {
+        Some(header) => without_header(&contents, header),
+        None => contents.clone(),
+    };
+    let segments = literate_segments(&segment_source, syntax, ss, config.literate.all_comments)
+        .with_context(|| format!("Failed to find comments in {}", path.display()))?;
+    if !segments.iter().any(|s| matches!(s, Segment::Prose(_))) {
+        return source_file::render(config, page_layout, doc, font_ids, path, ss, theme, context);
+    }
+
+    let selection = match context.excerpt {
+        Some(excerpt) => Some(excerpt.select(path, &contents, Some(syntax), ss)?),
+        None => None,
+    };
+    let shown = |lines: Range<usize>| selection.as_ref().is_none_or(|s| s.intersects(&lines));
+
+    // highlight every line up front, finding the file's symbols in the same pass;
+    // prose lines still need to pass through the highlighter to keep its parse
+    // state correct for the code that follows
+    let cross_references = context.cross_references.as_deref();
+    let scanner = SymbolScanner::new(
+        context
+            .todos
+            .as_deref()
+            .map(Todos::tags)
+            .unwrap_or_default(),
+        |name: &str| cross_references.is_some_and(|c| c.defines(name)),
+    );
+    let (highlighted, symbols) = highlight_and_scan(&contents, syntax, ss, theme, scanner)
+        .with_context(|| format!("Failed to highlight source code of {}", path.display()))?;
+    let references = match cross_references {
+        Some(cross_references) => cross_references.line_references(path, symbols.references),
+        None => Vec::new(),
+    };
+    let markers = symbols.markers;
+
+    let text_size = Pt(page_layout.fonts.body_pt);
+    let source_lines: Vec<&str> = LinesWithEndings::from(contents.as_str()).collect();
+    let lines: Vec<Vec<Span>> = highlighted
+        .into_iter()
+        .map(|ranges| code_spans(config, ranges, font_ids, text_size))
+        .collect();
+
+    let base_dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+    let mut typesetter = Typesetter::new(
+        config,
+        page_layout,
+        doc,
+        font_ids,
+        ss,
+        theme,
+        &base_dir,
+        image_paths,
+    );
+    if let Some(continuation) = context.continuation.take() {
+        typesetter.flow.continue_file(continuation, font_ids, path);
+    }
+    let paragraph_gap = typesetter.flow.doc.fonts[font_ids.regular].line_height(text_size) * 0.5;
+    let mut outline_items = symbols.outline;
+    outline_items.retain(|item| shown(item.line..item.line + 1));
+    let outline_rows = match context.outlines.as_deref() {
+        Some(outlines) if outlines.wants_box(&outline_items) => place_box(
+            &mut typesetter.flow,
+            font_ids,
+            Pt(page_layout.fonts.small_pt),
+            &outline_items,
+        ),
+        _ => Vec::new(),
+    };
+    // comment lines are credited to the page their prose finishes on; lines left
+    // out of an excerpt have no page
+    let mut line_pages: Vec<Option<usize>> = Vec::with_capacity(lines.len());
+    let mut omissions = Omissions::default();
+    let mut placed_any = false;
+    let header = context.header.clone();
+    let in_header = |lines: &Range<usize>| {
+        header
+            .as_ref()
+            .is_some_and(|h| h.start < lines.end && lines.start < h.end)
+    };
+    let mut header_placed = false;
+    for (segment, range) in segments.iter().zip(segment_lines(&segments, lines.len())) {
+        if !shown(range.clone()) {
+            omissions.skip(range.start);
+            line_pages.resize(range.end, None);
+            continue;
+        }
+        if placed_any {
+            typesetter.flow.gap(paragraph_gap);
+        }
+        placed_any = true;
+        match segment {
+            Segment::Prose(text) => {
+                if let Some(run) = omissions.show(range.start) {
+                    typesetter.omission(&run);
+                }
+                if in_header(&range) && !header_placed {
+                    typesetter.header(header.as_ref().expect("file has a header"));
+                    header_placed = true;
+                }
+                typesetter.blocks(&parse(text), Pt(0.0))?;
+                let page_index = typesetter.flow.page_index();
+                line_pages.resize(range.end, Some(page_index));
+            }
+            Segment::Code { start, end } => {
+                for (n, spans) in lines.iter().enumerate().take(*end).skip(*start) {
+                    if !shown(n..n + 1) {
+                        omissions.skip(n);
+                        line_pages.resize(n + 1, None);
+                        continue;
+                    }
+                    if let Some(run) = omissions.show(n) {
+                        typesetter.omission(&run);
+                    }
+                    if in_header(&(n..n + 1)) {
+                        if !header_placed {
+                            typesetter.header(header.as_ref().expect("file has a header"));
+                            header_placed = true;
+                        }
+                        line_pages.resize(n + 1, Some(typesetter.flow.page_index()));
+                        continue;
+                    }
+                    let line_references = references.get(n).map(Vec::as_slice).unwrap_or_default();
+                    let marks: Vec<Range<usize>> = line_references
+                        .iter()
+                        .map(|r| whitespace::shown_range(source_lines[n], &r.range, &config.code))
+                        .collect();
+                    let flagged = markers.binary_search_by_key(&n, |m| m.line).is_ok();
+                    let placed = typesetter.listing_line(n + 1, spans.clone(), &marks, flagged);
+                    if let Some(cross_references) = context.cross_references.as_deref_mut() {
+                        cross_references.add_links(line_references, placed);
+                    }
+                    let page_index = typesetter.flow.page_index();
+                    line_pages.resize(n + 1, Some(page_index));
+                }
+            }
+        }
+    }
+    if let Some(run) = omissions.finish(lines.len()) {
+        typesetter.omission(&run);
+    }
+    let last_page = typesetter.flow.page_index();
+    line_pages.resize(lines.len(), Some(last_page));
+    if let Some(outlines) = context.outlines {
+        outlines.add_file(path, outline_items, outline_rows, &line_pages);
+    }
+    if let Some(todos) = context.todos {
+        todos.add_file(path, markers, &line_pages);
+    }
+
+    let mut result = typesetter.flow.finish();
+    result.page_lines = source_file::page_lines(&result, &line_pages, &outline_items);
+    result.line_pages = line_pages;
+    result.definitions = symbols.definitions;
+    Ok(result)
+}
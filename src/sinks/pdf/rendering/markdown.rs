@@ -0,0 +1,712 @@
+//! Typeset Markdown rendering.
+//!
+//! Documentation files in the frontmatter (README, CONTRIBUTING, CHANGELOG) read
+//! poorly as a raw listing full of `#`, `**` and link syntax. When `[pdf.markdown]`
+//! is enabled, Markdown files are parsed into a [`crate::markdown`] block tree and
+//! typeset through a [`Flow`]:
+//!
+//! - Headings in bold at sizes derived from the configured heading sizes
+//! - Paragraphs reflowed word by word to the measure
+//! - Bulleted, numbered and task lists with hanging indents
+//! - Block quotes with a bar in the margin
+//! - Tables with columns sized to their content
+//! - Fenced code blocks highlighted with the configured syntect theme
+//! - Local images placed inline, scaled to fit the measure
+//!
+//! Raw HTML is dropped, and remote images are replaced by their alt text since
+//! the book must render offline.
+
+use crate::boilerplate::header_label;
+use crate::markdown::{parse, plain_text, resolve_local_image, Block, Inline, InlineStyle};
+use crate::sinks::pdf::config::{CodeConfig, PageLayout, PDF};
+use crate::sinks::pdf::fonts::FontIds;
+use crate::sinks::pdf::rendering::flow::{Continuation, Flow, Span};
+use crate::sinks::pdf::rendering::listing::{self, wrap_code, ListingStyle, PlacedMark};
+use crate::sinks::pdf::rendering::source_file::{highlighted_span, RenderResult};
+use crate::sinks::pdf::rendering::ImagePathMap;
+use crate::whitespace;
+use anyhow::{Context, Result};
+use pdf_gen::*;
+use std::ops::Range;
+use std::path::Path;
+use syntect::easy::HighlightLines;
+use syntect::highlighting::Theme;
+use syntect::parsing::SyntaxSet;
+use syntect::util::LinesWithEndings;
+
+/// A wrapped line of inline content.
+enum Line {
+    Text(Vec<Span>),
+    Image { url: String, alt: String },
+}
+
+/// A word or separator produced when breaking inline content for wrapping.
+enum Token {
+    /// Adjacent runs with no whitespace between them, possibly in different styles
+    Word(Vec<Span>),
+    Space,
+    Break,
+    Image {
+        url: String,
+        alt: String,
+    },
+}
+
+/// Where a Markdown file's images go and where it starts.
+pub struct ProseContext<'a> {
+    /// Paths of the images placed in the document, so the booklet can reload them
+    pub image_paths: &'a mut ImagePathMap,
+    /// The page to carry on from, for files placed `Continuous`
+    pub continuation: Option<&'a mut Continuation>,
+}
+
+/// Render a Markdown file as typeset prose, following on from the file before
+/// when `context` has a continuation.
+///
+/// Returns the first page index and number of pages rendered, like
+/// [`super::source_file::render`].
+pub fn render(
+    config: &PDF,
+    page_layout: &PageLayout,
+    doc: &mut Document,
+    font_ids: &FontIds,
+    path: &Path,
+    ss: &SyntaxSet,
+    theme: &Theme,
+    context: ProseContext,
+) -> Result<RenderResult> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read contents of {}", path.display()))?;
+    let blocks = parse(&contents);
+    let base_dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+
+    let mut typesetter = Typesetter::new(
+        config,
+        page_layout,
+        doc,
+        font_ids,
+        ss,
+        theme,
+        &base_dir,
+        context.image_paths,
+    );
+    if let Some(continuation) = context.continuation {
+        typesetter.flow.continue_file(continuation, font_ids, path);
+    }
+    typesetter.blocks(&blocks, Pt(0.0))?;
+    Ok(typesetter.flow.finish())
+}
+
+/// Typesets parsed Markdown blocks onto pages through a [`Flow`].
+///
+/// Also used by other renderers that interleave prose with code.
+pub(crate) struct Typesetter<'a> {
+    pub flow: Flow<'a>,
+    font_ids: &'a FontIds,
+    ss: &'a SyntaxSet,
+    theme: &'a Theme,
+    base_dir: &'a Path,
+    image_paths: &'a mut ImagePathMap,
+    body_size: Pt,
+    small_size: Pt,
+    heading_size: Pt,
+    subheading_size: Pt,
+    text_colour: Colour,
+    /// Left offsets of quote bars to draw beside every line
+    bars: Vec<Pt>,
+    /// List marker waiting to be placed on the next line
+    pending_marker: Option<(Pt, Span)>,
+    /// Layout of numbered code lines, for listings interleaved with prose
+    listing: ListingStyle,
+    /// Tabs and indentation of fenced code blocks, as for listings
+    code: &'a CodeConfig,
+}
+
+impl<'a> Typesetter<'a> {
+    pub fn new(
+        config: &'a PDF,
+        page_layout: &'a PageLayout,
+        doc: &'a mut Document,
+        font_ids: &'a FontIds,
+        ss: &'a SyntaxSet,
+        theme: &'a Theme,
+        base_dir: &'a Path,
+        image_paths: &'a mut ImagePathMap,
+    ) -> Self {
+        Self {
+            flow: Flow::new(page_layout, doc, font_ids),
+            font_ids,
+            ss,
+            theme,
+            base_dir,
+            image_paths,
+            body_size: Pt(page_layout.fonts.body_pt),
+            small_size: Pt(page_layout.fonts.small_pt),
+            heading_size: Pt(page_layout.fonts.heading_pt.max(page_layout.fonts.body_pt)),
+            subheading_size: Pt(page_layout
+                .fonts
+                .subheading_pt
+                .max(page_layout.fonts.body_pt)),
+            text_colour: colours::BLACK,
+            bars: Vec::new(),
+            pending_marker: None,
+            listing: ListingStyle::new(config, page_layout),
+            code: &config.code,
+        }
+    }
+
+    fn line_height(&self, size: Pt) -> Pt {
+        self.flow.doc.fonts[self.font_ids.regular].line_height(size)
+    }
+
+    fn ascent(&self, size: Pt) -> Pt {
+        self.flow.doc.fonts[self.font_ids.regular].ascent(size)
+    }
+
+    fn width(&self, text: &str, font: SpanFont) -> Pt {
+        layout::width_of_text(text, &self.flow.doc.fonts[font.id], font.size)
+    }
+
+    /// Emit a line of positioned spans, adding any pending list marker and quote bars.
+    fn emit_line(&mut self, mut spans: Vec<(Pt, Span)>, size: Pt) {
+        let line_height = self.line_height(size);
+        let ascent = self.ascent(size);
+        if let Some(marker) = self.pending_marker.take() {
+            spans.insert(0, marker);
+        }
+        let baseline = self.flow.add_line_at(spans, line_height, ascent);
+        let left = self.flow.content_box().x1;
+        let descent = self.flow.doc.fonts[self.font_ids.regular].descent(size);
+        for bar in self.bars.clone() {
+            self.flow.draw_line(
+                (left + bar, baseline + descent),
+                (left + bar, baseline + ascent),
+                Pt(1.0),
+                0.75,
+            );
+        }
+    }
+
+    /// Typeset a sequence of blocks at the given indent.
+    pub fn blocks(&mut self, blocks: &[Block], indent: Pt) -> Result<()> {
+        for (i, block) in blocks.iter().enumerate() {
+            if i > 0 {
+                self.flow.gap(self.line_height(self.body_size) * 0.5);
+            }
+            self.block(block, indent)?;
+        }
+        Ok(())
+    }
+
+    fn block(&mut self, block: &Block, indent: Pt) -> Result<()> {
+        match block {
+            Block::Heading(level, inlines) => self.heading(*level, inlines, indent),
+            Block::Paragraph(inlines) => {
+                let base = SpanFont {
+                    id: self.font_ids.regular,
+                    size: self.body_size,
+                };
+                self.paragraph(inlines, indent, base)
+            }
+            Block::Code { lang, code } => self.code(lang.as_deref(), code, indent),
+            Block::List { start, items } => self.list(*start, items, indent),
+            Block::Quote(blocks) => {
+                let bar_width = self.width("  ", self.body_font());
+                self.bars.push(indent + bar_width * 0.25);
+                let previous_colour = self.text_colour;
+                self.text_colour = Colour::new_grey(0.35);
+                let result = self.blocks(blocks, indent + bar_width);
+                self.text_colour = previous_colour;
+                self.bars.pop();
+                result
+            }
+            Block::Table { head, rows } => {
+                self.table(head, rows, indent);
+                Ok(())
+            }
+            Block::Rule => {
+                self.flow.add_rule(indent, Pt(0.5), 0.75);
+                Ok(())
+            }
+        }
+    }
+
+    fn body_font(&self) -> SpanFont {
+        SpanFont {
+            id: self.font_ids.regular,
+            size: self.body_size,
+        }
+    }
+
+    fn heading(&mut self, level: u8, inlines: &[Inline], indent: Pt) -> Result<()> {
+        let size = match level {
+            1 => self.heading_size,
+            2 => (self.heading_size + self.subheading_size) / 2.0,
+            3 => self.subheading_size,
+            _ => self.body_size,
+        };
+        // headings want clear space above them, except at the top of a page
+        self.flow.gap(self.line_height(self.body_size) * 0.5);
+        self.flow
+            .ensure_space(self.line_height(size) + self.line_height(self.body_size) * 2.0);
+        let base = SpanFont {
+            id: self.font_ids.bold,
+            size,
+        };
+        self.paragraph(inlines, indent, base)?;
+        if level <= 2 {
+            self.flow.add_rule(indent, Pt(0.5), 0.75);
+        }
+        Ok(())
+    }
+
+    /// Typeset inline content as a wrapped paragraph.
+    fn paragraph(&mut self, inlines: &[Inline], indent: Pt, base: SpanFont) -> Result<()> {
+        let measure = self.flow.measure() - indent;
+        let lines = self.wrap(inlines, base, measure);
+        for line in lines {
+            match line {
+                Line::Text(spans) => {
+                    let mut x = indent;
+                    let mut placed = Vec::with_capacity(spans.len());
+                    for span in spans {
+                        let width = self.width(&span.0, span.2);
+                        placed.push((x, span));
+                        x = x + width;
+                    }
+                    self.emit_line(placed, base.size);
+                }
+                Line::Image { url, alt } => self.image(&url, &alt, indent, base.size)?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Place an image on its own line, or its alt text if it can't be loaded.
+    fn image(&mut self, url: &str, alt: &str, indent: Pt, size: Pt) -> Result<()> {
+        let path = resolve_local_image(self.base_dir, url);
+        self.image_or_alt(path.as_deref(), url, alt, indent, size)
+    }
+
+    fn image_or_alt(
+        &mut self,
+        path: Option<&Path>,
+        url: &str,
+        alt: &str,
+        indent: Pt,
+        size: Pt,
+    ) -> Result<()> {
+        if let Some(path) = path {
+            let bbox = self.flow.content_box();
+            let max_width = bbox.x2 - bbox.x1 - indent;
+            let max_height = (bbox.y2 - bbox.y1) * 0.5;
+            if self
+                .flow
+                .add_image(path, indent, max_width, max_height, self.image_paths)
+                .is_ok()
+            {
+                return Ok(());
+            }
+        }
+
+        let label = if alt.is_empty() {
+            format!("[image: {}]", url)
+        } else {
+            format!("[image: {}]", alt)
+        };
+        let font = SpanFont {
+            id: self.font_ids.italic,
+            size,
+        };
+        self.emit_line(vec![(indent, (label, Colour::new_grey(0.5), font))], size);
+        Ok(())
+    }
+
+    /// Font and colour for a styled run of text.
+    fn styled(&self, style: InlineStyle, base: SpanFont) -> (Colour, SpanFont) {
+        let bold = style.bold || base.id == self.font_ids.bold;
+        let id = match (bold, style.italic) {
+            (true, true) => self.font_ids.bold_italic,
+            (true, false) => self.font_ids.bold,
+            (false, true) => self.font_ids.italic,
+            (false, false) => self.font_ids.regular,
+        };
+        let colour = if style.code {
+            Colour::new_rgb_bytes(143, 63, 113)
+        } else if style.link {
+            Colour::new_rgb_bytes(38, 139, 210)
+        } else if style.strikethrough {
+            Colour::new_grey(0.6)
+        } else {
+            self.text_colour
+        };
+        (
+            colour,
+            SpanFont {
+                id,
+                size: base.size,
+            },
+        )
+    }
+
+    /// Split inline content into words and separators.
+    fn tokenise(&self, inlines: &[Inline], base: SpanFont) -> Vec<Token> {
+        let mut tokens: Vec<Token> = Vec::new();
+        // whether the next non-space text continues the previous word
+        let mut in_word = false;
+
+        for inline in inlines {
+            match inline {
+                Inline::Text(text, style) => {
+                    let (colour, font) = self.styled(*style, base);
+                    let mut rest = text.as_str();
+                    while !rest.is_empty() {
+                        let is_space = rest.starts_with(char::is_whitespace);
+                        let end = rest
+                            .find(|c: char| c.is_whitespace() != is_space)
+                            .unwrap_or(rest.len());
+                        let (chunk, remainder) = rest.split_at(end);
+                        rest = remainder;
+
+                        if is_space {
+                            if !matches!(tokens.last(), Some(Token::Space) | None) {
+                                tokens.push(Token::Space);
+                            }
+                            in_word = false;
+                        } else if in_word {
+                            if let Some(Token::Word(parts)) = tokens.last_mut() {
+                                parts.push((chunk.to_string(), colour, font));
+                            }
+                        } else {
+                            tokens.push(Token::Word(vec![(chunk.to_string(), colour, font)]));
+                            in_word = true;
+                        }
+                    }
+                }
+                Inline::Image { url, alt } => {
+                    tokens.push(Token::Image {
+                        url: url.clone(),
+                        alt: alt.clone(),
+                    });
+                    in_word = false;
+                }
+                Inline::LineBreak => {
+                    tokens.push(Token::Break);
+                    in_word = false;
+                }
+            }
+        }
+        tokens
+    }
+
+    /// Greedily wrap inline content into lines no wider than `measure`.
+    fn wrap(&self, inlines: &[Inline], base: SpanFont, measure: Pt) -> Vec<Line> {
+        let space_width = self.width(" ", base);
+        let mut lines = Vec::new();
+        let mut current: Vec<Span> = Vec::new();
+        let mut current_width = Pt(0.0);
+        let mut pending_space = false;
+
+        for token in self.tokenise(inlines, base) {
+            match token {
+                Token::Space => pending_space = !current.is_empty(),
+                Token::Break => {
+                    lines.push(Line::Text(std::mem::take(&mut current)));
+                    current_width = Pt(0.0);
+                    pending_space = false;
+                }
+                Token::Image { url, alt } => {
+                    if !current.is_empty() {
+                        lines.push(Line::Text(std::mem::take(&mut current)));
+                    }
+                    lines.push(Line::Image { url, alt });
+                    current_width = Pt(0.0);
+                    pending_space = false;
+                }
+                Token::Word(parts) => {
+                    let word_width: Pt = parts.iter().map(|p| self.width(&p.0, p.2)).sum();
+                    let space = if pending_space { space_width } else { Pt(0.0) };
+
+                    if !current.is_empty() && current_width + space + word_width > measure {
+                        lines.push(Line::Text(std::mem::take(&mut current)));
+                        current_width = Pt(0.0);
+                    } else if pending_space {
+                        if let Some(last) = current.last_mut() {
+                            last.0.push(' ');
+                        }
+                        current_width = current_width + space_width;
+                    }
+                    pending_space = false;
+
+                    if word_width > measure {
+                        // a word longer than the measure (URLs, paths) breaks anywhere
+                        for (text, colour, font) in parts {
+                            let mut continuing = false;
+                            for c in text.chars() {
+                                let w = self.width(c.encode_utf8(&mut [0; 4]), font);
+                                if !current.is_empty() && current_width + w > measure {
+                                    lines.push(Line::Text(std::mem::take(&mut current)));
+                                    current_width = Pt(0.0);
+                                    continuing = false;
+                                }
+                                match current.last_mut() {
+                                    Some(last) if continuing => last.0.push(c),
+                                    _ => current.push((c.to_string(), colour, font)),
+                                }
+                                continuing = true;
+                                current_width = current_width + w;
+                            }
+                        }
+                    } else {
+                        current_width = current_width + word_width;
+                        current.extend(parts);
+                    }
+                }
+            }
+        }
+        if !current.is_empty() {
+            lines.push(Line::Text(current));
+        }
+        lines
+    }
+
+    /// Typeset a fenced code block with syntax highlighting.
+    pub fn code(&mut self, lang: Option<&str>, code: &str, indent: Pt) -> Result<()> {
+        let size = self.body_size;
+        let bar_width = self.width("  ", self.body_font());
+        let inner = indent + bar_width;
+        let measure = self.flow.measure() - inner;
+
+        let syntax = lang
+            .and_then(|lang| self.ss.find_syntax_by_token(lang))
+            .unwrap_or_else(|| self.ss.find_syntax_plain_text());
+        let mut h = HighlightLines::new(syntax, self.theme);
+
+        let code = whitespace::expand(code, self.code);
+        self.bars.push(indent + bar_width * 0.25);
+        for line in LinesWithEndings::from(code.as_str()) {
+            let ranges = h
+                .highlight_line(line, self.ss)
+                .with_context(|| format!("Failed to highlight code block line `{}`", line))?;
+            let spans: Vec<Span> = ranges
+                .into_iter()
+                .map(|(style, text)| {
+                    highlighted_span(
+                        style,
+                        text.trim_end_matches(['\n', '\r']),
+                        self.font_ids,
+                        size,
+                    )
+                })
+                .filter(|span| !span.0.is_empty())
+                .collect();
+
+            self.code_rows(spans, inner, measure);
+        }
+        self.bars.pop();
+        Ok(())
+    }
+
+    /// Typeset a small grey label on its own line, such as a notebook `In [n]:` marker.
+    pub fn label(&mut self, text: &str) {
+        let font = SpanFont {
+            id: self.font_ids.regular,
+            size: self.small_size,
+        };
+        self.emit_line(
+            vec![(Pt(0.0), (text.to_string(), Colour::new_grey(0.5), font))],
+            self.small_size,
+        );
+    }
+
+    /// Typeset unhighlighted text line for line, wrapping long lines, as for
+    /// program output.
+    pub fn preformatted(&mut self, text: &str, colour: Colour) {
+        let font = self.body_font();
+        let measure = self.flow.measure();
+        for line in text.lines() {
+            let spans = vec![(line.to_string(), colour, font)];
+            self.code_rows(spans, Pt(0.0), measure);
+        }
+    }
+
+    /// Place an image from disk on its own line, scaled to fit the measure and at
+    /// most half the page height.
+    pub fn image_file(&mut self, path: &Path, alt: &str) -> Result<()> {
+        let url = path.display().to_string();
+        self.image_or_alt(Some(path), &url, alt, Pt(0.0), self.body_size)
+    }
+
+    /// Typeset a numbered line of highlighted code, laid out like a source listing,
+    /// and report where the `marks` landed (see [`listing::listing_line`]).
+    pub fn listing_line(
+        &mut self,
+        number: usize,
+        spans: Vec<Span>,
+        marks: &[Range<usize>],
+        flagged: bool,
+    ) -> Vec<PlacedMark> {
+        listing::listing_line(
+            &mut self.flow,
+            self.font_ids,
+            &self.listing,
+            number,
+            spans,
+            marks,
+            flagged,
+        )
+    }
+
+    /// Place the marker for a run of lines left out of an excerpt (see
+    /// [`listing::omission`]).
+    pub fn omission(&mut self, lines: &Range<usize>) {
+        listing::omission(&mut self.flow, self.font_ids, &self.listing, lines);
+    }
+
+    /// Place the marker for a collapsed standard file header (see
+    /// [`crate::boilerplate`]).
+    pub fn header(&mut self, lines: &Range<usize>) {
+        let text = header_label(lines);
+        listing::gutter_note(&mut self.flow, self.font_ids, &self.listing, text);
+    }
+
+    /// Place a line of code starting `inner` from the margin, wrapping long lines
+    /// like a listing.
+    fn code_rows(&mut self, spans: Vec<Span>, inner: Pt, measure: Pt) {
+        let size = self.body_size;
+        let offset = self.width(&" ".repeat(self.listing.wrap_indent), self.body_font());
+        let rows = wrap_code(spans, measure, offset, |text, font| self.width(text, font));
+        for row in rows {
+            let placed = row
+                .spans
+                .into_iter()
+                .map(|(x, _, span)| (inner + x, span))
+                .collect();
+            self.emit_line(placed, size);
+        }
+    }
+
+    fn list(&mut self, start: Option<u64>, items: &[Vec<Block>], indent: Pt) -> Result<()> {
+        let font = self.body_font();
+        let content_indent = indent + self.width("    ", font);
+        for (i, item) in items.iter().enumerate() {
+            if i > 0 {
+                self.flow.gap(self.line_height(self.body_size) * 0.25);
+            }
+            let marker = match start {
+                Some(n) => format!("{}.", n + i as u64),
+                None => "•".to_string(),
+            };
+            let marker_x = content_indent - self.width(&format!("{} ", marker), font);
+            self.pending_marker = Some((marker_x, (marker, self.text_colour, font)));
+            self.blocks(item, content_indent)?;
+            // an empty item still shows its marker
+            if let Some(marker) = self.pending_marker.take() {
+                self.emit_line(vec![marker], self.body_size);
+            }
+        }
+        Ok(())
+    }
+
+    /// Typeset a table with columns sized to their content.
+    ///
+    /// Columns keep their natural width when the table fits the measure; otherwise
+    /// the width is shared out in proportion to each column's content and cells wrap.
+    fn table(&mut self, head: &[Vec<Inline>], rows: &[Vec<Vec<Inline>>], indent: Pt) {
+        let font = self.body_font();
+        let bold = SpanFont {
+            id: self.font_ids.bold,
+            size: self.body_size,
+        };
+        let columns = std::iter::once(head.len())
+            .chain(rows.iter().map(Vec::len))
+            .max()
+            .unwrap_or(0);
+        if columns == 0 {
+            return;
+        }
+
+        let column_gap = self.width("  ", font);
+        let measure = self.flow.measure() - indent - column_gap * (columns - 1) as f32;
+        let mut natural = vec![Pt(0.0); columns];
+        for (row, row_font) in
+            std::iter::once((head, bold)).chain(rows.iter().map(|r| (r.as_slice(), font)))
+        {
+            for (i, cell) in row.iter().enumerate() {
+                let width = self.width(&plain_text(cell), row_font);
+                if width > natural[i] {
+                    natural[i] = width;
+                }
+            }
+        }
+        let total: Pt = natural.iter().copied().sum();
+        let min_width = self.width("MMMM", font);
+        let widths: Vec<Pt> = if total <= measure {
+            natural
+        } else {
+            natural
+                .iter()
+                .map(|w| {
+                    let share = measure * (w.0 / total.0.max(1.0));
+                    if share < min_width {
+                        min_width
+                    } else {
+                        share
+                    }
+                })
+                .collect()
+        };
+
+        let mut offsets = Vec::with_capacity(columns);
+        let mut x = indent;
+        for width in &widths {
+            offsets.push(x);
+            x = x + *width + column_gap;
+        }
+        let table_width = x - column_gap - indent;
+
+        if !head.is_empty() {
+            self.table_row(head, bold, &widths, &offsets);
+            let left = self.flow.content_box().x1;
+            let y = self.flow.cursor();
+            self.flow.draw_line(
+                (left + indent, y),
+                (left + indent + table_width, y),
+                Pt(0.5),
+                0.5,
+            );
+            self.flow.gap(Pt(2.0));
+        }
+        for row in rows {
+            self.table_row(row, font, &widths, &offsets);
+        }
+    }
+
+    fn table_row(&mut self, cells: &[Vec<Inline>], font: SpanFont, widths: &[Pt], offsets: &[Pt]) {
+        let wrapped: Vec<Vec<Line>> = cells
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| self.wrap(cell, font, *width))
+            .collect();
+        let height = wrapped.iter().map(Vec::len).max().unwrap_or(0);
+        for i in 0..height {
+            let mut placed = Vec::new();
+            for (column, lines) in wrapped.iter().enumerate() {
+                let spans = match lines.get(i) {
+                    Some(Line::Text(spans)) => spans.clone(),
+                    Some(Line::Image { alt, .. }) => {
+                        vec![(format!("[{}]", alt), Colour::new_grey(0.5), font)]
+                    }
+                    None => continue,
+                };
+                let mut x = offsets[column];
+                for span in spans {
+                    let width = self.width(&span.0, span.2);
+                    placed.push((x, span));
+                    x = x + width;
+                }
+            }
+            self.emit_line(placed, font.size);
+        }
+    }
+}
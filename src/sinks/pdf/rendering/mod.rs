@@ -15,37 +15,138 @@
 //!
 //! Frontmatter files (README, LICENSE, etc.) are rendered first with their own
 //! bookmark section, providing readers with project context before diving into code.
+//! Markdown frontmatter is typeset as prose by the [`markdown`] module unless
+//! `[pdf.markdown]` is disabled, in which case it is listed like any other file.
+//! Source files go through the [`literate`] layout instead of the plain listing
+//! when `[pdf.literate]` is enabled. Jupyter notebooks in either section are
+//! rendered cell by cell by the [`notebook`] module.
 //!
 //! The render function accepts a progress bar from the caller, updating it with the
 //! current file name and incrementing after each file is processed. This provides
 //! visual feedback during long renders of large repositories.
 //!
+//! ## Layout
+//!
+//! Each section is rendered with the [`PageLayout`] from [`PDF::layout_for`], so
+//! `[pdf.layout.<section>]` can give it its own margins, font sizes, columns and
+//! file placement; the table of contents has its own `[pdf.layout.contents]`. Every
+//! laid-out page is made by [`PageLayout::new_page`], with the inner margin on the
+//! binding side. With `fit_lines` set, the source section's columns are
+//! dropped when the 95th-percentile line (see [`crate::line_analysis`]) wouldn't
+//! fit a column unwrapped.
+//!
+//! Frontmatter files, tours and source files start on a new page by default.
+//! Placed `RectoStart`, a blank verso is added where needed, numbered with the
+//! section (unless it comes before the section's first page) but without a header
+//! or footer. Placed `Continuous`, each file follows the one before on the same
+//! page (see [`flow`]); the table of contents and bookmarks point at the page the
+//! file's name landed on, and the running header names the file at the top of the
+//! page. Images and binary files still start a page of their own.
+//!
 //! ## Cross-Document Resources
 //!
 //! Image file paths are tracked in an [`ImagePathMap`] during rendering so that
-//! booklet generation can reload images into its separate document. See the
-//! [`crate::sinks::pdf::booklet`] module for details on why this is necessary.
+//! booklet, 2-up and volume generation can reload images into their separate
+//! documents. See the [`crate::sinks::pdf::booklet`] module for details on why this
+//! is necessary.
+//!
+//! ## Symbol Index
+//!
+//! With `[pdf.index]` enabled, the definitions in each rendered source file are
+//! collected as it's rendered, using the line-to-page mapping the renderer reports.
+//! The [`index`] appendix is rendered last, once the table of contents has been
+//! inserted and the final page of every definition is known.
+//!
+//! ## Cross-References
+//!
+//! With `[pdf.cross_references]` enabled, every source file's definitions are read
+//! before rendering starts, listings record where they placed references to them, and
+//! the [`cross_references`] module adds the links (and optional page references) once
+//! the table of contents is in place.
+//!
+//! ## TODO Register
+//!
+//! With `[pdf.todos]` enabled, listings report the marker comments (`TODO`, `FIXME`,
+//! ...) they contain, and the [`todos`] register is rendered after the tags
+//! appendix. Its page numbers and links are added after the table of contents is
+//! inserted.
+//!
+//! ## Guided Tours
+//!
+//! Tours listed in `source.tour_files` are rendered by the [`tour`] module between
+//! the frontmatter and the source files, each step's prose followed by the code it
+//! points at. The rows linking steps to their files' full listings get their page
+//! numbers once the table of contents is inserted.
+//!
+//! ## Standard File Header
+//!
+//! With `[pdf.boilerplate]` enabled, the comment block that most source files open
+//! with is found before rendering starts. It's printed once by the [`boilerplate`]
+//! module at the end of the frontmatter, and each listing collapses it to a marker.
+//!
+//! ## Outlines
+//!
+//! With `[pdf.outline]` enabled, listings open with an [`outline`] box of the file's
+//! top-level items, and the items are bookmarked under the file's entry. The boxes
+//! get their page numbers and links at the same point as cross-references.
+//!
+//! ## Volumes
+//!
+//! With `[pdf.volumes]` set, the breaks between volumes are planned once everything
+//! but the index is rendered, just ahead of the table of contents, which marks each
+//! entry with its volume. If the pages are numbered per volume, their metadata is
+//! renumbered then, before anything cites them. The volumes themselves are written
+//! from the finished document by [`crate::sinks::pdf::volumes`], in place of the
+//! whole book's booklet and cover.
 //!
 //! Page metadata ([`PageMetadata`]) is collected for each content page during rendering,
 //! tracking which source file each page belongs to. After all content is rendered,
 //! headers and footers are applied via [`header_footer::render_headers_and_footers()`],
 //! which uses this metadata to populate template placeholders like `{file}`.
 
+mod boilerplate;
 mod colophon;
 mod commits;
+mod cross_references;
+mod flow;
 mod header_footer;
 mod hex_dump;
 mod images;
+mod index;
+mod keep_together;
+mod listing;
+mod literate;
+mod markdown;
+mod notebook;
+mod outline;
 mod source_file;
 mod table_of_contents;
 mod tags;
 mod title_page;
+mod todos;
+mod tour;
 
 pub use header_footer::PageMetadata;
+pub use title_page::{load_image, render as render_title_page, render_template, TemplateBlock};
 
-use crate::sinks::pdf::booklet::render_booklet;
-use crate::sinks::pdf::config::{RenderStats, Section, PDF};
+use crate::boilerplate::StandardHeader;
+use crate::sinks::pdf::booklet::{render_booklet, PageRun};
+use crate::sinks::pdf::config::{
+    ColumnsConfig, FilePlacement, PageLayout, RenderStats, Section, VolumeNumbering, PDF,
+};
+use crate::sinks::pdf::cover::render_cover;
 use crate::sinks::pdf::fonts::{FontIds, LoadedFonts};
+use crate::sinks::pdf::rendering::cross_references::CrossReferences;
+use crate::sinks::pdf::rendering::flow::Continuation;
+use crate::sinks::pdf::rendering::literate::LiterateContext;
+use crate::sinks::pdf::rendering::markdown::ProseContext;
+use crate::sinks::pdf::rendering::notebook::NotebookContext;
+use crate::sinks::pdf::rendering::outline::Outlines;
+use crate::sinks::pdf::rendering::source_file::ListingContext;
+use crate::sinks::pdf::rendering::todos::Todos;
+use crate::sinks::pdf::rendering::tour::{TourContext, TourLinks};
+use crate::sinks::pdf::two_up::render_two_up;
+use crate::sinks::pdf::volumes::{number_per_volume, plan_breaks, render_volumes};
 use crate::source::Source;
 use anyhow::{Context, Result};
 use indicatif::ProgressBar;
@@ -86,8 +187,43 @@ impl PDF {
             bold_italic: doc.add_font(fonts.bold_italic),
         };
 
+        // each section is laid out with its own margins and font sizes
+        let frontmatter_layout = self.layout_for(Section::Frontmatter);
+        let tour_layout = self.layout_for(Section::Tour);
+        let mut source_layout = self.layout_for(Section::Source);
+        let commits_layout = self.layout_for(Section::CommitHistory);
+        let tags_layout = self.layout_for(Section::Tags);
+        let todos_layout = self.layout_for(Section::Todos);
+        let index_layout = self.layout_for(Section::Index);
+        let contents_layout = self.layout_for_contents();
+
+        // source goes into columns only if most lines fit one without wrapping
+        if source_layout.columns.count > 1 && source_layout.columns.fit_lines {
+            let content_box = source_layout.new_page(0).content_box;
+            let column = flow::column_boxes(content_box, &source_layout.columns)[0];
+            let max_chars = crate::character_width::calculate_max_chars_per_line(
+                (column.x2 - column.x1).0 / 72.0,
+                0.0,
+                0.0,
+                &doc.fonts[font_ids.regular],
+                source_layout.fonts.body_pt,
+            );
+            let stats = crate::line_analysis::line_stats(
+                &source.source_paths(),
+                Path::new(""),
+                max_chars,
+                &self.code,
+            )
+            .with_context(|| "Failed to measure source line lengths")?;
+            if stats.percentile_95 > max_chars {
+                source_layout.columns = ColumnsConfig::default();
+            }
+        }
+
         // track image paths for booklet rendering
         let mut image_paths: ImagePathMap = HashMap::new();
+        // notebook image outputs are written here, and kept until the booklet is done
+        let output_images = crate::notebook::OutputImages::new()?;
 
         let mut info = Info::default();
         if let Some(title) = &source.title {
@@ -135,19 +271,38 @@ impl PDF {
             .italicized();
 
         let mut frontmatter_pages: HashMap<PathBuf, usize> = HashMap::new();
+        let mut tour_pages: Vec<(String, usize)> = Vec::new();
         let mut source_pages: HashMap<PathBuf, usize> = HashMap::new();
         let mut page_offset = doc.page_order.len();
         // track metadata for each content page (for header/footer rendering)
         let mut page_metadata: Vec<PageMetadata> = Vec::new();
         // track page counts within each section for section-specific numbering
         let mut frontmatter_page_count: usize = 0;
+        let mut tour_page_count: usize = 0;
         let mut source_page_count: usize = 0;
         let mut commit_history_page_count: usize = 0;
+        // symbol definitions found in source files, for the index
+        let mut index_entries: Vec<index::IndexEntry> = Vec::new();
+
+        // the standard header is found up front; it's printed with the frontmatter
+        let standard_header = if self.boilerplate.enabled {
+            StandardHeader::detect(
+                Path::new(""),
+                &source.source_paths(),
+                &ss,
+                self.boilerplate.min_files,
+            )
+            .with_context(|| "Failed to find the standard file header")?
+        } else {
+            None
+        };
 
         // render frontmatter files first if present
-        if !source.frontmatter_files.is_empty() {
+        if !source.frontmatter_files.is_empty() || standard_header.is_some() {
             let frontmatter_bookmark = doc.add_bookmark(None, "Frontmatter", doc.page_order.len());
             frontmatter_bookmark.borrow_mut().bolded();
+            let placement = frontmatter_layout.placement;
+            let mut continuation = Continuation::default();
 
             for file in source.frontmatter_files.iter() {
                 let file_name = file
@@ -156,7 +311,18 @@ impl PDF {
                     .unwrap_or_else(|| file.display().to_string());
                 progress.set_message(file_name.clone());
 
+                if placement == FilePlacement::RectoStart
+                    && start_recto(&mut doc, &frontmatter_layout)
+                {
+                    count_recto_blank(
+                        &mut page_metadata,
+                        Section::Frontmatter,
+                        &mut frontmatter_page_count,
+                    );
+                }
                 frontmatter_pages.insert(file.clone(), doc.page_order.len() - page_offset);
+                let follow_on =
+                    (placement == FilePlacement::Continuous).then_some(&mut continuation);
 
                 match file
                     .extension()
@@ -167,8 +333,14 @@ impl PDF {
                 {
                     "png" | "svg" | "bmp" | "ico" | "jpg" | "jpeg" | "webp" | "avif" | "tga"
                     | "tiff" => {
-                        let page_index =
-                            images::render(self, &mut doc, &font_ids, file, &mut image_paths)?;
+                        continuation.close(&mut doc, &frontmatter_layout.columns);
+                        let page_index = images::render(
+                            &frontmatter_layout,
+                            &mut doc,
+                            &font_ids,
+                            file,
+                            &mut image_paths,
+                        )?;
                         // images are single pages
                         page_metadata.push(
                             PageMetadata::new(Section::Frontmatter, frontmatter_page_count)
@@ -182,29 +354,72 @@ impl PDF {
                         doc.add_bookmark(Some(frontmatter_bookmark.clone()), file_name, page_index);
                     }
                     _ => {
-                        let result = source_file::render(
-                            self,
-                            &mut doc,
-                            &font_ids,
-                            file,
-                            &ss,
-                            &ts.themes[self.theme.name()],
-                        )
+                        let theme = &ts.themes[self.theme.name()];
+                        let result = if self.markdown.enabled && crate::markdown::is_markdown(file)
+                        {
+                            markdown::render(
+                                self,
+                                &frontmatter_layout,
+                                &mut doc,
+                                &font_ids,
+                                file,
+                                &ss,
+                                theme,
+                                ProseContext {
+                                    image_paths: &mut image_paths,
+                                    continuation: follow_on,
+                                },
+                            )
+                        } else if crate::notebook::is_notebook(file) {
+                            notebook::render(
+                                self,
+                                &frontmatter_layout,
+                                &mut doc,
+                                &font_ids,
+                                file,
+                                &ss,
+                                theme,
+                                NotebookContext {
+                                    image_paths: &mut image_paths,
+                                    output_images: &output_images,
+                                    continuation: follow_on,
+                                },
+                            )
+                        } else {
+                            source_file::render(
+                                self,
+                                &frontmatter_layout,
+                                &mut doc,
+                                &font_ids,
+                                file,
+                                &ss,
+                                theme,
+                                ListingContext {
+                                    continuation: follow_on,
+                                    ..ListingContext::default()
+                                },
+                            )
+                        }
                         .with_context(|| {
                             format!("Failed to render frontmatter file {}!", file.display())
                         })?;
 
                         // track metadata for each page rendered
                         let file_display = file.display().to_string();
-                        for _ in 0..result.page_count {
+                        for i in 0..result.page_count {
                             page_metadata.push(
                                 PageMetadata::new(Section::Frontmatter, frontmatter_page_count)
-                                    .with_file(file_display.clone()),
+                                    .with_file(file_display.clone())
+                                    .with_listing(
+                                        result.page_lines.get(i).cloned().unwrap_or_default(),
+                                    ),
                             );
                             frontmatter_page_count += 1;
                         }
 
                         if let Some(page_index) = result.first_page {
+                            // in continuous flow the file may start on the next page
+                            frontmatter_pages.insert(file.clone(), page_index - page_offset);
                             let file_name = file
                                 .file_name()
                                 .map(|n| n.to_string_lossy().to_string())
@@ -220,6 +435,94 @@ impl PDF {
 
                 progress.inc(1);
             }
+            continuation.close(&mut doc, &frontmatter_layout.columns);
+
+            if let Some(header) = &standard_header {
+                let theme = &ts.themes[self.theme.name()];
+                frontmatter_pages.insert(
+                    PathBuf::from(crate::boilerplate::TITLE),
+                    doc.page_order.len() - page_offset,
+                );
+                let result = boilerplate::render(
+                    self,
+                    &frontmatter_layout,
+                    &mut doc,
+                    &font_ids,
+                    header,
+                    &ss,
+                    theme,
+                    &mut image_paths,
+                )
+                .with_context(|| "Failed to render the standard file header")?;
+                for _ in 0..result.page_count {
+                    page_metadata.push(PageMetadata::new(
+                        Section::Frontmatter,
+                        frontmatter_page_count,
+                    ));
+                    frontmatter_page_count += 1;
+                }
+                if let Some(page_index) = result.first_page {
+                    doc.add_bookmark(
+                        Some(frontmatter_bookmark.clone()),
+                        crate::boilerplate::TITLE,
+                        page_index,
+                    );
+                }
+            }
+        }
+
+        // render guided tours after the frontmatter; their links to the listings are
+        // added once the listings have been placed
+        let mut tour_links = TourLinks::new(source.source_paths());
+        if !source.tour_files.is_empty() {
+            let tour_bookmark = doc.add_bookmark(None, "Guided Tour", doc.page_order.len());
+            tour_bookmark.borrow_mut().bolded();
+            let placement = tour_layout.placement;
+            let mut continuation = Continuation::default();
+
+            for file in source.tour_files.iter() {
+                progress.set_message(file.display().to_string());
+
+                if placement == FilePlacement::RectoStart && start_recto(&mut doc, &tour_layout) {
+                    count_recto_blank(&mut page_metadata, Section::Tour, &mut tour_page_count);
+                }
+                let follow_on =
+                    (placement == FilePlacement::Continuous).then_some(&mut continuation);
+
+                let theme = &ts.themes[self.theme.name()];
+                let result = tour::render(
+                    self,
+                    &tour_layout,
+                    &mut doc,
+                    &font_ids,
+                    file,
+                    &ss,
+                    theme,
+                    TourContext {
+                        image_paths: &mut image_paths,
+                        links: &mut tour_links,
+                        continuation: follow_on,
+                    },
+                )
+                .with_context(|| format!("Failed to render tour {}!", file.display()))?;
+
+                let file_display = file.display().to_string();
+                for _ in 0..result.page_count {
+                    page_metadata.push(
+                        PageMetadata::new(Section::Tour, tour_page_count)
+                            .with_file(file_display.clone()),
+                    );
+                    tour_page_count += 1;
+                }
+
+                if let Some(page_index) = result.first_page {
+                    tour_pages.push((result.title.clone(), page_index - page_offset));
+                    doc.add_bookmark(Some(tour_bookmark.clone()), result.title, page_index);
+                }
+
+                progress.inc(1);
+            }
+            continuation.close(&mut doc, &tour_layout.columns);
         }
 
         let source_code_bookmark = doc.add_bookmark(None, "Source Files", doc.page_order.len());
@@ -227,17 +530,37 @@ impl PDF {
             source_code_bookmark.borrow_mut().bolded();
         }
 
+        // definitions are gathered up front so references to later files can be linked
+        let mut cross_references = if self.cross_references.enabled {
+            Some(
+                CrossReferences::build(self, &source.source_paths(), &ss)
+                    .with_context(|| "Failed to find definitions for cross-references")?,
+            )
+        } else {
+            None
+        };
+
+        let mut outlines = self.outline.enabled.then(|| Outlines::new(self));
+        let mut todos = self.todos.enabled.then(|| Todos::new(self));
+
         // track folder bookmarks for hierarchical structure
         let mut folder_bookmarks: HashMap<PathBuf, Rc<RefCell<OutlineEntry>>> = HashMap::new();
+        let placement = source_layout.placement;
+        let mut continuation = Continuation::default();
 
-        for file in source.source_files.iter() {
+        for entry in source.source_files.iter() {
+            let file = &entry.path;
             let file_name = file
                 .file_name()
                 .map(|n| n.to_string_lossy().to_string())
                 .unwrap_or_else(|| file.display().to_string());
             progress.set_message(file_name);
 
+            if placement == FilePlacement::RectoStart && start_recto(&mut doc, &source_layout) {
+                count_recto_blank(&mut page_metadata, Section::Source, &mut source_page_count);
+            }
             source_pages.insert(file.clone(), doc.page_order.len() - page_offset);
+            let follow_on = (placement == FilePlacement::Continuous).then_some(&mut continuation);
 
             // render an image or source file depending on its extension
             match file
@@ -249,14 +572,21 @@ impl PDF {
             {
                 "png" | "svg" | "bmp" | "ico" | "jpg" | "jpeg" | "webp" | "avif" | "tga"
                 | "tiff" => {
-                    let page_index =
-                        images::render(self, &mut doc, &font_ids, file, &mut image_paths)?;
+                    continuation.close(&mut doc, &source_layout.columns);
+                    let page_index = images::render(
+                        &source_layout,
+                        &mut doc,
+                        &font_ids,
+                        file,
+                        &mut image_paths,
+                    )?;
                     // images are single pages
                     page_metadata.push(
                         PageMetadata::new(Section::Source, source_page_count)
                             .with_file(file.display().to_string()),
                     );
                     source_page_count += 1;
+                    tour_links.add_file(file, page_index, &[]);
                     let parent_bookmark = get_or_create_folder_bookmark(
                         &mut doc,
                         &mut folder_bookmarks,
@@ -271,22 +601,85 @@ impl PDF {
                     doc.add_bookmark(Some(parent_bookmark), file_name, page_index);
                 }
                 _ => {
-                    let result = source_file::render(
-                        self,
-                        &mut doc,
-                        &font_ids,
-                        file,
-                        &ss,
-                        &ts.themes[self.theme.name()],
-                    )
+                    let theme = &ts.themes[self.theme.name()];
+                    let result = if crate::notebook::is_notebook(file) {
+                        notebook::render(
+                            self,
+                            &source_layout,
+                            &mut doc,
+                            &font_ids,
+                            file,
+                            &ss,
+                            theme,
+                            NotebookContext {
+                                image_paths: &mut image_paths,
+                                output_images: &output_images,
+                                continuation: follow_on,
+                            },
+                        )
+                    } else if self.literate.enabled {
+                        literate::render(
+                            self,
+                            &source_layout,
+                            &mut doc,
+                            &font_ids,
+                            file,
+                            &ss,
+                            theme,
+                            LiterateContext {
+                                image_paths: &mut image_paths,
+                                listing: ListingContext {
+                                    cross_references: cross_references.as_mut(),
+                                    outlines: outlines.as_mut(),
+                                    todos: todos.as_mut(),
+                                    excerpt: entry.excerpt.as_ref(),
+                                    header: standard_header.as_ref().and_then(|h| h.lines(file)),
+                                    continuation: follow_on,
+                                },
+                            },
+                        )
+                    } else {
+                        source_file::render(
+                            self,
+                            &source_layout,
+                            &mut doc,
+                            &font_ids,
+                            file,
+                            &ss,
+                            theme,
+                            ListingContext {
+                                cross_references: cross_references.as_mut(),
+                                outlines: outlines.as_mut(),
+                                todos: todos.as_mut(),
+                                excerpt: entry.excerpt.as_ref(),
+                                header: standard_header.as_ref().and_then(|h| h.lines(file)),
+                                continuation: follow_on,
+                            },
+                        )
+                    }
                     .with_context(|| format!("Failed to render source file {}!", file.display()))?;
 
+                    if let Some(cross_references) = cross_references.as_mut() {
+                        cross_references.record_pages(file, &result.line_pages);
+                    }
+                    if let Some(page_index) = result.first_page {
+                        // in continuous flow the file may start on the next page
+                        source_pages.insert(file.clone(), page_index - page_offset);
+                        tour_links.add_file(file, page_index, &result.line_pages);
+                    }
+                    if self.index.enabled {
+                        index_entries.extend(index::collect(file, &result));
+                    }
+
                     // track metadata for each page rendered
                     let file_display = file.display().to_string();
-                    for _ in 0..result.page_count {
+                    for i in 0..result.page_count {
                         page_metadata.push(
                             PageMetadata::new(Section::Source, source_page_count)
-                                .with_file(file_display.clone()),
+                                .with_file(file_display.clone())
+                                .with_listing(
+                                    result.page_lines.get(i).cloned().unwrap_or_default(),
+                                ),
                         );
                         source_page_count += 1;
                     }
@@ -303,13 +696,26 @@ impl PDF {
                             .file_name()
                             .map(|n| n.to_string_lossy().to_string())
                             .unwrap_or_else(|| file.display().to_string());
-                        doc.add_bookmark(Some(parent_bookmark), file_name, page_index);
+                        let file_bookmark =
+                            doc.add_bookmark(Some(parent_bookmark), file_name, page_index);
+
+                        // top-level items go under the file
+                        if let Some(outlines) = &outlines {
+                            for (item, item_page) in outlines.items(file) {
+                                doc.add_bookmark(
+                                    Some(file_bookmark.clone()),
+                                    format!("{} {}", item.label, item.name),
+                                    *item_page,
+                                );
+                            }
+                        }
                     }
                 }
             }
 
             progress.inc(1);
         }
+        continuation.close(&mut doc, &source_layout.columns);
 
         progress.finish_with_message("Files rendered");
 
@@ -331,7 +737,7 @@ impl PDF {
             .commits()
             .with_context(|| "Failed to get commits for repository")?;
         let commit_result = commits::render(
-            self,
+            &commits_layout,
             &mut doc,
             &font_ids,
             commit_list,
@@ -368,7 +774,7 @@ impl PDF {
             let tag_list = source
                 .tags(self.tags_appendix.order)
                 .with_context(|| "Failed to get tags for repository")?;
-            let result = tags::render(self, &mut doc, &font_ids, tag_list)
+            let result = tags::render(&tags_layout, &mut doc, &font_ids, tag_list)
                 .with_context(|| "Failed to render tags appendix")?;
             if let Some(tags_page) = result.first_page {
                 doc.add_bookmark(None, "Tags", tags_page);
@@ -396,16 +802,123 @@ impl PDF {
             page_metadata.push(PageMetadata::new(Section::Tags, i));
         }
 
+        // render the TODO register if enabled; its page numbers and links come once the TOC is in
+        let pages_before_todos = doc.page_order.len();
+        let todos_result = match todos {
+            Some(todos) if !todos.is_empty() => {
+                let result = todos::render(
+                    self,
+                    &todos_layout,
+                    &mut doc,
+                    &font_ids,
+                    todos,
+                    page_offset,
+                    &page_metadata,
+                )
+                .with_context(|| "Failed to render TODO register")?;
+                if let Some(todos_page) = result.first_page {
+                    doc.add_bookmark(None, "TODO Register", todos_page);
+                }
+                Some(result)
+            }
+            _ => None,
+        };
+
+        // track TODO register pages, marking blank recto-alignment page separately
+        let todos_total_pages = doc.page_order.len() - pages_before_todos;
+        let todos_blank_inserted = todos_result.as_ref().is_some_and(|r| r.blank_inserted);
+        if todos_blank_inserted {
+            page_metadata.push(PageMetadata::new(Section::Todos, 0).skip_numbering());
+        }
+        let todos_content_pages = if todos_blank_inserted {
+            todos_total_pages.saturating_sub(1)
+        } else {
+            todos_total_pages
+        };
+        for i in 0..todos_content_pages {
+            page_metadata.push(PageMetadata::new(Section::Todos, i));
+        }
+
+        // the index is rendered after the TOC, but the TOC needs to know where it'll start
+        let index_page = if index_entries.is_empty() {
+            None
+        } else {
+            Some(index::first_page_after(doc.page_order.len()))
+        };
+
+        // volumes are planned before the TOC, which says which volume each entry is in
+        let volume_breaks = if self.volumes.enabled() {
+            let appendix_pages = [
+                commit_result.first_page,
+                tags_result.first_page,
+                todos_result.as_ref().and_then(|r| r.first_page),
+            ];
+            let appendices = appendix_pages.iter().flatten().count() + index_page.iter().count();
+            let toc_pages = table_of_contents::page_count(
+                &contents_layout,
+                &doc,
+                &font_ids,
+                page_offset,
+                table_of_contents::entry_count(
+                    &frontmatter_pages,
+                    &tour_pages,
+                    &source_pages,
+                    appendices,
+                ),
+            );
+
+            // a volume can start with any source file or the appendices
+            let source_start = |path: &PathBuf| source_pages.get(path).map(|p| p + page_offset);
+            let mut candidates: Vec<usize> = source
+                .source_files
+                .iter()
+                .filter_map(|entry| source_start(&entry.path))
+                .chain(appendix_pages.iter().flatten().take(1).copied())
+                .map(|page| page + toc_pages)
+                .collect();
+            candidates.sort_unstable();
+            candidates.dedup();
+            let source_paths = source.source_paths();
+            let forced: Vec<usize> = self
+                .volumes
+                .split_files(&source_paths)
+                .into_iter()
+                .filter_map(|i| source_start(&source_paths[i]))
+                .map(|page| page + toc_pages)
+                .collect();
+
+            let breaks = plan_breaks(
+                &candidates,
+                &forced,
+                doc.page_order.len() + toc_pages,
+                self.volumes.max_pages_per_volume,
+            );
+            // renumbered before anything cites the pages, from the TOC on
+            if self.volumes.numbering == VolumeNumbering::PerVolume {
+                number_per_volume(&mut page_metadata, page_offset + toc_pages, &breaks);
+            }
+            breaks
+        } else {
+            Vec::new()
+        };
+
+        let content_offset = page_offset;
         let num_toc_pages = table_of_contents::render(
             self,
+            &contents_layout,
             &mut doc,
             &font_ids,
             page_offset,
             frontmatter_pages,
+            tour_pages,
             source_pages,
             commit_result.first_page,
             tags_result.first_page,
+            todos_result.as_ref().and_then(|r| r.first_page),
+            index_page,
             commit_content_pages,
+            &volume_breaks,
+            &page_metadata,
         )
         .with_context(|| "Failed to render table of contents")?;
         page_offset += num_toc_pages;
@@ -418,6 +931,84 @@ impl PDF {
             }
         }
 
+        if let Some(todos_result) = todos_result {
+            todos_result.resolve(
+                self,
+                &todos_layout,
+                &mut doc,
+                &font_ids,
+                content_offset,
+                &page_metadata,
+                num_toc_pages,
+            );
+        }
+
+        // link references to their definitions now that every page has its final position
+        if let Some(cross_references) = cross_references {
+            cross_references.resolve(
+                self,
+                &source_layout,
+                &mut doc,
+                &font_ids,
+                content_offset,
+                &page_metadata,
+                num_toc_pages,
+            );
+        }
+
+        if let Some(outlines) = outlines {
+            outlines.resolve(
+                self,
+                &source_layout,
+                &mut doc,
+                &font_ids,
+                content_offset,
+                &page_metadata,
+                num_toc_pages,
+            );
+        }
+
+        tour_links.resolve(
+            self,
+            &tour_layout,
+            &mut doc,
+            &font_ids,
+            content_offset,
+            &page_metadata,
+            num_toc_pages,
+        );
+
+        // render the symbol index now that every page has its final position
+        let pages_before_index = doc.page_order.len();
+        let index_result = index::render(
+            self,
+            &index_layout,
+            &mut doc,
+            &font_ids,
+            index_entries,
+            content_offset,
+            &page_metadata,
+            num_toc_pages,
+        )
+        .with_context(|| "Failed to render symbol index")?;
+        if let Some(index_page) = index_result.first_page {
+            doc.add_bookmark(None, "Index", index_page);
+        }
+
+        // track index pages, marking blank recto-alignment page separately
+        let index_total_pages = doc.page_order.len() - pages_before_index;
+        if index_result.blank_inserted {
+            page_metadata.push(PageMetadata::new(Section::Index, 0).skip_numbering());
+        }
+        let index_content_pages = if index_result.blank_inserted {
+            index_total_pages.saturating_sub(1)
+        } else {
+            index_total_pages
+        };
+        for i in 0..index_content_pages {
+            page_metadata.push(PageMetadata::new(Section::Index, i));
+        }
+
         // render headers and footers on all content pages
         let title = source.title.as_deref();
         header_footer::render_headers_and_footers(
@@ -431,15 +1022,45 @@ impl PDF {
 
         let page_count = doc.page_order.len();
 
+        // a book split into volumes is bound volume by volume, so the booklet and
+        // cover are made for each of them instead of the whole
+        let volumes = if volume_breaks.is_empty() {
+            Vec::new()
+        } else {
+            render_volumes(self, source, &doc, &font_ids, &image_paths, &volume_breaks)
+                .with_context(|| "Failed to render volumes")?
+        };
+
         // generate booklet PDF if configured
-        let booklet_sheets = if let Some(booklet_path) = self.booklet_outfile_path() {
-            let sheets = render_booklet(self, source, &doc, &font_ids, &image_paths, &booklet_path)
-                .with_context(|| "Failed to render booklet PDF")?;
+        let booklet_sheets = match self.booklet_outfile_path() {
+            Some(booklet_path) if volumes.is_empty() => {
+                let runs = [PageRun::all(&doc, &font_ids, &image_paths)];
+                let sheets = render_booklet(self, source, &runs, &booklet_path)
+                    .with_context(|| "Failed to render booklet PDF")?;
+                Some(sheets)
+            }
+            _ => None,
+        };
+
+        // and the 2-up reading copy
+        let two_up_sheets = if let Some(two_up_path) = self.two_up_outfile_path() {
+            let sheets = render_two_up(self, source, &doc, &font_ids, &image_paths, &two_up_path)
+                .with_context(|| "Failed to render 2-up PDF")?;
             Some(sheets)
         } else {
             None
         };
 
+        // and the print-on-demand cover, now the spine's thickness is known
+        let cover_spine_in = match self.cover_outfile_path() {
+            Some(cover_path) if volumes.is_empty() => {
+                let spine_in = render_cover(self, source, page_count, &cover_path)
+                    .with_context(|| "Failed to render cover PDF")?;
+                Some(spine_in)
+            }
+            _ => None,
+        };
+
         let file =
             std::fs::File::create(&self.outfile).with_context(|| "Failed to create output file")?;
         let mut file = std::io::BufWriter::new(file);
@@ -449,10 +1070,38 @@ impl PDF {
         Ok(RenderStats {
             page_count,
             booklet_sheets,
+            two_up_sheets,
+            cover_spine_in,
+            volumes,
         })
     }
 }
 
+/// Add a blank page if need be so the next page is a recto. Returns whether one was
+/// added.
+fn start_recto(doc: &mut Document, page_layout: &PageLayout) -> bool {
+    if doc.page_order.len() % 2 == 0 {
+        return false;
+    }
+    doc.add_page(page_layout.new_page(doc.page_order.len()));
+    true
+}
+
+/// Account for a blank page [`start_recto`] added ahead of a file. It's numbered
+/// with the pages around it, without showing its number, except ahead of the
+/// section's first page, so a section is numbered from its first file however it
+/// starts.
+fn count_recto_blank(
+    page_metadata: &mut Vec<PageMetadata>,
+    section: Section,
+    page_count: &mut usize,
+) {
+    page_metadata.push(PageMetadata::new(section, *page_count).skip_numbering());
+    if *page_count > 0 {
+        *page_count += 1;
+    }
+}
+
 /// Get or create folder bookmarks for all ancestor directories of a file path,
 /// returning the immediate parent folder's bookmark.
 fn get_or_create_folder_bookmark(
@@ -0,0 +1,124 @@
+//! Jupyter notebook rendering.
+//!
+//! Notebooks are typeset cell by cell through the Markdown [`Typesetter`]:
+//! Markdown cells as prose, code cells as highlighted blocks in the kernel's
+//! language under an `In [n]:` label, and each cell's recorded outputs beneath
+//! it. Image outputs are written to disk by [`crate::notebook::OutputImages`]
+//! and placed like any other image, so the booklet can reload them by path.
+
+use crate::notebook::{self, Cell, Output, OutputImages};
+use crate::sinks::pdf::config::{PageLayout, PDF};
+use crate::sinks::pdf::fonts::FontIds;
+use crate::sinks::pdf::rendering::flow::Continuation;
+use crate::sinks::pdf::rendering::markdown::Typesetter;
+use crate::sinks::pdf::rendering::source_file::RenderResult;
+use crate::sinks::pdf::rendering::ImagePathMap;
+use anyhow::Result;
+use pdf_gen::*;
+use std::path::Path;
+use syntect::highlighting::Theme;
+use syntect::parsing::SyntaxSet;
+
+/// Where a notebook's images go and where it starts.
+pub struct NotebookContext<'a> {
+    /// Paths of the images placed in the document, so the booklet can reload them
+    pub image_paths: &'a mut ImagePathMap,
+    /// Where image outputs are written for the length of the render
+    pub output_images: &'a OutputImages,
+    /// The page to carry on from, for files placed `Continuous`
+    pub continuation: Option<&'a mut Continuation>,
+}
+
+/// Render a notebook file, following on from the file before when `context` has
+/// a continuation.
+///
+/// Returns the first page index and number of pages rendered, like
+/// [`super::source_file::render`].
+pub fn render(
+    config: &PDF,
+    page_layout: &PageLayout,
+    doc: &mut Document,
+    font_ids: &FontIds,
+    path: &Path,
+    ss: &SyntaxSet,
+    theme: &Theme,
+    context: NotebookContext,
+) -> Result<RenderResult> {
+    let notebook = notebook::load(path)?;
+    let base_dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+    let error_colour = Colour::new_rgb_bytes(220, 50, 47);
+    let output_images = context.output_images;
+
+    let mut typesetter = Typesetter::new(
+        config,
+        page_layout,
+        doc,
+        font_ids,
+        ss,
+        theme,
+        &base_dir,
+        context.image_paths,
+    );
+    if let Some(continuation) = context.continuation {
+        typesetter.flow.continue_file(continuation, font_ids, path);
+    }
+    let cell_gap =
+        typesetter.flow.doc.fonts[font_ids.regular].line_height(Pt(page_layout.fonts.body_pt));
+    for (cell_index, cell) in notebook.cells.iter().enumerate() {
+        if cell_index > 0 {
+            typesetter.flow.gap(cell_gap);
+        }
+        match cell {
+            Cell::Markdown(text) => typesetter.blocks(&crate::markdown::parse(text), Pt(0.0))?,
+            Cell::Raw(text) => typesetter.preformatted(text, colours::BLACK),
+            Cell::Code {
+                execution_count,
+                source,
+                outputs,
+            } => {
+                typesetter.label(&execution_label("In", *execution_count));
+                typesetter.code(Some(&notebook.language), source, Pt(0.0))?;
+
+                for (output_index, output) in outputs.iter().enumerate() {
+                    match output {
+                        Output::Stream { name, text } => {
+                            let colour = if name == "stderr" {
+                                error_colour
+                            } else {
+                                colours::BLACK
+                            };
+                            typesetter.preformatted(text, colour);
+                        }
+                        Output::Result {
+                            execution_count,
+                            text,
+                        } => {
+                            typesetter.label(&execution_label("Out", *execution_count));
+                            typesetter.preformatted(text, colours::BLACK);
+                        }
+                        Output::Image { extension, data } => {
+                            let image_path = output_images.write(
+                                path,
+                                cell_index,
+                                output_index,
+                                extension,
+                                data,
+                            )?;
+                            typesetter.image_file(&image_path, "output image")?;
+                        }
+                        Output::Error(text) => typesetter.preformatted(text, error_colour),
+                    }
+                }
+            }
+        }
+    }
+    Ok(typesetter.flow.finish())
+}
+
+/// Format a Jupyter-style execution label such as `In [3]:`.
+fn execution_label(prefix: &str, execution_count: Option<u64>) -> String {
+    match execution_count {
+        Some(n) => format!("{} [{}]:", prefix, n),
+        None => format!("{} [ ]:", prefix),
+    }
+}
@@ -0,0 +1,209 @@
+//! Per-file outlines.
+//!
+//! With `[pdf.outline]` enabled, each source file's top-level items (functions,
+//! types, `impl` blocks, classes, modules) are found by
+//! [`crate::syntax_analysis::SymbolScanner`] as the file is highlighted (see
+//! [`crate::syntax_analysis::FileSymbols::outline`]). Files with enough of them open
+//! with a small outline box, a mini table of contents listing each item with its
+//! line and page number, and every item is added as a child bookmark under the
+//! file's entry in the PDF outline.
+//!
+//! The box is placed before the listing, when the pages the items will land on
+//! aren't known yet. Its rows are laid out with the line numbers only, and the page
+//! numbers and links are added by [`Outlines::resolve`] once the table of contents
+//! has been inserted, the same way cross-references are.
+
+use crate::sinks::pdf::config::{PageLayout, PDF};
+use crate::sinks::pdf::fonts::FontIds;
+use crate::sinks::pdf::rendering::flow::Flow;
+use crate::sinks::pdf::rendering::header_footer::{page_label, PageMetadata};
+use crate::syntax_analysis::OutlineItem;
+use pdf_gen::*;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// An outline box row waiting for its item's page to be known.
+pub struct PlacedRow {
+    page: usize,
+    rect: Rect,
+    baseline: Pt,
+}
+
+/// A placed outline box row and the page its item landed on.
+struct PendingRow {
+    row: PlacedRow,
+    target: usize,
+}
+
+/// Outline boxes waiting for page numbers, and each file's items for bookmarks.
+pub struct Outlines {
+    min_items: usize,
+    rows: Vec<PendingRow>,
+    /// Top-level items of each rendered file with the page index each landed on
+    items: HashMap<PathBuf, Vec<(OutlineItem, usize)>>,
+}
+
+impl Outlines {
+    pub fn new(config: &PDF) -> Self {
+        Self {
+            min_items: config.outline.min_items,
+            rows: Vec::new(),
+            items: HashMap::new(),
+        }
+    }
+
+    /// Whether a file with these items gets an outline box.
+    pub fn wants_box(&self, items: &[OutlineItem]) -> bool {
+        !items.is_empty() && items.len() >= self.min_items
+    }
+
+    /// Record where a file's items landed once its listing has been laid out.
+    ///
+    /// `rows` are the box rows placed for the items, if the file got a box.
+    pub fn add_file(
+        &mut self,
+        path: &Path,
+        items: Vec<OutlineItem>,
+        rows: Vec<PlacedRow>,
+        line_pages: &[Option<usize>],
+    ) {
+        let mut placed = Vec::with_capacity(items.len());
+        let mut rows = rows.into_iter();
+        for item in items {
+            let row = rows.next();
+            let Some(&Some(target)) = line_pages.get(item.line) else {
+                continue;
+            };
+            if let Some(row) = row {
+                self.rows.push(PendingRow { row, target });
+            }
+            placed.push((item, target));
+        }
+        self.items.insert(path.to_path_buf(), placed);
+    }
+
+    /// The top-level items of a rendered file and the page index each landed on.
+    pub fn items(&self, path: &Path) -> &[(OutlineItem, usize)] {
+        self.items.get(path).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// Add the page numbers and links to the outline boxes.
+    ///
+    /// `link_offset` and `content_offset` are as for
+    /// [`super::cross_references::CrossReferences::resolve`].
+    pub fn resolve(
+        self,
+        config: &PDF,
+        page_layout: &PageLayout,
+        doc: &mut Document,
+        font_ids: &FontIds,
+        content_offset: usize,
+        page_metadata: &[PageMetadata],
+        link_offset: usize,
+    ) {
+        let font = SpanFont {
+            id: font_ids.regular,
+            size: Pt(page_layout.fonts.small_pt),
+        };
+        for PendingRow { row, target } in self.rows {
+            let page_id = doc.page_order[row.page + link_offset];
+            if let Some(metadata) = page_metadata.get(target.saturating_sub(content_offset)) {
+                let text = page_label(config, metadata);
+                let width = layout::width_of_text(&text, &doc.fonts[font.id], font.size);
+                doc.pages
+                    .get_mut(page_id)
+                    .expect("page exists")
+                    .add_span(SpanLayout {
+                        text,
+                        font,
+                        colour: colours::BLACK,
+                        coords: (row.rect.x2 - width, row.baseline),
+                    });
+            }
+            doc.pages
+                .get_mut(page_id)
+                .expect("page exists")
+                .add_intradocument_link_by_index(row.rect, target + link_offset);
+        }
+    }
+}
+
+/// Place an outline box listing `items` at the cursor.
+///
+/// Returns the placed rows, one per item, for [`Outlines::add_file`].
+pub fn place_box(
+    flow: &mut Flow,
+    font_ids: &FontIds,
+    size: Pt,
+    items: &[OutlineItem],
+) -> Vec<PlacedRow> {
+    let regular = &flow.doc.fonts[font_ids.regular];
+    let italic = &flow.doc.fonts[font_ids.italic];
+    let row_height = regular.line_height(size);
+    let row_ascent = regular.ascent(size);
+    let row_descent = regular.descent(size);
+
+    // columns: kind, name, then line and page numbers against the right edge
+    let kind_width = items
+        .iter()
+        .map(|item| layout::width_of_text(item.label, italic, size))
+        .fold(Pt(0.0), |a, b| if b > a { b } else { a })
+        + layout::width_of_text("  ", regular, size);
+    let page_width = layout::width_of_text("  0000", regular, size);
+
+    let kind_font = SpanFont {
+        id: font_ids.italic,
+        size,
+    };
+    let name_font = SpanFont {
+        id: font_ids.regular,
+        size,
+    };
+    let grey = Colour::new_grey(0.5);
+
+    flow.add_rule(Pt(0.0), Pt(0.5), 0.75);
+    // keep the heading with at least the first item
+    flow.ensure_space(row_height * 2.0);
+    flow.add_line(
+        Pt(0.0),
+        vec![(
+            "Outline".to_string(),
+            colours::BLACK,
+            SpanFont {
+                id: font_ids.bold,
+                size,
+            },
+        )],
+        row_height,
+        row_ascent,
+    );
+
+    let mut rows = Vec::with_capacity(items.len());
+    for item in items {
+        let measure = flow.measure();
+        let line = format!("line {}", item.line + 1);
+        let line_width = layout::width_of_text(&line, &flow.doc.fonts[font_ids.regular], size);
+        let baseline = flow.add_line_at(
+            vec![
+                (Pt(0.0), (item.label.to_string(), grey, kind_font)),
+                (kind_width, (item.name.clone(), colours::BLACK, name_font)),
+                (measure - page_width - line_width, (line, grey, name_font)),
+            ],
+            row_height,
+            row_ascent,
+        );
+        let bbox = flow.content_box();
+        rows.push(PlacedRow {
+            page: flow.page_index(),
+            rect: Rect {
+                x1: bbox.x1,
+                x2: bbox.x2,
+                y1: baseline + row_descent,
+                y2: baseline + row_ascent,
+            },
+            baseline,
+        });
+    }
+    flow.add_rule(Pt(0.0), Pt(0.5), 0.75);
+    rows
+}
@@ -1,17 +1,51 @@
 //! Source file rendering with syntax highlighting.
 //!
 //! Renders source code files with line numbers, syntax highlighting based on file
-//! extension, and natural text wrapping. Binary files can be rendered as hex dumps
-//! (when enabled) or display a placeholder.
+//! extension, and code-aware wrapping (see [`crate::wrapping`]). Files with no known
+//! syntax are listed as plain text. Binary files can be rendered as hex dumps (when
+//! enabled) or display a placeholder.
+//!
+//! Tabs, indentation and visible whitespace follow `[pdf.code]`; see
+//! [`crate::whitespace`].
+//!
+//! Highlighted files are placed line by line through the shared listing layout, so
+//! the page each line lands on is known (for the symbol index) along with where
+//! references to definitions were placed (for cross-reference links). The file is
+//! parsed once: the same pass that highlights it finds its definitions, outline
+//! items, TODO markers and references (see [`highlight_and_scan`]).
+//!
+//! Files included as excerpts show only their selected lines, still under their
+//! true line numbers, with a marker standing in for each run of lines left out.
+//! A file's standard header (see [`crate::boilerplate`]) is collapsed the same way.
+//!
+//! Files placed `Continuous` follow on from the file before, under its name; see
+//! [`super::flow`].
+//!
+//! With `[pdf.keep_together]` enabled, pages break early to keep short items and
+//! the ends of long ones together; see [`super::keep_together`].
 
-use crate::sinks::pdf::config::PDF;
+use crate::boilerplate::header_label;
+use crate::sinks::pdf::config::{PageLayout, PDF};
 use crate::sinks::pdf::fonts::FontIds;
+use crate::sinks::pdf::rendering::cross_references::CrossReferences;
+use crate::sinks::pdf::rendering::flow::{Continuation, Flow, Span};
+use crate::sinks::pdf::rendering::header_footer::PageLines;
 use crate::sinks::pdf::rendering::hex_dump;
+use crate::sinks::pdf::rendering::keep_together::KeepTogether;
+use crate::sinks::pdf::rendering::listing::{
+    gutter_note, listing_line, omission, row_count, ListingStyle,
+};
+use crate::sinks::pdf::rendering::outline::{place_box, Outlines};
+use crate::sinks::pdf::rendering::todos::Todos;
+use crate::source::{Excerpt, Omissions};
+use crate::syntax_analysis::{
+    highlight_and_scan, item_lines, Definition, OutlineItem, SymbolScanner,
+};
+use crate::whitespace;
 use anyhow::{Context, Result};
-use pdf_gen::layout::Margins;
 use pdf_gen::*;
+use std::ops::Range;
 use std::path::Path;
-use syntect::easy::HighlightLines;
 use syntect::highlighting::FontStyle;
 use syntect::parsing::SyntaxSet;
 use syntect::util::LinesWithEndings;
@@ -22,6 +56,36 @@ pub struct RenderResult {
     pub first_page: Option<usize>,
     /// Number of pages rendered
     pub page_count: usize,
+    /// Whether the first page was begun by the file before, in continuous flow, and
+    /// so isn't counted in `page_count`
+    pub continued: bool,
+    /// Page index each source line finishes on, indexed by zero-based line number,
+    /// or None for lines left out of an excerpt. Empty for content that isn't laid
+    /// out as numbered lines (images, prose, hex).
+    pub line_pages: Vec<Option<usize>>,
+    /// Lines and top-level item shown on each page, indexed by page within the
+    /// file. Empty when `line_pages` is.
+    pub page_lines: Vec<PageLines>,
+    /// Symbols defined in the file, for the index. Empty when `line_pages` is.
+    pub definitions: Vec<Definition>,
+}
+
+/// Book-wide bookkeeping that listings report to as they're laid out, and the part
+/// of the file to show.
+#[derive(Default)]
+pub struct ListingContext<'a> {
+    /// Where references to definitions land, for go-to-definition links
+    pub cross_references: Option<&'a mut CrossReferences>,
+    /// Each file's top-level items, for outline boxes and bookmarks
+    pub outlines: Option<&'a mut Outlines>,
+    /// Marker comments, for the TODO register
+    pub todos: Option<&'a mut Todos>,
+    /// The lines to show, if the file is included as an excerpt
+    pub excerpt: Option<&'a Excerpt>,
+    /// Zero-based lines of the standard file header, collapsed to a marker
+    pub header: Option<Range<usize>>,
+    /// The page to carry on from, for files placed `Continuous`
+    pub continuation: Option<&'a mut Continuation>,
 }
 
 /// Render a source file with syntax highlighting.
@@ -31,23 +95,34 @@ pub struct RenderResult {
 /// as hex dumps (when `config.binary_hex.enabled` is enabled) or shown as a grey
 /// placeholder.
 ///
+/// Highlighted listings report to `context`: references to definitions elsewhere in
+/// the book are recorded as they're placed so they can be linked once every page is
+/// known, and with outlines enabled the file's top-level items are listed in a box
+/// ahead of the code.
+///
 /// Returns the first page index and number of pages rendered.
 pub fn render(
     config: &PDF,
+    page_layout: &PageLayout,
     doc: &mut Document,
     font_ids: &FontIds,
     path: &Path,
     ss: &SyntaxSet,
     theme: &syntect::highlighting::Theme,
+    mut context: ListingContext,
 ) -> Result<RenderResult> {
-    let text_size = Pt(config.fonts.body_pt);
-    let small_size = Pt(config.fonts.small_pt);
-    let subheading_size = Pt(config.fonts.subheading_pt);
+    let text_size = Pt(page_layout.fonts.body_pt);
+    let small_size = Pt(page_layout.fonts.small_pt);
+    let subheading_size = Pt(page_layout.fonts.subheading_pt);
 
     // read the contents, or handle binary files
     let (contents, is_binary) = match std::fs::read_to_string(path) {
-        Ok(contents) => (contents.replace("    ", "  "), false),
+        Ok(contents) => (whitespace::expand(&contents, &config.code), false),
         Err(e) if e.kind() == std::io::ErrorKind::InvalidData => {
+            // binary files start a page of their own
+            if let Some(continuation) = context.continuation.take() {
+                continuation.close(doc, &page_layout.columns);
+            }
             // binary file - check if we should render as hex
             if config.binary_hex.enabled {
                 let data = std::fs::read(path)
@@ -62,7 +137,14 @@ pub fn render(
                 };
 
                 return Ok(hex_dump::render(
-                    config, doc, font_ids, path, data, truncated, theme,
+                    config,
+                    page_layout,
+                    doc,
+                    font_ids,
+                    path,
+                    data,
+                    truncated,
+                    theme,
                 ));
             }
             // fallback to placeholder
@@ -74,110 +156,154 @@ pub fn render(
         }
     };
 
-    // figure out the syntax if we can (skip for binary files)
+    // figure out the syntax (skip for binary files); files without one are listed
+    // as plain text so they wrap like any other listing
     let syntax = if is_binary {
         None
     } else {
-        ss.find_syntax_by_extension(
+        let syntax = ss.find_syntax_by_extension(
             path.extension()
                 .map(std::ffi::OsStr::to_str)
                 .unwrap_or_default()
                 .unwrap_or_default(),
-        )
+        );
+        Some(syntax.unwrap_or_else(|| ss.find_syntax_plain_text()))
     };
 
-    // start the set of pages with the path
-    let mut text: Vec<(String, Colour, SpanFont)> = Vec::default();
-
-    if is_binary {
-        // render binary placeholder
-        text.push((
-            contents,
-            Colour::new_grey(0.5),
-            SpanFont {
-                id: font_ids.italic,
-                size: text_size,
-            },
-        ));
-    } else if let Some(syntax) = syntax {
-        // load the contents of the file
-        let mut h = HighlightLines::new(syntax, theme);
-
-        // highlight the file, converting into spans
-        for (i, line) in LinesWithEndings::from(contents.as_str()).enumerate() {
-            let ranges: Vec<(syntect::highlighting::Style, &str)> = h
-                .highlight_line(line, ss)
-                .with_context(|| format!("Failed to highlight source code for line `{}`", line))?;
-
-            text.push((
-                format!("{:>4}  ", i + 1),
-                Colour::new_grey(0.75),
-                SpanFont {
-                    id: font_ids.regular,
-                    size: small_size,
-                },
-            ));
-            for (style, s) in ranges.into_iter() {
-                let colour = Colour::new_rgb_bytes(
-                    style.foreground.r,
-                    style.foreground.g,
-                    style.foreground.b,
-                );
-
-                let font_id = match (
-                    style.font_style.intersects(FontStyle::BOLD),
-                    style.font_style.intersects(FontStyle::ITALIC),
-                ) {
-                    (true, true) => font_ids.bold_italic,
-                    (true, false) => font_ids.bold,
-                    (false, true) => font_ids.italic,
-                    (false, false) => font_ids.regular,
-                };
+    let selection = match (context.excerpt, is_binary) {
+        (Some(excerpt), false) => Some(excerpt.select(path, &contents, syntax, ss)?),
+        _ => None,
+    };
+    let shown = |line: usize| selection.as_ref().is_none_or(|s| s.contains(line));
 
-                text.push((
-                    s.to_string(),
-                    colour,
-                    SpanFont {
-                        id: font_id,
-                        size: text_size,
-                    },
-                ));
+    // highlighted files are placed line by line so we know where each line lands
+    if let Some(syntax) = syntax {
+        let cross_references = context.cross_references.as_deref();
+        let scanner = SymbolScanner::new(
+            context
+                .todos
+                .as_deref()
+                .map(Todos::tags)
+                .unwrap_or_default(),
+            |name: &str| cross_references.is_some_and(|c| c.defines(name)),
+        );
+        let (highlighted, symbols) = highlight_and_scan(&contents, syntax, ss, theme, scanner)
+            .with_context(|| format!("Failed to highlight source code of {}", path.display()))?;
+        let references = match cross_references {
+            Some(cross_references) => cross_references.line_references(path, symbols.references),
+            None => Vec::new(),
+        };
+        let markers = symbols.markers;
+        let mut outline_items = symbols.outline;
+        outline_items.retain(|item| shown(item.line));
+
+        let style = ListingStyle::new(config, page_layout);
+        let mut flow = Flow::new(page_layout, doc, font_ids);
+        if let Some(continuation) = context.continuation.take() {
+            flow.continue_file(continuation, font_ids, path);
+        }
+        let outline_rows = match context.outlines.as_deref() {
+            Some(outlines) if outlines.wants_box(&outline_items) => {
+                place_box(&mut flow, font_ids, small_size, &outline_items)
             }
+            _ => Vec::new(),
+        };
+        let keep_together = config.keep_together.enabled.then(|| {
+            let rows = LinesWithEndings::from(contents.as_str())
+                .enumerate()
+                .map(|(i, line)| match context.header.as_ref() {
+                    _ if !shown(i) => 0,
+                    Some(header) if header.contains(&i) => usize::from(i == header.start),
+                    _ if config.code.visible_whitespace => {
+                        row_count(&mut flow, font_ids, &style, &whitespace::visible(line))
+                    }
+                    _ => row_count(&mut flow, font_ids, &style, line),
+                })
+                .collect();
+            KeepTogether::new(
+                &config.keep_together,
+                item_lines(&contents, &outline_items),
+                rows,
+            )
+        });
+        let line_height = flow.doc.fonts[font_ids.regular].line_height(text_size);
+        let mut line_pages = Vec::new();
+        let mut omissions = Omissions::default();
+        let mut header_placed = false;
+        let lines = LinesWithEndings::from(contents.as_str()).zip(highlighted);
+        for (i, (line, ranges)) in lines.enumerate() {
+            if !shown(i) {
+                omissions.skip(i);
+                line_pages.push(None);
+                continue;
+            }
+            if let Some(lines) = omissions.show(i) {
+                omission(&mut flow, font_ids, &style, &lines);
+            }
+            // the header is printed once in the frontmatter; its lines keep their
+            // numbers but are shown as a single marker
+            if let Some(header) = context.header.as_ref().filter(|h| h.contains(&i)) {
+                if !header_placed {
+                    gutter_note(&mut flow, font_ids, &style, header_label(header));
+                    header_placed = true;
+                }
+                line_pages.push(Some(flow.page_index()));
+                continue;
+            }
+            if let Some(keep_together) = &keep_together {
+                let fits = (flow.remaining().0 / line_height.0).floor().max(0.0) as usize;
+                if !flow.at_top() && keep_together.break_before(i, fits) {
+                    flow.break_column();
+                }
+            }
+            let spans = code_spans(config, ranges, font_ids, text_size);
+
+            let line_references = references.get(i).map(Vec::as_slice).unwrap_or_default();
+            let marks: Vec<Range<usize>> = line_references
+                .iter()
+                .map(|r| whitespace::shown_range(line, &r.range, &config.code))
+                .collect();
+            let flagged = markers.binary_search_by_key(&i, |m| m.line).is_ok();
+            let placed = listing_line(&mut flow, font_ids, &style, i + 1, spans, &marks, flagged);
+            if let Some(cross_references) = context.cross_references.as_deref_mut() {
+                cross_references.add_links(line_references, placed);
+            }
+            line_pages.push(Some(flow.page_index()));
         }
-    } else {
-        // render without syntax highlighting
-        // note: don't show line numbers on these files
-        for line in contents.lines() {
-            text.push((
-                format!("{}\n", line),
-                colours::BLACK,
-                SpanFont {
-                    id: font_ids.regular,
-                    size: text_size,
-                },
-            ));
+        if let Some(lines) = omissions.finish(line_pages.len()) {
+            omission(&mut flow, font_ids, &style, &lines);
+        }
+
+        if let Some(outlines) = context.outlines {
+            outlines.add_file(path, outline_items, outline_rows, &line_pages);
         }
+        if let Some(todos) = context.todos {
+            todos.add_file(path, markers, &line_pages);
+        }
+
+        let mut result = flow.finish();
+        result.page_lines = page_lines(&result, &line_pages, &outline_items);
+        result.line_pages = line_pages;
+        result.definitions = symbols.definitions;
+        return Ok(result);
     }
 
+    // binary files without a hex dump get a placeholder
+    let mut text: Vec<(String, Colour, SpanFont)> = vec![(
+        contents,
+        Colour::new_grey(0.5),
+        SpanFont {
+            id: font_ids.italic,
+            size: text_size,
+        },
+    )];
+
     // and render it into pages
-    let wrap_width = if syntax.is_some() {
-        layout::width_of_text("      ", &doc.fonts[font_ids.regular], small_size)
-    } else {
-        Pt(0.0)
-    };
+    let wrap_width = Pt(0.0);
     let mut first_page = None;
     let mut page_count = 0;
     while !text.is_empty() {
-        let margins = Margins::trbl(
-            In(0.25).into(),
-            In(0.25).into(),
-            In(0.5).into(),
-            In(0.25).into(),
-        )
-        .with_gutter(In(0.25).into(), doc.page_order.len());
-        let page_size = config.page_size();
-
-        let mut page = Page::new(page_size, Some(margins));
+        let mut page = page_layout.new_page(doc.page_order.len());
         let start = layout::baseline_start(&page, &doc.fonts[font_ids.regular], text_size);
         let start = (
             start.0,
@@ -211,5 +337,187 @@ pub fn render(
     Ok(RenderResult {
         first_page,
         page_count,
+        continued: false,
+        line_pages: Vec::new(),
+        page_lines: Vec::new(),
+        definitions: Vec::new(),
     })
 }
+
+/// Work out which lines, and which top-level item, each page of a listing shows.
+///
+/// A page is in the item in effect at its first line, or failing that (at the top
+/// of a file) the first item that starts on it.
+pub fn page_lines(
+    result: &RenderResult,
+    line_pages: &[Option<usize>],
+    items: &[OutlineItem],
+) -> Vec<PageLines> {
+    let mut pages = vec![PageLines::default(); result.page_count];
+    let Some(first_page) = result.first_page else {
+        return pages;
+    };
+    // a page carried over from the file before is that file's
+    let first_page = first_page + usize::from(result.continued);
+
+    for (line, page) in line_pages.iter().enumerate() {
+        let Some(entry) = page
+            .and_then(|page| page.checked_sub(first_page))
+            .and_then(|i| pages.get_mut(i))
+        else {
+            continue;
+        };
+        entry.lines = Some(match entry.lines {
+            Some((first, _)) => (first, line + 1),
+            None => (line + 1, line + 1),
+        });
+    }
+
+    for page in pages.iter_mut() {
+        let Some((first, last)) = page.lines else {
+            continue;
+        };
+        page.symbol = items
+            .iter()
+            .rev()
+            .find(|item| item.line < first)
+            .or_else(|| items.iter().find(|item| item.line < last))
+            .map(|item| format!("{} {}", item.label, item.name));
+    }
+    pages
+}
+
+/// Convert a highlighted line into spans, showing whitespace as `[pdf.code]` asks:
+/// all of it as glyphs with visible whitespace, and trailing whitespace as glyphs
+/// in a warning colour when it's marked.
+pub fn code_spans(
+    config: &PDF,
+    ranges: Vec<(syntect::highlighting::Style, &str)>,
+    font_ids: &FontIds,
+    size: Pt,
+) -> Vec<Span> {
+    let code = &config.code;
+    let line: String = ranges.iter().map(|(_, s)| *s).collect();
+    let trailing = if code.mark_trailing_whitespace {
+        whitespace::trailing_start(&line)
+    } else {
+        usize::MAX
+    };
+
+    let trailing_colour = Colour::new_rgb_bytes(220, 50, 47);
+    let mut spans = Vec::with_capacity(ranges.len());
+    let mut byte = 0;
+    for (style, s) in ranges {
+        let s = s.trim_end_matches(['\n', '\r']);
+        let (body, tail) = s.split_at(trailing.saturating_sub(byte).min(s.len()));
+        byte += s.len();
+        if !body.is_empty() {
+            let body = if code.visible_whitespace {
+                whitespace::visible(body)
+            } else {
+                body.to_string()
+            };
+            spans.push(highlighted_span(style, &body, font_ids, size));
+        }
+        if !tail.is_empty() {
+            spans.push((
+                whitespace::visible(tail),
+                trailing_colour,
+                SpanFont {
+                    id: font_ids.regular,
+                    size,
+                },
+            ));
+        }
+    }
+    spans
+}
+
+/// Convert a syntect-highlighted token into a span, picking the font variant from
+/// the token's bold/italic style.
+pub fn highlighted_span(
+    style: syntect::highlighting::Style,
+    text: &str,
+    font_ids: &FontIds,
+    size: Pt,
+) -> (String, Colour, SpanFont) {
+    let colour = Colour::new_rgb_bytes(style.foreground.r, style.foreground.g, style.foreground.b);
+
+    let font_id = match (
+        style.font_style.intersects(FontStyle::BOLD),
+        style.font_style.intersects(FontStyle::ITALIC),
+    ) {
+        (true, true) => font_ids.bold_italic,
+        (true, false) => font_ids.bold,
+        (false, true) => font_ids.italic,
+        (false, false) => font_ids.regular,
+    };
+
+    (text.to_string(), colour, SpanFont { id: font_id, size })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(name: &str, line: usize) -> OutlineItem {
+        OutlineItem {
+            name: name.to_string(),
+            label: "fn",
+            line,
+        }
+    }
+
+    #[test]
+    fn finds_the_lines_and_item_on_each_page() {
+        let result = RenderResult {
+            first_page: Some(10),
+            page_count: 3,
+            continued: false,
+            line_pages: Vec::new(),
+            page_lines: Vec::new(),
+            definitions: Vec::new(),
+        };
+        // lines 1-4 on page 10, 5-8 on page 11 (line 9 omitted), nothing on page 12
+        let line_pages = [10, 10, 10, 10, 11, 11, 11, 11]
+            .map(Some)
+            .into_iter()
+            .chain([None])
+            .collect::<Vec<_>>();
+        let items = [item("parse", 2), item("render", 6)];
+        assert_eq!(
+            page_lines(&result, &line_pages, &items),
+            vec![
+                PageLines {
+                    lines: Some((1, 4)),
+                    symbol: Some("fn parse".to_string()),
+                },
+                PageLines {
+                    lines: Some((5, 8)),
+                    symbol: Some("fn parse".to_string()),
+                },
+                PageLines::default(),
+            ]
+        );
+    }
+
+    #[test]
+    fn leaves_a_carried_page_to_the_file_before() {
+        let result = RenderResult {
+            first_page: Some(10),
+            page_count: 1,
+            continued: true,
+            line_pages: Vec::new(),
+            page_lines: Vec::new(),
+            definitions: Vec::new(),
+        };
+        let line_pages = [10, 10, 11, 11].map(Some);
+        assert_eq!(
+            page_lines(&result, &line_pages, &[]),
+            vec![PageLines {
+                lines: Some((3, 4)),
+                symbol: None,
+            }]
+        );
+    }
+}
@@ -1,24 +1,25 @@
 //! Table of contents with clickable links.
 //!
-//! Generates a TOC listing frontmatter files, source files, and commit history
-//! with page numbers. Each entry links to its corresponding page within the document.
+//! Generates a TOC listing frontmatter files, source files, the appendices and
+//! the symbol index with page numbers. Each entry links to its corresponding page within the document.
 //! Supports automatic pagination when entries exceed a single page.
 //!
-//! Frontmatter appears as a flat list under its own heading before the hierarchical
-//! source code tree structure.
+//! Frontmatter and guided tours appear as flat lists under their own headings before
+//! the hierarchical source code tree structure.
 //!
 //! The TOC is rendered after all content pages but inserted before them in the final
 //! document. This requires pre-calculating the number of TOC pages so intradocument
 //! links point to correct page indices. Pages are padded to an even count for booklet
-//! alignment.
+//! alignment. [`page_count`] makes the same calculation for volume planning, which
+//! has to know how long the contents will be before they're rendered.
 
-use crate::sinks::pdf::config::{Section, PDF};
+use crate::sinks::pdf::config::{PageLayout, Section, VolumeNumbering, PDF};
 use crate::sinks::pdf::fonts::FontIds;
-use crate::sinks::pdf::rendering::header_footer::format_page_number;
+use crate::sinks::pdf::rendering::flow::{column_boxes, draw_column_rules};
+use crate::sinks::pdf::rendering::header_footer::{format_page_number, PageMetadata};
 use anyhow::Result;
 use owned_ttf_parser::AsFaceRef;
 use pdf_gen::id_arena_crate::Id;
-use pdf_gen::layout::Margins;
 use pdf_gen::pdf_writer_crate::types::LineCapStyle;
 use pdf_gen::pdf_writer_crate::Content;
 use pdf_gen::*;
@@ -163,25 +164,117 @@ fn flatten_children(children: &[TocEntry], result: &mut Vec<FlatEntry>, prefix:
     }
 }
 
+/// The entries for the frontmatter, guided tours and source files. Each section is
+/// numbered from its first file, as its pages are (see
+/// [`super::count_recto_blank`]), so blank pages between files count and a blank
+/// ahead of the first doesn't.
+fn file_entries(
+    frontmatter_pages: HashMap<PathBuf, usize>,
+    tour_pages: Vec<(String, usize)>,
+    source_pages: HashMap<PathBuf, usize>,
+) -> Vec<TocDisplayEntry> {
+    let mut entries: Vec<TocDisplayEntry> = Vec::new();
+
+    // add frontmatter section if there are frontmatter files
+    if !frontmatter_pages.is_empty() {
+        // sort by page number for consistent ordering
+        let mut frontmatter_entries: Vec<_> = frontmatter_pages.into_iter().collect();
+        frontmatter_entries.sort_by_key(|(_, page)| *page);
+
+        // the first file starts the section, after any blank page ahead of it
+        let first_page = frontmatter_entries[0].1;
+        entries.push(TocDisplayEntry {
+            text: "Frontmatter".to_string(),
+            abs_page: first_page,
+            section: Section::Frontmatter,
+            page_in_section: 0,
+        });
+
+        for (path, page) in frontmatter_entries {
+            let name = path
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_else(|| path.display().to_string());
+            entries.push(TocDisplayEntry {
+                text: format!("  └── {}", name),
+                abs_page: page,
+                section: Section::Frontmatter,
+                page_in_section: page - first_page,
+            });
+        }
+    }
+
+    // add guided tour section, titled by the tours themselves
+    if let Some((_, tour_start)) = tour_pages.first() {
+        let tour_start = *tour_start;
+        entries.push(TocDisplayEntry {
+            text: "Guided Tour".to_string(),
+            abs_page: tour_start,
+            section: Section::Tour,
+            page_in_section: 0,
+        });
+        for (title, page) in tour_pages {
+            entries.push(TocDisplayEntry {
+                text: format!("  └── {}", title),
+                abs_page: page,
+                section: Section::Tour,
+                page_in_section: page - tour_start,
+            });
+        }
+    }
+
+    // build tree structure for source files and flatten for rendering
+    let tree = build_tree(source_pages);
+    let flat_entries = flatten_tree(&tree);
+    // the first source file starts the section, after the frontmatter and tours
+    let source_start = tree.min_page().unwrap_or(0);
+
+    // calculate source section page count (unused after simplifying appendix calculations)
+    let _source_max_page = flat_entries.iter().map(|e| e.page).max().unwrap_or(0);
+
+    entries.extend(flat_entries.into_iter().map(|e| {
+        // source pages are stored relative to page_offset, so page_in_section
+        // is the page number minus the pages before the section
+        let page_in_section = e.page.saturating_sub(source_start);
+        TocDisplayEntry {
+            text: format!("{}{}", e.prefix, e.name),
+            abs_page: e.page,
+            section: Section::Source,
+            page_in_section,
+        }
+    }));
+
+    entries
+}
+
 /// Render the table of contents.
 ///
 /// Inserts TOC pages at `skip_pages` position and returns the number of pages added.
-/// Pages are padded to an even count to maintain booklet alignment.
+/// Pages are padded to an even count to maintain booklet alignment. If the book is
+/// split into volumes, starting at the pages in `volume_breaks`, each page number
+/// says which volume it's in; if they're numbered per volume, the numbers are taken
+/// from `page_metadata`, as printed on the pages.
 pub fn render(
     config: &PDF,
+    page_layout: &PageLayout,
     doc: &mut Document,
     font_ids: &FontIds,
     skip_pages: usize,
     frontmatter_pages: HashMap<PathBuf, usize>,
+    tour_pages: Vec<(String, usize)>,
     source_pages: HashMap<PathBuf, usize>,
     git_history_page: Option<usize>,
     tags_page: Option<usize>,
+    todos_page: Option<usize>,
+    index_page: Option<usize>,
     _commit_history_page_count: usize,
+    volume_breaks: &[usize],
+    page_metadata: &[PageMetadata],
 ) -> Result<usize> {
-    let page_size = config.page_size();
-    let contents_size = Pt(config.fonts.heading_pt);
-    let entry_size = Pt(config.fonts.body_pt);
-    let subheading_size = Pt(config.fonts.subheading_pt);
+    let page_size = page_layout.page_size;
+    let contents_size = Pt(page_layout.fonts.heading_pt);
+    let entry_size = Pt(page_layout.fonts.body_pt);
+    let subheading_size = Pt(page_layout.fonts.subheading_pt);
 
     let height_contents = doc.fonts[font_ids.bold].line_height(contents_size);
     let height_entry = doc.fonts[font_ids.regular].line_height(entry_size);
@@ -211,62 +304,7 @@ pub fn render(
         .unwrap_or_else(|| (Pt(-2.0), Pt(0.5)));
 
     // build entries list with section information for proper page number formatting
-    let mut entries: Vec<TocDisplayEntry> = Vec::new();
-
-    // track frontmatter page count for calculating source section indices
-    let frontmatter_page_count = frontmatter_pages
-        .values()
-        .max()
-        .map(|&m| m + 1)
-        .unwrap_or(0);
-
-    // add frontmatter section if there are frontmatter files
-    if !frontmatter_pages.is_empty() {
-        // sort by page number for consistent ordering
-        let mut frontmatter_entries: Vec<_> = frontmatter_pages.into_iter().collect();
-        frontmatter_entries.sort_by_key(|(_, page)| *page);
-
-        if let Some((_, first_page)) = frontmatter_entries.first() {
-            entries.push(TocDisplayEntry {
-                text: "Frontmatter".to_string(),
-                abs_page: *first_page,
-                section: Section::Frontmatter,
-                page_in_section: *first_page,
-            });
-        }
-
-        for (path, page) in frontmatter_entries {
-            let name = path
-                .file_name()
-                .map(|n| n.to_string_lossy().to_string())
-                .unwrap_or_else(|| path.display().to_string());
-            entries.push(TocDisplayEntry {
-                text: format!("  └── {}", name),
-                abs_page: page,
-                section: Section::Frontmatter,
-                page_in_section: page,
-            });
-        }
-    }
-
-    // build tree structure for source files and flatten for rendering
-    let tree = build_tree(source_pages);
-    let flat_entries = flatten_tree(&tree);
-
-    // calculate source section page count (unused after simplifying appendix calculations)
-    let _source_max_page = flat_entries.iter().map(|e| e.page).max().unwrap_or(0);
-
-    entries.extend(flat_entries.into_iter().map(|e| {
-        // source pages are stored relative to page_offset, so page_in_section
-        // is the page number minus the frontmatter page count
-        let page_in_section = e.page.saturating_sub(frontmatter_page_count);
-        TocDisplayEntry {
-            text: format!("{}{}", e.prefix, e.name),
-            abs_page: e.page,
-            section: Section::Source,
-            page_in_section,
-        }
-    }));
+    let mut entries = file_entries(frontmatter_pages, tour_pages, source_pages);
 
     if let Some(git_history_page) = git_history_page {
         let abs_page = git_history_page - skip_pages;
@@ -290,39 +328,43 @@ pub fn render(
         });
     }
 
-    // pre-calculate how many TOC pages we'll need so intradocument links are correct
-    let num_toc_pages = {
-        // create a temporary page to calculate layout metrics
-        let temp_page = Page::new(page_size, Some(Margins::all(In(0.5))));
-        let (_, start_y_first) =
-            layout::baseline_start(&temp_page, &doc.fonts[font_ids.bold], contents_size);
-        let (_, start_y_subsequent) =
-            layout::baseline_start(&temp_page, &doc.fonts[font_ids.regular], entry_size);
-        let bottom_y = temp_page.content_box.y1 + descent_entry;
-
-        // first page: starts after "Contents" heading
-        let first_page_usable = start_y_first - height_contents - bottom_y;
-        let entries_first_page = (first_page_usable / height_entry).floor() as usize;
-
-        // subsequent pages: full height available
-        let subsequent_page_usable = start_y_subsequent - bottom_y;
-        let entries_per_subsequent = (subsequent_page_usable / height_entry).floor() as usize;
-
-        // calculate total pages needed
-        let entries_after_first = entries.len().saturating_sub(entries_first_page);
-        let additional_pages = entries_after_first.div_ceil(entries_per_subsequent.max(1));
-        let mut count = 1 + additional_pages;
-
-        // pad to even for booklet alignment
-        if count % 2 == 1 {
-            count += 1;
+    if let Some(todos_page_idx) = todos_page {
+        let abs_page = todos_page_idx - skip_pages;
+        // always the first content page of the section
+        entries.push(TocDisplayEntry {
+            text: "TODO Register".to_string(),
+            abs_page,
+            section: Section::Todos,
+            page_in_section: 0,
+        });
+    }
+
+    if let Some(index_page_idx) = index_page {
+        let abs_page = index_page_idx - skip_pages;
+        // always the first content page of the section
+        entries.push(TocDisplayEntry {
+            text: "Index".to_string(),
+            abs_page,
+            section: Section::Index,
+            page_in_section: 0,
+        });
+    }
+
+    // the sections start again in each volume if it's numbered per volume
+    if config.volumes.numbering == VolumeNumbering::PerVolume {
+        for entry in entries.iter_mut() {
+            if let Some(metadata) = page_metadata.get(entry.abs_page) {
+                entry.page_in_section = metadata.page_in_section;
+            }
         }
-        count
-    };
+    }
+
+    // pre-calculate how many TOC pages we'll need so intradocument links are correct
+    let num_toc_pages = page_count(page_layout, doc, font_ids, skip_pages, entries.len());
 
     let mut pages: Vec<Page> = Vec::default();
     while !entries.is_empty() {
-        let mut page = Page::new(page_size, Some(Margins::all(In(0.5))));
+        let mut page = page_layout.new_page(skip_pages + pages.len());
 
         let start = if pages.is_empty() {
             layout::baseline_start(&page, &doc.fonts[font_ids.bold], contents_size)
@@ -330,7 +372,7 @@ pub fn render(
             layout::baseline_start(&page, &doc.fonts[font_ids.regular], entry_size)
         };
 
-        let (x, mut y) = start;
+        let (_, mut y) = start;
         if pages.is_empty() {
             page.add_span(SpanLayout {
                 text: "Contents".to_string(),
@@ -339,77 +381,95 @@ pub fn render(
                     size: contents_size,
                 },
                 colour: colours::BLACK,
-                coords: (x, y),
+                coords: (start.0, y),
             });
             y -= height_contents;
         }
 
-        'page: loop {
-            if y < page.content_box.y1 + descent_entry || entries.is_empty() {
-                break 'page;
+        // entries fill each column top to bottom, below the heading on the first page
+        let top = y;
+        let boxes = column_boxes(page.content_box, &page_layout.columns);
+        let mut used = 0;
+        for column in &boxes {
+            if entries.is_empty() {
+                break;
+            }
+            used += 1;
+            let mut y = top;
+            loop {
+                if y < column.y1 + descent_entry || entries.is_empty() {
+                    break;
+                }
+
+                let entry = entries.remove(0);
+                let entry_width = layout::width_of_text(
+                    &format!("{} ", entry.text),
+                    &doc.fonts[font_ids.regular],
+                    entry_size,
+                );
+                // format page number using section-specific style and prefix
+                let numbering = config.numbering_for_section(entry.section);
+                let prefix = config.prefix_for_section(entry.section);
+                let display_page_num = numbering.start + entry.page_in_section as i32;
+                let mut pagenum = format!(
+                    "{}{}",
+                    prefix,
+                    format_page_number(display_page_num, numbering.style)
+                );
+                let target = entry.abs_page + skip_pages + num_toc_pages;
+                if !volume_breaks.is_empty() {
+                    let volume = 1 + volume_breaks.iter().filter(|b| **b <= target).count();
+                    pagenum = format!("vol. {} · {}", volume, pagenum);
+                }
+                let pagenum_width =
+                    layout::width_of_text(&pagenum, &doc.fonts[font_ids.regular], entry_size);
+
+                let mut underline = Content::new();
+                underline
+                    .set_stroke_gray(0.75)
+                    .set_line_cap(LineCapStyle::ButtCap)
+                    .set_line_width(*underline_thickness)
+                    .move_to(*column.x1 + *entry_width, *y + *underline_offset)
+                    .line_to(
+                        *column.x2
+                            - *layout::width_of_text(
+                                &format!(" {}", pagenum),
+                                &doc.fonts[font_ids.regular],
+                                entry_size,
+                            ),
+                        *y + *underline_offset,
+                    )
+                    .stroke();
+                page.add_content(underline);
+
+                page.add_span(SpanLayout {
+                    text: entry.text,
+                    font: entry_font,
+                    colour: colours::BLACK,
+                    coords: (column.x1, y),
+                });
+                page.add_span(SpanLayout {
+                    text: pagenum,
+                    font: entry_font,
+                    colour: colours::BLACK,
+                    coords: (column.x2 - pagenum_width, y),
+                });
+
+                page.add_intradocument_link_by_index(
+                    Rect {
+                        x1: column.x1,
+                        x2: column.x2,
+                        y1: y,
+                        y2: y + doc.fonts[font_ids.regular].ascent(entry_size),
+                    },
+                    target,
+                );
+
+                y -= height_entry;
             }
-
-            let entry = entries.remove(0);
-            let entry_width = layout::width_of_text(
-                &format!("{} ", entry.text),
-                &doc.fonts[font_ids.regular],
-                entry_size,
-            );
-            // format page number using section-specific style and prefix
-            let numbering = config.numbering_for_section(entry.section);
-            let prefix = config.prefix_for_section(entry.section);
-            let display_page_num = numbering.start + entry.page_in_section as i32;
-            let pagenum = format!(
-                "{}{}",
-                prefix,
-                format_page_number(display_page_num, numbering.style)
-            );
-            let pagenum_width =
-                layout::width_of_text(&pagenum, &doc.fonts[font_ids.regular], entry_size);
-
-            let mut underline = Content::new();
-            underline
-                .set_stroke_gray(0.75)
-                .set_line_cap(LineCapStyle::ButtCap)
-                .set_line_width(*underline_thickness)
-                .move_to(*page.content_box.x1 + *entry_width, *y + *underline_offset)
-                .line_to(
-                    *page.content_box.x2
-                        - *layout::width_of_text(
-                            &format!(" {}", pagenum),
-                            &doc.fonts[font_ids.regular],
-                            entry_size,
-                        ),
-                    *y + *underline_offset,
-                )
-                .stroke();
-            page.add_content(underline);
-
-            page.add_span(SpanLayout {
-                text: entry.text,
-                font: entry_font,
-                colour: colours::BLACK,
-                coords: (x, y),
-            });
-            page.add_span(SpanLayout {
-                text: pagenum,
-                font: entry_font,
-                colour: colours::BLACK,
-                coords: (page.content_box.x2 - pagenum_width, y),
-            });
-
-            page.add_intradocument_link_by_index(
-                Rect {
-                    x1: page.content_box.x1,
-                    x2: page.content_box.x2,
-                    y1: y,
-                    y2: y + doc.fonts[font_ids.regular].ascent(entry_size),
-                },
-                entry.abs_page + skip_pages + num_toc_pages,
-            );
-
-            y -= height_entry;
         }
+        let rule_top = top + doc.fonts[font_ids.regular].ascent(entry_size);
+        draw_column_rules(&mut page, &boxes, used, rule_top, &page_layout.columns);
 
         pages.push(page);
     }
@@ -429,3 +489,135 @@ pub fn render(
 
     Ok(added_page_count)
 }
+
+/// Number of pages the table of contents takes for `entry_count` entries, padded
+/// to an even count.
+pub fn page_count(
+    page_layout: &PageLayout,
+    doc: &Document,
+    font_ids: &FontIds,
+    skip_pages: usize,
+    entry_count: usize,
+) -> usize {
+    let contents_size = Pt(page_layout.fonts.heading_pt);
+    let entry_size = Pt(page_layout.fonts.body_pt);
+    let height_contents = doc.fonts[font_ids.bold].line_height(contents_size);
+    let height_entry = doc.fonts[font_ids.regular].line_height(entry_size);
+    let descent_entry = doc.fonts[font_ids.regular].descent(entry_size);
+
+    // create a temporary page to calculate layout metrics
+    let temp_page = page_layout.new_page(skip_pages);
+    let (_, start_y_first) =
+        layout::baseline_start(&temp_page, &doc.fonts[font_ids.bold], contents_size);
+    let (_, start_y_subsequent) =
+        layout::baseline_start(&temp_page, &doc.fonts[font_ids.regular], entry_size);
+    let bottom_y = temp_page.content_box.y1 + descent_entry;
+
+    // first page: starts after "Contents" heading
+    let first_page_usable = start_y_first - height_contents - bottom_y;
+    let entries_first_page = (first_page_usable / height_entry).floor() as usize;
+
+    // subsequent pages: full height available
+    let subsequent_page_usable = start_y_subsequent - bottom_y;
+    let entries_per_subsequent = (subsequent_page_usable / height_entry).floor() as usize;
+
+    // every column of a page holds as many entries as the first
+    let columns = page_layout.columns.count.max(1);
+    let entries_first_page = entries_first_page * columns;
+    let entries_per_subsequent = entries_per_subsequent * columns;
+
+    // calculate total pages needed
+    let entries_after_first = entry_count.saturating_sub(entries_first_page);
+    let additional_pages = entries_after_first.div_ceil(entries_per_subsequent.max(1));
+    let mut count = 1 + additional_pages;
+
+    // pad to even for booklet alignment
+    if count % 2 == 1 {
+        count += 1;
+    }
+    count
+}
+
+/// Number of entries the table of contents will have, for [`page_count`] ahead of
+/// rendering it: each section's heading and files, and one per appendix.
+pub fn entry_count(
+    frontmatter_pages: &HashMap<PathBuf, usize>,
+    tour_pages: &[(String, usize)],
+    source_pages: &HashMap<PathBuf, usize>,
+    appendices: usize,
+) -> usize {
+    let section = |files: usize| if files == 0 { 0 } else { files + 1 };
+    let source_entries = flatten_tree(&build_tree(source_pages.clone())).len();
+    section(frontmatter_pages.len()) + section(tour_pages.len()) + source_entries + appendices
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sinks::pdf::rendering::count_recto_blank;
+    use crate::sinks::pdf::rendering::header_footer::PageMetadata;
+
+    /// Place files of the given lengths as `RectoStart` does, returning where each
+    /// starts and the section's page count.
+    fn place_recto_start(
+        page_metadata: &mut Vec<PageMetadata>,
+        section: Section,
+        lengths: &[usize],
+    ) -> (Vec<usize>, usize) {
+        let mut page_count = 0;
+        let mut starts = Vec::new();
+        for length in lengths {
+            if page_metadata.len() % 2 == 1 {
+                count_recto_blank(page_metadata, section, &mut page_count);
+            }
+            starts.push(page_metadata.len());
+            for _ in 0..*length {
+                page_metadata.push(PageMetadata::new(section, page_count));
+                page_count += 1;
+            }
+        }
+        (starts, page_count)
+    }
+
+    #[test]
+    fn recto_start_blanks_are_counted_and_cited() {
+        let mut page_metadata = Vec::new();
+        // a three-page README leaves the LICENSE a blank page to skip
+        let (frontmatter, frontmatter_count) =
+            place_recto_start(&mut page_metadata, Section::Frontmatter, &[3, 1]);
+        // the source starts after a blank, and has one between its files
+        let (source, source_count) =
+            place_recto_start(&mut page_metadata, Section::Source, &[1, 1]);
+
+        assert_eq!(frontmatter, vec![0, 4]);
+        assert_eq!(frontmatter_count, 5);
+        assert_eq!(source, vec![6, 8]);
+        assert_eq!(source_count, 3);
+
+        let entries = file_entries(
+            HashMap::from([
+                (PathBuf::from("README.md"), frontmatter[0]),
+                (PathBuf::from("LICENSE"), frontmatter[1]),
+            ]),
+            Vec::new(),
+            HashMap::from([
+                (PathBuf::from("a.rs"), source[0]),
+                (PathBuf::from("b.rs"), source[1]),
+            ]),
+        );
+        let cited = |name: &str| {
+            let entry = entries
+                .iter()
+                .find(|entry| entry.text.ends_with(name))
+                .expect("file is listed");
+            let page = &page_metadata[entry.abs_page];
+            assert_eq!(page.section, entry.section);
+            assert_eq!(page.page_in_section, entry.page_in_section);
+            entry.page_in_section
+        };
+        assert_eq!(cited("README.md"), 0);
+        assert_eq!(cited("LICENSE"), 4);
+        assert_eq!(cited("a.rs"), 0);
+        assert_eq!(cited("b.rs"), 2);
+    }
+}
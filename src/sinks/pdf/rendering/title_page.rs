@@ -30,6 +30,9 @@
 //! 2. Vertically centre the entire block on the page
 //! 3. Render image and text segments from top to bottom
 //! 4. Each text line is horizontally centred
+//!
+//! The print-on-demand cover lays out its front and back the same way, within an
+//! area of the cover instead of the whole page (see [`render_template`]).
 
 use crate::sinks::pdf::config::{TitlePageImagePosition, PDF};
 use crate::sinks::pdf::fonts::FontIds;
@@ -38,6 +41,7 @@ use crate::source::Source;
 use anyhow::Result;
 use jiff::Zoned;
 use pdf_gen::*;
+use std::path::Path;
 
 /// A segment of the title page template.
 #[derive(Debug, Clone, PartialEq)]
@@ -154,6 +158,10 @@ fn source_title_marker() -> &'static str {
     "__TITLE_MARKER__"
 }
 
+/// An image loaded into a document and sized to fit: its arena index, width and
+/// height.
+pub type SizedImage = (usize, Pt, Pt);
+
 /// Render the title page with customisable template and optional image.
 ///
 /// The title page is always exactly one page. Content is vertically centred
@@ -170,47 +178,119 @@ pub fn render(
     source: &Source,
     image_paths: &mut ImagePathMap,
 ) -> Result<()> {
-    let title_size = Pt(config.fonts.title_pt);
-    let body_size = Pt(config.fonts.body_pt);
-    const SPACING: Pt = Pt(72.0 * 0.25); // spacing between image and text
-
     let page_size = config.page_size();
     let mut page = Page::new(page_size, None);
 
     // load image if configured
     let image_data = if let Some(image_path) = config.title_page_image_path() {
-        let image = Image::new_from_disk(&image_path)?;
-        let aspect_ratio = image.aspect_ratio();
-        let image_id = doc.add_image(image);
-        let image_index = image_id.index();
+        let image = load_image(config, doc, &image_path, page_size)?;
 
         // track for booklet rendering
-        image_paths.insert(image_index, image_path.clone());
+        image_paths.insert(image.0, image_path);
 
-        // calculate image size (constrain by max height and page width)
-        let max_height = config.title_page.image_max_height_in * 72.0;
-        let max_width = page_size.0 .0 * 0.8; // 80% of page width
+        Some(image)
+    } else {
+        None
+    };
 
-        let (width, height) = if aspect_ratio >= 1.0 {
-            // landscape: constrain by width first
-            let w = max_width.min(max_height * aspect_ratio);
-            let h = w / aspect_ratio;
-            (Pt(w), Pt(h.min(max_height)))
-        } else {
-            // portrait: constrain by height first
-            let h = max_height;
-            let w = (h * aspect_ratio).min(max_width);
-            (Pt(w), Pt(h))
-        };
+    let area = Rect {
+        x1: Pt(0.0),
+        y1: Pt(0.0),
+        x2: page_size.0,
+        y2: page_size.1,
+    };
+    render_template(
+        config,
+        doc,
+        font_ids,
+        &mut page,
+        source,
+        TemplateBlock {
+            template: &config.title_page.template,
+            image: image_data,
+            area,
+        },
+    );
 
-        Some((image_index, width, height))
+    doc.add_page(page);
+    Ok(())
+}
+
+/// Load an image into `doc`, scaled to fit an area of `area_size` (the title
+/// page, or the front of the cover).
+///
+/// The image keeps its aspect ratio within the configured maximum height and 80%
+/// of the area's width, and never takes more than half the area's height so there's
+/// room left for the title on short landscape pages.
+pub fn load_image(
+    config: &PDF,
+    doc: &mut Document,
+    image_path: &Path,
+    area_size: (Pt, Pt),
+) -> Result<SizedImage> {
+    let image = Image::new_from_disk(image_path)?;
+    let aspect_ratio = image.aspect_ratio();
+    let image_id = doc.add_image(image);
+    let image_index = image_id.index();
+
+    // calculate image size (constrain by max height and area width)
+    let max_height = (config.title_page.image_max_height_in * 72.0).min(area_size.1 .0 * 0.5);
+    let max_width = area_size.0 .0 * 0.8; // 80% of area width
+
+    let (width, height) = if aspect_ratio >= 1.0 {
+        // landscape: constrain by width first
+        let w = max_width.min(max_height * aspect_ratio);
+        let h = w / aspect_ratio;
+        (Pt(w), Pt(h.min(max_height)))
     } else {
-        None
+        // portrait: constrain by height first
+        let h = max_height;
+        let w = (h * aspect_ratio).min(max_width);
+        (Pt(w), Pt(h))
     };
 
+    Ok((image_index, width, height))
+}
+
+/// A template to lay out, the image to go with it, and the part of the page they
+/// share.
+pub struct TemplateBlock<'a> {
+    /// Template text with `{title}`, `{authors}` and the other placeholders
+    pub template: &'a str,
+    /// The image, already loaded into the document, if there is one
+    pub image: Option<SizedImage>,
+    /// The part of the page to centre within
+    pub area: Rect,
+}
+
+/// Lay out a template, and the image if there is one, centred within the area of
+/// `page` that `block` gives.
+///
+/// This is the title page's layout, shared with the covers: `{title}` is set in
+/// the title font and the image goes where `[pdf.title_page]` puts it.
+pub fn render_template(
+    config: &PDF,
+    doc: &Document,
+    font_ids: &FontIds,
+    page: &mut Page,
+    source: &Source,
+    block: TemplateBlock,
+) {
+    let TemplateBlock {
+        template,
+        image: image_data,
+        area,
+    } = block;
+    let title_size = Pt(config.fonts.title_pt);
+    let body_size = Pt(config.fonts.body_pt);
+    const SPACING: Pt = Pt(72.0 * 0.25); // spacing between image and text
+
+    let area_width = area.x2 - area.x1;
+    let area_height = area.y2 - area.y1;
+
     // expand template and parse into segments
     // temporarily mark title for identification after expansion
-    let template = config.title_page.template.replace(
+    let template = template.replace(
         "{title}",
         &format!(
             "{}\n{}",
@@ -235,22 +315,22 @@ pub fn render(
     // determine starting y position based on image position
     let (image_y, text_start_y) = match config.title_page.image_position {
         TitlePageImagePosition::Top => {
-            let start_y = (page_size.1 + total_height) / 2.0;
+            let start_y = area.y1 + (area_height + total_height) / 2.0;
             let image_y = image_data.as_ref().map(|(_, _, h)| start_y - *h);
             let text_y = start_y - image_height;
             (image_y, text_y)
         }
         TitlePageImagePosition::Centre => {
             // image in centre, text above and below (text flows around)
-            // for simplicity, put image in centre of page, text above it
+            // for simplicity, put image in centre of the area, text above it
             let image_y = image_data
                 .as_ref()
-                .map(|(_, _, h)| (page_size.1 + *h) / 2.0 - *h);
-            let text_y = (page_size.1 + total_height) / 2.0;
+                .map(|(_, _, h)| area.y1 + (area_height + *h) / 2.0 - *h);
+            let text_y = area.y1 + (area_height + total_height) / 2.0;
             (image_y, text_y)
         }
         TitlePageImagePosition::Bottom => {
-            let start_y = (page_size.1 + total_height) / 2.0;
+            let start_y = area.y1 + (area_height + total_height) / 2.0;
             let text_y = start_y;
             let image_y = image_data
                 .as_ref()
@@ -262,7 +342,7 @@ pub fn render(
     // render image if present and position is Top
     if let (Some((image_index, width, height)), Some(img_y)) = (&image_data, image_y) {
         if config.title_page.image_position == TitlePageImagePosition::Top {
-            let x = (page_size.0 - *width) / 2.0;
+            let x = area.x1 + (area_width - *width) / 2.0;
             page.add_image(ImageLayout {
                 image_index: *image_index,
                 position: Rect {
@@ -296,7 +376,7 @@ pub fn render(
 
                 let line_height = doc.fonts[font_id].line_height(size);
                 let text_width = layout::width_of_text(line, &doc.fonts[font_id], size);
-                let x = (page_size.0 - text_width) / 2.0;
+                let x = area.x1 + (area_width - text_width) / 2.0;
 
                 if !line.is_empty() {
                     page.add_span(SpanLayout {
@@ -318,7 +398,7 @@ pub fn render(
                         layout::width_of_text(line, &doc.fonts[font_ids.regular], body_size)
                     })
                     .fold(Pt(0.0), |a, b| if b.0 > a.0 { b } else { a });
-                let x = (page_size.0 - max_width) / 2.0;
+                let x = area.x1 + (area_width - max_width) / 2.0;
 
                 for line in lines {
                     page.add_span(SpanLayout {
@@ -344,9 +424,9 @@ pub fn render(
             TitlePageImagePosition::Bottom => true,
         };
         if render_now {
-            let x = (page_size.0 - *width) / 2.0;
+            let x = area.x1 + (area_width - *width) / 2.0;
             let image_y = match config.title_page.image_position {
-                TitlePageImagePosition::Centre => (page_size.1 - *height) / 2.0,
+                TitlePageImagePosition::Centre => area.y1 + (area_height - *height) / 2.0,
                 TitlePageImagePosition::Bottom => y - SPACING,
                 TitlePageImagePosition::Top => unreachable!(),
             };
@@ -361,7 +441,4 @@ pub fn render(
             });
         }
     }
-
-    doc.add_page(page);
-    Ok(())
 }
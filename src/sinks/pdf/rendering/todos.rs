@@ -0,0 +1,325 @@
+//! TODO register appendix rendering.
+//!
+//! With `[pdf.todos]` enabled, comments containing one of the configured tags
+//! (`TODO`, `FIXME`, `HACK`, `XXX` by default) are collected as each source file is
+//! laid out, found in the same pass that highlights the listing (see
+//! [`crate::syntax_analysis::FileSymbols::markers`]). The register lists them grouped by tag or by file, each
+//! with its file, line, comment text and the number of the page it's on.
+//!
+//! The register is rendered with the other appendices, before the table of
+//! contents is inserted and before the pages are numbered per volume, if they are.
+//! Room is left for each page number, which is added with the entry's link by
+//! [`TodosRenderResult::resolve`] once every page has its final number.
+
+use crate::sinks::pdf::config::{PageLayout, TodoGrouping, VolumeNumbering, PDF};
+use crate::sinks::pdf::fonts::FontIds;
+use crate::sinks::pdf::rendering::flow::{draw_line, Flow};
+use crate::sinks::pdf::rendering::header_footer::{page_label, page_reference, PageMetadata};
+use crate::sinks::pdf::rendering::index::fit_start;
+use crate::syntax_analysis::Marker;
+use anyhow::Result;
+use pdf_gen::*;
+use std::path::{Path, PathBuf};
+
+/// A marker comment and the page it landed on.
+pub struct TodoEntry {
+    pub tag: String,
+    pub file: PathBuf,
+    /// Zero-based line index of the comment
+    pub line: usize,
+    pub text: String,
+    /// Page index of the comment, before the table of contents is inserted
+    pub page: usize,
+}
+
+/// Marker comments collected from the listings, in book order.
+pub struct Todos {
+    tags: Vec<String>,
+    entries: Vec<TodoEntry>,
+}
+
+impl Todos {
+    pub fn new(config: &PDF) -> Self {
+        Self {
+            tags: config.todos.tags.clone(),
+            entries: Vec::new(),
+        }
+    }
+
+    /// The tags that mark a comment for the register.
+    pub fn tags(&self) -> &[String] {
+        &self.tags
+    }
+
+    /// Record a file's markers once its listing has been laid out.
+    pub fn add_file(&mut self, path: &Path, markers: Vec<Marker>, line_pages: &[Option<usize>]) {
+        for marker in markers {
+            let Some(&Some(page)) = line_pages.get(marker.line) else {
+                continue;
+            };
+            self.entries.push(TodoEntry {
+                tag: marker.tag,
+                file: path.to_path_buf(),
+                line: marker.line,
+                text: marker.text,
+                page,
+            });
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+/// A register entry's page number and link, waiting for the table of contents to
+/// be inserted.
+struct PendingRow {
+    page: usize,
+    rect: Rect,
+    baseline: Pt,
+    /// Where the leader to the page number starts, after the location
+    leader_start: Pt,
+    target: usize,
+}
+
+/// Result of rendering the TODO register.
+pub struct TodosRenderResult {
+    /// Page index of the first content page, or None if there were no entries.
+    pub first_page: Option<usize>,
+    /// Whether a blank page was inserted for recto alignment.
+    pub blank_inserted: bool,
+    rows: Vec<PendingRow>,
+}
+
+impl TodosRenderResult {
+    /// Add the entries' page numbers, with leaders to them, and links.
+    ///
+    /// `link_offset` and `content_offset` are as for
+    /// [`super::cross_references::CrossReferences::resolve`].
+    pub fn resolve(
+        self,
+        config: &PDF,
+        page_layout: &PageLayout,
+        doc: &mut Document,
+        font_ids: &FontIds,
+        content_offset: usize,
+        page_metadata: &[PageMetadata],
+        link_offset: usize,
+    ) {
+        let font = SpanFont {
+            id: font_ids.regular,
+            size: Pt(page_layout.fonts.body_pt),
+        };
+        for row in self.rows {
+            let page_id = doc.page_order[row.page + link_offset];
+            let text = page_metadata
+                .get(row.target.saturating_sub(content_offset))
+                .map(|metadata| page_reference(config, metadata))
+                .unwrap_or_default();
+            let fonts = &doc.fonts;
+            let x = row.rect.x2 - layout::width_of_text(&text, &fonts[font.id], font.size);
+            let page = doc.pages.get_mut(page_id).expect("page exists");
+
+            // a leader from the location to its page number, as in the index
+            let leader_end = x - layout::width_of_text(" ", &fonts[font.id], font.size);
+            if leader_end > row.leader_start {
+                let y = row.baseline - Pt(1.5);
+                draw_line(page, (row.leader_start, y), (leader_end, y), Pt(0.5), 0.75);
+            }
+            page.add_span(SpanLayout {
+                text,
+                font,
+                colour: colours::BLACK,
+                coords: (x, row.baseline),
+            });
+            page.add_intradocument_link_by_index(row.rect, row.target + link_offset);
+        }
+    }
+}
+
+/// Render the TODO register.
+///
+/// `content_offset` is the page index of the first content page, matching the
+/// first entry of `page_metadata`.
+pub fn render(
+    config: &PDF,
+    page_layout: &PageLayout,
+    doc: &mut Document,
+    font_ids: &FontIds,
+    todos: Todos,
+    content_offset: usize,
+    page_metadata: &[PageMetadata],
+) -> Result<TodosRenderResult> {
+    let Todos { tags, mut entries } = todos;
+    if entries.is_empty() {
+        return Ok(TodosRenderResult {
+            first_page: None,
+            blank_inserted: false,
+            rows: Vec::new(),
+        });
+    }
+
+    // entries are in book order already, so a stable sort by tag keeps files in order
+    if config.todos.group_by == TodoGrouping::Tag {
+        entries.sort_by_key(|entry| tags.iter().position(|tag| *tag == entry.tag));
+    }
+
+    // insert a blank page so we open to the correct side (recto)
+    let blank_inserted = doc.page_order.len() % 2 == 1;
+    if blank_inserted {
+        doc.add_page(Page::new(page_layout.page_size, None));
+    }
+
+    let heading_size = Pt(page_layout.fonts.heading_pt);
+    let subheading_size = Pt(page_layout.fonts.subheading_pt);
+    let text_size = Pt(page_layout.fonts.body_pt);
+    let small_size = Pt(page_layout.fonts.small_pt);
+
+    let heading_font = SpanFont {
+        id: font_ids.bold,
+        size: heading_size,
+    };
+    let group_font = SpanFont {
+        id: font_ids.bold,
+        size: subheading_size,
+    };
+    let text_font = SpanFont {
+        id: font_ids.regular,
+        size: text_size,
+    };
+    let detail_font = SpanFont {
+        id: font_ids.regular,
+        size: small_size,
+    };
+    let detail_colour = Colour::new_grey(0.5);
+    let per_volume =
+        config.volumes.enabled() && config.volumes.numbering == VolumeNumbering::PerVolume;
+
+    let mut flow = Flow::new(page_layout, doc, font_ids);
+    let heading_height = flow.doc.fonts[font_ids.bold].line_height(heading_size);
+    let heading_ascent = flow.doc.fonts[font_ids.bold].ascent(heading_size);
+    let group_height = flow.doc.fonts[font_ids.bold].line_height(subheading_size);
+    let group_ascent = flow.doc.fonts[font_ids.bold].ascent(subheading_size);
+    let entry_height = flow.doc.fonts[font_ids.regular].line_height(text_size);
+    let entry_ascent = flow.doc.fonts[font_ids.regular].ascent(text_size);
+    let indent = layout::width_of_text("  ", &flow.doc.fonts[font_ids.regular], text_size);
+
+    flow.add_line(
+        Pt(0.0),
+        vec![("TODO Register".to_string(), colours::BLACK, heading_font)],
+        heading_height,
+        heading_ascent,
+    );
+
+    let mut rows = Vec::new();
+    let mut group: Option<String> = None;
+    for entry in entries {
+        let (entry_group, label) = match config.todos.group_by {
+            TodoGrouping::Tag => (
+                entry.tag.clone(),
+                format!("{}:{}", entry.file.display(), entry.line + 1),
+            ),
+            TodoGrouping::File => (
+                entry.file.display().to_string(),
+                format!("{} line {}", entry.tag, entry.line + 1),
+            ),
+        };
+        if group.as_ref() != Some(&entry_group) {
+            flow.gap(entry_height * 0.5);
+            // keep the group heading with its first entry
+            flow.ensure_space(group_height + entry_height * 2.0);
+            flow.add_line(
+                Pt(0.0),
+                vec![(entry_group.clone(), colours::BLACK, group_font)],
+                group_height,
+                group_ascent,
+            );
+            group = Some(entry_group);
+        }
+
+        // room for the page number, which may yet be numbered per volume
+        let page = page_metadata
+            .get(entry.page.saturating_sub(content_offset))
+            .map(|metadata| page_label(config, metadata))
+            .unwrap_or_default();
+        let page = if per_volume {
+            format!(" vol. 00 · {}", page)
+        } else {
+            format!(" {}", page)
+        };
+        let measure = flow.measure();
+        let page_width = layout::width_of_text(&page, &flow.doc.fonts[font_ids.regular], text_size);
+        let label = fit_start(&label, measure - page_width, |text| {
+            layout::width_of_text(text, &flow.doc.fonts[font_ids.regular], small_size)
+        });
+        let label_width =
+            layout::width_of_text(&label, &flow.doc.fonts[font_ids.regular], small_size);
+
+        // keep the location with the first line of the comment
+        let text_lines = wrap_words(&entry.text, measure - indent, |text| {
+            layout::width_of_text(text, &flow.doc.fonts[font_ids.regular], text_size)
+        });
+        let lines_together = if text_lines.is_empty() { 1.0 } else { 2.0 };
+        flow.ensure_space(entry_height * lines_together);
+
+        let baseline = flow.add_line_at(
+            vec![(Pt(0.0), (label, detail_colour, detail_font))],
+            entry_height,
+            entry_ascent,
+        );
+        let bbox = flow.content_box();
+        rows.push(PendingRow {
+            page: flow.page_index(),
+            rect: Rect {
+                x1: bbox.x1,
+                x2: bbox.x2,
+                y1: baseline,
+                y2: baseline + entry_ascent,
+            },
+            baseline,
+            leader_start: bbox.x1 + label_width,
+            target: entry.page,
+        });
+
+        for line in text_lines {
+            flow.add_line(
+                indent,
+                vec![(line, colours::BLACK, text_font)],
+                entry_height,
+                entry_ascent,
+            );
+        }
+    }
+
+    let result = flow.finish();
+    Ok(TodosRenderResult {
+        first_page: result.first_page,
+        blank_inserted,
+        rows,
+    })
+}
+
+/// Break text into lines no wider than `width` at spaces.
+///
+/// Words wider than `width` get a line of their own.
+fn wrap_words(text: &str, width: Pt, measure: impl Fn(&str) -> Pt) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        if line.is_empty() {
+            line.push_str(word);
+            continue;
+        }
+        let candidate = format!("{} {}", line, word);
+        if measure(&candidate) <= width {
+            line = candidate;
+        } else {
+            lines.push(std::mem::replace(&mut line, word.to_string()));
+        }
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
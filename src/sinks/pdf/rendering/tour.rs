@@ -0,0 +1,306 @@
+//! Guided tour rendering.
+//!
+//! Each tour in `source.tour_files` is typeset as a narrative through the Markdown
+//! [`Typesetter`]: the tour's title and introduction, then each step's commentary
+//! followed by the lines it points at, numbered and highlighted like a listing.
+//! Highlighting starts from the top of the file so the parse state is right for
+//! the lines shown.
+//!
+//! Tours come before the source files, so when a step is placed the page its
+//! file's listing will land on isn't known yet. Each step ends with a "Full
+//! listing" row naming the file and line; the page number and link are added by
+//! [`TourLinks::resolve`] once the table of contents has been inserted, the same
+//! way outline boxes are.
+
+use crate::markdown::parse;
+use crate::sinks::pdf::config::{PageLayout, PDF};
+use crate::sinks::pdf::fonts::FontIds;
+use crate::sinks::pdf::rendering::flow::Continuation;
+use crate::sinks::pdf::rendering::header_footer::{page_reference, PageMetadata};
+use crate::sinks::pdf::rendering::markdown::Typesetter;
+use crate::sinks::pdf::rendering::source_file::code_spans;
+use crate::sinks::pdf::rendering::ImagePathMap;
+use crate::tour::{self, Step};
+use crate::whitespace;
+use anyhow::{Context, Result};
+use pdf_gen::*;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use syntect::easy::HighlightLines;
+use syntect::highlighting::Theme;
+use syntect::parsing::SyntaxSet;
+use syntect::util::LinesWithEndings;
+
+/// A "Full listing" row waiting for its file's page to be known.
+struct PendingRow {
+    page: usize,
+    rect: Rect,
+    baseline: Pt,
+    file: PathBuf,
+    /// Zero-based line the step starts at
+    line: usize,
+}
+
+/// Links from tour steps to the listings of the files they point at.
+pub struct TourLinks {
+    /// Files with a listing in the book
+    listed: HashSet<PathBuf>,
+    rows: Vec<PendingRow>,
+    /// First page and line pages of each rendered file
+    files: HashMap<PathBuf, (usize, Vec<Option<usize>>)>,
+}
+
+impl TourLinks {
+    /// `listed` are the source files that will get a listing.
+    pub fn new(listed: Vec<PathBuf>) -> Self {
+        Self {
+            listed: listed.into_iter().collect(),
+            rows: Vec::new(),
+            files: HashMap::new(),
+        }
+    }
+
+    /// Record where a file's listing landed once it has been laid out.
+    pub fn add_file(&mut self, path: &Path, first_page: usize, line_pages: &[Option<usize>]) {
+        self.files
+            .insert(path.to_path_buf(), (first_page, line_pages.to_vec()));
+    }
+
+    /// The page a step's line landed on: the line's own page, the page of the next
+    /// line shown if an excerpt left it out, or the file's first page.
+    fn target(&self, file: &Path, line: usize) -> Option<usize> {
+        let (first_page, line_pages) = self.files.get(file)?;
+        line_pages
+            .iter()
+            .skip(line)
+            .find_map(|page| *page)
+            .or(Some(*first_page))
+    }
+
+    /// Add the page numbers and links to the "Full listing" rows.
+    ///
+    /// `link_offset` and `content_offset` are as for
+    /// [`super::cross_references::CrossReferences::resolve`].
+    pub fn resolve(
+        self,
+        config: &PDF,
+        page_layout: &PageLayout,
+        doc: &mut Document,
+        font_ids: &FontIds,
+        content_offset: usize,
+        page_metadata: &[PageMetadata],
+        link_offset: usize,
+    ) {
+        let font = SpanFont {
+            id: font_ids.regular,
+            size: Pt(page_layout.fonts.small_pt),
+        };
+        for row in &self.rows {
+            let Some(target) = self.target(&row.file, row.line) else {
+                continue;
+            };
+            let page_id = doc.page_order[row.page + link_offset];
+            if let Some(metadata) = page_metadata.get(target.saturating_sub(content_offset)) {
+                let text = page_reference(config, metadata);
+                let width = layout::width_of_text(&text, &doc.fonts[font.id], font.size);
+                doc.pages
+                    .get_mut(page_id)
+                    .expect("page exists")
+                    .add_span(SpanLayout {
+                        text,
+                        font,
+                        colour: colours::BLACK,
+                        coords: (row.rect.x2 - width, row.baseline),
+                    });
+            }
+            doc.pages
+                .get_mut(page_id)
+                .expect("page exists")
+                .add_intradocument_link_by_index(row.rect, target + link_offset);
+        }
+    }
+}
+
+/// Result of rendering a guided tour.
+pub struct TourRenderResult {
+    /// The tour's title, for the table of contents and bookmarks
+    pub title: String,
+    /// Page index of the first page, or None if nothing was rendered
+    pub first_page: Option<usize>,
+    /// Number of pages rendered
+    pub page_count: usize,
+}
+
+/// Where a tour's images go and where its steps link to.
+pub struct TourContext<'a> {
+    /// Paths of the images placed in the document, so the booklet can reload them
+    pub image_paths: &'a mut ImagePathMap,
+    /// The "Full listing" rows waiting for their files' pages
+    pub links: &'a mut TourLinks,
+    /// The page to carry on from, for tours placed `Continuous`
+    pub continuation: Option<&'a mut Continuation>,
+}
+
+/// Render a guided tour, following on from the tour before when `context` has a
+/// continuation.
+pub fn render(
+    config: &PDF,
+    page_layout: &PageLayout,
+    doc: &mut Document,
+    font_ids: &FontIds,
+    path: &Path,
+    ss: &SyntaxSet,
+    theme: &Theme,
+    context: TourContext,
+) -> Result<TourRenderResult> {
+    let tour = tour::load(path)?;
+    let base_dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+    let links = context.links;
+
+    let mut typesetter = Typesetter::new(
+        config,
+        page_layout,
+        doc,
+        font_ids,
+        ss,
+        theme,
+        &base_dir,
+        context.image_paths,
+    );
+    if let Some(continuation) = context.continuation {
+        typesetter.flow.continue_file(continuation, font_ids, path);
+    }
+    let step_gap =
+        typesetter.flow.doc.fonts[font_ids.regular].line_height(Pt(page_layout.fonts.body_pt));
+
+    let mut introduction = format!("# {}\n", tour.title);
+    if let Some(description) = &tour.description {
+        introduction.push('\n');
+        introduction.push_str(description);
+    }
+    typesetter.blocks(&parse(&introduction), Pt(0.0))?;
+
+    for (i, step) in tour.steps.iter().enumerate() {
+        typesetter.flow.gap(step_gap);
+        let heading = match &step.title {
+            Some(title) => format!("## {}. {}\n\n", i + 1, title),
+            None => format!("## Step {}\n\n", i + 1),
+        };
+        typesetter.blocks(&parse(&(heading + &step.description)), Pt(0.0))?;
+
+        if let Some(file) = &step.file {
+            let first_line = step_code(
+                config,
+                page_layout,
+                &mut typesetter,
+                font_ids,
+                file,
+                step,
+                ss,
+                theme,
+            )
+            .with_context(|| {
+                format!("Failed to render step {} of tour {}", i + 1, path.display())
+            })?;
+            if links.listed.contains(file) {
+                let row = listing_row(page_layout, &mut typesetter, font_ids, file, first_line);
+                links.rows.push(row);
+            }
+        }
+    }
+    let result = typesetter.flow.finish();
+    Ok(TourRenderResult {
+        title: tour.title,
+        first_page: result.first_page,
+        page_count: result.page_count,
+    })
+}
+
+/// Typeset the lines a step points at, returning the zero-based line the excerpt
+/// starts at. Files that can't be read as text are skipped.
+fn step_code(
+    config: &PDF,
+    page_layout: &PageLayout,
+    typesetter: &mut Typesetter,
+    font_ids: &FontIds,
+    file: &Path,
+    step: &Step,
+    ss: &SyntaxSet,
+    theme: &Theme,
+) -> Result<usize> {
+    let Some(lines) = &step.lines else {
+        return Ok(0);
+    };
+    let Ok(contents) = std::fs::read_to_string(file) else {
+        return Ok(lines.start);
+    };
+    let contents = whitespace::expand(&contents, &config.code);
+
+    let syntax = ss
+        .find_syntax_by_extension(
+            file.extension()
+                .map(std::ffi::OsStr::to_str)
+                .unwrap_or_default()
+                .unwrap_or_default(),
+        )
+        .unwrap_or_else(|| ss.find_syntax_plain_text());
+    let text_size = Pt(page_layout.fonts.body_pt);
+    let mut h = HighlightLines::new(syntax, theme);
+
+    let gap = typesetter.flow.doc.fonts[font_ids.regular].line_height(text_size) * 0.5;
+    typesetter.flow.gap(gap);
+    for (n, line) in LinesWithEndings::from(contents.as_str())
+        .enumerate()
+        .take(lines.end)
+    {
+        let ranges = h
+            .highlight_line(line, ss)
+            .with_context(|| format!("Failed to highlight source code for line `{}`", line))?;
+        if n < lines.start {
+            continue;
+        }
+        let spans = code_spans(config, ranges, font_ids, text_size);
+        typesetter.listing_line(n + 1, spans, &[], false);
+    }
+    Ok(lines.start)
+}
+
+/// Place the row pointing at a file's full listing, to be given its page number by
+/// [`TourLinks::resolve`].
+fn listing_row(
+    page_layout: &PageLayout,
+    typesetter: &mut Typesetter,
+    font_ids: &FontIds,
+    file: &Path,
+    line: usize,
+) -> PendingRow {
+    let size = Pt(page_layout.fonts.small_pt);
+    let flow = &mut typesetter.flow;
+    let row_height = flow.doc.fonts[font_ids.italic].line_height(size);
+    let row_ascent = flow.doc.fonts[font_ids.italic].ascent(size);
+    let row_descent = flow.doc.fonts[font_ids.italic].descent(size);
+
+    let text = format!("Full listing: {}, line {}", file.display(), line + 1);
+    let font = SpanFont {
+        id: font_ids.italic,
+        size,
+    };
+    let baseline = flow.add_line_at(
+        vec![(Pt(0.0), (text, Colour::new_grey(0.5), font))],
+        row_height,
+        row_ascent,
+    );
+    let bbox = flow.content_box();
+    PendingRow {
+        page: flow.page_index(),
+        rect: Rect {
+            x1: bbox.x1,
+            x2: bbox.x2,
+            y1: baseline + row_descent,
+            y2: baseline + row_ascent,
+        },
+        baseline,
+        file: file.to_path_buf(),
+        line,
+    }
+}
@@ -0,0 +1,60 @@
+//! 2-up reading copy generation.
+//!
+//! For reading at a desk rather than binding: each landscape sheet shows one
+//! spread of the book, the verso on the left and the recto on the right, so
+//! listings that run across a page turn can be read side by side. Landscape
+//! pages go one above the other on an upright sheet instead. Sheets are printed
+//! single-sided and not folded.
+//!
+//! Pages are copied into Form XObjects and placed with the booklet's
+//! [`create_imposed_page`], only in reading order (see
+//! [`crate::sinks::pdf::imposition::calculate_spreads`]) instead of signature
+//! order. Metadata, fonts and images are carried over as for the booklet.
+
+use crate::sinks::pdf::booklet::{
+    imposed_document, page_xobjects, sheet_layout, write_imposed, PageRun,
+};
+use crate::sinks::pdf::config::PDF;
+use crate::sinks::pdf::fonts::FontIds;
+use crate::sinks::pdf::imposition::{calculate_spreads, create_imposed_page};
+use crate::sinks::pdf::rendering::ImagePathMap;
+use crate::source::Source;
+use anyhow::Result;
+use pdf_gen::*;
+use std::path::PathBuf;
+
+/// Generate the 2-up reading copy of the digital document.
+///
+/// Returns the number of sheets it's printed on.
+pub fn render_two_up(
+    config: &PDF,
+    source: &Source,
+    source_doc: &Document,
+    source_font_ids: &FontIds,
+    image_paths: &ImagePathMap,
+    output_path: &PathBuf,
+) -> Result<usize> {
+    let layout = sheet_layout(
+        config,
+        config.two_up.sheet_width_in,
+        config.two_up.sheet_height_in,
+    );
+    let (mut doc, font_ids) = imposed_document(config, source, "2-up")?;
+    let page_xobjs = page_xobjects(
+        &mut doc,
+        &font_ids,
+        &[PageRun::all(source_doc, source_font_ids, image_paths)],
+        Pt(0.0),
+        "2-up copy",
+    )?;
+
+    let spreads = calculate_spreads(page_xobjs.len());
+    for spread in spreads.iter() {
+        let left = spread.left_page.map(|idx| page_xobjs[idx]);
+        let right = spread.right_page.map(|idx| page_xobjs[idx]);
+        doc.add_page(create_imposed_page(&layout, left, right));
+    }
+
+    write_imposed(&doc, output_path, "2-up")?;
+    Ok(spreads.len())
+}
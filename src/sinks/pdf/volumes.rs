@@ -0,0 +1,362 @@
+//! Splitting a book too thick to bind into volumes.
+//!
+//! The book is rendered whole, as always, and the digital PDF is written whole;
+//! volumes are made from it afterwards for printing. Each volume is a PDF of its
+//! own beside the main one (`book.pdf` gives `book-vol1.pdf`, `book-vol2.pdf`, ...)
+//! with its share of the pages, and its own booklet and cover if those are
+//! configured.
+//!
+//! ## Where Volumes Break
+//!
+//! A volume only starts where a source file or the appendices do, so no file is
+//! split between volumes unless it's too long for one by itself. Volumes start
+//! before each file or directory in `split_before`, and before any file that would
+//! otherwise take the volume past `max_pages_per_volume`. The breaks are planned
+//! just before the table of contents is rendered, so it can say which volume each
+//! entry is in; the symbol index, rendered after it, goes at the end of the last
+//! volume on top of the limit.
+//!
+//! ## Title Pages and Numbering
+//!
+//! Each volume opens with the title page, with a line such as "Volume 2 of 3"
+//! added below the template; the first volume's replaces the book's own. The front
+//! of a volume's cover carries the same line, and its spine can give the volume's
+//! number. Volumes starting on a recto get a blank verso after their title page,
+//! so every page is printed on the same side it is in the book. Pages keep the
+//! book's numbering by default, so the table of contents, index and
+//! cross-references in any volume refer to the others. Numbered per volume, each
+//! section counts again from its first page in each volume (see
+//! [`number_per_volume`]), and pages elsewhere are cited with their volume.
+//!
+//! Like the booklet's, a volume's pages are copies of the book's in Form XObjects,
+//! without links or bookmarks.
+
+use crate::sinks::pdf::booklet::{
+    imposed_document, page_xobjects, render_booklet, write_imposed, PageRun,
+};
+use crate::sinks::pdf::config::{Section, VolumeStats, PDF};
+use crate::sinks::pdf::cover::render_cover;
+use crate::sinks::pdf::fonts::FontIds;
+use crate::sinks::pdf::rendering::{render_title_page, ImagePathMap, PageMetadata};
+use crate::source::Source;
+use anyhow::{Context, Result};
+use pdf_gen::*;
+use std::collections::HashMap;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+/// One volume of the book: its number, from 1, and the book's pages in it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Volume {
+    pub number: usize,
+    pub pages: Range<usize>,
+}
+
+/// The configuration a volume's title page and cover are made with: its label
+/// added below the title page template, and its number filled in on the spine.
+fn labelled_config(config: &PDF, volume: &Volume, count: usize) -> PDF {
+    let fill = |template: &str| {
+        template
+            .replace("{volume}", &volume.number.to_string())
+            .replace("{volumes}", &count.to_string())
+    };
+    let mut labelled = config.clone();
+    labelled.title_page.template = format!(
+        "{}\n\n{}",
+        config.title_page.template,
+        fill(&config.volumes.label_template)
+    );
+    labelled.cover.spine_template = fill(&config.cover.spine_template);
+    labelled
+}
+
+/// Pages a volume starting at book page `start` opens with: its title page, and a
+/// blank verso if `start` is a recto.
+fn title_pages(start: usize) -> usize {
+    if start % 2 == 0 {
+        2
+    } else {
+        1
+    }
+}
+
+/// Where each volume after the first starts, as pages of the book.
+///
+/// `candidates` are the pages a volume may start on, in order, of which `forced`
+/// must start one; `end` is the book's page count. A volume is broken off before a
+/// candidate if running on to the next one (or the end) would take it past
+/// `max_pages`, counting its title pages; 0 means no limit. The book's own title
+/// page is left out of the first volume, which gets a volume title page instead.
+pub fn plan_breaks(
+    candidates: &[usize],
+    forced: &[usize],
+    end: usize,
+    max_pages: usize,
+) -> Vec<usize> {
+    let mut breaks = Vec::new();
+    let mut start = 1;
+    for (i, &page) in candidates.iter().enumerate() {
+        if page <= start || page >= end {
+            continue;
+        }
+        let next = candidates.get(i + 1).copied().unwrap_or(end).min(end);
+        let too_long = max_pages > 0 && title_pages(start) + (next - start) > max_pages;
+        if too_long || forced.contains(&page) {
+            breaks.push(page);
+            start = page;
+        }
+    }
+    breaks
+}
+
+/// The volumes a book of `page_count` pages splits into at `breaks`.
+pub fn volumes(breaks: &[usize], page_count: usize) -> Vec<Volume> {
+    let starts = std::iter::once(1).chain(breaks.iter().copied());
+    let ends = breaks.iter().copied().chain(std::iter::once(page_count));
+    starts
+        .zip(ends)
+        .enumerate()
+        .map(|(i, (start, end))| Volume {
+            number: i + 1,
+            pages: start..end,
+        })
+        .collect()
+}
+
+/// Number the pages of a book split at `breaks` volume by volume: each section
+/// counts again from its first page in each volume, and each page notes the
+/// volume it's in. The first of `page_metadata` is book page `first_page`.
+pub fn number_per_volume(page_metadata: &mut [PageMetadata], first_page: usize, breaks: &[usize]) {
+    let mut volume = 0;
+    let mut starts: HashMap<Section, usize> = HashMap::new();
+    for (i, metadata) in page_metadata.iter_mut().enumerate() {
+        let page_volume = 1 + breaks.iter().filter(|b| **b <= first_page + i).count();
+        if page_volume != volume {
+            volume = page_volume;
+            starts.clear();
+        }
+        let start = *starts
+            .entry(metadata.section)
+            .or_insert(metadata.page_in_section);
+        metadata.page_in_section -= start;
+        metadata.volume = Some(volume);
+    }
+}
+
+/// `path` for volume `number`: `book.pdf` becomes `book-vol2.pdf`.
+pub fn volume_path(path: &Path, number: usize) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!("{}-vol{}.pdf", stem, number))
+}
+
+/// Write each volume of the rendered book, split at `breaks`, and its booklet and
+/// cover if configured.
+pub fn render_volumes(
+    config: &PDF,
+    source: &Source,
+    doc: &Document,
+    font_ids: &FontIds,
+    image_paths: &ImagePathMap,
+    breaks: &[usize],
+) -> Result<Vec<VolumeStats>> {
+    let volumes = volumes(breaks, doc.page_order.len());
+    let count = volumes.len();
+
+    // the title pages go in a document of their own, which is never written
+    let (mut titles_doc, title_font_ids) = imposed_document(config, source, "title pages")?;
+    let mut title_image_paths: ImagePathMap = HashMap::new();
+    let mut title_ranges: Vec<Range<usize>> = Vec::new();
+    for volume in volumes.iter() {
+        let title_config = labelled_config(config, volume, count);
+        let first = titles_doc.page_order.len();
+        render_title_page(
+            &title_config,
+            &mut titles_doc,
+            &title_font_ids,
+            source,
+            &mut title_image_paths,
+        )
+        .with_context(|| format!("Failed to render title page of volume {}", volume.number))?;
+        if title_pages(volume.pages.start) == 2 {
+            titles_doc.add_page(Page::new(config.page_size(), None));
+        }
+        title_ranges.push(first..titles_doc.page_order.len());
+    }
+
+    let mut stats = Vec::new();
+    for (volume, title_range) in volumes.iter().zip(title_ranges) {
+        let kind = format!("volume {} of {}", volume.number, count);
+        let runs = [
+            PageRun {
+                doc: &titles_doc,
+                font_ids: &title_font_ids,
+                image_paths: &title_image_paths,
+                pages: &titles_doc.page_order[title_range],
+            },
+            PageRun {
+                doc,
+                font_ids,
+                image_paths,
+                pages: &doc.page_order[volume.pages.clone()],
+            },
+        ];
+        let page_count: usize = runs.iter().map(|run| run.pages.len()).sum();
+
+        let outfile = volume_path(&config.outfile, volume.number);
+        let (mut volume_doc, volume_font_ids) = imposed_document(config, source, &kind)?;
+        let page_xobjs = page_xobjects(&mut volume_doc, &volume_font_ids, &runs, Pt(0.0), &kind)?;
+        for xobj_id in page_xobjs {
+            let mut page = Page::new(config.page_size(), None);
+            page.add_form_xobject(FormXObjectLayout {
+                xobj_id,
+                transform: Transform::scale(1.0, 1.0),
+            });
+            volume_doc.add_page(page);
+        }
+        write_imposed(&volume_doc, &outfile, &kind)?;
+
+        // each volume is bound on its own, with a booklet and cover to suit
+        let booklet = match config.booklet_outfile_path() {
+            Some(path) => {
+                let path = volume_path(&path, volume.number);
+                let mut volume_config = config.clone();
+                volume_config.booklet.outfile = path.display().to_string();
+                let sheets = render_booklet(&volume_config, source, &runs, &path)
+                    .with_context(|| format!("Failed to render booklet of {}", kind))?;
+                Some((path, sheets))
+            }
+            None => None,
+        };
+        let cover_spine_in = match config.cover_outfile_path() {
+            Some(path) => {
+                let path = volume_path(&path, volume.number);
+                let cover_config = labelled_config(config, volume, count);
+                let spine_in = render_cover(&cover_config, source, page_count, &path)
+                    .with_context(|| format!("Failed to render cover of {}", kind))?;
+                Some(spine_in)
+            }
+            None => None,
+        };
+
+        stats.push(VolumeStats {
+            outfile,
+            page_count,
+            booklet,
+            cover_spine_in,
+        });
+    }
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_labelled_config() {
+        let mut config = PDF::default();
+        config.title_page.template = "{title}".to_string();
+        config.cover.spine_template = "{title}   {volume}/{volumes}".to_string();
+        let volume = Volume {
+            number: 2,
+            pages: 10..20,
+        };
+        let labelled = labelled_config(&config, &volume, 3);
+        assert_eq!(labelled.title_page.template, "{title}\n\nVolume 2 of 3");
+        assert_eq!(labelled.cover.spine_template, "{title}   2/3");
+    }
+
+    #[test]
+    fn test_no_limit_no_breaks() {
+        assert!(plan_breaks(&[10, 20, 30], &[], 40, 0).is_empty());
+    }
+
+    #[test]
+    fn test_breaks_before_the_file_that_overflows() {
+        // volume 1 is pages 0..20, then each later volume has a title page or two
+        let breaks = plan_breaks(&[10, 20, 31, 45], &[], 60, 20);
+        assert_eq!(breaks, vec![20, 31, 45]);
+        for volume in volumes(&breaks, 60) {
+            let length = title_pages(volume.pages.start) + volume.pages.len();
+            assert!(length <= 20, "volume {:?} is {} pages", volume, length);
+        }
+    }
+
+    #[test]
+    fn test_forced_breaks() {
+        assert_eq!(plan_breaks(&[10, 20, 30], &[20], 40, 0), vec![20]);
+        // a file too long for any volume still gets one of its own
+        assert_eq!(plan_breaks(&[10, 50], &[], 60, 20), vec![10, 50]);
+    }
+
+    #[test]
+    fn test_volumes_cover_the_book() {
+        let volumes = volumes(&[20, 31], 60);
+        assert_eq!(
+            volumes,
+            vec![
+                Volume {
+                    number: 1,
+                    pages: 1..20
+                },
+                Volume {
+                    number: 2,
+                    pages: 20..31
+                },
+                Volume {
+                    number: 3,
+                    pages: 31..60
+                },
+            ]
+        );
+        assert_eq!(title_pages(20), 2);
+        assert_eq!(title_pages(31), 1);
+    }
+
+    #[test]
+    fn test_number_per_volume() {
+        // the content starts on book page 3; volumes start at pages 7 and 10
+        let sections = [
+            (Section::Frontmatter, 0),
+            (Section::Frontmatter, 1),
+            (Section::Source, 0),
+            (Section::Source, 1),
+            (Section::Source, 2),
+            (Section::Source, 3),
+            (Section::Source, 4),
+            (Section::CommitHistory, 0),
+            (Section::CommitHistory, 1),
+        ];
+        let mut page_metadata: Vec<PageMetadata> = sections
+            .iter()
+            .map(|&(section, page)| PageMetadata::new(section, page))
+            .collect();
+        number_per_volume(&mut page_metadata, 3, &[7, 10]);
+        let numbered: Vec<(Option<usize>, usize)> = page_metadata
+            .iter()
+            .map(|metadata| (metadata.volume, metadata.page_in_section))
+            .collect();
+        assert_eq!(
+            numbered,
+            vec![
+                (Some(1), 0),
+                (Some(1), 1),
+                (Some(1), 0),
+                (Some(1), 1),
+                (Some(2), 0),
+                (Some(2), 1),
+                (Some(2), 2),
+                (Some(3), 0),
+                (Some(3), 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_volume_path() {
+        assert_eq!(
+            volume_path(Path::new("out/book.pdf"), 2),
+            PathBuf::from("out/book-vol2.pdf")
+        );
+    }
+}
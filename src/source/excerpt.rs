@@ -0,0 +1,562 @@
+//! Source files included only in part.
+//!
+//! An entry in `source_files` is usually just a path, but it can also be a table
+//! that selects part of the file, by line range, by symbol name, or both:
+//!
+//! ```toml
+//! source_files = [
+//!     "src/main.rs",
+//!     { path = "src/parser.rs", lines = "10-80, 120-140" },
+//!     { path = "src/lib.rs", symbol = "Parser" },
+//! ]
+//! ```
+//!
+//! Line ranges are one-based and inclusive. Symbols are looked up by name each time
+//! the book is rendered, so they follow the code around by themselves; line ranges
+//! don't, so the `update` command records the commit they refer to (`revision`)
+//! and moves them over any changes made since with [`Excerpt::follow`]. A symbol
+//! that's been renamed or removed can't be followed, so `update` drops it with
+//! [`Excerpt::drop_missing_symbol`] rather than leave the book failing to render.
+
+use crate::syntax_analysis::symbol_lines;
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use syntect::parsing::{SyntaxReference, SyntaxSet};
+
+/// A source file to include in the book, in whole or in part.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "Entry", into = "Entry")]
+pub struct SourceFile {
+    /// Path relative to the repository root
+    pub path: PathBuf,
+    /// The part of the file to show, or None for all of it
+    pub excerpt: Option<Excerpt>,
+}
+
+impl From<PathBuf> for SourceFile {
+    fn from(path: PathBuf) -> Self {
+        Self {
+            path,
+            excerpt: None,
+        }
+    }
+}
+
+/// The lines of a file to show.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Excerpt {
+    /// Line ranges to show
+    pub lines: Vec<LineRange>,
+    /// Name of the items to show (every definition, `impl` block or module with it)
+    pub symbol: Option<String>,
+    /// Commit the line ranges refer to, so they can be moved as the file changes
+    pub revision: Option<String>,
+}
+
+/// How a source file entry is written in `src-book.toml`.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum Entry {
+    Path(PathBuf),
+    Excerpt {
+        path: PathBuf,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        lines: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        symbol: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        revision: Option<String>,
+    },
+}
+
+impl TryFrom<Entry> for SourceFile {
+    type Error = anyhow::Error;
+
+    fn try_from(entry: Entry) -> Result<Self> {
+        let (path, lines, symbol, revision) = match entry {
+            Entry::Path(path) => return Ok(path.into()),
+            Entry::Excerpt {
+                path,
+                lines,
+                symbol,
+                revision,
+            } => (path, lines, symbol, revision),
+        };
+        let lines = match lines {
+            Some(lines) => parse_line_ranges(&lines)
+                .with_context(|| format!("Invalid line ranges for {}", path.display()))?,
+            None => Vec::new(),
+        };
+        let excerpt = (!lines.is_empty() || symbol.is_some()).then_some(Excerpt {
+            lines,
+            symbol,
+            revision,
+        });
+        Ok(Self { path, excerpt })
+    }
+}
+
+impl From<SourceFile> for Entry {
+    fn from(file: SourceFile) -> Self {
+        match file.excerpt {
+            None => Entry::Path(file.path),
+            Some(excerpt) => Entry::Excerpt {
+                path: file.path,
+                lines: (!excerpt.lines.is_empty()).then(|| format_line_ranges(&excerpt.lines)),
+                symbol: excerpt.symbol,
+                revision: excerpt.revision,
+            },
+        }
+    }
+}
+
+/// A one-based, inclusive range of lines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineRange {
+    pub first: usize,
+    pub last: usize,
+}
+
+impl FromStr for LineRange {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (first, last) = s.split_once(['-', '–']).unwrap_or((s, s));
+        let parse = |n: &str| {
+            n.trim()
+                .parse::<usize>()
+                .map_err(|_| anyhow!("`{}` is not a line number", n.trim()))
+        };
+        let (first, last) = (parse(first)?, parse(last)?);
+        if first == 0 {
+            bail!("Line numbers start at 1");
+        }
+        if last < first {
+            bail!("Line range `{}` ends before it starts", s.trim());
+        }
+        Ok(Self { first, last })
+    }
+}
+
+impl fmt::Display for LineRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.first == self.last {
+            write!(f, "{}", self.first)
+        } else {
+            write!(f, "{}-{}", self.first, self.last)
+        }
+    }
+}
+
+/// Parse comma-separated line ranges such as `10-80, 120-140, 200`.
+pub fn parse_line_ranges(s: &str) -> Result<Vec<LineRange>> {
+    s.split(',')
+        .filter(|range| !range.trim().is_empty())
+        .map(str::parse)
+        .collect()
+}
+
+/// Write line ranges the way [`parse_line_ranges`] reads them.
+pub fn format_line_ranges(ranges: &[LineRange]) -> String {
+    ranges
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// A run of changed lines from a diff, in git's one-based hunk header terms.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Hunk {
+    pub old_start: usize,
+    pub old_lines: usize,
+    pub new_start: usize,
+    pub new_lines: usize,
+}
+
+impl Hunk {
+    /// Old and new lines as one-based half-open ranges. A hunk that only inserts
+    /// (or only deletes) gives the position after its start line.
+    fn ranges(&self) -> (Range<usize>, Range<usize>) {
+        let range = |start: usize, lines: usize| {
+            if lines == 0 {
+                start + 1..start + 1
+            } else {
+                start..start + lines
+            }
+        };
+        (
+            range(self.old_start, self.old_lines),
+            range(self.new_start, self.new_lines),
+        )
+    }
+}
+
+/// Move line ranges over a file's changes, given as hunks in file order.
+///
+/// Lines added inside a range join it and lines deleted from it leave; a range
+/// whose first or last line was changed takes in the lines that replaced it. Ranges
+/// whose lines have all been deleted are dropped.
+pub fn shift_line_ranges(ranges: &[LineRange], hunks: &[Hunk]) -> Vec<LineRange> {
+    // `start` picks where a changed line's replacement begins rather than ends
+    let map = |line: usize, start: bool| -> usize {
+        let mut delta: isize = 0;
+        for hunk in hunks {
+            let (old, new) = hunk.ranges();
+            if line < old.start {
+                break;
+            }
+            if line < old.end {
+                return if start {
+                    new.start
+                } else {
+                    new.end.saturating_sub(1)
+                };
+            }
+            delta = new.end as isize - old.end as isize;
+        }
+        line.saturating_add_signed(delta)
+    };
+
+    ranges
+        .iter()
+        .filter_map(|range| {
+            let first = map(range.first, true).max(1);
+            let last = map(range.last, false);
+            (first <= last).then_some(LineRange { first, last })
+        })
+        .collect()
+}
+
+impl Excerpt {
+    /// Work out which lines of `contents` the excerpt shows.
+    ///
+    /// `syntax` is needed to find a symbol; it's an error for the symbol to be
+    /// missing, since an empty excerpt is almost certainly a stale selector.
+    pub fn select(
+        &self,
+        path: &Path,
+        contents: &str,
+        syntax: Option<&SyntaxReference>,
+        ss: &SyntaxSet,
+    ) -> Result<Selection> {
+        let total = contents.lines().count();
+        let mut ranges: Vec<Range<usize>> = self
+            .lines
+            .iter()
+            .map(|range| range.first - 1..range.last)
+            .collect();
+        if let Some(symbol) = &self.symbol {
+            let syntax = syntax.ok_or_else(|| {
+                anyhow!(
+                    "Can't find `{}` in {}: its syntax isn't known",
+                    symbol,
+                    path.display()
+                )
+            })?;
+            let found = symbol_lines(contents, syntax, ss, symbol)
+                .with_context(|| format!("Failed to find `{}` in {}", symbol, path.display()))?;
+            if found.is_empty() {
+                bail!("`{}` isn't defined in {}", symbol, path.display());
+            }
+            ranges.extend(found);
+        }
+        Ok(Selection::new(ranges, total))
+    }
+
+    /// Read a file and work out which of its lines the excerpt shows, finding its
+    /// syntax from its extension.
+    pub fn select_file(&self, path: &Path, ss: &SyntaxSet) -> Result<Selection> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let syntax = ss.find_syntax_by_extension(
+            path.extension()
+                .and_then(|e| e.to_str())
+                .unwrap_or_default(),
+        );
+        self.select(path, &contents, syntax, ss)
+    }
+
+    /// Move the line ranges over the changes committed to `path` since `revision`,
+    /// and record `head` as the revision they now refer to.
+    pub fn follow(
+        &mut self,
+        repo: &git2::Repository,
+        path: &Path,
+        head: &git2::Commit,
+    ) -> Result<()> {
+        if let (false, Some(revision)) = (self.lines.is_empty(), &self.revision) {
+            let old_tree = repo
+                .revparse_single(revision)
+                .and_then(|object| object.peel_to_tree())
+                .with_context(|| format!("Failed to find revision {}", revision))?;
+            let new_tree = head
+                .tree()
+                .with_context(|| "Failed to get the tree for HEAD")?;
+            let mut options = git2::DiffOptions::new();
+            options.pathspec(path).context_lines(0);
+            let diff = repo
+                .diff_tree_to_tree(Some(&old_tree), Some(&new_tree), Some(&mut options))
+                .with_context(|| format!("Failed to diff {}", path.display()))?;
+
+            let mut hunks = Vec::new();
+            diff.foreach(
+                &mut |_, _| true,
+                None,
+                Some(&mut |_, hunk| {
+                    hunks.push(Hunk {
+                        old_start: hunk.old_start() as usize,
+                        old_lines: hunk.old_lines() as usize,
+                        new_start: hunk.new_start() as usize,
+                        new_lines: hunk.new_lines() as usize,
+                    });
+                    true
+                }),
+                None,
+            )
+            .with_context(|| format!("Failed to read the diff of {}", path.display()))?;
+            self.lines = shift_line_ranges(&self.lines, &hunks);
+        }
+        self.revision = Some(head.id().to_string());
+        Ok(())
+    }
+
+    /// Drop the symbol if `contents` of `path` no longer defines it, returning its
+    /// name.
+    ///
+    /// A symbol that can't be looked up at all, in a file of unknown syntax, is
+    /// dropped too, since [`Excerpt::select`] would fail on it just the same.
+    pub fn drop_missing_symbol(
+        &mut self,
+        path: &Path,
+        contents: &str,
+        ss: &SyntaxSet,
+    ) -> Option<String> {
+        let symbol = self.symbol.as_deref()?;
+        let syntax = ss.find_syntax_by_extension(
+            path.extension()
+                .and_then(|e| e.to_str())
+                .unwrap_or_default(),
+        );
+        let defined = syntax
+            .and_then(|syntax| symbol_lines(contents, syntax, ss, symbol).ok())
+            .is_some_and(|lines| !lines.is_empty());
+        if defined {
+            None
+        } else {
+            self.symbol.take()
+        }
+    }
+
+    /// Trim the line ranges to a file of `total` lines.
+    pub fn clamp(&mut self, total: usize) {
+        self.lines.retain(|range| range.first <= total);
+        for range in self.lines.iter_mut() {
+            range.last = range.last.min(total);
+        }
+    }
+}
+
+/// The lines of a file an excerpt shows.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Selection {
+    /// Sorted, non-overlapping, zero-based half-open ranges of shown lines
+    ranges: Vec<Range<usize>>,
+}
+
+impl Selection {
+    /// Select `ranges` (zero-based, half-open, in any order) of a file of `total`
+    /// lines.
+    pub fn new(mut ranges: Vec<Range<usize>>, total: usize) -> Self {
+        ranges.sort_by_key(|range| range.start);
+        let mut merged: Vec<Range<usize>> = Vec::with_capacity(ranges.len());
+        for range in ranges {
+            let range = range.start.min(total)..range.end.min(total);
+            if range.is_empty() {
+                continue;
+            }
+            match merged.last_mut() {
+                Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+                _ => merged.push(range),
+            }
+        }
+        Self { ranges: merged }
+    }
+
+    pub fn contains(&self, line: usize) -> bool {
+        self.ranges.iter().any(|range| range.contains(&line))
+    }
+
+    /// Whether any of `lines` is shown.
+    pub fn intersects(&self, lines: &Range<usize>) -> bool {
+        self.ranges
+            .iter()
+            .any(|range| range.start < lines.end && lines.start < range.end)
+    }
+}
+
+/// Tracks the runs of lines left out as a listing is laid out, in order.
+#[derive(Default)]
+pub struct Omissions {
+    start: Option<usize>,
+}
+
+impl Omissions {
+    /// Note that `line` was left out.
+    pub fn skip(&mut self, line: usize) {
+        self.start.get_or_insert(line);
+    }
+
+    /// Note that `line` is about to be shown, returning the run of lines left out
+    /// just before it, if any.
+    pub fn show(&mut self, line: usize) -> Option<Range<usize>> {
+        self.start.take().map(|start| start..line)
+    }
+
+    /// Finish a file of `total` lines, returning the run left out at its end.
+    pub fn finish(self, total: usize) -> Option<Range<usize>> {
+        self.start.map(|start| start..total)
+    }
+}
+
+/// The marker printed in place of a run of omitted lines (zero-based, half-open).
+pub fn omission_label(lines: &Range<usize>) -> String {
+    if lines.len() == 1 {
+        format!("⋯ line {} omitted ⋯", lines.start + 1)
+    } else {
+        format!("⋯ lines {}–{} omitted ⋯", lines.start + 1, lines.end)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ranges(s: &str) -> Vec<LineRange> {
+        parse_line_ranges(s).expect("valid ranges")
+    }
+
+    #[test]
+    fn can_parse_line_ranges() {
+        assert_eq!(
+            ranges("10-80, 120 - 140,200"),
+            vec![
+                LineRange {
+                    first: 10,
+                    last: 80
+                },
+                LineRange {
+                    first: 120,
+                    last: 140
+                },
+                LineRange {
+                    first: 200,
+                    last: 200
+                },
+            ]
+        );
+        assert_eq!(format_line_ranges(&ranges("10-80, 200")), "10-80, 200");
+        assert!(parse_line_ranges("0-5").is_err());
+        assert!(parse_line_ranges("20-10").is_err());
+        assert!(parse_line_ranges("ten").is_err());
+    }
+
+    #[test]
+    fn shifts_line_ranges_over_changes() {
+        // two lines inserted after line 5, ahead of the range
+        let inserted = Hunk {
+            old_start: 5,
+            old_lines: 0,
+            new_start: 6,
+            new_lines: 2,
+        };
+        assert_eq!(
+            shift_line_ranges(&ranges("10-20"), &[inserted]),
+            ranges("12-22")
+        );
+
+        // lines 12 and 13 deleted from inside the range
+        let deleted = Hunk {
+            old_start: 12,
+            old_lines: 2,
+            new_start: 11,
+            new_lines: 0,
+        };
+        assert_eq!(
+            shift_line_ranges(&ranges("10-20, 30-31"), &[deleted]),
+            ranges("10-18, 28-29")
+        );
+
+        // the whole range deleted
+        let gone = Hunk {
+            old_start: 10,
+            old_lines: 11,
+            new_start: 9,
+            new_lines: 0,
+        };
+        assert_eq!(shift_line_ranges(&ranges("10-20"), &[gone]), vec![]);
+
+        // the first line rewritten as three lines
+        let rewritten = Hunk {
+            old_start: 10,
+            old_lines: 1,
+            new_start: 10,
+            new_lines: 3,
+        };
+        assert_eq!(
+            shift_line_ranges(&ranges("10-20"), &[rewritten]),
+            ranges("10-22")
+        );
+    }
+
+    #[test]
+    fn drops_renamed_symbols() {
+        let ss = SyntaxSet::load_defaults_newlines();
+        let path = Path::new("src/parser.rs");
+        let mut excerpt = Excerpt {
+            lines: ranges("1-2"),
+            symbol: Some("Parser".to_string()),
+            revision: None,
+        };
+
+        let contents = "use std::fmt;\n\npub struct Parser {\n    pos: usize,\n}\n";
+        assert_eq!(excerpt.drop_missing_symbol(path, contents, &ss), None);
+        assert_eq!(excerpt.symbol.as_deref(), Some("Parser"));
+
+        let renamed = contents.replace("Parser", "Lexer");
+        assert_eq!(
+            excerpt.drop_missing_symbol(path, &renamed, &ss),
+            Some("Parser".to_string())
+        );
+        assert_eq!(excerpt.symbol, None);
+        assert_eq!(excerpt.lines, ranges("1-2"));
+        assert!(excerpt.select(path, &renamed, None, &ss).is_ok());
+    }
+
+    #[test]
+    fn merges_selected_ranges() {
+        let selection = Selection::new(vec![20..30, 0..5, 3..8, 8..10, 95..120], 100);
+        assert_eq!(selection.ranges, vec![0..10, 20..30, 95..100]);
+        assert!(selection.contains(9));
+        assert!(!selection.contains(10));
+        assert!(selection.intersects(&(15..21)));
+        assert!(!selection.intersects(&(10..20)));
+    }
+
+    #[test]
+    fn tracks_omitted_runs() {
+        let mut omissions = Omissions::default();
+        assert_eq!(omissions.show(0), None);
+        omissions.skip(1);
+        omissions.skip(2);
+        assert_eq!(omissions.show(3), Some(1..3));
+        omissions.skip(4);
+        assert_eq!(omissions.finish(10), Some(4..10));
+        assert_eq!(omission_label(&(80..200)), "⋯ lines 81–200 omitted ⋯");
+        assert_eq!(omission_label(&(80..81)), "⋯ line 81 omitted ⋯");
+    }
+}
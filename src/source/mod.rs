@@ -6,12 +6,14 @@
 
 mod author;
 mod commit;
+mod excerpt;
 mod tag;
 use std::collections::HashMap;
 use std::path::PathBuf;
 
 pub use author::*;
 pub use commit::*;
+pub use excerpt::*;
 pub use tag::*;
 
 mod providers;
@@ -67,11 +69,18 @@ pub struct Source {
     #[serde(default)]
     pub frontmatter_files: Vec<PathBuf>,
 
+    /// Guided tours (CodeTour `.tour` files or a `tour.md`) rendered as narratives
+    /// in their own section after the frontmatter, each step followed by the code it
+    /// points at. See [`crate::tour`].
+    #[serde(default)]
+    pub tour_files: Vec<PathBuf>,
+
     /// Source files to include in the book, in display order.
     /// Generated by walking the repository (respecting .gitignore) and sorted by the
-    /// entrypoint-aware algorithm if an entrypoint is specified.
+    /// entrypoint-aware algorithm if an entrypoint is specified. Entries can select
+    /// part of a file instead of the whole thing; see [`excerpt`].
     #[serde(default)]
-    pub source_files: Vec<PathBuf>,
+    pub source_files: Vec<SourceFile>,
 
     /// Repository authors extracted from git commit history.
     /// Sorted by prominence (commit count) at render time.
@@ -93,6 +102,11 @@ impl Source {
         }
     }
 
+    /// Paths of the source files, in display order.
+    pub fn source_paths(&self) -> Vec<PathBuf> {
+        self.source_files.iter().map(|f| f.path.clone()).collect()
+    }
+
     /// Applies legacy field values to their new locations.
     /// Called after deserialization to migrate old config formats.
     pub fn apply_legacy_fields(&mut self) {
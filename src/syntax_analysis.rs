@@ -0,0 +1,1170 @@
+//! Scope-based analysis of source files.
+//!
+//! Features that need to understand a file's structure (literate layout, for one)
+//! work from syntect's scope stacks rather than per-language parsers. Every bundled
+//! syntax names its scopes using the same TextMate conventions (`comment.*`,
+//! `entity.name.*`, `punctuation.definition.comment`), so one pass over the parse
+//! operations works across languages.
+//!
+//! The analysis is independent of the output format; both sinks call into it and
+//! then render the results in their own way.
+
+use anyhow::{Context, Result};
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::Range;
+use std::path::Path;
+use syntect::highlighting::{HighlightIterator, HighlightState, Highlighter, Style, Theme};
+use syntect::parsing::{ParseState, Scope, ScopeStack, ScopeStackOp, SyntaxReference, SyntaxSet};
+use syntect::util::LinesWithEndings;
+
+/// A run of lines in a file, split for literate layout.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Segment {
+    /// Comment text with the comment markers removed, to be typeset as Markdown
+    Prose(String),
+    /// Code lines, as a half-open range of zero-based line indices
+    Code { start: usize, end: usize },
+}
+
+/// The kind of symbol a definition introduces.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SymbolKind {
+    Function,
+    Type,
+    Class,
+    Trait,
+    Constant,
+}
+
+impl SymbolKind {
+    /// Short label shown next to index entries.
+    pub fn label(&self) -> &'static str {
+        match self {
+            SymbolKind::Function => "fn",
+            SymbolKind::Type => "type",
+            SymbolKind::Class => "class",
+            SymbolKind::Trait => "trait",
+            SymbolKind::Constant => "const",
+        }
+    }
+
+    /// Classify an `entity.name.*` scope, ignoring names that aren't definitions
+    /// worth indexing (HTML tags, Markdown sections, Rust `impl` targets, labels).
+    fn from_scope(name: &str) -> Option<Self> {
+        let kind = name.strip_prefix("entity.name.")?.split('.').next()?;
+        match kind {
+            "function" | "method" => Some(SymbolKind::Function),
+            "type" | "struct" | "enum" | "union" | "typedef" => Some(SymbolKind::Type),
+            "class" => Some(SymbolKind::Class),
+            "trait" | "interface" | "protocol" => Some(SymbolKind::Trait),
+            "constant" => Some(SymbolKind::Constant),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for SymbolKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.label())
+    }
+}
+
+/// A symbol defined in a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Definition {
+    pub name: String,
+    pub kind: SymbolKind,
+    /// Zero-based line index of the definition
+    pub line: usize,
+}
+
+/// A top-level item listed in a file's outline.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutlineItem {
+    pub name: String,
+    /// Short kind label, e.g. `fn` or `impl`
+    pub label: &'static str,
+    /// Zero-based line index of the item
+    pub line: usize,
+}
+
+/// A `TODO`-style marker found in a comment.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Marker {
+    /// The tag that matched, e.g. `FIXME`
+    pub tag: String,
+    /// Zero-based line index
+    pub line: usize,
+    /// The rest of the comment after the tag
+    pub text: String,
+}
+
+/// The comment block a file opens with, such as a licence header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LeadingComment {
+    /// Zero-based, half-open range of the block's lines
+    pub lines: Range<usize>,
+    /// The block's text with comment markers and indentation removed, so the same
+    /// header compares equal whichever comment syntax it's written in
+    pub text: String,
+}
+
+/// An identifier in code that names a known symbol.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reference {
+    pub name: String,
+    /// Zero-based line index
+    pub line: usize,
+    /// Byte range of the identifier within its line
+    pub range: std::ops::Range<usize>,
+}
+
+/// What listings need to know about a file's symbols, gathered in one walk over its
+/// tokens by a [`SymbolScanner`].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct FileSymbols {
+    /// As found by [`definitions`]
+    pub definitions: Vec<Definition>,
+    /// The top-level items for the file's outline: definitions, plus the `impl`
+    /// blocks, modules and macros the index leaves out.
+    ///
+    /// Only names on unindented lines count as top level, which keeps methods,
+    /// nested functions and test modules out without knowing each language's block
+    /// structure. Each line contributes at most one item.
+    pub outline: Vec<OutlineItem>,
+    /// Comments that start a note with one of the scanner's tags (e.g. `TODO`).
+    ///
+    /// Tags only count as whole words inside comment scopes, so `todo_list` and a
+    /// `"TODO"` string are ignored. Each line contributes at most one marker: the
+    /// earliest tag in its comment text.
+    pub markers: Vec<Marker>,
+    /// Identifiers in code naming the symbols the scanner looks for.
+    ///
+    /// Identifiers inside comments and strings are skipped, as are definition names
+    /// themselves, so only uses of a symbol are reported.
+    pub references: Vec<Reference>,
+}
+
+/// How a single line was classified.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum LineKind {
+    Code,
+    Blank,
+    /// A line that is entirely comment, with the comment markers removed
+    Comment {
+        text: String,
+        /// Whether the comment is a block comment, whose continuation lines may start with `*`
+        block: bool,
+    },
+}
+
+/// Walk a file's tokens with the scope stack in effect for each.
+///
+/// `visit` is called once per line with the line index, the line text, and the
+/// tokens on that line as `(text, scopes)` pairs.
+pub fn for_each_token<F>(
+    contents: &str,
+    syntax: &SyntaxReference,
+    ss: &SyntaxSet,
+    mut visit: F,
+) -> Result<()>
+where
+    F: FnMut(usize, &str, &[(&str, &[Scope])]),
+{
+    for_each_parsed_line(contents, syntax, ss, |i, line, _, tokens| {
+        visit(i, line, tokens)
+    })
+}
+
+/// [`for_each_token`], also passing `visit` the parser's scope operations for each
+/// line so it can highlight from the same parse.
+fn for_each_parsed_line<'c, F>(
+    contents: &'c str,
+    syntax: &SyntaxReference,
+    ss: &SyntaxSet,
+    mut visit: F,
+) -> Result<()>
+where
+    F: FnMut(usize, &'c str, &[(usize, ScopeStackOp)], &[(&'c str, &[Scope])]),
+{
+    let mut state = ParseState::new(syntax);
+    let mut stack = ScopeStack::new();
+    for (i, line) in LinesWithEndings::from(contents).enumerate() {
+        let ops = state
+            .parse_line(line, ss)
+            .with_context(|| format!("Failed to parse line {}", i + 1))?;
+
+        // collect owned scope snapshots first since the stack changes between tokens
+        let mut snapshots: Vec<(usize, usize, Vec<Scope>)> = Vec::new();
+        let mut last = 0;
+        for (offset, op) in ops.iter() {
+            if *offset > last {
+                snapshots.push((last, *offset, stack.as_slice().to_vec()));
+                last = *offset;
+            }
+            stack
+                .apply(op)
+                .with_context(|| format!("Failed to apply scope on line {}", i + 1))?;
+        }
+        if last < line.len() {
+            snapshots.push((last, line.len(), stack.as_slice().to_vec()));
+        }
+
+        let tokens: Vec<(&str, &[Scope])> = snapshots
+            .iter()
+            .map(|(start, end, scopes)| (&line[*start..*end], scopes.as_slice()))
+            .collect();
+        visit(i, line, &ops, &tokens);
+    }
+    Ok(())
+}
+
+/// A line's highlighted ranges, as [`syntect::easy::HighlightLines`] gives them.
+pub type HighlightedLine<'c> = Vec<(Style, &'c str)>;
+
+/// Highlight a file with `theme` and gather its symbols from the same parse.
+///
+/// Returns each line's highlighted ranges along with everything `scanner` found.
+pub fn highlight_and_scan<'c, F>(
+    contents: &'c str,
+    syntax: &SyntaxReference,
+    ss: &SyntaxSet,
+    theme: &Theme,
+    mut scanner: SymbolScanner<F>,
+) -> Result<(Vec<HighlightedLine<'c>>, FileSymbols)>
+where
+    F: Fn(&str) -> bool,
+{
+    let highlighter = Highlighter::new(theme);
+    let mut highlight_state = HighlightState::new(&highlighter, ScopeStack::new());
+    let mut lines = Vec::new();
+    for_each_parsed_line(contents, syntax, ss, |i, line, ops, tokens| {
+        lines.push(HighlightIterator::new(&mut highlight_state, ops, line, &highlighter).collect());
+        scanner.scan_line(i, line, tokens);
+    })?;
+    Ok((lines, scanner.finish()))
+}
+
+/// Gathers a file's [`FileSymbols`] a line at a time, so one walk over its tokens
+/// serves the index, outline, TODO register and cross-references alike.
+pub struct SymbolScanner<'a, F> {
+    tags: &'a [String],
+    is_symbol: F,
+    definitions: NamedTokens<SymbolKind>,
+    outline: NamedTokens<&'static str>,
+    markers: Vec<Marker>,
+    references: Vec<Reference>,
+}
+
+impl<'a, F: Fn(&str) -> bool> SymbolScanner<'a, F> {
+    /// Look for markers starting with one of `tags`, and references to the
+    /// symbols `is_symbol` accepts, as well as definitions and outline items.
+    pub fn new(tags: &'a [String], is_symbol: F) -> Self {
+        SymbolScanner {
+            tags,
+            is_symbol,
+            definitions: NamedTokens::new(SymbolKind::from_scope),
+            outline: NamedTokens::new(outline_label),
+            markers: Vec::new(),
+            references: Vec::new(),
+        }
+    }
+
+    /// Scan the next line's tokens, as passed to [`for_each_token`]'s `visit`.
+    pub fn scan_line(&mut self, line: usize, text: &str, tokens: &[(&str, &[Scope])]) {
+        self.definitions.scan_line(line, text, tokens);
+        self.outline.scan_line(line, text, tokens);
+        self.markers.extend(line_marker(line, tokens, self.tags));
+        line_references(line, tokens, &self.is_symbol, &mut self.references);
+    }
+
+    pub fn finish(self) -> FileSymbols {
+        FileSymbols {
+            definitions: self
+                .definitions
+                .finish()
+                .into_iter()
+                .map(|(name, kind, line, _)| Definition { name, kind, line })
+                .collect(),
+            outline: outline_items(self.outline.finish()),
+            markers: self.markers,
+            references: self.references,
+        }
+    }
+}
+
+/// Returns true if any scope in the stack starts with the given prefix.
+pub fn has_scope(scopes: &[Scope], prefix: &str) -> bool {
+    scopes.iter().any(|s| s.build_string().starts_with(prefix))
+}
+
+/// Find the symbols defined in a file from the `entity.name.*` scopes its syntax
+/// assigns to definition names.
+///
+/// Adjacent tokens with the same definition scope are joined, so names the
+/// highlighter splits up still come out whole.
+pub fn definitions(
+    contents: &str,
+    syntax: &SyntaxReference,
+    ss: &SyntaxSet,
+) -> Result<Vec<Definition>> {
+    Ok(named_tokens(contents, syntax, ss, SymbolKind::from_scope)?
+        .into_iter()
+        .map(|(name, kind, line, _)| Definition { name, kind, line })
+        .collect())
+}
+
+/// Keep the top-level names for the outline, one per line.
+fn outline_items(named: Vec<(String, &'static str, usize, bool)>) -> Vec<OutlineItem> {
+    let mut items: Vec<OutlineItem> = Vec::new();
+    for (name, label, line, top_level) in named {
+        if top_level && items.last().map(|item| item.line) != Some(line) {
+            items.push(OutlineItem { name, label, line });
+        }
+    }
+    items
+}
+
+/// Find the lines spanned by every item named `name` (definitions, `impl` blocks,
+/// modules and macros, at any depth) as zero-based half-open ranges in file order.
+///
+/// As with [`FileSymbols::outline`], indentation stands in for block structure: an item runs
+/// from its name to the last line indented deeper than it, plus a closing line at
+/// its own indentation (`}`, `end`). Comments and attributes directly above it at
+/// the same indentation are taken in too.
+pub fn symbol_lines(
+    contents: &str,
+    syntax: &SyntaxReference,
+    ss: &SyntaxSet,
+    name: &str,
+) -> Result<Vec<Range<usize>>> {
+    let lines: Vec<&str> = contents.lines().collect();
+    let mut starts: Vec<usize> = named_tokens(contents, syntax, ss, outline_label)?
+        .into_iter()
+        .filter(|(found, ..)| found == name)
+        .map(|(_, _, line, _)| line)
+        .collect();
+    starts.dedup();
+    Ok(starts
+        .into_iter()
+        .filter(|&line| line < lines.len())
+        .map(|line| item_extent(&lines, line))
+        .collect())
+}
+
+/// Find the lines spanned by each of a file's outline items, in the same order, by
+/// indentation as in [`symbol_lines`].
+pub fn item_lines(contents: &str, items: &[OutlineItem]) -> Vec<Range<usize>> {
+    let lines: Vec<&str> = contents.lines().collect();
+    items
+        .iter()
+        .map(|item| {
+            if item.line < lines.len() {
+                item_extent(&lines, item.line)
+            } else {
+                item.line..item.line
+            }
+        })
+        .collect()
+}
+
+/// Width in bytes of a line's indentation.
+///
+/// Only ASCII spaces and tabs count, so the indentation can be sliced off without
+/// landing inside a multi-byte space such as a no-break space, and every scan of a
+/// line agrees on where its text starts.
+fn indentation(line: &str) -> usize {
+    line.len() - line.trim_start_matches([' ', '\t']).len()
+}
+
+/// The lines belonging to the item named on `line`, by indentation.
+fn item_extent(lines: &[&str], line: usize) -> Range<usize> {
+    let base = indentation(lines[line]);
+
+    // doc comments, attributes and decorators directly above
+    let mut start = line;
+    while start > 0 {
+        let above = lines[start - 1];
+        let trimmed = &above[indentation(above)..];
+        let preamble = ["//", "/*", "*", "#", "@", "--", ";"]
+            .iter()
+            .any(|prefix| trimmed.starts_with(prefix));
+        if trimmed.is_empty() || indentation(above) != base || !preamble {
+            break;
+        }
+        start -= 1;
+    }
+
+    let mut end = line + 1;
+    for (i, text) in lines.iter().enumerate().skip(line + 1) {
+        let trimmed = text.trim();
+        if trimmed.is_empty() {
+            continue;
+        }
+        if indentation(text) > base {
+            end = i + 1;
+            continue;
+        }
+        // lines at the item's own indentation belong to it if they close a block
+        // or carry on a long signature (`where`, `) -> T {`, a lone `{`)
+        if trimmed.starts_with("where") || trimmed == "{" {
+            end = i + 1;
+            continue;
+        }
+        if trimmed.starts_with(['}', ')', ']']) || trimmed == "end" {
+            end = i + 1;
+            if trimmed.ends_with(['{', '(', '[']) {
+                continue;
+            }
+        }
+        break;
+    }
+    start..end
+}
+
+/// Label an `entity.name.*` scope for the outline.
+fn outline_label(name: &str) -> Option<&'static str> {
+    if let Some(kind) = SymbolKind::from_scope(name) {
+        return Some(kind.label());
+    }
+    match name.strip_prefix("entity.name.")?.split('.').next()? {
+        "impl" => Some("impl"),
+        "module" | "namespace" => Some("mod"),
+        "macro" => Some("macro"),
+        _ => None,
+    }
+}
+
+/// Collect the names `classify` accepts, as `(name, kind, line, top_level)`, where
+/// `top_level` is whether the name's line is unindented.
+///
+/// Adjacent tokens with the same kind are joined, so names the highlighter splits
+/// up still come out whole.
+fn named_tokens<K: Copy + PartialEq>(
+    contents: &str,
+    syntax: &SyntaxReference,
+    ss: &SyntaxSet,
+    classify: fn(&str) -> Option<K>,
+) -> Result<Vec<(String, K, usize, bool)>> {
+    let mut named = NamedTokens::new(classify);
+    for_each_token(contents, syntax, ss, |line, text, tokens| {
+        named.scan_line(line, text, tokens);
+    })?;
+    Ok(named.finish())
+}
+
+/// The names found so far by [`named_tokens`], a line at a time.
+struct NamedTokens<K> {
+    classify: fn(&str) -> Option<K>,
+    found: Vec<(String, K, usize, bool)>,
+}
+
+impl<K: Copy + PartialEq> NamedTokens<K> {
+    fn new(classify: fn(&str) -> Option<K>) -> Self {
+        NamedTokens {
+            classify,
+            found: Vec::new(),
+        }
+    }
+
+    fn scan_line(&mut self, line: usize, text: &str, tokens: &[(&str, &[Scope])]) {
+        let top_level = indentation(text) == 0;
+        let mut previous: Option<K> = None;
+        for (token, scopes) in tokens {
+            let kind = scopes
+                .iter()
+                .rev()
+                .find_map(|s| (self.classify)(&s.build_string()));
+            match kind {
+                Some(kind) if previous == Some(kind) => {
+                    if let Some(last) = self.found.last_mut() {
+                        last.0.push_str(token);
+                    }
+                }
+                Some(kind) => self.found.push((token.to_string(), kind, line, top_level)),
+                None => {}
+            }
+            previous = kind;
+        }
+    }
+
+    fn finish(mut self) -> Vec<(String, K, usize, bool)> {
+        for name in self.found.iter_mut() {
+            name.0 = name.0.trim().to_string();
+        }
+        self.found.retain(|name| !name.0.is_empty());
+        self.found
+    }
+}
+
+/// The marker on one line, for [`FileSymbols::markers`].
+fn line_marker(line: usize, tokens: &[(&str, &[Scope])], tags: &[String]) -> Option<Marker> {
+    if tags.is_empty() {
+        return None;
+    }
+    let comment: String = tokens
+        .iter()
+        .filter(|(_, scopes)| {
+            has_scope(scopes, "comment.") && !has_scope(scopes, "punctuation.definition.comment")
+        })
+        .map(|(token, _)| *token)
+        .collect();
+    let (start, tag) = tags
+        .iter()
+        .filter_map(|tag| find_word(&comment, tag).map(|start| (start, tag)))
+        .min_by_key(|(start, _)| *start)?;
+    let text = comment[start + tag.len()..]
+        .trim_start_matches(|c: char| c == ':' || c.is_whitespace())
+        .trim_end()
+        .trim_end_matches("*/")
+        .trim_end();
+    Some(Marker {
+        tag: tag.clone(),
+        line,
+        text: text.to_string(),
+    })
+}
+
+/// Byte offset of the first whole-word occurrence of `word` in `text`.
+fn find_word(text: &str, word: &str) -> Option<usize> {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    text.match_indices(word).map(|(i, _)| i).find(|&i| {
+        let before = text[..i].chars().next_back();
+        let after = text[i + word.len()..].chars().next();
+        !before.is_some_and(is_word) && !after.is_some_and(is_word)
+    })
+}
+
+/// Add the references on one line, for [`FileSymbols::references`], to
+/// `references`.
+fn line_references<F>(
+    line: usize,
+    tokens: &[(&str, &[Scope])],
+    is_symbol: &F,
+    references: &mut Vec<Reference>,
+) where
+    F: Fn(&str) -> bool,
+{
+    let mut offset = 0;
+    for (token, scopes) in tokens {
+        let skip = scopes.iter().any(|s| {
+            let name = s.build_string();
+            name.starts_with("comment.")
+                || name.starts_with("string.")
+                || name.starts_with("entity.name.")
+        });
+        if !skip {
+            for (start, word) in identifiers(token) {
+                if is_symbol(word) {
+                    references.push(Reference {
+                        name: word.to_string(),
+                        line,
+                        range: offset + start..offset + start + word.len(),
+                    });
+                }
+            }
+        }
+        offset += token.len();
+    }
+}
+
+/// Split text into identifier-like words, with their byte offsets.
+fn identifiers(text: &str) -> Vec<(usize, &str)> {
+    let is_ident = |c: char| c.is_alphanumeric() || c == '_';
+    let mut words = Vec::new();
+    let mut start = None;
+    for (i, c) in text.char_indices() {
+        match (start, is_ident(c)) {
+            (None, true) => start = Some(i),
+            (Some(s), false) => {
+                words.push((s, &text[s..i]));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(s) = start {
+        words.push((s, &text[s..]));
+    }
+    words
+        .into_iter()
+        .filter(|(_, word)| !word.starts_with(|c: char| c.is_ascii_digit()))
+        .collect()
+}
+
+/// Read a file and find its definitions.
+///
+/// Binary files and files without a known syntax have no definitions.
+pub fn file_definitions(path: &Path, ss: &SyntaxSet) -> Result<Vec<Definition>> {
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::InvalidData => return Ok(Vec::new()),
+        Err(e) => {
+            return Err(e).with_context(|| format!("Failed to read {}", path.display()));
+        }
+    };
+    let Some(syntax) = ss.find_syntax_by_extension(
+        path.extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or_default(),
+    ) else {
+        return Ok(Vec::new());
+    };
+    definitions(&contents, syntax, ss)
+        .with_context(|| format!("Failed to find definitions in {}", path.display()))
+}
+
+/// Order symbol names the way a book index does: case-insensitively and ignoring
+/// leading underscores, with the exact name as a tie-breaker so the order is stable.
+pub fn compare_symbols(a: &str, b: &str) -> Ordering {
+    let key = |name: &str| name.trim_start_matches('_').to_lowercase();
+    key(a).cmp(&key(b)).then_with(|| a.cmp(b))
+}
+
+/// The letter an index entry is filed under, or `#` for names that don't start
+/// with a letter.
+pub fn index_letter(name: &str) -> char {
+    name.trim_start_matches('_')
+        .chars()
+        .next()
+        .filter(|c| c.is_alphabetic())
+        .map(|c| c.to_uppercase().next().unwrap_or(c))
+        .unwrap_or('#')
+}
+
+/// Lines read when looking for a file's leading comment; headers longer than this
+/// aren't worth detecting.
+const LEADING_COMMENT_MAX_LINES: usize = 200;
+
+/// Find the comment block a file opens with.
+///
+/// The block starts at the first non-blank line (after a `#!` line) and runs over
+/// the lines that are entirely comment, up to the first blank or code line.
+/// Documentation comments aren't headers, so a file opening with one has none.
+pub fn leading_comment(
+    contents: &str,
+    syntax: &SyntaxReference,
+    ss: &SyntaxSet,
+) -> Result<Option<LeadingComment>> {
+    let head: String = LinesWithEndings::from(contents)
+        .take(LEADING_COMMENT_MAX_LINES)
+        .collect();
+
+    let mut kinds = Vec::new();
+    for_each_token(&head, syntax, ss, |i, line, tokens| {
+        if line.trim().is_empty() {
+            // an empty line inside a block comment is part of it, not the end of it
+            let in_block = tokens
+                .iter()
+                .any(|(_, scopes)| has_scope(scopes, "comment.block"));
+            kinds.push(if in_block {
+                LineKind::Comment {
+                    text: String::new(),
+                    block: true,
+                }
+            } else {
+                LineKind::Blank
+            });
+            return;
+        }
+        if i == 0 && line.starts_with("#!") {
+            kinds.push(LineKind::Blank);
+            return;
+        }
+
+        let mut text = String::new();
+        let mut block = false;
+        for (token, scopes) in tokens {
+            if token.trim().is_empty() {
+                continue;
+            }
+            if !has_scope(scopes, "comment.") || is_prose_comment(scopes, false) {
+                kinds.push(LineKind::Code);
+                return;
+            }
+            block |= has_scope(scopes, "comment.block");
+            if !has_scope(scopes, "punctuation.definition.comment") {
+                text.push_str(token);
+            }
+        }
+        kinds.push(LineKind::Comment { text, block });
+    })?;
+
+    let Some(start) = kinds.iter().position(|k| *k != LineKind::Blank) else {
+        return Ok(None);
+    };
+    let end = kinds[start..]
+        .iter()
+        .position(|k| !matches!(k, LineKind::Comment { .. }))
+        .map(|offset| start + offset)
+        .unwrap_or(kinds.len());
+    if start == end {
+        return Ok(None);
+    }
+
+    let lines: Vec<String> = kinds[start..end]
+        .iter()
+        .filter_map(|k| match k {
+            LineKind::Comment { text, block } => {
+                let text = text.trim();
+                let text = match text.strip_prefix('*') {
+                    Some(rest) if *block => rest.trim_start(),
+                    _ => text,
+                };
+                Some(text.to_string())
+            }
+            _ => None,
+        })
+        .collect();
+    let text = lines.join("\n").trim_matches('\n').to_string();
+    if text.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(LeadingComment {
+        lines: start..end,
+        text,
+    }))
+}
+
+/// Returns true if the scopes mark a comment that should be treated as prose.
+///
+/// Documentation comments (`///`, `/** */`, Python docstrings) are always prose;
+/// other comments only when `all_comments` is set.
+fn is_prose_comment(scopes: &[Scope], all_comments: bool) -> bool {
+    scopes.iter().any(|s| {
+        let name = s.build_string();
+        name.starts_with("comment.") && (all_comments || name.contains(".documentation"))
+    })
+}
+
+/// Split a file into alternating prose and code segments for literate layout.
+///
+/// A line is prose when all of its non-whitespace text is inside a documentation
+/// comment (or any comment, with `all_comments`). Blank lines between two prose
+/// lines stay in the prose as paragraph breaks; blank lines at the edges of code
+/// runs are dropped. Comments that share a line with code stay in the listing.
+pub fn literate_segments(
+    contents: &str,
+    syntax: &SyntaxReference,
+    ss: &SyntaxSet,
+    all_comments: bool,
+) -> Result<Vec<Segment>> {
+    let mut kinds = Vec::new();
+    for_each_token(contents, syntax, ss, |_, line, tokens| {
+        if line.trim().is_empty() {
+            kinds.push(LineKind::Blank);
+            return;
+        }
+
+        let mut text = String::new();
+        let mut block = false;
+        for (token, scopes) in tokens {
+            if token.trim().is_empty() {
+                text.push_str(token);
+                continue;
+            }
+            if !is_prose_comment(scopes, all_comments) {
+                kinds.push(LineKind::Code);
+                return;
+            }
+            block |= has_scope(scopes, "comment.block");
+            if !has_scope(scopes, "punctuation.definition.comment") {
+                text.push_str(token);
+            }
+        }
+        kinds.push(LineKind::Comment {
+            text: text.trim_end().to_string(),
+            block,
+        });
+    })?;
+
+    let blank: Vec<bool> = kinds.iter().map(|k| *k == LineKind::Blank).collect();
+
+    // blank lines belong to the prose only when surrounded by it
+    let is_comment = |k: Option<&LineKind>| matches!(k, Some(LineKind::Comment { .. }));
+    for i in 0..kinds.len() {
+        if kinds[i] != LineKind::Blank {
+            continue;
+        }
+        let previous = kinds[..i].iter().rev().find(|k| **k != LineKind::Blank);
+        let next = kinds[i + 1..].iter().find(|k| **k != LineKind::Blank);
+        if is_comment(previous) && is_comment(next) {
+            kinds[i] = LineKind::Comment {
+                text: String::new(),
+                block: false,
+            };
+        } else {
+            kinds[i] = LineKind::Code;
+        }
+    }
+
+    let mut segments = Vec::new();
+    let mut i = 0;
+    while i < kinds.len() {
+        let end = (i..kinds.len())
+            .find(|&j| is_comment(Some(&kinds[j])) != is_comment(Some(&kinds[i])))
+            .unwrap_or(kinds.len());
+
+        if is_comment(Some(&kinds[i])) {
+            let lines: Vec<(&str, bool)> = kinds[i..end]
+                .iter()
+                .filter_map(|k| match k {
+                    LineKind::Comment { text, block } => Some((text.as_str(), *block)),
+                    _ => None,
+                })
+                .collect();
+            let prose = clean_comment_lines(&lines);
+            if !prose.trim().is_empty() {
+                segments.push(Segment::Prose(prose));
+            }
+        } else {
+            let start = (i..end).find(|&j| !blank[j]);
+            let last = (i..end).rev().find(|&j| !blank[j]);
+            if let (Some(start), Some(last)) = (start, last) {
+                segments.push(Segment::Code {
+                    start,
+                    end: last + 1,
+                });
+            }
+        }
+        i = end;
+    }
+    Ok(segments)
+}
+
+/// The lines each of `segments` covers, as zero-based half-open ranges: code
+/// segments carry their own, and prose takes the lines between the code around it.
+pub fn segment_lines(segments: &[Segment], total: usize) -> Vec<Range<usize>> {
+    segments
+        .iter()
+        .enumerate()
+        .map(|(i, segment)| match segment {
+            Segment::Code { start, end } => *start..*end,
+            Segment::Prose(_) => {
+                let start = segments[..i]
+                    .iter()
+                    .rev()
+                    .find_map(|s| match s {
+                        Segment::Code { end, .. } => Some(*end),
+                        Segment::Prose(_) => None,
+                    })
+                    .unwrap_or(0);
+                let end = segments[i + 1..]
+                    .iter()
+                    .find_map(|s| match s {
+                        Segment::Code { start, .. } => Some(*start),
+                        Segment::Prose(_) => None,
+                    })
+                    .unwrap_or(total);
+                start..end
+            }
+        })
+        .collect()
+}
+
+/// Turn comment lines (markers already removed) into Markdown text.
+///
+/// Block comment continuation stars are stripped, then the common indent is
+/// removed. A flush-left first line is left out of the indent calculation so
+/// docstrings that start on the opening quote line dedent like PEP 257 describes.
+fn clean_comment_lines(lines: &[(&str, bool)]) -> String {
+    let lines: Vec<&str> = lines
+        .iter()
+        .map(|(text, block)| {
+            if *block {
+                let trimmed = &text[indentation(text)..];
+                match trimmed.strip_prefix('*') {
+                    Some(rest) if !rest.starts_with('*') => rest,
+                    _ => text,
+                }
+            } else {
+                text
+            }
+        })
+        .collect();
+
+    let non_empty: Vec<(usize, &str)> = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, l)| !l.trim().is_empty())
+        .map(|(i, l)| (i, *l))
+        .collect();
+    let skip_first = non_empty.len() > 1 && indentation(non_empty[0].1) == 0;
+    let indent = non_empty
+        .iter()
+        .skip(usize::from(skip_first))
+        .map(|(_, l)| indentation(l))
+        .min()
+        .unwrap_or(0);
+
+    let mut prose = String::new();
+    for line in lines {
+        let strip = indent.min(indentation(line));
+        prose.push_str(&line[strip..]);
+        prose.push('\n');
+    }
+    prose.trim_matches('\n').to_string() + "\n"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scan(contents: &str, tags: &[String], is_symbol: impl Fn(&str) -> bool) -> FileSymbols {
+        let ss = SyntaxSet::load_defaults_newlines();
+        let syntax = ss.find_syntax_by_extension("rs").expect("syntax exists");
+        let mut scanner = SymbolScanner::new(tags, is_symbol);
+        for_each_token(contents, syntax, &ss, |line, text, tokens| {
+            scanner.scan_line(line, text, tokens)
+        })
+        .expect("can scan file");
+        scanner.finish()
+    }
+
+    fn segments(extension: &str, contents: &str) -> Vec<Segment> {
+        let ss = SyntaxSet::load_defaults_newlines();
+        let syntax = ss
+            .find_syntax_by_extension(extension)
+            .expect("syntax exists");
+        literate_segments(contents, syntax, &ss, false).expect("can split file")
+    }
+
+    #[test]
+    fn unicode_spaces_are_not_indentation() {
+        let contents = "///\u{a0}foo\n/// bar\nfn x() {}\n";
+        assert_eq!(
+            segments("rs", contents)[0],
+            Segment::Prose("\u{a0}foo\nbar\n".to_string())
+        );
+    }
+
+    #[test]
+    fn indentation_is_ascii_spaces_and_tabs() {
+        assert_eq!(indentation(" \tfoo"), 2);
+        assert_eq!(indentation("\u{a0}foo"), 0);
+        // a line after the item indented only by a no-break space isn't inside it
+        let lines = ["fn a() {", "\u{a0}x", "    y", "}"];
+        assert_eq!(item_extent(&lines, 0), 0..1);
+        let lines = ["fn a() {", "    y", "}"];
+        assert_eq!(item_extent(&lines, 0), 0..3);
+    }
+
+    #[test]
+    fn splits_rust_doc_comments_from_code() {
+        let contents = "//! Crate docs.\n\n/// Adds one.\n///\n/// - a list\nfn add(x: u8) -> u8 {\n    // plain comment\n    x + 1\n}\n";
+        assert_eq!(
+            segments("rs", contents),
+            vec![
+                Segment::Prose("Crate docs.\n\nAdds one.\n\n- a list\n".to_string()),
+                Segment::Code { start: 5, end: 9 },
+            ]
+        );
+    }
+
+    #[test]
+    fn strips_jsdoc_stars() {
+        let contents = "/**\n * Does a thing.\n *   indented\n */\nfunction foo() {}\n";
+        assert_eq!(
+            segments("js", contents),
+            vec![
+                Segment::Prose("Does a thing.\n  indented\n".to_string()),
+                Segment::Code { start: 4, end: 5 },
+            ]
+        );
+    }
+
+    #[test]
+    fn dedents_python_docstrings() {
+        let contents =
+            "def foo():\n    \"\"\"Does a thing.\n\n    More detail.\n    \"\"\"\n    return 1\n";
+        assert_eq!(
+            segments("py", contents),
+            vec![
+                Segment::Code { start: 0, end: 1 },
+                Segment::Prose("Does a thing.\n\nMore detail.\n".to_string()),
+                Segment::Code { start: 5, end: 6 },
+            ]
+        );
+    }
+
+    #[test]
+    fn finds_rust_definitions() {
+        let ss = SyntaxSet::load_defaults_newlines();
+        let syntax = ss.find_syntax_by_extension("rs").expect("syntax exists");
+        let contents = "pub struct Foo;\ntrait Bar {}\nimpl Bar for Foo {\n    fn baz(&self) {}\n}\nfn main() { baz(); }\n";
+        let found: Vec<(String, SymbolKind, usize)> = definitions(contents, syntax, &ss)
+            .expect("can find definitions")
+            .into_iter()
+            .map(|d| (d.name, d.kind, d.line))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("Foo".to_string(), SymbolKind::Type, 0),
+                ("Bar".to_string(), SymbolKind::Trait, 1),
+                ("baz".to_string(), SymbolKind::Function, 3),
+                ("main".to_string(), SymbolKind::Function, 5),
+            ]
+        );
+    }
+
+    #[test]
+    fn outlines_top_level_items() {
+        let contents = "pub struct Foo;\nimpl Foo {\n    fn new() -> Self { Foo }\n}\nfn main() {}\nmod tests {\n    fn helper() {}\n}\n";
+        let items = scan(contents, &[], |_| false).outline;
+        assert_eq!(item_lines(contents, &items), vec![0..1, 1..4, 4..5, 5..8]);
+        let found: Vec<(String, &str, usize)> = items
+            .into_iter()
+            .map(|item| (item.name, item.label, item.line))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("Foo".to_string(), "type", 0),
+                ("Foo".to_string(), "impl", 1),
+                ("main".to_string(), "fn", 4),
+                ("tests".to_string(), "mod", 5),
+            ]
+        );
+    }
+
+    #[test]
+    fn finds_the_lines_of_a_symbol() {
+        let ss = SyntaxSet::load_defaults_newlines();
+        let syntax = ss.find_syntax_by_extension("rs").expect("syntax exists");
+        let contents = "use std::fmt;\n\n/// A parser.\n#[derive(Debug)]\npub struct Parser {\n    pos: usize,\n}\n\nimpl Parser {\n    fn new() -> Self {\n        Parser { pos: 0 }\n    }\n}\n\nfn main() {}\n";
+        let lines = |name| symbol_lines(contents, syntax, &ss, name).expect("can find symbol");
+        assert_eq!(lines("Parser"), vec![2..7, 8..13]);
+        assert_eq!(lines("new"), vec![9..12]);
+        assert_eq!(lines("main"), vec![14..15]);
+        assert_eq!(lines("missing"), Vec::<Range<usize>>::new());
+    }
+
+    #[test]
+    fn finds_leading_comments() {
+        let ss = SyntaxSet::load_defaults_newlines();
+        let find = |extension: &str, contents: &str| {
+            let syntax = ss
+                .find_syntax_by_extension(extension)
+                .expect("syntax exists");
+            leading_comment(contents, syntax, &ss).expect("can find comment")
+        };
+
+        let rust =
+            "// Copyright Example\n//\n// Licensed under MIT.\n\n//! Crate docs.\nfn main() {}\n";
+        let python =
+            "#!/usr/bin/env python\n# Copyright Example\n#\n# Licensed under MIT.\nimport os\n";
+        let c = "/*\n * Copyright Example\n *\n * Licensed under MIT.\n */\nint x;\n";
+        let c_empty_line = "/*\n Copyright Example\n\n Licensed under MIT.\n */\nint x;\n";
+        let header = "Copyright Example\n\nLicensed under MIT.";
+        for (extension, contents, lines) in [
+            ("rs", rust, 0..3),
+            ("py", python, 1..4),
+            ("c", c, 0..5),
+            ("c", c_empty_line, 0..5),
+        ] {
+            assert_eq!(
+                find(extension, contents),
+                Some(LeadingComment {
+                    lines,
+                    text: header.to_string()
+                }),
+                "{}",
+                extension
+            );
+        }
+
+        assert_eq!(find("rs", "//! Crate docs.\nfn main() {}\n"), None);
+        assert_eq!(find("rs", "fn main() {}\n// trailing\n"), None);
+    }
+
+    #[test]
+    fn finds_markers_in_comments_only() {
+        let contents = "// TODO: split this up\nlet todo_list = \"TODO not me\";\n/* FIXME leaks */\nlet x = 1; // XXX\n// TODOS are not tags\n";
+        let tags: Vec<String> = ["TODO", "FIXME", "HACK", "XXX"]
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        let found: Vec<(String, usize, String)> = scan(contents, &tags, |_| false)
+            .markers
+            .into_iter()
+            .map(|m| (m.tag, m.line, m.text))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("TODO".to_string(), 0, "split this up".to_string()),
+                ("FIXME".to_string(), 2, "leaks".to_string()),
+                ("XXX".to_string(), 3, String::new()),
+            ]
+        );
+    }
+
+    #[test]
+    fn finds_references_outside_comments_and_strings() {
+        let contents =
+            "fn parse() {}\n// parse here\nfn main() { let p = parse(); println!(\"parse\"); }\n";
+        let found = scan(contents, &[], |name| name == "parse").references;
+        assert_eq!(
+            found,
+            vec![Reference {
+                name: "parse".to_string(),
+                line: 2,
+                range: 20..25,
+            }]
+        );
+    }
+
+    #[test]
+    fn highlights_and_scans_in_one_pass() {
+        let ss = SyntaxSet::load_defaults_newlines();
+        let syntax = ss.find_syntax_by_extension("rs").expect("syntax exists");
+        let theme = &syntect::highlighting::ThemeSet::load_defaults().themes["InspiredGitHub"];
+        let contents =
+            "// TODO: tidy\npub struct Foo;\nimpl Foo {\n    fn new() -> Foo { Foo }\n}\n";
+        let tags = vec!["TODO".to_string()];
+        let scanner = SymbolScanner::new(&tags, |name: &str| name == "Foo");
+        let (lines, symbols) =
+            highlight_and_scan(contents, syntax, &ss, theme, scanner).expect("can scan file");
+
+        let mut h = syntect::easy::HighlightLines::new(syntax, theme);
+        for (line, highlighted) in LinesWithEndings::from(contents).zip(&lines) {
+            assert_eq!(&h.highlight_line(line, &ss).unwrap(), highlighted);
+        }
+        assert_eq!(symbols, scan(contents, &tags, |name| name == "Foo"));
+        assert_eq!(
+            symbols.definitions,
+            definitions(contents, syntax, &ss).unwrap()
+        );
+        assert_eq!(symbols.markers.len(), 1);
+        assert!(!symbols.references.is_empty());
+    }
+
+    #[test]
+    fn orders_and_files_symbols_like_an_index() {
+        let mut names = vec!["zeta", "Alpha", "_beta", "alpha"];
+        names.sort_by(|a, b| compare_symbols(a, b));
+        assert_eq!(names, vec!["Alpha", "alpha", "_beta", "zeta"]);
+        assert_eq!(index_letter("_beta"), 'B');
+        assert_eq!(index_letter("42"), '#');
+    }
+
+    #[test]
+    fn keeps_plain_comments_in_code_unless_asked() {
+        let ss = SyntaxSet::load_defaults_newlines();
+        let syntax = ss.find_syntax_by_extension("go").expect("syntax exists");
+        let contents = "// Package main does things.\npackage main\n";
+        assert_eq!(
+            literate_segments(contents, syntax, &ss, false).expect("can split"),
+            vec![Segment::Code { start: 0, end: 2 }]
+        );
+        assert_eq!(
+            literate_segments(contents, syntax, &ss, true).expect("can split"),
+            vec![
+                Segment::Prose("Package main does things.\n".to_string()),
+                Segment::Code { start: 1, end: 2 },
+            ]
+        );
+    }
+}
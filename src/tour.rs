@@ -0,0 +1,289 @@
+//! Guided tour parsing shared by the PDF and EPUB sinks.
+//!
+//! A tour is an ordered walkthrough of the code: each step is a bit of Markdown
+//! commentary, usually pointing at some lines of a file. The sinks render a tour
+//! as a narrative, each step's prose followed by the code it points at, with a
+//! link back to the file's full listing.
+//!
+//! Two formats are read:
+//!
+//! - VS Code [CodeTour](https://github.com/microsoft/codetour) files
+//!   (`.tours/*.tour`), JSON documents with a list of steps. A step's `selection`
+//!   gives the lines to show; steps with only a `line` show that line with a few
+//!   lines of context either side.
+//! - A `tour.md` Markdown file. The `#` heading is the tour's title and the text
+//!   under it the introduction; each `##` section is a step. The first link in a
+//!   section to a repository file with a line fragment (`src/main.rs#L10-L40` or
+//!   `src/main.rs#L10`) picks the code shown after it.
+//!
+//! File paths in both formats are relative to the repository root.
+
+use anyhow::{Context, Result};
+use serde_json::Value;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+/// Lines of context shown either side of a step that points at a single line.
+const CONTEXT_LINES: usize = 3;
+
+/// Returns true if the path looks like a tour: a CodeTour file or a `tour.md`.
+pub fn is_tour(path: &Path) -> bool {
+    is_code_tour(path)
+        || path
+            .file_name()
+            .map(|name| name.eq_ignore_ascii_case("tour.md"))
+            .unwrap_or_default()
+}
+
+fn is_code_tour(path: &Path) -> bool {
+    path.extension()
+        .map(|e| e.eq_ignore_ascii_case("tour"))
+        .unwrap_or_default()
+}
+
+/// A parsed tour.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tour {
+    pub title: String,
+    /// Markdown introduction shown before the first step
+    pub description: Option<String>,
+    pub steps: Vec<Step>,
+}
+
+/// A stop on a tour.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Step {
+    pub title: Option<String>,
+    /// Markdown commentary
+    pub description: String,
+    /// File the step points at, relative to the repository root
+    pub file: Option<PathBuf>,
+    /// Zero-based lines of `file` to show; steps without lines only link to the file
+    pub lines: Option<Range<usize>>,
+}
+
+/// Read and parse a tour file.
+///
+/// The title falls back to the file name when the tour doesn't give one.
+pub fn load(path: &Path) -> Result<Tour> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read tour {}", path.display()))?;
+    let fallback_title = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.display().to_string());
+    if is_code_tour(path) {
+        parse_code_tour(&contents, &fallback_title)
+            .with_context(|| format!("Failed to parse tour {}", path.display()))
+    } else {
+        Ok(parse_markdown_tour(&contents, &fallback_title))
+    }
+}
+
+/// Parse CodeTour JSON.
+pub fn parse_code_tour(json: &str, fallback_title: &str) -> Result<Tour> {
+    let value: Value = serde_json::from_str(json).with_context(|| "Tour is not valid JSON")?;
+
+    let steps = value["steps"]
+        .as_array()
+        .with_context(|| "Tour has no steps array")?
+        .iter()
+        .map(parse_code_tour_step)
+        .collect();
+
+    Ok(Tour {
+        title: value["title"]
+            .as_str()
+            .unwrap_or(fallback_title)
+            .to_string(),
+        description: value["description"]
+            .as_str()
+            .filter(|d| !d.trim().is_empty())
+            .map(ToString::to_string),
+        steps,
+    })
+}
+
+fn parse_code_tour_step(step: &Value) -> Step {
+    let file = step["file"].as_str().map(PathBuf::from);
+    let line = |value: &Value| value["line"].as_u64().map(|line| line.max(1) as usize);
+    let selection = &step["selection"];
+    let lines = match (line(&selection["start"]), line(&selection["end"])) {
+        (Some(start), Some(end)) => Some(start - 1..end.max(start)),
+        _ => line(step).map(|line| {
+            let first = (line - 1).saturating_sub(CONTEXT_LINES);
+            first..line + CONTEXT_LINES
+        }),
+    };
+
+    Step {
+        title: step["title"].as_str().map(ToString::to_string),
+        description: step["description"].as_str().unwrap_or_default().to_string(),
+        lines: file.as_ref().and(lines),
+        file,
+    }
+}
+
+/// Parse a `tour.md` file.
+pub fn parse_markdown_tour(markdown: &str, fallback_title: &str) -> Tour {
+    let mut title = None;
+    let mut introduction = String::new();
+    let mut steps: Vec<(String, String)> = Vec::new();
+    let mut fence: Option<&str> = None;
+
+    for line in markdown.lines() {
+        let trimmed = line.trim_start();
+        // headings inside code blocks aren't headings
+        if let Some(marker) = fence {
+            if trimmed.starts_with(marker) {
+                fence = None;
+            }
+        } else if trimmed.starts_with("```") {
+            fence = Some("```");
+        } else if trimmed.starts_with("~~~") {
+            fence = Some("~~~");
+        } else if let Some(heading) = line.strip_prefix("## ") {
+            steps.push((heading.trim().to_string(), String::new()));
+            continue;
+        } else if let Some(heading) = line.strip_prefix("# ") {
+            if title.is_none() && steps.is_empty() {
+                title = Some(heading.trim().to_string());
+                continue;
+            }
+        }
+
+        let text = match steps.last_mut() {
+            Some((_, body)) => body,
+            None => &mut introduction,
+        };
+        text.push_str(line);
+        text.push('\n');
+    }
+
+    let steps = steps
+        .into_iter()
+        .map(|(title, body)| {
+            let (file, lines) = match first_location(&body) {
+                Some((file, lines)) => (Some(file), lines),
+                None => (None, None),
+            };
+            Step {
+                title: Some(title),
+                description: body.trim().to_string(),
+                file,
+                lines,
+            }
+        })
+        .collect();
+
+    let introduction = introduction.trim();
+    Tour {
+        title: title.unwrap_or_else(|| fallback_title.to_string()),
+        description: (!introduction.is_empty()).then(|| introduction.to_string()),
+        steps,
+    }
+}
+
+/// Find the first Markdown link in `text` to a repository file with a line fragment.
+fn first_location(text: &str) -> Option<(PathBuf, Option<Range<usize>>)> {
+    text.match_indices("](").find_map(|(start, _)| {
+        let target = &text[start + 2..];
+        let target = &target[..target.find(')')?];
+        let target = target.split_whitespace().next()?;
+        parse_location(target)
+    })
+}
+
+/// Parse a link target like `src/main.rs#L10-L40` into a path and zero-based lines.
+fn parse_location(target: &str) -> Option<(PathBuf, Option<Range<usize>>)> {
+    let (path, fragment) = target.split_once('#')?;
+    if path.is_empty() || path.contains("://") {
+        return None;
+    }
+    let (first, last) = match fragment.split_once('-') {
+        Some((first, last)) => (first, last),
+        None => (fragment, fragment),
+    };
+    let line = |s: &str| {
+        s.strip_prefix('L')?
+            .parse::<usize>()
+            .ok()
+            .filter(|&n| n > 0)
+    };
+    let lines = match (line(first), line(last)) {
+        (Some(first), Some(last)) => Some(first - 1..last.max(first)),
+        _ => None,
+    };
+    Some((PathBuf::from(path), lines))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_parse_code_tours() {
+        let tour = parse_code_tour(
+            r#"{
+                "$schema": "https://aka.ms/codetour-schema",
+                "title": "Getting started",
+                "steps": [
+                    {"title": "Welcome", "description": "Start here."},
+                    {"file": "src/main.rs", "line": 2, "description": "The entry point."},
+                    {"file": "src/lib.rs", "description": "Selected.",
+                     "selection": {"start": {"line": 10, "character": 1},
+                                   "end": {"line": 12, "character": 4}}}
+                ]
+            }"#,
+            "fallback",
+        )
+        .expect("can parse tour");
+
+        assert_eq!(tour.title, "Getting started");
+        assert_eq!(tour.description, None);
+        assert_eq!(tour.steps.len(), 3);
+        assert_eq!(tour.steps[0].title.as_deref(), Some("Welcome"));
+        assert_eq!(tour.steps[0].file, None);
+        assert_eq!(tour.steps[0].lines, None);
+        assert_eq!(tour.steps[1].file, Some(PathBuf::from("src/main.rs")));
+        assert_eq!(tour.steps[1].lines, Some(0..5));
+        assert_eq!(tour.steps[2].lines, Some(9..12));
+    }
+
+    #[test]
+    fn can_parse_markdown_tours() {
+        let tour = parse_markdown_tour(
+            "# Onboarding\n\
+             \n\
+             Read this first.\n\
+             \n\
+             ## Entry point\n\
+             \n\
+             Everything starts in [main](src/main.rs#L10-L40).\n\
+             \n\
+             ```\n\
+             ## not a step\n\
+             ```\n\
+             \n\
+             ## Config\n\
+             \n\
+             See [the docs](https://example.com/x#L1) and [config](src/config.rs#L7).\n\
+             \n\
+             ## Wrapping up\n\
+             \n\
+             That's all.\n",
+            "fallback",
+        );
+
+        assert_eq!(tour.title, "Onboarding");
+        assert_eq!(tour.description.as_deref(), Some("Read this first."));
+        assert_eq!(tour.steps.len(), 3);
+        assert_eq!(tour.steps[0].title.as_deref(), Some("Entry point"));
+        assert_eq!(tour.steps[0].file, Some(PathBuf::from("src/main.rs")));
+        assert_eq!(tour.steps[0].lines, Some(9..40));
+        assert!(tour.steps[0].description.contains("## not a step"));
+        assert_eq!(tour.steps[1].file, Some(PathBuf::from("src/config.rs")));
+        assert_eq!(tour.steps[1].lines, Some(6..7));
+        assert_eq!(tour.steps[2].file, None);
+    }
+}
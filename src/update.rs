@@ -10,18 +10,22 @@
 //! - Refreshes the author list from git commit history
 //! - Keeps existing frontmatter files that still exist
 //! - Prompts user to select newly detected frontmatter candidates
+//! - Keeps existing guided tours that still exist and adds newly detected ones
 //! - Handles missing entrypoints interactively
+//! - Keeps excerpt selectors, moving line ranges over the changes committed since
+//!   they were recorded and dropping symbols that are no longer defined
 
 use crate::config_wizard::Configuration;
-use crate::detection::detect_frontmatter;
+use crate::detection::{detect_frontmatter, detect_tours};
 use crate::file_ordering::sort_with_entrypoint;
-use crate::source::GitRepository;
+use crate::source::{Excerpt, GitRepository, SourceFile};
 use anyhow::{Context, Result};
 use dialoguer::theme::ColorfulTheme;
 use dialoguer::{Confirm, FuzzySelect, MultiSelect};
 use globset::Glob;
-use std::collections::HashSet;
-use std::path::PathBuf;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use syntect::parsing::SyntaxSet;
 
 /// Run the update command.
 ///
@@ -142,6 +146,37 @@ pub fn run() -> Result<()> {
     let frontmatter_set: HashSet<_> = frontmatter_files.iter().cloned().collect();
     discovered_files.retain(|f| !frontmatter_set.contains(f));
 
+    // keep tours that still exist and add new ones; CodeTour files live in a hidden
+    // directory, so they're checked on disk rather than against the discovered files
+    let mut tour_files: Vec<PathBuf> = source
+        .tour_files
+        .iter()
+        .filter(|f| source.repository.join(f).is_file())
+        .cloned()
+        .collect();
+    let new_tours: Vec<PathBuf> = detect_tours(&source.repository, &discovered_files)
+        .into_iter()
+        .filter(|f| !source.tour_files.contains(f))
+        .collect();
+    if !new_tours.is_empty() {
+        let tour_strings: Vec<String> = new_tours.iter().map(|p| p.display().to_string()).collect();
+
+        println!(
+            "\nDetected {} new guided tour(s): {}",
+            new_tours.len(),
+            tour_strings.join(", ")
+        );
+
+        let defaults: Vec<bool> = new_tours.iter().map(|_| true).collect();
+        let selections = MultiSelect::with_theme(&theme)
+            .with_prompt("Select new tours to add")
+            .items(&tour_strings)
+            .defaults(&defaults)
+            .interact()?;
+        tour_files.extend(selections.into_iter().map(|i| new_tours[i].clone()));
+    }
+    discovered_files.retain(|f| !tour_files.contains(f));
+
     // handle entrypoint
     let entrypoint = if let Some(ep) = source.entrypoint_path() {
         if discovered_files.contains(&ep) {
@@ -171,8 +206,17 @@ pub fn run() -> Result<()> {
     // sort files with entrypoint priority
     sort_with_entrypoint(&mut discovered_files, entrypoint.as_ref());
 
+    // carry excerpts over to files that are still in the book
+    let kept_excerpts: Vec<&SourceFile> = source
+        .source_files
+        .iter()
+        .filter(|f| f.excerpt.is_some() && discovered_files.contains(&f.path))
+        .collect();
+    let mut excerpts = follow_excerpts(&source.repository, &kept_excerpts)?;
+    let excerpt_count = excerpts.len();
+
     // calculate change counts before reassigning
-    let old_source_set: HashSet<_> = source.source_files.iter().cloned().collect();
+    let old_source_set: HashSet<_> = source.source_paths().into_iter().collect();
     let new_source_set: HashSet<_> = discovered_files.iter().cloned().collect();
 
     let added_count = discovered_files
@@ -182,14 +226,22 @@ pub fn run() -> Result<()> {
     let removed_count = source
         .source_files
         .iter()
-        .filter(|f| !new_source_set.contains(*f))
+        .filter(|f| !new_source_set.contains(&f.path))
         .count();
 
     // update source fields
     let frontmatter_count = frontmatter_files.len();
     let source_count = discovered_files.len();
     source.frontmatter_files = frontmatter_files;
-    source.source_files = discovered_files;
+    let tour_count = tour_files.len();
+    source.tour_files = tour_files;
+    source.source_files = discovered_files
+        .into_iter()
+        .map(|path| SourceFile {
+            excerpt: excerpts.remove(&path),
+            path,
+        })
+        .collect();
     source.entrypoint = entrypoint
         .map(|p| p.to_string_lossy().to_string())
         .unwrap_or_default();
@@ -209,12 +261,91 @@ pub fn run() -> Result<()> {
         "  Source files: {} (+{} added, -{} removed)",
         source_count, added_count, removed_count
     );
+    println!("  Excerpts:     {} file(s)", excerpt_count);
     println!("  Frontmatter:  {} file(s)", frontmatter_count);
+    println!("  Tours:        {} tour(s)", tour_count);
     println!("  Authors:      {} author(s)", author_count);
 
     Ok(())
 }
 
+/// Bring excerpts up to date with the repository's HEAD.
+///
+/// Line ranges are moved over the changes committed since the revision they were
+/// recorded against and trimmed to the files' lengths at HEAD. Symbols are looked
+/// up by name when rendering, so they only need to still be defined at HEAD; ones
+/// that have been renamed or removed are dropped, since they'd stop the book from
+/// rendering. Excerpts that no longer select anything are dropped, leaving the
+/// whole file in the book.
+fn follow_excerpts(repository: &Path, files: &[&SourceFile]) -> Result<HashMap<PathBuf, Excerpt>> {
+    let mut excerpts = HashMap::new();
+    if files.is_empty() {
+        return Ok(excerpts);
+    }
+
+    let repo = git2::Repository::open(repository).with_context(|| {
+        format!(
+            "Failed to open path {} as a git repository!",
+            repository.display()
+        )
+    })?;
+    let head = repo
+        .head()
+        .and_then(|head| head.peel_to_commit())
+        .with_context(|| "Failed to find the HEAD commit")?;
+    let (ss, _): (SyntaxSet, _) = bincode::serde::decode_from_slice(
+        crate::highlight::SERIALIZED_SYNTAX,
+        bincode::config::standard(),
+    )
+    .expect("can deserialize syntaxes");
+
+    for file in files {
+        let Some(mut excerpt) = file.excerpt.clone() else {
+            continue;
+        };
+        if let Err(e) = excerpt.follow(&repo, &file.path, &head) {
+            println!(
+                "  Couldn't follow the excerpt of {}: {:#}",
+                file.path.display(),
+                e
+            );
+        }
+        if let Some(contents) = head_contents(&repo, &head, &file.path) {
+            excerpt.clamp(contents.lines().count());
+            if let Some(symbol) = excerpt.drop_missing_symbol(&file.path, &contents, &ss) {
+                println!(
+                    "  `{}` is no longer defined in {}; dropping it from the excerpt",
+                    symbol,
+                    file.path.display()
+                );
+            }
+        }
+        if excerpt.lines.is_empty() && excerpt.symbol.is_none() {
+            println!(
+                "  The excerpt of {} no longer selects any lines; including the whole file",
+                file.path.display()
+            );
+            continue;
+        }
+        excerpts.insert(file.path.clone(), excerpt);
+    }
+    Ok(excerpts)
+}
+
+/// The text of `path` as committed at `head`, if it's there and isn't binary.
+fn head_contents(repo: &git2::Repository, head: &git2::Commit, path: &Path) -> Option<String> {
+    let blob = head
+        .tree()
+        .ok()?
+        .get_path(path)
+        .ok()?
+        .to_object(repo)
+        .ok()?
+        .peel_to_blob()
+        .ok()?;
+    String::from_utf8(blob.content().to_vec()).ok()
+}
+
 /// Prompt user to select an entrypoint from the file list.
 fn select_entrypoint(theme: &ColorfulTheme, files: &[PathBuf]) -> Result<Option<PathBuf>> {
     let file_strings: Vec<String> = files.iter().map(|p| p.display().to_string()).collect();
@@ -0,0 +1,191 @@
+//! Whitespace handling for code listings, shared by the sinks and the layout
+//! analysis.
+//!
+//! Source text goes through [`expand`] before it's highlighted: tabs are expanded to
+//! the next tab stop, and leading indentation can be reflowed to a narrower step
+//! (4→2, say) to save page width. Only leading whitespace is reflowed, so string
+//! literals and aligned comments keep their spacing. Every line keeps its place, so
+//! line numbers are unaffected.
+//!
+//! Visible whitespace is applied to the highlighted text afterwards, since `·` and
+//! `→` in the source would confuse the highlighter. With it enabled, each expanded
+//! tab keeps a tab character in its first column for [`visible`] to turn into `→`.
+//! Replacing characters changes byte offsets within a line; [`shown_range`] maps a
+//! range found in the expanded text onto the displayed text.
+
+use crate::sinks::pdf::CodeConfig;
+use std::ops::Range;
+
+/// Glyph shown for a space with visible whitespace.
+const SPACE: char = '·';
+/// Glyph shown for the first column of a tab with visible whitespace.
+const TAB: char = '→';
+
+/// Expand tabs and reflow indentation in a file's contents.
+pub fn expand(contents: &str, code: &CodeConfig) -> String {
+    contents
+        .split_inclusive('\n')
+        .map(|line| expand_line(line, code))
+        .collect()
+}
+
+/// Expand tabs and reflow indentation in one line.
+pub fn expand_line(line: &str, code: &CodeConfig) -> String {
+    let tab_width = code.tab_width.max(1);
+    let indent_end = line
+        .find(|c: char| c != ' ' && c != '\t')
+        .unwrap_or(line.len());
+    let (leading, rest) = line.split_at(indent_end);
+
+    let mut out = String::with_capacity(line.len());
+    let mut column = 0;
+    let push_tab = |out: &mut String, column: &mut usize| {
+        let width = tab_width - *column % tab_width;
+        if code.visible_whitespace {
+            out.push('\t');
+            out.push_str(&" ".repeat(width - 1));
+        } else {
+            out.push_str(&" ".repeat(width));
+        }
+        *column += width;
+    };
+
+    // blank lines are left alone; their whitespace is all trailing
+    let blank = rest.trim_end_matches(['\n', '\r']).is_empty();
+    match code.reflow_indent.filter(|_| code.indent > 0 && !blank) {
+        Some(step) => {
+            let columns = leading.chars().fold(0, |column, c| match c {
+                '\t' => column + tab_width - column % tab_width,
+                _ => column + 1,
+            });
+            let columns = columns / code.indent * step + columns % code.indent;
+            // with visible whitespace, tab-indented lines show a tab per level
+            if code.visible_whitespace && leading.contains('\t') && step > 0 {
+                for level in 0..columns / step {
+                    out.push('\t');
+                    out.push_str(&" ".repeat(step - 1));
+                    column = (level + 1) * step;
+                }
+            }
+            out.push_str(&" ".repeat(columns - column));
+            column = columns;
+        }
+        None => {
+            for c in leading.chars() {
+                if c == '\t' {
+                    push_tab(&mut out, &mut column);
+                } else {
+                    out.push(c);
+                    column += 1;
+                }
+            }
+        }
+    }
+
+    for c in rest.chars() {
+        match c {
+            '\t' => push_tab(&mut out, &mut column),
+            '\n' | '\r' => out.push(c),
+            _ => {
+                out.push(c);
+                column += 1;
+            }
+        }
+    }
+    out
+}
+
+/// The number of columns a line takes up once expanded.
+pub fn visual_width(line: &str, code: &CodeConfig) -> usize {
+    expand_line(line, code)
+        .trim_end_matches(['\n', '\r'])
+        .chars()
+        .count()
+}
+
+/// Show the whitespace in expanded text as glyphs.
+pub fn visible(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            ' ' => SPACE,
+            '\t' => TAB,
+            c => c,
+        })
+        .collect()
+}
+
+/// Byte offset where a line's trailing whitespace starts, ignoring its line ending.
+/// Blank lines have no trailing whitespace worth marking.
+pub fn trailing_start(line: &str) -> usize {
+    let line = line.trim_end_matches(['\n', '\r']);
+    match line.trim_end() {
+        "" => line.len(),
+        trimmed => trimmed.len(),
+    }
+}
+
+/// Map a byte range in an expanded line onto the line as displayed.
+pub fn shown_range(line: &str, range: &Range<usize>, code: &CodeConfig) -> Range<usize> {
+    if !code.visible_whitespace {
+        return range.clone();
+    }
+    let offset = |byte: usize| {
+        byte + line[..byte]
+            .chars()
+            .map(|c| match c {
+                ' ' => SPACE.len_utf8() - 1,
+                '\t' => TAB.len_utf8() - 1,
+                _ => 0,
+            })
+            .sum::<usize>()
+    };
+    offset(range.start)..offset(range.end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn code(reflow_indent: Option<usize>, visible_whitespace: bool) -> CodeConfig {
+        CodeConfig {
+            tab_width: 4,
+            indent: 4,
+            reflow_indent,
+            visible_whitespace,
+            mark_trailing_whitespace: false,
+            wrap_indent: 2,
+            continuation_glyph: String::new(),
+        }
+    }
+
+    #[test]
+    fn expands_tabs_and_reflows_leading_indentation_only() {
+        let plain = code(None, false);
+        assert_eq!(expand_line("\tif x {\n", &plain), "    if x {\n");
+        assert_eq!(expand_line("a\tb", &plain), "a   b");
+        assert_eq!(visual_width("ab\tc\n", &plain), 5);
+
+        let reflowed = code(Some(2), false);
+        assert_eq!(
+            expand("fn f() {\n        let s = \"a    b\";\n}\n", &reflowed),
+            "fn f() {\n    let s = \"a    b\";\n}\n"
+        );
+        assert_eq!(expand_line("\t\t x", &reflowed), "     x");
+        assert_eq!(expand_line("      \n", &reflowed), "      \n");
+    }
+
+    #[test]
+    fn shows_whitespace() {
+        let shown = code(None, true);
+        let line = expand_line("\tx = 1  ", &shown);
+        assert_eq!(line, "\t   x = 1  ");
+        assert_eq!(visible(&line), "→···x·=·1··");
+        assert_eq!(trailing_start(&line), 9);
+        assert_eq!(trailing_start("    \n"), 4);
+
+        // `1` moves from byte 8 to byte 8 + 2 (tab) + 3 + 2 (spaces)
+        let one = line.find('1').expect("line has a 1");
+        let range = shown_range(&line, &(one..one + 1), &shown);
+        assert_eq!(&visible(&line)[range], "1");
+    }
+}
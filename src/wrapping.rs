@@ -0,0 +1,127 @@
+//! Code-aware line wrapping, shared by the PDF listings and the layout analysis.
+//!
+//! A line too long for the measure is broken after whitespace or punctuation where
+//! the row has a chance to, and between any two characters where it doesn't.
+//! Continuation rows hang: they're indented to the line's own indentation plus
+//! `[pdf.code] wrap_indent` columns, so a wrapped statement reads as part of the
+//! line above rather than as a new one. The listings mark each continuation row
+//! with a glyph in the gutter where a line number would go.
+//!
+//! Widths are in whatever unit the caller measures with: points for the PDF
+//! listings, columns for the analysis in [`crate::line_analysis`].
+
+/// A row may end after one of these, if the next row starts with a word.
+const BREAK_AFTER: &[char] = &[',', ';', '(', '[', '{'];
+
+/// A character of a line: its byte offset within the line and its width.
+pub type Glyph = (usize, char, f32);
+
+/// The indent of a line's continuation rows: its leading whitespace plus `offset`,
+/// but never more than half the measure.
+pub fn hang(glyphs: &[Glyph], offset: f32, measure: f32) -> f32 {
+    let indent: f32 = glyphs
+        .iter()
+        .take_while(|(_, c, _)| c.is_whitespace())
+        .map(|(_, _, w)| w)
+        .sum();
+    (indent + offset).min(measure * 0.5)
+}
+
+/// Whether a row may start at `glyphs[i]`.
+fn can_break_before(glyphs: &[Glyph], i: usize) -> bool {
+    let prev = glyphs[i - 1].1;
+    let c = glyphs[i].1;
+    let starts_word = c.is_alphanumeric() || matches!(c, '_' | '"' | '\'' | '&' | '*');
+    (prev.is_whitespace() && !c.is_whitespace())
+        || (BREAK_AFTER.contains(&prev) && starts_word)
+        // method chains break before the dot
+        || (c == '.' && (prev.is_alphabetic() || matches!(prev, ')' | '_' | '?')))
+}
+
+/// Break a line into rows no wider than `measure`, continuation rows starting
+/// `hang` in.
+///
+/// Returns the index into `glyphs` at which each continuation row starts; a line
+/// that fits returns none. Whitespace may run past the measure rather than start a
+/// row, and every row holds at least one character.
+pub fn break_line(glyphs: &[Glyph], measure: f32, hang: f32) -> Vec<usize> {
+    let indent_end = glyphs
+        .iter()
+        .position(|(_, c, _)| !c.is_whitespace())
+        .unwrap_or(glyphs.len());
+
+    let mut breaks = Vec::new();
+    let mut row_start = 0;
+    let mut x = 0.0;
+    let mut opportunity = None;
+    let mut i = 0;
+    while i < glyphs.len() {
+        let (_, c, w) = glyphs[i];
+        // breaking inside the indentation would leave an empty row
+        if i > row_start && i > indent_end && can_break_before(glyphs, i) {
+            opportunity = Some(i);
+        }
+        if x + w > measure && i > row_start && !c.is_whitespace() {
+            let at = opportunity.take().unwrap_or(i);
+            breaks.push(at);
+            row_start = at;
+            x = hang;
+            // measure the moved characters again on the new row
+            i = at;
+            continue;
+        }
+        x += w;
+        i += 1;
+    }
+    breaks
+}
+
+/// The number of rows a line takes up when `max_chars` columns fit on a row, with
+/// every character one column wide.
+pub fn row_count(line: &str, max_chars: usize, wrap_indent: usize) -> usize {
+    let glyphs: Vec<Glyph> = line.char_indices().map(|(i, c)| (i, c, 1.0)).collect();
+    let measure = max_chars as f32;
+    let hang = hang(&glyphs, wrap_indent as f32, measure);
+    break_line(&glyphs, measure, hang).len() + 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows(line: &str, max_chars: usize, wrap_indent: usize) -> Vec<&str> {
+        let glyphs: Vec<Glyph> = line.char_indices().map(|(i, c)| (i, c, 1.0)).collect();
+        let measure = max_chars as f32;
+        let hang = hang(&glyphs, wrap_indent as f32, measure);
+        let mut starts: Vec<usize> = std::iter::once(0)
+            .chain(
+                break_line(&glyphs, measure, hang)
+                    .into_iter()
+                    .map(|i| glyphs[i].0),
+            )
+            .collect();
+        starts.push(line.len());
+        starts.windows(2).map(|w| &line[w[0]..w[1]]).collect()
+    }
+
+    #[test]
+    fn breaks_at_whitespace_and_punctuation() {
+        assert_eq!(rows("let x = 1;", 20, 2), vec!["let x = 1;"]);
+        assert_eq!(
+            rows("    call(first, second, third);", 21, 2),
+            vec!["    call(first, ", "second, third);"]
+        );
+        assert_eq!(
+            rows("value.iter().map(f).collect()", 16, 2),
+            vec!["value.iter()", ".map(f)", ".collect()"]
+        );
+        // continuation rows are narrower by the hang, and words too long for a row
+        // are broken anywhere
+        assert_eq!(
+            rows("  abcdefghijklmnop", 10, 2),
+            vec!["  abcdefgh", "ijklmn", "op"]
+        );
+        assert_eq!(row_count("  abcdefghijklmnop", 10, 2), 3);
+        assert_eq!(row_count("", 10, 2), 1);
+    }
+}